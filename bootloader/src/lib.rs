@@ -36,21 +36,113 @@ use bios::{
 /// ONLY USED FOR `MemoryEntry`!
 pub const MAX_MEMORY_MAP_ENTRIES: usize = 16;
 
+/// # Max Kernel Segments
+/// This is the max number of loadable segments the kernel's elf can have.
+pub const MAX_KERNEL_SEGMENTS: usize = 8;
+
+/// # Kernel Segment
+/// One loadable segment of the kernel, already copied into physical memory.
+///
+/// Every field is a `u64` so this has the same layout in every stage.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct KernelSegment {
+    /// Where this segment was loaded in physical memory.
+    pub phys_addr: u64,
+    /// Where the kernel expects this segment to be mapped.
+    pub virt_addr: u64,
+    /// Size of the segment in memory, including its zeroed bytes.
+    pub mem_size: u64,
+    /// The elf segment flags.
+    pub flags: u64,
+}
+
+impl KernelSegment {
+    pub const fn is_executable(&self) -> bool {
+        self.flags & 1 != 0
+    }
+
+    pub const fn is_writable(&self) -> bool {
+        self.flags & 2 != 0
+    }
+}
+
+/// # Kernel Info
+/// Where each segment of the kernel was loaded, and where it starts.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct KernelInfo {
+    pub entry_point: u64,
+    pub segment_count: u64,
+    pub segments: [KernelSegment; MAX_KERNEL_SEGMENTS],
+}
+
+impl KernelInfo {
+    /// # Segments
+    /// The segments that have been loaded.
+    pub fn segments(&self) -> &[KernelSegment] {
+        &self.segments[..(self.segment_count as usize).min(MAX_KERNEL_SEGMENTS)]
+    }
+
+    /// # Push Segment
+    /// Record another loaded segment, returns `false` if there is no room left.
+    pub fn push_segment(&mut self, segment: KernelSegment) -> bool {
+        let Some(slot) = self.segments.get_mut(self.segment_count as usize) else {
+            return false;
+        };
+
+        *slot = segment;
+        self.segment_count += 1;
+        true
+    }
+}
+
 /// # `Stage16` to `Stage32` Info Block
 /// Used for sending data between these stages.
 #[repr(C)]
 pub struct Stage16toStage32 {
     pub stage64_ptr: u64,
-    pub kernel_ptr: (u64, u64),
+    pub kernel: KernelInfo,
     pub memory_map: [MemoryEntry; MAX_MEMORY_MAP_ENTRIES],
     pub video_mode: (VesaModeId, VesaMode),
+    /// Sum of all the bytes above, see [`Stage16toStage32::seal`].
+    pub checksum: u32,
+}
+
+impl Stage16toStage32 {
+    /// # Calculate Checksum
+    /// Sum every byte of this info block, excluding the checksum itself.
+    pub fn calculate_checksum(&self) -> u32 {
+        let bytes = unsafe {
+            core::slice::from_raw_parts(
+                self as *const Self as *const u8,
+                core::mem::offset_of!(Self, checksum),
+            )
+        };
+
+        bytes
+            .iter()
+            .fold(0u32, |sum, byte| sum.wrapping_add(*byte as u32))
+    }
+
+    /// # Seal
+    /// Store the checksum of this block, must be the last thing done before jumping stages.
+    pub fn seal(&mut self) {
+        self.checksum = self.calculate_checksum();
+    }
+
+    /// # Is Valid
+    /// Check if the stored checksum still matches the contents of this block.
+    pub fn is_valid(&self) -> bool {
+        self.checksum == self.calculate_checksum()
+    }
 }
 
 /// # `Stage32` to `Stage64` Info Block
 /// Used for sending data between these stages.
 #[repr(C)]
 pub struct Stage32toStage64 {
-    pub kernel_ptr: (u64, u64),
+    pub kernel: KernelInfo,
     pub memory_map: [MemoryEntry; MAX_MEMORY_MAP_ENTRIES],
     pub video_mode: (VesaModeId, VesaMode),
 }
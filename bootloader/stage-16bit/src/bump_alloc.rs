@@ -48,12 +48,8 @@ impl BumpAlloc {
         Some(core::slice::from_raw_parts_mut(allocation_start, size))
     }
 
-    pub fn push_ptr_to(&mut self, new_ptr: *mut u8) {
-        if new_ptr > self.end {
-            panic!("Cannot push ptr past end of allocation area!");
-        }
-
-        self.current_ptr = new_ptr;
+    pub fn current_ptr(&self) -> *mut u8 {
+        self.current_ptr
     }
 
     pub fn align_ptr_to(&mut self, alignment: usize) {
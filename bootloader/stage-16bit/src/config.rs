@@ -27,7 +27,9 @@ OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWA
 #[derive(Default)]
 pub struct BootloaderConfig<'a> {
     pub bootloader32: &'a str,
+    pub bootloader32_relocs: &'a str,
     pub bootloader64: &'a str,
+    pub bootloader64_relocs: &'a str,
     pub kernel: &'a str,
     pub expected_vbe_mode: Option<(u16, u16)>,
 }
@@ -50,7 +52,9 @@ impl<'a> BootloaderConfig<'a> {
         {
             match first_option {
                 "bootloader32" => config.bootloader32 = second_option,
+                "bootloader32-relocs" => config.bootloader32_relocs = second_option,
                 "bootloader64" => config.bootloader64 = second_option,
+                "bootloader64-relocs" => config.bootloader64_relocs = second_option,
                 "kernel" => config.kernel = second_option,
                 "vbe-mode" => {
                     let mut info_split = second_option.split('x');
@@ -23,7 +23,7 @@ DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE,
 OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
 */
 
-use bios::disk::raw_read;
+use bios::disk::{raw_read, MAX_SECTORS_PER_READ};
 use bios::BiosStatus;
 use fs::read_block::BlockDevice;
 
@@ -33,6 +33,11 @@ use fs::io::{Read, Seek, SeekFrom};
 #[link_section = ".buffer"]
 static mut TEMP_BUFFER: [u8; 512] = [0u8; 512];
 
+/// Batched reads need a buffer below 1MiB that does not cross a segment, so
+/// it gets its own 64K region in low memory.
+#[link_section = ".disk_buffer"]
+static mut BATCH_BUFFER: [u8; MAX_SECTORS_PER_READ * 512] = [0u8; MAX_SECTORS_PER_READ * 512];
+
 pub struct BiosDisk {
     id: u16,
     seek: u64,
@@ -53,13 +58,48 @@ impl BlockDevice for BiosDisk {
     fn read_block<'a>(&'a mut self, block_offset: u64) -> Result<&'a [u8]> {
         unsafe {
             #[allow(static_mut_refs)]
-            match raw_read(self.id, block_offset, 1, TEMP_BUFFER.as_mut_ptr()) {
-                BiosStatus::Success => Ok(TEMP_BUFFER.as_slice()),
-                BiosStatus::InvalidInput | BiosStatus::InvalidData => Err(FsError::InvalidInput),
-                BiosStatus::NotSupported => Err(FsError::NotSupported),
-                BiosStatus::Failed => Err(FsError::ReadError),
+            status_to_result(raw_read(self.id, block_offset, 1, TEMP_BUFFER.as_mut_ptr()))?;
+            #[allow(static_mut_refs)]
+            Ok(TEMP_BUFFER.as_slice())
+        }
+    }
+
+    fn read_blocks(&mut self, block_offset: u64, buf: &mut [u8]) -> Result<usize> {
+        let mut bytes_read = 0;
+
+        for (batch_index, batch) in buf
+            .chunks_mut(MAX_SECTORS_PER_READ * Self::BLOCK_SIZE)
+            .enumerate()
+        {
+            let sectors = batch.len() / Self::BLOCK_SIZE;
+            let batch_bytes = sectors * Self::BLOCK_SIZE;
+
+            if sectors == 0 {
+                break;
             }
+
+            let lba = block_offset + (batch_index * MAX_SECTORS_PER_READ) as u64;
+
+            unsafe {
+                #[allow(static_mut_refs)]
+                status_to_result(raw_read(self.id, lba, sectors, BATCH_BUFFER.as_mut_ptr()))?;
+                #[allow(static_mut_refs)]
+                batch[..batch_bytes].copy_from_slice(&BATCH_BUFFER[..batch_bytes]);
+            }
+
+            bytes_read += batch_bytes;
         }
+
+        Ok(bytes_read)
+    }
+}
+
+fn status_to_result(status: BiosStatus) -> Result<()> {
+    match status {
+        BiosStatus::Success => Ok(()),
+        BiosStatus::InvalidInput | BiosStatus::InvalidData => Err(FsError::InvalidInput),
+        BiosStatus::NotSupported => Err(FsError::NotSupported),
+        BiosStatus::Failed => Err(FsError::ReadError),
     }
 }
 
@@ -0,0 +1,56 @@
+/*
+  ____                 __               __                __
+ / __ \__ _____ ____  / /___ ____ _    / /  ___  ___ ____/ /__ ____
+/ /_/ / // / _ `/ _ \/ __/ // /  ' \  / /__/ _ \/ _ `/ _  / -_) __/
+\___\_\_,_/\_,_/_//_/\__/\_,_/_/_/_/ /____/\___/\_,_/\_,_/\__/_/
+    Part of the Quantum OS Project
+
+Copyright 2024 Gavin Kellam
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and
+associated documentation files (the "Software"), to deal in the Software without restriction,
+including without limitation the rights to use, copy, modify, merge, publish, distribute,
+sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial
+portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT
+NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
+OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+use elf::{stream::ReadAt, ElfErrorKind};
+use fs::fatfs::{FatFile, ReadSeek};
+use fs::io::{Read, Seek, SeekFrom};
+
+/// # Kernel File
+/// Lets the kernel's elf be read straight out of its FAT file, so only the
+/// parts of it that get loaded are ever read from disk.
+pub struct KernelFile<'a, Part: ReadSeek>(pub FatFile<'a, Part>);
+
+impl<'a, Part: ReadSeek> ReadAt for KernelFile<'a, Part> {
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> elf::Result<()> {
+        if buf.is_empty() {
+            return Ok(());
+        }
+
+        let end = offset
+            .checked_add(buf.len() as u64)
+            .ok_or(ElfErrorKind::NotEnoughBytes)?;
+
+        if end > self.0.filesize() as u64 {
+            return Err(ElfErrorKind::NotEnoughBytes);
+        }
+
+        self.0
+            .seek(SeekFrom::Start(offset))
+            .map_err(|_| ElfErrorKind::ReadError)?;
+        self.0.read(buf).map_err(|_| ElfErrorKind::ReadError)?;
+
+        Ok(())
+    }
+}
@@ -26,27 +26,44 @@ OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWA
 #![no_std]
 #![no_main]
 
-use crate::{disk::BiosDisk, mbr::Mbr};
+use crate::{disk::BiosDisk, kernel::KernelFile, mbr::Mbr};
 use bios::memory::MemoryEntry;
-use bios::video::Vesa;
-use bootloader::Stage16toStage32;
+use bios::video::{self, Vesa};
+use bootloader::{KernelSegment, Stage16toStage32, MAX_KERNEL_SEGMENTS};
 use bump_alloc::BumpAlloc;
 use config::BootloaderConfig;
-use fs::fatfs::Fat;
+use elf::stream::ElfReader;
+use elf::tables::{ArchKind, SegmentKind};
+use fs::cache::{CachedBlockDevice, WritePolicy};
+use fs::fatfs::{Fat, ReadSeek};
 use fs::io::Read;
 use lldebug::make_debug;
-use lldebug::{debug_ready, logln};
-use serial::Serial;
+use lldebug::{debug_ready, logln, telemetry};
+use reloc::RelocationTable;
+use serial::{debugcon, Serial};
 use unreal::enter_unreal;
 
 mod bump_alloc;
 mod config;
 mod disk;
+mod kernel;
 mod mbr;
 mod memory;
 mod panic;
+mod reloc;
 mod unreal;
 
+/// Stage32 only identity maps the first 1GiB of memory, so everything stage64 needs
+/// must be loaded below this address.
+const IDENTITY_MAPPED_END: u64 = 1024 * 1024 * 1024;
+
+/// Size of the stack given to stage32 and stage64.
+const STAGE_STACK_SIZE: usize = 1024 * 1024;
+
+/// Sectors kept in memory while walking the boot partition, the cache lives on
+/// our (small) stack so this is kept low.
+const DISK_CACHE_SECTORS: usize = 8;
+
 make_debug! {
     "Serial": Option<Serial> = Serial::probe_first(serial::baud::SerialBaud::Baud115200);
 }
@@ -60,9 +77,63 @@ extern "C" fn entry(disk_id: u16) {
     main(disk_id);
 }
 
+/// # Read Relocations
+/// Read the relocation table of a stage, which meta builds from the stage's elf.
+fn read_relocations<Part: ReadSeek>(
+    fatfs: &mut Fat<Part>,
+    alloc: &mut BumpAlloc,
+    path: &str,
+) -> RelocationTable<'static> {
+    let mut relocs = fatfs
+        .open(path)
+        .expect("Unable to find a stage's relocation table");
+    let relocs_buffer = unsafe { alloc.allocate(relocs.filesize()) }.unwrap();
+    relocs
+        .read(relocs_buffer)
+        .expect("Unable to read a stage's relocation table");
+
+    RelocationTable::new(relocs_buffer).expect("Stage's relocation table is not valid!")
+}
+
+/// # Load Stage
+/// Load the stage at `path` into the first free memory (from the memory map) after
+/// `search_start`, and relocate it to run from there.
+fn load_stage<Part: ReadSeek>(
+    fatfs: &mut Fat<Part>,
+    memory_map: &[MemoryEntry],
+    path: &str,
+    relocs: &RelocationTable,
+    search_start: u64,
+) -> &'static mut [u8] {
+    let mut stage = fatfs.open(path).expect("Unable to find stage");
+
+    // The file doesn't always include the stage's .bss
+    let image_size = relocs.image_size().max(stage.filesize());
+    let load_base = memory::find_free_region(
+        memory_map,
+        search_start..IDENTITY_MAPPED_END,
+        image_size as u64,
+        4096,
+    )
+    .expect("Cannot find free memory for stage!");
+
+    let image = unsafe { core::slice::from_raw_parts_mut(load_base as *mut u8, image_size) };
+    let (file, bss) = image.split_at_mut(stage.filesize());
+    stage.read(file).expect("Unable to read stage");
+    bss.fill(0);
+
+    relocs
+        .relocate(image, load_base)
+        .expect("Stage's relocation table does not fit its image!");
+
+    image
+}
+
 #[debug_ready]
 fn main(disk_id: u16) -> ! {
     logln!("Quantum Loader");
+    debugcon::install_telemetry();
+    telemetry!(boot_phase: "stage16");
 
     // - Memory Setup
     let memory_map = crate::memory::memory_map();
@@ -88,7 +159,12 @@ fn main(disk_id: u16) -> ! {
     //        since partitions currently cannot be used to create Fats that
     //        escape this closure. This means we need to create a new Fat
     //        which should be avoided if its already known to be valid.
-    let mut mbr = Mbr::new(BiosDisk::new(disk_id)).expect("Cannot read MBR!");
+    let disk = CachedBlockDevice::<_, 512, DISK_CACHE_SECTORS>::new(
+        BiosDisk::new(disk_id),
+        WritePolicy::WriteThrough,
+    )
+    .expect("Cannot cache boot disk!");
+    let mut mbr = Mbr::new(disk).expect("Cannot read MBR!");
     let partition_number = (0..4)
         .into_iter()
         .find_map(|part_number| {
@@ -120,20 +196,13 @@ fn main(disk_id: u16) -> ! {
     let (want_x, want_y) = qconfig.expected_vbe_mode.unwrap_or((800, 600));
 
     let vesa = Vesa::quarry().unwrap();
-    let (closest_video_id, closest_video_info) = vesa
-        .modes()
-        .filter_map(|id| id.querry().ok().map(|mode| (id, mode)))
-        .filter(|(_, mode)| mode.bpp == 32)
-        .reduce(|closest_mode, (id, mode)| {
-            if closest_mode.1.width.abs_diff(want_x) > mode.width.abs_diff(want_x)
-                && closest_mode.1.height.abs_diff(want_y) > mode.height.abs_diff(want_y)
-            {
-                (id, mode)
-            } else {
-                closest_mode
-            }
-        })
-        .expect("Failed to find a optimal video mode");
+    let (closest_video_id, closest_video_info) = video::closest_mode(
+        vesa.modes()
+            .filter_map(|id| id.querry().ok().map(|mode| (id, mode))),
+        (want_x, want_y),
+        32,
+    )
+    .expect("Failed to find a optimal video mode");
 
     logln!(
         "Optimal Video Mode  = (0x{:00x}) {:?}",
@@ -161,59 +230,111 @@ fn main(disk_id: u16) -> ! {
 
     stage_to_stage.video_mode = (closest_video_id, closest_video_info);
 
-    // - Bootloader32
-    let mut bootloader32 = fatfs
-        .open(qconfig.bootloader32)
-        .expect("Unable to find bootloader32");
-
-    // Our bootloader needs to be at 0x00200000
-    let bootloader32_entrypoint = 0x00200000 as *mut u8;
-    alloc.push_ptr_to(bootloader32_entrypoint);
+    // - Stage Relocations
+    // Read before any stage is placed, so they are never overwritten by one
+    let bootloader32_relocs = read_relocations(&mut fatfs, &mut alloc, qconfig.bootloader32_relocs);
+    let bootloader64_relocs = read_relocations(&mut fatfs, &mut alloc, qconfig.bootloader64_relocs);
 
-    logln!("stage32 size = {} Bytes", bootloader32.filesize());
-    let bootloader32_buffer = unsafe { alloc.allocate(bootloader32.filesize()) }.unwrap();
-    bootloader32
-        .read(bootloader32_buffer)
-        .expect("Unable to read bootloader32");
+    // - Bootloader32
+    let bootloader32_buffer = load_stage(
+        &mut fatfs,
+        memory_map,
+        qconfig.bootloader32,
+        &bootloader32_relocs,
+        alloc.current_ptr() as u64,
+    );
+    let bootloader32_entrypoint = bootloader32_buffer.as_mut_ptr();
+    logln!(
+        "stage32 size = {} Bytes (loaded at 0x{:08x})",
+        bootloader32_buffer.len(),
+        bootloader32_entrypoint as u64
+    );
 
     // - Bootloader64
-    let mut bootloader64 = fatfs
-        .open(qconfig.bootloader64)
-        .expect("Unable to find bootloader64");
+    let bootloader64_buffer = load_stage(
+        &mut fatfs,
+        memory_map,
+        qconfig.bootloader64,
+        &bootloader64_relocs,
+        bootloader32_buffer.as_ptr_range().end as u64,
+    );
+    let bootloader64_entrypoint = bootloader64_buffer.as_mut_ptr();
+    logln!(
+        "stage64 size = {} Bytes (loaded at 0x{:08x})",
+        bootloader64_buffer.len(),
+        bootloader64_entrypoint as u64
+    );
 
-    // Our bootloader needs to be at 0x00400000
-    let bootloader64_entrypoint = 0x00400000 as *mut u8;
-    alloc.push_ptr_to(bootloader64_entrypoint);
+    // kernel elf file
+    let mut kernel_elf = ElfReader::new(KernelFile(
+        fatfs.open(qconfig.kernel).expect("Unable to find kernel"),
+    ))
+    .expect("Kernel's elf is not valid!");
+
+    let kernel_header = kernel_elf.init_header();
+    assert!(
+        kernel_header.is_64bit() && kernel_header.is_le() && kernel_header.arch() == ArchKind::X64,
+        "Kernel's elf is not a little endian x86_64 elf!"
+    );
 
-    logln!("stage64 size = {} Bytes", bootloader64.filesize());
-    let bootloader64_buffer = unsafe { alloc.allocate(bootloader64.filesize()) }.unwrap();
-    bootloader64
-        .read(bootloader64_buffer)
-        .expect("Unable to read bootloader64");
+    // The kernel's segments are only mapped by stage64, so they (and the stack) can be
+    // placed in any free memory after stage64.
+    let kernel_region_size = kernel_elf
+        .load_size(4096)
+        .expect("Unable to read the kernel's program headers")
+        + STAGE_STACK_SIZE;
+    let kernel_offset = memory::find_free_region(
+        memory_map,
+        (bootloader64_buffer.as_ptr_range().end as u64)..IDENTITY_MAPPED_END,
+        kernel_region_size as u64,
+        4096,
+    )
+    .expect("Cannot find free memory for the kernel!");
+    alloc = unsafe { BumpAlloc::new(kernel_offset as *mut u8, kernel_region_size) };
 
-    // kernel elf file
-    let kernel_offset = 0x00500000 as *mut u8;
-    alloc.push_ptr_to(kernel_offset);
+    logln!(
+        "kernel size = {} Bytes (loaded at 0x{:08x})",
+        kernel_region_size - STAGE_STACK_SIZE,
+        kernel_offset
+    );
 
-    let mut kernel_file = fatfs.open(qconfig.kernel).expect("Unable to find kernel");
+    stage_to_stage.kernel.segment_count = 0;
+    stage_to_stage.kernel.entry_point = kernel_elf
+        .load_into(|header| {
+            if header.segment_kind() != SegmentKind::Load {
+                return None;
+            }
 
-    logln!("kernel size = {} Bytes", kernel_file.filesize());
-    let kernel_buffer = unsafe { alloc.allocate(kernel_file.filesize()) }.unwrap();
-    kernel_file
-        .read(kernel_buffer)
+            alloc.align_ptr_to(4096);
+            let segment = unsafe { alloc.allocate(header.in_mem_size()) }.unwrap();
+
+            assert!(
+                stage_to_stage.kernel.push_segment(KernelSegment {
+                    phys_addr: segment.as_ptr() as u64,
+                    virt_addr: header.expected_vaddr(),
+                    mem_size: header.in_mem_size() as u64,
+                    flags: header.flags() as u64,
+                }),
+                "Kernel has more than {} loadable segments!",
+                MAX_KERNEL_SEGMENTS
+            );
+
+            Some(segment)
+        })
         .expect("Unable to read kernel");
 
-    let stack_region = unsafe { alloc.allocate(1024 * 1024) }.unwrap();
+    alloc.align_ptr_to(4096);
+    let stack_region = unsafe { alloc.allocate(STAGE_STACK_SIZE) }.unwrap();
 
     closest_video_id.set().expect("Unable to set video mode");
 
     stage_to_stage.stage64_ptr = bootloader64_entrypoint as u64;
-    stage_to_stage.kernel_ptr = (kernel_buffer.as_ptr() as u64, kernel_buffer.len() as u64);
+    stage_to_stage.seal();
 
     unsafe {
         unreal::enter_stage2(
             bootloader32_entrypoint,
-            stack_region.as_ptr().add(1024 * 1024),
+            stack_region.as_ptr().add(STAGE_STACK_SIZE),
             stage_to_stage as *const Stage16toStage32,
         )
     };
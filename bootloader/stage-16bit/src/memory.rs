@@ -24,7 +24,7 @@ OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWA
 */
 
 use bios::memory::MemoryEntry;
-use core::mem::MaybeUninit;
+use core::{mem::MaybeUninit, ops::Range};
 
 #[no_mangle]
 static mut MEMORY_MAP_AREA: MaybeUninit<[MemoryEntry; 16]> = MaybeUninit::zeroed();
@@ -35,3 +35,27 @@ pub fn memory_map() -> &'static [MemoryEntry] {
         unsafe { bios::memory::read_mapping(MEMORY_MAP_AREA.assume_init_mut()) }.unwrap();
     unsafe { &MEMORY_MAP_AREA.assume_init_mut()[..stable_regions] }
 }
+
+/// # Find Free Region
+/// Find the lowest address in `search` (aligned to `alignment`) where `len` bytes
+/// fit inside one free region of the memory map.
+pub fn find_free_region(
+    memory_map: &[MemoryEntry],
+    search: Range<u64>,
+    len: u64,
+    alignment: u64,
+) -> Option<u64> {
+    memory_map
+        .iter()
+        .filter(|region| region.region_type == MemoryEntry::REGION_FREE)
+        .filter_map(|region| {
+            let start = region
+                .base_address
+                .max(search.start)
+                .next_multiple_of(alignment);
+            let end = (region.base_address + region.region_length).min(search.end);
+
+            (start + len <= end).then_some(start)
+        })
+        .min()
+}
@@ -0,0 +1,98 @@
+/*
+  ____                 __               __                __
+ / __ \__ _____ ____  / /___ ____ _    / /  ___  ___ ____/ /__ ____
+/ /_/ / // / _ `/ _ \/ __/ // /  ' \  / /__/ _ \/ _ `/ _  / -_) __/
+\___\_\_,_/\_,_/_//_/\__/\_,_/_/_/_/ /____/\___/\_,_/\_,_/\__/_/
+    Part of the Quantum OS Project
+
+Copyright 2024 Gavin Kellam
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and
+associated documentation files (the "Software"), to deal in the Software without restriction,
+including without limitation the rights to use, copy, modify, merge, publish, distribute,
+sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial
+portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT
+NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
+OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+/// # Relocation Table
+/// The fixups meta found for a stage, see meta's `relocs.rs` for the layout.
+///
+/// Stage32 and stage64 are linked at a fixed address, this lets us load them
+/// wherever the memory map has room instead.
+pub struct RelocationTable<'a> {
+    link_base: u64,
+    image_size: usize,
+    fixups32: &'a [u8],
+    fixups64: &'a [u8],
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        bytes.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+impl<'a> RelocationTable<'a> {
+    const HEADER_SIZE: usize = 24;
+
+    pub fn new(table: &'a [u8]) -> Option<Self> {
+        if table.get(..4)? != b"QREL" {
+            return None;
+        }
+
+        let link_base = u64::from_le_bytes(table.get(4..12)?.try_into().ok()?);
+        let image_size = read_u32(table, 12)? as usize;
+        let count32 = read_u32(table, 16)? as usize;
+        let count64 = read_u32(table, 20)? as usize;
+
+        let (fixups32, rest) = table
+            .get(Self::HEADER_SIZE..)?
+            .split_at_checked(count32 * 4)?;
+        let fixups64 = rest.get(..count64 * 4)?;
+
+        Some(Self {
+            link_base,
+            image_size,
+            fixups32,
+            fixups64,
+        })
+    }
+
+    /// # Image Size
+    /// How much memory the stage needs, including its `.bss`.
+    pub const fn image_size(&self) -> usize {
+        self.image_size
+    }
+
+    /// # Relocate
+    /// Fix up every absolute address in `image` for it running at `load_base`,
+    /// returns `None` if a fixup is outside of the image.
+    pub fn relocate(&self, image: &mut [u8], load_base: u64) -> Option<()> {
+        let delta = load_base.wrapping_sub(self.link_base);
+
+        for offset in (0..self.fixups32.len()).step_by(4) {
+            let offset = read_u32(self.fixups32, offset)? as usize;
+            let word = image.get_mut(offset..offset + 4)?;
+            let value = u32::from_le_bytes(word.try_into().ok()?).wrapping_add(delta as u32);
+            word.copy_from_slice(&value.to_le_bytes());
+        }
+
+        for offset in (0..self.fixups64.len()).step_by(4) {
+            let offset = read_u32(self.fixups64, offset)? as usize;
+            let word = image.get_mut(offset..offset + 8)?;
+            let value = u64::from_le_bytes(word.try_into().ok()?).wrapping_add(delta);
+            word.copy_from_slice(&value.to_le_bytes());
+        }
+
+        Some(())
+    }
+}
@@ -0,0 +1,166 @@
+/*
+  ____                 __               __                __
+ / __ \__ _____ ____  / /___ ____ _    / /  ___  ___ ____/ /__ ____
+/ /_/ / // / _ `/ _ \/ __/ // /  ' \  / /__/ _ \/ _ `/ _  / -_) __/
+\___\_\_,_/\_,_/_//_/\__/\_,_/_/_/_/ /____/\___/\_,_/\_,_/\__/_/
+    Part of the Quantum OS Project
+
+Copyright 2024 Gavin Kellam
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and
+associated documentation files (the "Software"), to deal in the Software without restriction,
+including without limitation the rights to use, copy, modify, merge, publish, distribute,
+sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial
+portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT
+NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
+OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+use arch::{
+    cpuid::{self, CpuFeatures},
+    gdt::GdtPointer,
+};
+use bootloader::Stage16toStage32;
+use core::ops::Range;
+use lldebug::{log, logln};
+
+/// # Dump GDT
+/// Print every entry of the currently loaded GDT.
+pub fn dump_gdt() {
+    let gdt = GdtPointer::current();
+
+    for (index, entry) in unsafe { gdt.entries() }.iter().enumerate() {
+        let base = ((entry >> 16) & 0xFFFFFF) | (((entry >> 56) & 0xFF) << 24);
+        let limit = (entry & 0xFFFF) | (((entry >> 48) & 0xF) << 16);
+        let access = (entry >> 40) & 0xFF;
+        let flags = (entry >> 52) & 0xF;
+
+        logln!(
+            "GDT[{index}] = 0x{entry:016x} (base=0x{base:08x} limit=0x{limit:05x} access=0b{access:08b} flags=0b{flags:04b})"
+        );
+    }
+}
+
+/// # A20 Enabled
+/// Check if the A20 line is enabled by seeing if the bootsector's signature (`0x7DFE`)
+/// shows up again 1MiB higher.
+///
+/// Stage16 loads us (and our allocations) above 1MiB, so only the low address is ever
+/// written to, and it's restored afterwards.
+pub fn a20_enabled() -> bool {
+    let low = 0x7DFE as *mut u16;
+    let high = 0x0010_7DFE as *const u16;
+
+    unsafe {
+        let before = low.read_volatile();
+        if high.read_volatile() != before {
+            return true;
+        }
+
+        // They could just happen to hold the same value, so change ours and look again
+        low.write_volatile(!before);
+        let enabled = high.read_volatile() != !before;
+        low.write_volatile(before);
+
+        enabled
+    }
+}
+
+/// # Memory Pattern Test
+/// Test that every word in `region` can hold a few bit patterns.
+///
+/// This test is non-destructive, each word is restored after testing it. Returns the
+/// address of the first word that failed.
+pub fn memory_pattern_test(region: Range<u64>) -> Result<(), u64> {
+    const PATTERNS: [u32; 4] = [0x0000_0000, 0xFFFF_FFFF, 0x5555_5555, 0xAAAA_AAAA];
+
+    let start = region.start.next_multiple_of(size_of::<u32>() as u64);
+    for addr in (start..region.end).step_by(size_of::<u32>()) {
+        if addr + size_of::<u32>() as u64 > region.end {
+            break;
+        }
+
+        let ptr = addr as *mut u32;
+        let before = unsafe { ptr.read_volatile() };
+
+        let failed =
+            PATTERNS
+                .iter()
+                .chain(core::iter::once(&(addr as u32)))
+                .any(|&pattern| unsafe {
+                    ptr.write_volatile(pattern);
+                    ptr.read_volatile() != pattern
+                });
+
+        unsafe { ptr.write_volatile(before) };
+
+        if failed {
+            return Err(addr);
+        }
+    }
+
+    Ok(())
+}
+
+/// # Missing CPU Feature
+/// Check the CPU supports everything we need to enter long mode, returning the name
+/// of the first missing feature.
+pub fn missing_cpu_feature() -> Option<&'static str> {
+    if !cpuid::is_supported() {
+        return Some("CPUID");
+    }
+
+    let features = CpuFeatures::read();
+
+    [
+        ("Long Mode", features.long_mode),
+        ("PAE", features.physical_address_extension),
+    ]
+    .into_iter()
+    .find_map(|(name, supported)| (!supported).then_some(name))
+}
+
+/// # Run Diagnostics
+/// Check the state we were left in by stage16, and the memory we are about to use.
+///
+/// Panics with a description of the first problem found.
+pub fn run(stage_to_stage: &Stage16toStage32, regions: &[(&str, Range<u64>)]) {
+    logln!("Inherited GDT:");
+    dump_gdt();
+
+    log!("Checking A20 Line...");
+    assert!(a20_enabled(), "A20 line is not enabled!");
+    logln!("OK");
+
+    log!("Checking Stage-to-Stage Checksum...");
+    assert!(
+        stage_to_stage.is_valid(),
+        "Stage-to-Stage block is corrupt! (stored=0x{:08x}, calculated=0x{:08x})",
+        stage_to_stage.checksum,
+        stage_to_stage.calculate_checksum()
+    );
+    logln!("OK");
+
+    for (name, region) in regions {
+        log!(
+            "Testing '{}' memory (0x{:08x}..0x{:08x})...",
+            name,
+            region.start,
+            region.end
+        );
+        if let Err(addr) = memory_pattern_test(region.clone()) {
+            panic!(
+                "Memory test failed for '{}' at address 0x{:08x}!",
+                name, addr
+            );
+        }
+        logln!("OK");
+    }
+}
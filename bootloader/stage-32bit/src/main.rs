@@ -27,17 +27,18 @@ OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWA
 #![no_std]
 #![feature(sync_unsafe_cell)]
 
-use core::{arch::asm, cell::SyncUnsafeCell};
+use core::{arch::asm, cell::SyncUnsafeCell, fmt::Write};
 
 use arch::{
     gdt::{CodeSegmentDesc, DataSegmentDesc, GlobalDescriptorTable},
     registers::{Segment, SegmentRegisters},
 };
-use bootgfx::{Color, Framebuffer};
+use bootgfx::{terminal::Terminal, Color, Framebuffer};
 use bootloader::{Stage16toStage32, Stage32toStage64};
-use lldebug::{debug_ready, logln, make_debug};
-use serial::{baud::SerialBaud, Serial};
+use lldebug::{debug_ready, logln, make_debug, telemetry};
+use serial::{baud::SerialBaud, debugcon, Serial};
 
+mod diagnostics;
 mod paging;
 mod panic;
 
@@ -59,6 +60,24 @@ extern "C" fn _start(stage_to_stage: u32) {
 
 #[debug_ready]
 fn main(stage_to_stage: &Stage16toStage32) {
+    debugcon::install_telemetry();
+    telemetry!(boot_phase: "stage32");
+
+    let [lvl4, lvl3, lvl2] = paging::page_table_regions();
+    let s2s_start = S2S.get() as u64;
+    diagnostics::run(
+        stage_to_stage,
+        &[
+            ("Page Table Lvl4", lvl4),
+            ("Page Table Lvl3", lvl3),
+            ("Page Table Lvl2", lvl2),
+            (
+                "Stage32to64",
+                s2s_start..(s2s_start + size_of::<Stage32toStage64>() as u64),
+            ),
+        ],
+    );
+
     let mut framebuffer = unsafe {
         Framebuffer::new_linear(
             stage_to_stage.video_mode.1.framebuffer as *mut u32,
@@ -80,6 +99,17 @@ fn main(stage_to_stage: &Stage16toStage32) {
     framebuffer.draw_glyph(20, 10, 'O', Color::WHITE);
     framebuffer.draw_glyph(30, 10, 'S', Color::WHITE);
 
+    if let Some(missing) = diagnostics::missing_cpu_feature() {
+        let mut terminal = Terminal::new(&mut framebuffer);
+        let _ = write!(
+            terminal,
+            "\x1b[3;2H\x1b[1;31mCPU not supported:\x1b[0m missing {}",
+            missing
+        );
+
+        panic!("CPU not supported: missing {}", missing);
+    }
+
     unsafe { paging::enable_paging() };
 
     // load gdt
@@ -104,13 +134,14 @@ fn main(stage_to_stage: &Stage16toStage32) {
         // load
         gdt.pack().load();
         logln!("Loaded long mode GDT!");
+        diagnostics::dump_gdt();
     }
 
     // build s2s
     unsafe {
         let s2s = &mut *S2S.get();
 
-        s2s.kernel_ptr = stage_to_stage.kernel_ptr;
+        s2s.kernel = stage_to_stage.kernel;
         s2s.memory_map = stage_to_stage.memory_map;
         s2s.video_mode = stage_to_stage.video_mode.clone();
 
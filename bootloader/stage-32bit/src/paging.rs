@@ -24,11 +24,12 @@ OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWA
 */
 
 use arch::{
+    msr::ia32_efer,
     paging64::{PageEntry2M, PageEntryLvl3, PageEntryLvl4, PageMapLvl2, PageMapLvl3, PageMapLvl4},
-    registers::{cr0, cr3, cr4, ia32_efer, Segment, SegmentRegisters},
+    registers::{cr0, cr3, cr4, Segment, SegmentRegisters},
     CpuPrivilege,
 };
-use core::cell::SyncUnsafeCell;
+use core::{cell::SyncUnsafeCell, ops::Range};
 use lldebug::{log, logln};
 use util::consts::{GIB, MIB};
 
@@ -40,6 +41,21 @@ static TABLE_LVL3: SyncUnsafeCell<PageMapLvl3> = SyncUnsafeCell::new(PageMapLvl3
 static TABLE_LVL2: SyncUnsafeCell<[PageMapLvl2; IDMAP_GIG_AMOUNT]> =
     SyncUnsafeCell::new([PageMapLvl2::new(); IDMAP_GIG_AMOUNT]);
 
+/// # Page Table Regions
+/// The memory the page tables will occupy once we start mapping.
+pub fn page_table_regions() -> [Range<u64>; 3] {
+    fn region_of<T>(table: &SyncUnsafeCell<T>) -> Range<u64> {
+        let start = table.get() as u64;
+        start..(start + size_of::<T>() as u64)
+    }
+
+    [
+        region_of(&TABLE_LVL4),
+        region_of(&TABLE_LVL3),
+        region_of(&TABLE_LVL2),
+    ]
+}
+
 pub fn identity_map() {
     for gig in 0..IDMAP_GIG_AMOUNT {
         let table_ptr = unsafe { &raw mut (*TABLE_LVL2.get())[gig] };
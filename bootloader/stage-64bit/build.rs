@@ -34,5 +34,11 @@ fn main() {
         local_path
             .join("../linkerscripts/x86-64-quantum_loader.ld")
             .display()
-    )
+    );
+
+    // Keep the relocations in the ELF, so meta can build the table stage16 uses to
+    // load this stage at any address. Relaxing would turn some GOT loads into absolute
+    // immediates that no relocation describes.
+    println!("cargo:rustc-link-arg-bins=--emit-relocs");
+    println!("cargo:rustc-link-arg-bins=--no-relax");
 }
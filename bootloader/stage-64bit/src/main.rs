@@ -27,12 +27,8 @@ OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWA
 #![no_std]
 
 use bootloader::Stage32toStage64;
-use elf::{
-    Elf,
-    tables::{ArchKind, SegmentKind},
-};
-use lldebug::{debug_ready, logln, make_debug};
-use serial::{Serial, baud::SerialBaud};
+use lldebug::{debug_ready, logln, make_debug, telemetry};
+use serial::{Serial, baud::SerialBaud, debugcon};
 
 mod panic;
 
@@ -50,24 +46,18 @@ extern "C" fn _start(stage_to_stage: u64) {
 #[debug_ready]
 fn main(stage_to_stage: &Stage32toStage64) {
     logln!("Stage64!");
-    let (kernel_elf_ptr, kernel_elf_size) = stage_to_stage.kernel_ptr;
+    debugcon::install_telemetry();
+    telemetry!(boot_phase: "stage64");
     logln!("Memory Map {:#?}", stage_to_stage.memory_map);
 
-    let elf = Elf::new(unsafe {
-        core::slice::from_raw_parts(kernel_elf_ptr as *const u8, kernel_elf_size as usize)
-    });
-
-    let elf_header = match elf.header() {
-        Ok(elf::tables::ElfHeader::Header64(h)) if h.arch() == ArchKind::X64 && h.is_le() => h,
-        _ => panic!("Kernel's elf is not valid!"),
-    };
-
-    elf.load_into(|h| {
-        if h.segment_kind() != SegmentKind::Load {
-            return None;
-        }
-
-        None
-    })
-    .unwrap();
+    let kernel = &stage_to_stage.kernel;
+    for segment in kernel.segments() {
+        logln!(
+            "Kernel segment 0x{:016x} -> 0x{:016x} ({} Bytes)",
+            segment.virt_addr,
+            segment.phys_addr,
+            segment.mem_size
+        );
+    }
+    logln!("Kernel entry point = 0x{:016x}", kernel.entry_point);
 }
@@ -0,0 +1,505 @@
+/*
+  ____                 __               __   _ __
+ / __ \__ _____ ____  / /___ ____ _    / /  (_) /
+/ /_/ / // / _ `/ _ \/ __/ // /  ' \  / /__/ / _ \
+\___\_\_,_/\_,_/_//_/\__/\_,_/_/_/_/ /____/_/_.__/
+    Part of the Quantum OS Project
+
+Copyright 2024 Gavin Kellam
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and
+associated documentation files (the "Software"), to deal in the Software without restriction,
+including without limitation the rights to use, copy, modify, merge, publish, distribute,
+sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial
+portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT
+NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
+OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+use crate::{
+    cpuid::CpuFeatures,
+    msr::{ia32_apic_base, rdmsr, wrmsr},
+};
+use hw::make_hw;
+
+/// # Apic Mode
+/// How the local APIC's registers are accessed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ApicMode {
+    /// Registers are memory mapped at the APIC base address.
+    XApic,
+    /// Registers are accessed through MSRs.
+    X2Apic,
+}
+
+/// # Detect
+/// Get the best APIC mode this CPU supports, or `None` if it has no APIC.
+pub fn detect() -> Option<ApicMode> {
+    let features = CpuFeatures::read();
+
+    if features.x2apic {
+        Some(ApicMode::X2Apic)
+    } else if features.apic {
+        Some(ApicMode::XApic)
+    } else {
+        None
+    }
+}
+
+/// # Local Apic Register
+/// The registers of the local APIC, as offsets from the xAPIC base.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u32)]
+pub enum LocalApicReg {
+    Id = 0x020,
+    Version = 0x030,
+    TaskPriority = 0x080,
+    EndOfInterrupt = 0x0B0,
+    SpuriousVector = 0x0F0,
+    ErrorStatus = 0x280,
+    InterruptCommandLow = 0x300,
+    InterruptCommandHigh = 0x310,
+    LvtTimer = 0x320,
+    LvtPerformanceCounter = 0x340,
+    LvtLint0 = 0x350,
+    LvtLint1 = 0x360,
+    LvtError = 0x370,
+    TimerInitialCount = 0x380,
+    TimerCurrentCount = 0x390,
+    TimerDivide = 0x3E0,
+}
+
+impl LocalApicReg {
+    const fn x2apic_msr(self) -> u32 {
+        0x800 + (self as u32 >> 4)
+    }
+}
+
+/// # Timer Mode
+/// How the local APIC timer counts down.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimerMode {
+    /// Fire once when the count reaches zero.
+    OneShot,
+    /// Reload the initial count and fire every time it reaches zero.
+    Periodic,
+}
+
+/// # Timer Divide
+/// What the bus clock is divided by before it drives the timer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimerDivide {
+    By1,
+    By2,
+    By4,
+    By8,
+    By16,
+    By32,
+    By64,
+    By128,
+}
+
+impl TimerDivide {
+    const fn encode(self) -> u32 {
+        match self {
+            Self::By1 => 0b1011,
+            Self::By2 => 0b0000,
+            Self::By4 => 0b0001,
+            Self::By8 => 0b0010,
+            Self::By16 => 0b0011,
+            Self::By32 => 0b1000,
+            Self::By64 => 0b1001,
+            Self::By128 => 0b1010,
+        }
+    }
+}
+
+/// # Ipi Kind
+/// The kind of inter-processor interrupt to send.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IpiKind {
+    /// A normal interrupt on the given vector.
+    Fixed(u8),
+    /// A non-maskable interrupt.
+    Nmi,
+    /// Reset the target CPU into its wait-for-SIPI state.
+    Init,
+    /// Start the target CPU in real mode at `page * 4096`.
+    Startup(u8),
+}
+
+impl IpiKind {
+    const LEVEL_ASSERT: u32 = 1 << 14;
+
+    /// The low 32 bits of the interrupt command register for this IPI.
+    const fn icr_low(self) -> u32 {
+        let (delivery_mode, vector) = match self {
+            Self::Fixed(vector) => (0b000, vector),
+            Self::Nmi => (0b100, 0),
+            Self::Init => (0b101, 0),
+            Self::Startup(page) => (0b110, page),
+        };
+
+        Self::LEVEL_ASSERT | (delivery_mode << 8) | vector as u32
+    }
+}
+
+/// # Local Apic
+/// The interrupt controller built into each CPU core.
+pub struct LocalApic {
+    mode: ApicMode,
+    mmio: *mut u32,
+}
+
+impl LocalApic {
+    const LVT_MASKED: u32 = 1 << 16;
+    const SOFTWARE_ENABLE: u32 = 1 << 8;
+
+    /// # New
+    /// Access the local APIC of the current CPU.
+    ///
+    /// For [`ApicMode::XApic`], `mmio` must point to where the APIC's
+    /// physical base (see [`LocalApic::physical_base`]) is mapped as
+    /// uncacheable memory. It is ignored for [`ApicMode::X2Apic`].
+    ///
+    /// # Safety
+    /// The CPU must support `mode`, and `mmio` must be mapped correctly.
+    pub unsafe fn new(mode: ApicMode, mmio: *mut u32) -> Self {
+        Self { mode, mmio }
+    }
+
+    /// # Physical Base
+    /// The physical address of the current CPU's xAPIC registers.
+    pub fn physical_base() -> u64 {
+        ia32_apic_base::get_apic_base()
+    }
+
+    /// # Mode
+    /// How this APIC is being accessed.
+    pub const fn mode(&self) -> ApicMode {
+        self.mode
+    }
+
+    /// # Read
+    /// Read a local APIC register.
+    pub fn read(&self, reg: LocalApicReg) -> u32 {
+        match self.mode {
+            ApicMode::XApic => unsafe {
+                core::ptr::read_volatile(self.mmio.byte_add(reg as usize))
+            },
+            ApicMode::X2Apic => unsafe { rdmsr(reg.x2apic_msr()) as u32 },
+        }
+    }
+
+    /// # Write
+    /// Write a local APIC register.
+    ///
+    /// # Safety
+    /// Writing APIC registers can change how (and if) interrupts are delivered.
+    pub unsafe fn write(&mut self, reg: LocalApicReg, value: u32) {
+        match self.mode {
+            ApicMode::XApic => core::ptr::write_volatile(self.mmio.byte_add(reg as usize), value),
+            ApicMode::X2Apic => wrmsr(reg.x2apic_msr(), value as u64),
+        }
+    }
+
+    /// # Enable
+    /// Enable the APIC, using `spurious_vector` for spurious interrupts.
+    ///
+    /// # Safety
+    /// The IDT must be able to handle interrupts on `spurious_vector`.
+    pub unsafe fn enable(&mut self, spurious_vector: u8) {
+        ia32_apic_base::set_apic_global_enable_flag(true);
+
+        if self.mode == ApicMode::X2Apic {
+            ia32_apic_base::set_x2apic_mode_flag(true);
+        }
+
+        self.write(
+            LocalApicReg::SpuriousVector,
+            Self::SOFTWARE_ENABLE | spurious_vector as u32,
+        );
+        self.write(LocalApicReg::TaskPriority, 0);
+    }
+
+    /// # Id
+    /// The APIC id of this CPU.
+    pub fn id(&self) -> u32 {
+        match self.mode {
+            ApicMode::XApic => self.read(LocalApicReg::Id) >> 24,
+            ApicMode::X2Apic => self.read(LocalApicReg::Id),
+        }
+    }
+
+    /// # Version
+    /// The version of this APIC.
+    pub fn version(&self) -> u8 {
+        self.read(LocalApicReg::Version) as u8
+    }
+
+    /// # Max Lvt Entry
+    /// The index of the last local vector table entry this APIC has.
+    pub fn max_lvt_entry(&self) -> u8 {
+        (self.read(LocalApicReg::Version) >> 16) as u8
+    }
+
+    /// # End Of Interrupt
+    /// Signal that the current interrupt has been handled.
+    pub fn end_of_interrupt(&mut self) {
+        unsafe { self.write(LocalApicReg::EndOfInterrupt, 0) };
+    }
+
+    /// # Error Status
+    /// Read (and clear) the APIC error status.
+    pub fn error_status(&mut self) -> u32 {
+        // The ESR must be written before it is read to latch new errors
+        unsafe { self.write(LocalApicReg::ErrorStatus, 0) };
+        self.read(LocalApicReg::ErrorStatus)
+    }
+
+    /// # Send Ipi
+    /// Send an inter-processor interrupt to the CPU with `apic_id`, and wait
+    /// for it to be accepted.
+    ///
+    /// # Safety
+    /// The target CPU must be able to handle the IPI (an INIT resets it!).
+    pub unsafe fn send_ipi(&mut self, apic_id: u32, kind: IpiKind) {
+        const DELIVERY_PENDING: u32 = 1 << 12;
+
+        match self.mode {
+            ApicMode::XApic => {
+                // Writing the low half is what sends the IPI
+                self.write(LocalApicReg::InterruptCommandHigh, apic_id << 24);
+                self.write(LocalApicReg::InterruptCommandLow, kind.icr_low());
+
+                while self.read(LocalApicReg::InterruptCommandLow) & DELIVERY_PENDING != 0 {
+                    core::hint::spin_loop();
+                }
+            }
+            ApicMode::X2Apic => {
+                // In x2APIC mode the ICR is a single 64-bit MSR
+                wrmsr(
+                    LocalApicReg::InterruptCommandLow.x2apic_msr(),
+                    ((apic_id as u64) << 32) | kind.icr_low() as u64,
+                );
+            }
+        }
+    }
+
+    /// # Start Timer
+    /// Start the local APIC timer, firing `vector` when `initial_count` ticks
+    /// of the divided bus clock have passed.
+    ///
+    /// # Safety
+    /// The IDT must be able to handle interrupts on `vector`.
+    pub unsafe fn start_timer(
+        &mut self,
+        mode: TimerMode,
+        vector: u8,
+        divide: TimerDivide,
+        initial_count: u32,
+    ) {
+        let mode_bits = match mode {
+            TimerMode::OneShot => 0b00,
+            TimerMode::Periodic => 0b01,
+        };
+
+        self.write(LocalApicReg::TimerDivide, divide.encode());
+        self.write(LocalApicReg::LvtTimer, (mode_bits << 17) | vector as u32);
+        self.write(LocalApicReg::TimerInitialCount, initial_count);
+    }
+
+    /// # Stop Timer
+    /// Mask the timer interrupt and stop the count.
+    pub fn stop_timer(&mut self) {
+        unsafe {
+            self.write(LocalApicReg::LvtTimer, Self::LVT_MASKED);
+            self.write(LocalApicReg::TimerInitialCount, 0);
+        }
+    }
+
+    /// # Timer Current Count
+    /// The number of ticks left before the timer fires.
+    pub fn timer_current_count(&self) -> u32 {
+        self.read(LocalApicReg::TimerCurrentCount)
+    }
+}
+
+#[make_hw(
+    field(RW, 0..8, pub vector),
+    field(RW, 8..11, pub delivery_mode),
+    field(RW, 11, pub logical_destination),
+    field(RO, 12, pub delivery_pending),
+    field(RW, 13, pub active_low),
+    field(RO, 14, pub remote_irr),
+    field(RW, 15, pub level_triggered),
+    field(RW, 16, pub masked),
+    field(RW, 56..64, pub destination)
+)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RedirectionEntry(u64);
+
+impl RedirectionEntry {
+    /// # New
+    /// A masked entry with fixed delivery to physical APIC 0.
+    pub fn new() -> Self {
+        Self(0).set_masked_flag(true)
+    }
+}
+
+impl Default for RedirectionEntry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// # Io Apic
+/// The system interrupt controller that routes external IRQs to local APICs.
+pub struct IoApic {
+    mmio: *mut u32,
+}
+
+impl IoApic {
+    const IOREGSEL: usize = 0x00;
+    const IOWIN: usize = 0x10;
+
+    const REG_ID: u32 = 0x00;
+    const REG_VERSION: u32 = 0x01;
+    const REG_REDIRECTION_BASE: u32 = 0x10;
+
+    /// # New
+    /// Access an IOAPIC whose registers are mapped at `mmio`.
+    ///
+    /// # Safety
+    /// `mmio` must point to an IOAPIC mapped as uncacheable memory.
+    pub unsafe fn new(mmio: *mut u32) -> Self {
+        Self { mmio }
+    }
+
+    fn read(&self, reg: u32) -> u32 {
+        unsafe {
+            core::ptr::write_volatile(self.mmio.byte_add(Self::IOREGSEL), reg);
+            core::ptr::read_volatile(self.mmio.byte_add(Self::IOWIN))
+        }
+    }
+
+    unsafe fn write(&mut self, reg: u32, value: u32) {
+        core::ptr::write_volatile(self.mmio.byte_add(Self::IOREGSEL), reg);
+        core::ptr::write_volatile(self.mmio.byte_add(Self::IOWIN), value);
+    }
+
+    /// # Id
+    /// The IOAPIC's id.
+    pub fn id(&self) -> u8 {
+        ((self.read(Self::REG_ID) >> 24) & 0xF) as u8
+    }
+
+    /// # Version
+    /// The IOAPIC's version.
+    pub fn version(&self) -> u8 {
+        self.read(Self::REG_VERSION) as u8
+    }
+
+    /// # Redirection Entries
+    /// The number of IRQ inputs this IOAPIC has.
+    pub fn redirection_entries(&self) -> u8 {
+        ((self.read(Self::REG_VERSION) >> 16) as u8) + 1
+    }
+
+    /// # Read Redirection
+    /// Read how `irq` is routed.
+    pub fn read_redirection(&self, irq: u8) -> RedirectionEntry {
+        assert!(
+            irq < self.redirection_entries(),
+            "IOAPIC irq {irq} is out of range"
+        );
+
+        let reg = Self::REG_REDIRECTION_BASE + irq as u32 * 2;
+        let low = self.read(reg) as u64;
+        let high = self.read(reg + 1) as u64;
+
+        RedirectionEntry(low | (high << 32))
+    }
+
+    /// # Write Redirection
+    /// Change how `irq` is routed.
+    ///
+    /// # Safety
+    /// The IDT must be able to handle interrupts on the entry's vector.
+    pub unsafe fn write_redirection(&mut self, irq: u8, entry: RedirectionEntry) {
+        assert!(
+            irq < self.redirection_entries(),
+            "IOAPIC irq {irq} is out of range"
+        );
+
+        let reg = Self::REG_REDIRECTION_BASE + irq as u32 * 2;
+
+        // Mask the entry while its being changed so half written entries never fire
+        self.write(reg, (RedirectionEntry::new().0) as u32);
+        self.write(reg + 1, (entry.0 >> 32) as u32);
+        self.write(reg, entry.0 as u32);
+    }
+
+    /// # Set Masked
+    /// Mask or unmask `irq`.
+    ///
+    /// # Safety
+    /// The IDT must be able to handle interrupts on the entry's vector.
+    pub unsafe fn set_masked(&mut self, irq: u8, masked: bool) {
+        let entry = self.read_redirection(irq).set_masked_flag(masked);
+        self.write_redirection(irq, entry);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_x2apic_msr() {
+        assert_eq!(LocalApicReg::Id.x2apic_msr(), 0x802);
+        assert_eq!(LocalApicReg::EndOfInterrupt.x2apic_msr(), 0x80B);
+        assert_eq!(LocalApicReg::TimerDivide.x2apic_msr(), 0x83E);
+    }
+
+    #[test]
+    fn test_ipi_command() {
+        assert_eq!(IpiKind::Fixed(0x40).icr_low(), 0x4040);
+        assert_eq!(IpiKind::Init.icr_low(), 0x4500);
+        assert_eq!(IpiKind::Startup(0x08).icr_low(), 0x4608);
+    }
+
+    #[test]
+    fn test_redirection_entry() {
+        let entry = RedirectionEntry::new()
+            .set_vector(0x30)
+            .set_level_triggered_flag(true)
+            .set_destination(3)
+            .set_masked_flag(false);
+
+        assert_eq!(entry.0, (3 << 56) | (1 << 15) | 0x30);
+        assert_eq!(entry.get_vector(), 0x30);
+        assert!(!entry.is_masked_set());
+    }
+
+    #[test]
+    fn test_io_apic_registers() {
+        // Fake IOAPIC that only has IOREGSEL and IOWIN, so reads return the
+        // last selected register.
+        let mut regs = [0u32; 8];
+        let io_apic = unsafe { IoApic::new(regs.as_mut_ptr()) };
+
+        unsafe { core::ptr::write_volatile(regs.as_mut_ptr().add(4), 0x0017_0011) };
+        assert_eq!(io_apic.read(IoApic::REG_VERSION), 0x0017_0011);
+        assert_eq!(io_apic.version(), 0x11);
+        assert_eq!(io_apic.redirection_entries(), 24);
+        assert_eq!(regs[0], IoApic::REG_VERSION);
+    }
+}
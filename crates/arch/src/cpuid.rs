@@ -0,0 +1,335 @@
+/*
+  ____                 __               __   _ __
+ / __ \__ _____ ____  / /___ ____ _    / /  (_) /
+/ /_/ / // / _ `/ _ \/ __/ // /  ' \  / /__/ / _ \
+\___\_\_,_/\_,_/_//_/\__/\_,_/_/_/_/ /____/_/_.__/
+    Part of the Quantum OS Project
+
+Copyright 2024 Gavin Kellam
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and
+associated documentation files (the "Software"), to deal in the Software without restriction,
+including without limitation the rights to use, copy, modify, merge, publish, distribute,
+sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial
+portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT
+NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
+OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+#[cfg(target_arch = "x86")]
+use core::arch::x86::__cpuid_count;
+#[cfg(target_arch = "x86")]
+pub use core::arch::x86::CpuidResult;
+#[cfg(target_arch = "x86_64")]
+use core::arch::x86_64::__cpuid_count;
+#[cfg(target_arch = "x86_64")]
+pub use core::arch::x86_64::CpuidResult;
+
+/// # Cpuid
+/// Run the `cpuid` instruction with the given leaf and sub-leaf.
+///
+/// # Note
+/// On 32-bit CPUs make sure to check [`is_supported`] first!
+#[inline]
+pub fn cpuid(leaf: u32, sub_leaf: u32) -> CpuidResult {
+    __cpuid_count(leaf, sub_leaf)
+}
+
+/// # Is Supported
+/// Check if the `cpuid` instruction is supported by trying to toggle the ID flag
+/// in EFLAGS.
+#[cfg(target_arch = "x86")]
+pub fn is_supported() -> bool {
+    const ID_FLAG: u32 = 1 << 21;
+    let before: u32;
+    let after: u32;
+
+    unsafe {
+        core::arch::asm!(
+            "pushfd",
+            "pop {before:e}",
+            "mov {after:e}, {before:e}",
+            "xor {after:e}, {id:e}",
+            "push {after:e}",
+            "popfd",
+            "pushfd",
+            "pop {after:e}",
+            "push {before:e}",
+            "popfd",
+            before = out(reg) before,
+            after = out(reg) after,
+            id = in(reg) ID_FLAG,
+        )
+    }
+
+    (before ^ after) & ID_FLAG != 0
+}
+
+/// # Is Supported
+/// All 64-bit CPUs support the `cpuid` instruction.
+#[cfg(target_arch = "x86_64")]
+pub const fn is_supported() -> bool {
+    true
+}
+
+const EMPTY: CpuidResult = CpuidResult {
+    eax: 0,
+    ebx: 0,
+    ecx: 0,
+    edx: 0,
+};
+
+/// Run `cpuid` only if the leaf is supported, otherwise return all zeros.
+pub(crate) fn checked_cpuid(leaf: u32, sub_leaf: u32) -> CpuidResult {
+    if !is_supported() {
+        return EMPTY;
+    }
+
+    let max_leaf = cpuid(leaf & 0x8000_0000, 0).eax;
+    if leaf > max_leaf {
+        return EMPTY;
+    }
+
+    cpuid(leaf, sub_leaf)
+}
+
+/// # Vendor
+/// Who made the CPU.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Vendor {
+    Intel,
+    Amd,
+    /// Any other vendor, with its 12 byte vendor string.
+    Other([u8; 12]),
+}
+
+impl Vendor {
+    /// # Read
+    /// Read the vendor of this CPU.
+    pub fn read() -> Self {
+        Self::from_leaf0(checked_cpuid(0, 0))
+    }
+
+    /// # From Leaf 0
+    /// Decode the vendor string from the result of `cpuid` leaf 0.
+    pub fn from_leaf0(leaf0: CpuidResult) -> Self {
+        let mut name = [0; 12];
+        name[0..4].copy_from_slice(&leaf0.ebx.to_le_bytes());
+        name[4..8].copy_from_slice(&leaf0.edx.to_le_bytes());
+        name[8..12].copy_from_slice(&leaf0.ecx.to_le_bytes());
+
+        match &name {
+            b"GenuineIntel" => Self::Intel,
+            b"AuthenticAMD" => Self::Amd,
+            _ => Self::Other(name),
+        }
+    }
+}
+
+/// # Version
+/// The family, model, and stepping of the CPU.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Version {
+    pub family: u16,
+    pub model: u8,
+    pub stepping: u8,
+}
+
+impl Version {
+    /// # Read
+    /// Read the version of this CPU.
+    pub fn read() -> Self {
+        Self::from_eax(checked_cpuid(1, 0).eax)
+    }
+
+    /// # From Eax
+    /// Decode the version from `eax` of `cpuid` leaf 1, including the
+    /// extended family and model fields.
+    pub const fn from_eax(eax: u32) -> Self {
+        let base_family = ((eax >> 8) & 0xF) as u16;
+        let base_model = ((eax >> 4) & 0xF) as u8;
+        let ext_model = ((eax >> 16) & 0xF) as u8;
+        let ext_family = ((eax >> 20) & 0xFF) as u16;
+
+        let family = if base_family == 0xF {
+            base_family + ext_family
+        } else {
+            base_family
+        };
+
+        let model = if base_family == 0x6 || base_family == 0xF {
+            (ext_model << 4) | base_model
+        } else {
+            base_model
+        };
+
+        Self {
+            family,
+            model,
+            stepping: (eax & 0xF) as u8,
+        }
+    }
+}
+
+/// # Cpu Features
+/// The optional features of the CPU the rest of the system cares about.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CpuFeatures {
+    /// A local APIC is present.
+    pub apic: bool,
+    /// The local APIC supports x2APIC (MSR based) mode.
+    pub x2apic: bool,
+    /// PAE paging.
+    pub physical_address_extension: bool,
+    /// Process context identifiers.
+    pub pcid: bool,
+    /// The `invpcid` instruction.
+    pub invpcid: bool,
+    pub sse2: bool,
+    pub sse4_2: bool,
+    pub avx: bool,
+    /// The NX (no execute) page bit.
+    pub no_execute: bool,
+    /// 1G huge pages.
+    pub huge_pages_1g: bool,
+    /// The `rdtscp` instruction.
+    pub rdtscp: bool,
+    /// 64-bit long mode.
+    pub long_mode: bool,
+    /// The TSC runs at a constant rate in every power state.
+    pub invariant_tsc: bool,
+}
+
+impl CpuFeatures {
+    /// # Read
+    /// Read the features of this CPU, every feature is `false` if `cpuid`
+    /// isn't supported.
+    pub fn read() -> Self {
+        Self::from_leaves(
+            checked_cpuid(0x1, 0),
+            checked_cpuid(0x7, 0),
+            checked_cpuid(0x8000_0001, 0),
+            checked_cpuid(0x8000_0007, 0),
+        )
+    }
+
+    /// # From Leaves
+    /// Decode the features from the results of `cpuid` leaves `0x1`, `0x7`,
+    /// `0x80000001`, and `0x80000007`.
+    pub const fn from_leaves(
+        std: CpuidResult,
+        ext_std: CpuidResult,
+        ext: CpuidResult,
+        power: CpuidResult,
+    ) -> Self {
+        const fn bit(reg: u32, bit: u32) -> bool {
+            reg & (1 << bit) != 0
+        }
+
+        Self {
+            apic: bit(std.edx, 9),
+            x2apic: bit(std.ecx, 21),
+            physical_address_extension: bit(std.edx, 6),
+            pcid: bit(std.ecx, 17),
+            invpcid: bit(ext_std.ebx, 10),
+            sse2: bit(std.edx, 26),
+            sse4_2: bit(std.ecx, 20),
+            avx: bit(std.ecx, 28),
+            no_execute: bit(ext.edx, 20),
+            huge_pages_1g: bit(ext.edx, 26),
+            rdtscp: bit(ext.edx, 27),
+            long_mode: bit(ext.edx, 29),
+            invariant_tsc: bit(power.edx, 8),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const fn result(eax: u32, ebx: u32, ecx: u32, edx: u32) -> CpuidResult {
+        CpuidResult { eax, ebx, ecx, edx }
+    }
+
+    #[test]
+    fn test_vendor() {
+        assert_eq!(
+            Vendor::from_leaf0(result(0xD, 0x756E6547, 0x6C65746E, 0x49656E69)),
+            Vendor::Intel
+        );
+        assert_eq!(
+            Vendor::from_leaf0(result(0xD, 0x68747541, 0x444D4163, 0x69746E65)),
+            Vendor::Amd
+        );
+        assert_eq!(
+            Vendor::from_leaf0(result(0, 0, 0, 0)),
+            Vendor::Other([0; 12])
+        );
+    }
+
+    #[test]
+    fn test_version() {
+        // Intel Core i7-8700 (family 6, model 0x9E, stepping 10)
+        assert_eq!(
+            Version::from_eax(0x000906EA),
+            Version {
+                family: 6,
+                model: 0x9E,
+                stepping: 10,
+            }
+        );
+
+        // AMD Ryzen 7 3700X (family 0x17, model 0x71, stepping 0)
+        assert_eq!(
+            Version::from_eax(0x00870F10),
+            Version {
+                family: 0x17,
+                model: 0x71,
+                stepping: 0,
+            }
+        );
+
+        // Extended model is ignored for older families
+        assert_eq!(
+            Version::from_eax(0x00010543),
+            Version {
+                family: 5,
+                model: 4,
+                stepping: 3,
+            }
+        );
+    }
+
+    #[test]
+    fn test_features() {
+        let features = CpuFeatures::from_leaves(
+            result(0, 0, (1 << 21) | (1 << 28), (1 << 9) | (1 << 26)),
+            result(0, 1 << 10, 0, 0),
+            result(0, 0, 0, (1 << 26) | (1 << 29)),
+            result(0, 0, 0, 1 << 8),
+        );
+
+        assert_eq!(
+            features,
+            CpuFeatures {
+                apic: true,
+                x2apic: true,
+                invpcid: true,
+                sse2: true,
+                avx: true,
+                huge_pages_1g: true,
+                long_mode: true,
+                invariant_tsc: true,
+                ..CpuFeatures::default()
+            }
+        );
+    }
+}
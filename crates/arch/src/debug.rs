@@ -0,0 +1,473 @@
+/*
+  ____                 __               __   _ __
+ / __ \__ _____ ____  / /___ ____ _    / /  (_) /
+/ /_/ / // / _ `/ _ \/ __/ // /  ' \  / /__/ / _ \
+\___\_\_,_/\_,_/_//_/\__/\_,_/_/_/_/ /____/_/_.__/
+    Part of the Quantum OS Project
+
+Copyright 2024 Gavin Kellam
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and
+associated documentation files (the "Software"), to deal in the Software without restriction,
+including without limitation the rights to use, copy, modify, merge, publish, distribute,
+sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial
+portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT
+NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
+OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+use crate::registers::cr4;
+use hw::make_hw;
+
+/// # Breakpoint Slots
+/// The number of hardware breakpoints (`DR0`-`DR3`).
+pub const BREAKPOINT_SLOTS: usize = 4;
+
+/// # Breakpoint Kind
+/// What kind of access triggers a hardware breakpoint.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BreakpointKind {
+    /// Executing the instruction at the address.
+    Execute,
+    /// Writing to the address.
+    Write,
+    /// Reading or writing the address (instruction fetches excluded).
+    ReadWrite,
+    /// An IO port access, needs `CR4.DE` to be set (which [`set_hw_breakpoint`]
+    /// does), otherwise the CPU treats these bits as undefined.
+    Io,
+}
+
+impl BreakpointKind {
+    const fn bits(self) -> u64 {
+        match self {
+            BreakpointKind::Execute => 0b00,
+            BreakpointKind::Write => 0b01,
+            BreakpointKind::Io => 0b10,
+            BreakpointKind::ReadWrite => 0b11,
+        }
+    }
+
+    const fn from_bits(bits: u64) -> Self {
+        match bits & 0b11 {
+            0b00 => BreakpointKind::Execute,
+            0b01 => BreakpointKind::Write,
+            0b10 => BreakpointKind::Io,
+            _ => BreakpointKind::ReadWrite,
+        }
+    }
+}
+
+/// # Breakpoint Length
+/// How many bytes starting at the address are watched.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BreakpointLength {
+    Byte,
+    Word,
+    Dword,
+    /// Only available in long mode.
+    Qword,
+}
+
+impl BreakpointLength {
+    /// # Bytes
+    /// The number of bytes watched, which the address must also be aligned to.
+    pub const fn bytes(self) -> usize {
+        match self {
+            BreakpointLength::Byte => 1,
+            BreakpointLength::Word => 2,
+            BreakpointLength::Dword => 4,
+            BreakpointLength::Qword => 8,
+        }
+    }
+
+    const fn bits(self) -> u64 {
+        match self {
+            BreakpointLength::Byte => 0b00,
+            BreakpointLength::Word => 0b01,
+            BreakpointLength::Qword => 0b10,
+            BreakpointLength::Dword => 0b11,
+        }
+    }
+
+    const fn from_bits(bits: u64) -> Self {
+        match bits & 0b11 {
+            0b00 => BreakpointLength::Byte,
+            0b01 => BreakpointLength::Word,
+            0b10 => BreakpointLength::Qword,
+            _ => BreakpointLength::Dword,
+        }
+    }
+}
+
+/// # Debug Error
+/// Errors from setting a hardware breakpoint.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DebugError {
+    /// All four breakpoint slots are in use.
+    NoFreeSlot,
+    /// The slot number isn't `0..4`.
+    InvalidSlot,
+    /// The address isn't aligned to the breakpoint's length.
+    NotAligned,
+    /// Execute breakpoints must be one byte long.
+    InvalidLength,
+}
+
+/// # Dr7
+/// The debug control register, which enables each breakpoint and sets what
+/// triggers it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Dr7(u64);
+
+impl Dr7 {
+    /// Exact breakpoint bits, recommended to be set whenever data breakpoints
+    /// are used.
+    const EXACT_BREAKPOINTS: u64 = (1 << 8) | (1 << 9);
+
+    pub const fn new(value: u64) -> Self {
+        Self(value)
+    }
+
+    pub const fn value(&self) -> u64 {
+        self.0
+    }
+
+    /// # Is Enabled
+    /// Check if `slot` is enabled, either locally or globally.
+    ///
+    /// Panics if `slot` isn't `0..4`.
+    pub const fn is_enabled(&self, slot: usize) -> bool {
+        assert!(slot < BREAKPOINT_SLOTS, "Breakpoint slot must be 0..4");
+        self.0 & (0b11 << (slot * 2)) != 0
+    }
+
+    /// # Free Slot
+    /// The first breakpoint slot that isn't enabled.
+    pub fn free_slot(&self) -> Option<usize> {
+        (0..BREAKPOINT_SLOTS).find(|&slot| !self.is_enabled(slot))
+    }
+
+    /// # Breakpoint
+    /// The kind and length of the breakpoint in `slot`, if it's enabled.
+    pub const fn breakpoint(&self, slot: usize) -> Option<(BreakpointKind, BreakpointLength)> {
+        if slot >= BREAKPOINT_SLOTS || !self.is_enabled(slot) {
+            return None;
+        }
+
+        let control = self.0 >> (16 + slot * 4);
+        Some((
+            BreakpointKind::from_bits(control),
+            BreakpointLength::from_bits(control >> 2),
+        ))
+    }
+
+    /// # With Breakpoint
+    /// Globally enable `slot` with the given kind and length.
+    ///
+    /// Panics if `slot` isn't `0..4`. A [`BreakpointKind::Io`] breakpoint also
+    /// needs `CR4.DE` set before it's written to `DR7`.
+    pub const fn with_breakpoint(
+        self,
+        slot: usize,
+        kind: BreakpointKind,
+        length: BreakpointLength,
+    ) -> Self {
+        assert!(slot < BREAKPOINT_SLOTS, "Breakpoint slot must be 0..4");
+        let control_shift = 16 + slot * 4;
+        let cleared = self.0 & !(0b11 << (slot * 2)) & !(0b1111 << control_shift);
+
+        Self(
+            cleared
+                | (0b10 << (slot * 2))
+                | ((kind.bits() | (length.bits() << 2)) << control_shift)
+                | Self::EXACT_BREAKPOINTS,
+        )
+    }
+
+    /// # Without Breakpoint
+    /// Disable `slot` and clear its kind and length.
+    ///
+    /// Panics if `slot` isn't `0..4`.
+    pub const fn without_breakpoint(self, slot: usize) -> Self {
+        assert!(slot < BREAKPOINT_SLOTS, "Breakpoint slot must be 0..4");
+        Self(self.0 & !(0b11 << (slot * 2)) & !(0b1111 << (16 + slot * 4)))
+    }
+}
+
+/// # Dr6
+/// The debug status register, which says what caused a `#DB` exception.
+#[make_hw(
+    field(RO, 0, pub breakpoint0),
+    field(RO, 1, pub breakpoint1),
+    field(RO, 2, pub breakpoint2),
+    field(RO, 3, pub breakpoint3),
+    field(RO, 13, pub debug_register_access),
+    field(RO, 14, pub single_step),
+    field(RO, 15, pub task_switch)
+)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Dr6(u64);
+
+impl Dr6 {
+    /// The value `DR6` should be reset to after handling a `#DB`.
+    pub const CLEARED: Self = Self(0xFFFF_0FF0);
+
+    pub const fn new(value: u64) -> Self {
+        Self(value)
+    }
+}
+
+/// # Debug Exception
+/// The decoded cause of a `#DB` exception.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DebugException {
+    /// The hardware breakpoint in this slot was hit.
+    Breakpoint(usize),
+    /// A single step (`RFLAGS.TF`) trap.
+    SingleStep,
+    /// A task switch into a task with the debug trap flag set.
+    TaskSwitch,
+    /// An instruction tried to access a debug register while `DR7.GD` was set.
+    DebugRegisterAccess,
+    /// None of the known status bits were set.
+    Unknown,
+}
+
+impl From<Dr6> for DebugException {
+    fn from(status: Dr6) -> Self {
+        let triggered = [
+            status.is_breakpoint0_set(),
+            status.is_breakpoint1_set(),
+            status.is_breakpoint2_set(),
+            status.is_breakpoint3_set(),
+        ];
+
+        if let Some(slot) = triggered.iter().position(|&hit| hit) {
+            DebugException::Breakpoint(slot)
+        } else if status.is_single_step_set() {
+            DebugException::SingleStep
+        } else if status.is_task_switch_set() {
+            DebugException::TaskSwitch
+        } else if status.is_debug_register_access_set() {
+            DebugException::DebugRegisterAccess
+        } else {
+            DebugException::Unknown
+        }
+    }
+}
+
+/// # Read Address
+/// Read the address of the breakpoint in `slot` (`DR0`-`DR3`).
+///
+/// # Safety
+/// Must be run in ring 0.
+pub unsafe fn read_address(slot: usize) -> Result<usize, DebugError> {
+    let value: usize;
+
+    unsafe {
+        match slot {
+            0 => core::arch::asm!("mov {}, dr0", out(reg) value, options(nomem, nostack)),
+            1 => core::arch::asm!("mov {}, dr1", out(reg) value, options(nomem, nostack)),
+            2 => core::arch::asm!("mov {}, dr2", out(reg) value, options(nomem, nostack)),
+            3 => core::arch::asm!("mov {}, dr3", out(reg) value, options(nomem, nostack)),
+            _ => return Err(DebugError::InvalidSlot),
+        }
+    }
+
+    Ok(value)
+}
+
+/// # Write Address
+/// Set the address of the breakpoint in `slot` (`DR0`-`DR3`).
+///
+/// # Safety
+/// Must be run in ring 0.
+pub unsafe fn write_address(slot: usize, address: usize) -> Result<(), DebugError> {
+    unsafe {
+        match slot {
+            0 => core::arch::asm!("mov dr0, {}", in(reg) address, options(nomem, nostack)),
+            1 => core::arch::asm!("mov dr1, {}", in(reg) address, options(nomem, nostack)),
+            2 => core::arch::asm!("mov dr2, {}", in(reg) address, options(nomem, nostack)),
+            3 => core::arch::asm!("mov dr3, {}", in(reg) address, options(nomem, nostack)),
+            _ => return Err(DebugError::InvalidSlot),
+        }
+    }
+
+    Ok(())
+}
+
+/// # Read Dr6
+///
+/// # Safety
+/// Must be run in ring 0.
+pub unsafe fn read_dr6() -> Dr6 {
+    let value: usize;
+    unsafe { core::arch::asm!("mov {}, dr6", out(reg) value, options(nomem, nostack)) };
+
+    Dr6(value as u64)
+}
+
+/// # Write Dr6
+///
+/// # Safety
+/// Must be run in ring 0.
+pub unsafe fn write_dr6(status: Dr6) {
+    unsafe { core::arch::asm!("mov dr6, {}", in(reg) status.0 as usize, options(nomem, nostack)) };
+}
+
+/// # Read Dr7
+///
+/// # Safety
+/// Must be run in ring 0.
+pub unsafe fn read_dr7() -> Dr7 {
+    let value: usize;
+    unsafe { core::arch::asm!("mov {}, dr7", out(reg) value, options(nomem, nostack)) };
+
+    Dr7(value as u64)
+}
+
+/// # Write Dr7
+///
+/// # Safety
+/// Must be run in ring 0, and every enabled breakpoint's address must be set.
+pub unsafe fn write_dr7(control: Dr7) {
+    unsafe { core::arch::asm!("mov dr7, {}", in(reg) control.0 as usize, options(nomem, nostack)) };
+}
+
+/// # Set Hw Breakpoint
+/// Watch `address` with the first free breakpoint slot, returning the slot
+/// that was used.
+///
+/// # Safety
+/// Must be run in ring 0, and a `#DB` handler must be installed.
+pub unsafe fn set_hw_breakpoint(
+    address: usize,
+    kind: BreakpointKind,
+    length: BreakpointLength,
+) -> Result<usize, DebugError> {
+    if kind == BreakpointKind::Execute && length != BreakpointLength::Byte {
+        return Err(DebugError::InvalidLength);
+    }
+    if !address.is_multiple_of(length.bytes()) {
+        return Err(DebugError::NotAligned);
+    }
+
+    let control = unsafe { read_dr7() };
+    let slot = control.free_slot().ok_or(DebugError::NoFreeSlot)?;
+
+    unsafe {
+        // IO breakpoints are only defined with debugging extensions enabled
+        if kind == BreakpointKind::Io {
+            cr4::set_debugging_extensions_flag(true);
+        }
+
+        write_address(slot, address)?;
+        write_dr7(control.with_breakpoint(slot, kind, length));
+    }
+
+    Ok(slot)
+}
+
+/// # Clear Hw Breakpoint
+/// Disable the breakpoint in `slot`.
+///
+/// # Safety
+/// Must be run in ring 0.
+pub unsafe fn clear_hw_breakpoint(slot: usize) -> Result<(), DebugError> {
+    if slot >= BREAKPOINT_SLOTS {
+        return Err(DebugError::InvalidSlot);
+    }
+
+    unsafe { write_dr7(read_dr7().without_breakpoint(slot)) };
+    Ok(())
+}
+
+/// # Take Debug Exception
+/// Decode why the current `#DB` happened and reset `DR6` for the next one.
+///
+/// # Safety
+/// Must be run in ring 0, normally from the `#DB` handler.
+pub unsafe fn take_debug_exception() -> DebugException {
+    let status = unsafe { read_dr6() };
+    unsafe { write_dr6(Dr6::CLEARED) };
+
+    status.into()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_dr7_encoding() {
+        let control =
+            Dr7::default().with_breakpoint(2, BreakpointKind::Write, BreakpointLength::Qword);
+
+        assert_eq!(control.value(), (0b10 << 4) | (0b1001 << 24) | (0b11 << 8));
+        assert_eq!(
+            control.breakpoint(2),
+            Some((BreakpointKind::Write, BreakpointLength::Qword))
+        );
+        assert_eq!(control.breakpoint(0), None);
+        assert_eq!(control.free_slot(), Some(0));
+    }
+
+    #[test]
+    fn test_dr7_slots_fill_and_clear() {
+        let control = (0..BREAKPOINT_SLOTS).fold(Dr7::default(), |control, slot| {
+            control.with_breakpoint(slot, BreakpointKind::Execute, BreakpointLength::Byte)
+        });
+        assert_eq!(control.free_slot(), None);
+
+        let control = control.without_breakpoint(1);
+        assert_eq!(control.free_slot(), Some(1));
+        assert_eq!(control.breakpoint(1), None);
+        assert_eq!(
+            control.breakpoint(3),
+            Some((BreakpointKind::Execute, BreakpointLength::Byte))
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_dr7_invalid_slot() {
+        Dr7::default().with_breakpoint(4, BreakpointKind::Write, BreakpointLength::Byte);
+    }
+
+    #[test]
+    fn test_dr7_replacing_breakpoint() {
+        let control = Dr7::default()
+            .with_breakpoint(0, BreakpointKind::ReadWrite, BreakpointLength::Dword)
+            .with_breakpoint(0, BreakpointKind::Write, BreakpointLength::Byte);
+
+        assert_eq!(
+            control.breakpoint(0),
+            Some((BreakpointKind::Write, BreakpointLength::Byte))
+        );
+    }
+
+    #[test]
+    fn test_dr6_decoding() {
+        assert_eq!(
+            DebugException::from(Dr6::new(Dr6::CLEARED.0 | 0b0100)),
+            DebugException::Breakpoint(2)
+        );
+        assert_eq!(
+            DebugException::from(Dr6::new(Dr6::CLEARED.0 | (1 << 14))),
+            DebugException::SingleStep
+        );
+        assert_eq!(
+            DebugException::from(Dr6::new(Dr6::CLEARED.0 | (1 << 13))),
+            DebugException::DebugRegisterAccess
+        );
+        assert_eq!(DebugException::from(Dr6::CLEARED), DebugException::Unknown);
+    }
+}
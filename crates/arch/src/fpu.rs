@@ -0,0 +1,463 @@
+/*
+  ____                 __               __   _ __
+ / __ \__ _____ ____  / /___ ____ _    / /  (_) /
+/ /_/ / // / _ `/ _ \/ __/ // /  ' \  / /__/ / _ \
+\___\_\_,_/\_,_/_//_/\__/\_,_/_/_/_/ /____/_/_.__/
+    Part of the Quantum OS Project
+
+Copyright 2024 Gavin Kellam
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and
+associated documentation files (the "Software"), to deal in the Software without restriction,
+including without limitation the rights to use, copy, modify, merge, publish, distribute,
+sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial
+portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT
+NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
+OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+use crate::cpuid::{checked_cpuid, CpuidResult};
+use crate::registers::{cr0, cr4};
+
+/// # Xcr0 Bits
+/// The state components that can be enabled in `XCR0`.
+pub mod xcr0 {
+    /// x87 FPU state, must always be enabled.
+    pub const X87: u64 = 1 << 0;
+    /// SSE (`XMM` and `MXCSR`) state.
+    pub const SSE: u64 = 1 << 1;
+    /// The upper halves of the `YMM` registers.
+    pub const AVX: u64 = 1 << 2;
+}
+
+/// The state components this module knows how to manage.
+const MANAGED_FEATURES: u64 = xcr0::X87 | xcr0::SSE | xcr0::AVX;
+
+/// Size of the legacy `fxsave` region.
+pub const FXSAVE_AREA_SIZE: usize = 512;
+
+/// Size of the legacy region plus the `xsave` header.
+const XSAVE_LEGACY_AND_HEADER_SIZE: usize = FXSAVE_AREA_SIZE + 64;
+
+/// # Save Mechanism
+/// The instructions used to save and restore the FPU state.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SaveMechanism {
+    /// `xsave`/`xrstor`, which can also save AVX state.
+    Xsave,
+    /// `fxsave`/`fxrstor`, which only save x87 and SSE state.
+    Fxsave,
+}
+
+impl SaveMechanism {
+    /// # Alignment
+    /// The alignment the state buffer needs for these instructions.
+    pub const fn alignment(&self) -> usize {
+        match self {
+            SaveMechanism::Xsave => 64,
+            SaveMechanism::Fxsave => 16,
+        }
+    }
+}
+
+/// # Fpu Support
+/// How this CPU's FPU state should be saved, and how big it is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FpuSupport {
+    pub mechanism: SaveMechanism,
+    /// The `XCR0` state components that get saved.
+    pub features: u64,
+    /// The number of bytes a saved state takes up.
+    pub state_size: usize,
+}
+
+impl FpuSupport {
+    /// # Read
+    /// Read the FPU support of this CPU, or `None` if it can't even `fxsave`.
+    pub fn read() -> Option<Self> {
+        Self::from_leaves(
+            checked_cpuid(0x1, 0),
+            checked_cpuid(0xD, 0),
+            checked_cpuid(0xD, 2),
+        )
+    }
+
+    /// # From Leaves
+    /// Decode the FPU support from the results of `cpuid` leaves `0x1`, `0xD`
+    /// (sub-leaf 0), and `0xD` (sub-leaf 2, the AVX component).
+    pub const fn from_leaves(
+        std: CpuidResult,
+        xsave: CpuidResult,
+        avx: CpuidResult,
+    ) -> Option<Self> {
+        let has_fxsr = std.edx & (1 << 24) != 0;
+        let has_xsave = std.ecx & (1 << 26) != 0;
+
+        if has_xsave {
+            let supported = ((xsave.edx as u64) << 32) | xsave.eax as u64;
+            let features = supported & MANAGED_FEATURES;
+
+            // Sub-leaf 0 only gives the size of every supported component together,
+            // so the area ends where the last managed component (AVX) ends.
+            let state_size = if features & xcr0::AVX != 0 {
+                (avx.ebx + avx.eax) as usize
+            } else {
+                XSAVE_LEGACY_AND_HEADER_SIZE
+            };
+
+            if features & (xcr0::X87 | xcr0::SSE) == (xcr0::X87 | xcr0::SSE) {
+                return Some(Self {
+                    mechanism: SaveMechanism::Xsave,
+                    features,
+                    state_size,
+                });
+            }
+        }
+
+        if has_fxsr {
+            return Some(Self {
+                mechanism: SaveMechanism::Fxsave,
+                features: xcr0::X87 | xcr0::SSE,
+                state_size: FXSAVE_AREA_SIZE,
+            });
+        }
+
+        None
+    }
+
+    /// # Enable
+    /// Turn on the FPU and SSE for this CPU, and the `XCR0` features if
+    /// `xsave` is used.
+    ///
+    /// # Safety
+    /// Must be run in ring 0, and the [`FpuSupport`] must have been read on this CPU.
+    pub unsafe fn enable(&self) {
+        unsafe {
+            cr0::set_x87_fpu_emulation_flag(false);
+            cr0::set_monitor_co_processor_flag(true);
+            cr0::set_numeric_error_flag(true);
+            cr4::set_os_supporting_fxsave_fxstor_flag(true);
+            cr4::set_os_supporting_unmasked_simd_float_flag(true);
+
+            if self.mechanism == SaveMechanism::Xsave {
+                cr4::set_xsave_flag(true);
+                xsetbv(0, self.features);
+            }
+
+            core::arch::asm!("fninit", options(nomem, nostack));
+        }
+    }
+}
+
+/// # Xgetbv
+/// Read the extended control register `index`.
+///
+/// # Safety
+/// `CR4.OSXSAVE` must be set.
+#[inline(always)]
+pub unsafe fn xgetbv(index: u32) -> u64 {
+    let (low, high): (u32, u32);
+    unsafe {
+        core::arch::asm!(
+            "xgetbv",
+            in("ecx") index,
+            out("eax") low,
+            out("edx") high,
+            options(nomem, nostack)
+        )
+    };
+
+    ((high as u64) << 32) | low as u64
+}
+
+/// # Xsetbv
+/// Write the extended control register `index`.
+///
+/// # Safety
+/// Must be run in ring 0 with `CR4.OSXSAVE` set, and `value` must only
+/// contain state components this CPU supports.
+#[inline(always)]
+pub unsafe fn xsetbv(index: u32, value: u64) {
+    unsafe {
+        core::arch::asm!(
+            "xsetbv",
+            in("ecx") index,
+            in("eax") value as u32,
+            in("edx") (value >> 32) as u32,
+            options(nomem, nostack)
+        )
+    };
+}
+
+/// # Set Task Switched
+/// Set `CR0.TS`, so the next FPU/SSE instruction raises a device not
+/// available (`#NM`) exception.
+///
+/// Setting this when switching tasks lets the FPU state be saved lazily,
+/// only when the new task actually uses it.
+///
+/// # Safety
+/// Must be run in ring 0, and a `#NM` handler must be installed.
+#[inline(always)]
+pub unsafe fn set_task_switched() {
+    unsafe { cr0::set_task_switch_flag(true) };
+}
+
+/// # Clear Task Switched
+/// Clear `CR0.TS` so FPU/SSE instructions can be used again, normally done
+/// from the `#NM` handler after switching the FPU state.
+///
+/// # Safety
+/// Must be run in ring 0.
+#[inline(always)]
+pub unsafe fn clear_task_switched() {
+    unsafe { core::arch::asm!("clts", options(nomem, nostack)) };
+}
+
+/// # Is Task Switched
+/// Check if FPU/SSE instructions will currently raise `#NM`.
+pub fn is_task_switched() -> bool {
+    cr0::is_task_switch_set()
+}
+
+/// # Fpu State Error
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FpuStateError {
+    /// The buffer is smaller than [`FpuSupport::state_size`].
+    TooSmall,
+    /// The buffer isn't aligned for the save mechanism.
+    NotAligned,
+}
+
+/// # Fpu State
+/// The saved FPU/SSE/AVX registers of one task.
+///
+/// The buffer is given by the caller, and should be [`FpuSupport::state_size`]
+/// bytes long.
+pub struct FpuState<'a> {
+    area: &'a mut [u8],
+    support: FpuSupport,
+}
+
+impl<'a> FpuState<'a> {
+    /// The default x87 control word, all exceptions masked.
+    const DEFAULT_FCW: u16 = 0x037F;
+    /// The default `MXCSR`, all exceptions masked.
+    const DEFAULT_MXCSR: u32 = 0x1F80;
+
+    /// # New
+    /// Use `area` to hold an FPU state, initialized to the default state a
+    /// new task should start with.
+    pub fn new(area: &'a mut [u8], support: FpuSupport) -> Result<Self, FpuStateError> {
+        if area.len() < support.state_size {
+            return Err(FpuStateError::TooSmall);
+        }
+        if !(area.as_ptr() as usize).is_multiple_of(support.mechanism.alignment()) {
+            return Err(FpuStateError::NotAligned);
+        }
+
+        let mut state = Self { area, support };
+        state.reset();
+
+        Ok(state)
+    }
+
+    /// # Reset
+    /// Put this state back to the default a new task starts with.
+    pub fn reset(&mut self) {
+        self.area.fill(0);
+        self.area[0..2].copy_from_slice(&Self::DEFAULT_FCW.to_le_bytes());
+        self.area[24..28].copy_from_slice(&Self::DEFAULT_MXCSR.to_le_bytes());
+    }
+
+    /// # Bytes
+    /// The raw saved state.
+    pub fn bytes(&self) -> &[u8] {
+        &self.area[..self.support.state_size]
+    }
+
+    /// # Save
+    /// Save the current FPU registers into this state.
+    ///
+    /// # Safety
+    /// [`FpuSupport::enable`] must have been called on this CPU, and `CR0.TS`
+    /// must be clear.
+    pub unsafe fn save(&mut self) {
+        let ptr = self.area.as_mut_ptr();
+
+        match self.support.mechanism {
+            SaveMechanism::Xsave => unsafe { xsave(ptr, self.support.features) },
+            SaveMechanism::Fxsave => unsafe { fxsave(ptr) },
+        }
+    }
+
+    /// # Restore
+    /// Load this state into the FPU registers.
+    ///
+    /// # Safety
+    /// [`FpuSupport::enable`] must have been called on this CPU, and `CR0.TS`
+    /// must be clear.
+    pub unsafe fn restore(&self) {
+        let ptr = self.area.as_ptr();
+
+        match self.support.mechanism {
+            SaveMechanism::Xsave => unsafe { xrstor(ptr, self.support.features) },
+            SaveMechanism::Fxsave => unsafe { fxrstor(ptr) },
+        }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+unsafe fn xsave(ptr: *mut u8, features: u64) {
+    unsafe {
+        core::arch::asm!(
+            "xsave64 [{0}]",
+            in(reg) ptr,
+            in("eax") features as u32,
+            in("edx") (features >> 32) as u32,
+            options(nostack)
+        )
+    };
+}
+
+#[cfg(target_arch = "x86")]
+unsafe fn xsave(ptr: *mut u8, features: u64) {
+    unsafe {
+        core::arch::asm!(
+            "xsave [{0}]",
+            in(reg) ptr,
+            in("eax") features as u32,
+            in("edx") (features >> 32) as u32,
+            options(nostack)
+        )
+    };
+}
+
+#[cfg(target_arch = "x86_64")]
+unsafe fn xrstor(ptr: *const u8, features: u64) {
+    unsafe {
+        core::arch::asm!(
+            "xrstor64 [{0}]",
+            in(reg) ptr,
+            in("eax") features as u32,
+            in("edx") (features >> 32) as u32,
+            options(nostack, readonly)
+        )
+    };
+}
+
+#[cfg(target_arch = "x86")]
+unsafe fn xrstor(ptr: *const u8, features: u64) {
+    unsafe {
+        core::arch::asm!(
+            "xrstor [{0}]",
+            in(reg) ptr,
+            in("eax") features as u32,
+            in("edx") (features >> 32) as u32,
+            options(nostack, readonly)
+        )
+    };
+}
+
+#[cfg(target_arch = "x86_64")]
+unsafe fn fxsave(ptr: *mut u8) {
+    unsafe { core::arch::asm!("fxsave64 [{0}]", in(reg) ptr, options(nostack)) };
+}
+
+#[cfg(target_arch = "x86")]
+unsafe fn fxsave(ptr: *mut u8) {
+    unsafe { core::arch::asm!("fxsave [{0}]", in(reg) ptr, options(nostack)) };
+}
+
+#[cfg(target_arch = "x86_64")]
+unsafe fn fxrstor(ptr: *const u8) {
+    unsafe { core::arch::asm!("fxrstor64 [{0}]", in(reg) ptr, options(nostack, readonly)) };
+}
+
+#[cfg(target_arch = "x86")]
+unsafe fn fxrstor(ptr: *const u8) {
+    unsafe { core::arch::asm!("fxrstor [{0}]", in(reg) ptr, options(nostack, readonly)) };
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const fn result(eax: u32, ebx: u32, ecx: u32, edx: u32) -> CpuidResult {
+        CpuidResult { eax, ebx, ecx, edx }
+    }
+
+    const EMPTY: CpuidResult = result(0, 0, 0, 0);
+
+    #[repr(C, align(64))]
+    struct Area([u8; 1024]);
+
+    #[test]
+    fn test_xsave_with_avx() {
+        let support = FpuSupport::from_leaves(
+            result(0, 0, 1 << 26, 1 << 24),
+            result(0x7, 0, 0x340, 0),
+            result(256, 576, 0, 0),
+        )
+        .unwrap();
+
+        assert_eq!(support.mechanism, SaveMechanism::Xsave);
+        assert_eq!(support.features, xcr0::X87 | xcr0::SSE | xcr0::AVX);
+        assert_eq!(support.state_size, 832);
+    }
+
+    #[test]
+    fn test_xsave_ignores_unmanaged_features() {
+        let support = FpuSupport::from_leaves(
+            result(0, 0, 1 << 26, 1 << 24),
+            result(0xE3, 0, 0xA88, 0),
+            result(256, 576, 0, 0),
+        )
+        .unwrap();
+
+        assert_eq!(support.features, xcr0::X87 | xcr0::SSE);
+        assert_eq!(support.state_size, 576);
+    }
+
+    #[test]
+    fn test_fxsave_fallback() {
+        let support = FpuSupport::from_leaves(result(0, 0, 0, 1 << 24), EMPTY, EMPTY).unwrap();
+
+        assert_eq!(support.mechanism, SaveMechanism::Fxsave);
+        assert_eq!(support.state_size, FXSAVE_AREA_SIZE);
+        assert_eq!(FpuSupport::from_leaves(EMPTY, EMPTY, EMPTY), None);
+    }
+
+    #[test]
+    fn test_state_buffer_checks() {
+        let support = FpuSupport::from_leaves(
+            result(0, 0, 1 << 26, 1 << 24),
+            result(0x7, 0, 0, 0),
+            result(256, 576, 0, 0),
+        )
+        .unwrap();
+        let mut area = Area([0xFF; 1024]);
+
+        assert_eq!(
+            FpuState::new(&mut area.0[..512], support).err(),
+            Some(FpuStateError::TooSmall)
+        );
+        assert_eq!(
+            FpuState::new(&mut area.0[16..], support).err(),
+            Some(FpuStateError::NotAligned)
+        );
+
+        let state = FpuState::new(&mut area.0, support).unwrap();
+        assert_eq!(state.bytes().len(), 832);
+        assert_eq!(&state.bytes()[0..2], &[0x7F, 0x03]);
+        assert_eq!(&state.bytes()[24..28], &[0x80, 0x1F, 0, 0]);
+        assert!(state.bytes()[512..].iter().all(|&byte| byte == 0));
+    }
+}
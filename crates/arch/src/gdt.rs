@@ -42,6 +42,27 @@ impl<const TABLE_SIZE: usize> GlobalDescriptorTable<TABLE_SIZE> {
         self.0[loc] = entry.into_entry();
     }
 
+    /// # Store Tss
+    /// Store a descriptor for `tss`, system descriptors are 16 bytes so this takes
+    /// up both `loc` and `loc + 1`.
+    pub fn store_tss(&mut self, loc: usize, tss: &'static TaskStateSegment) {
+        assert!(
+            loc > 0 && loc + 1 < TABLE_SIZE,
+            "TSS entry {loc} does not fit in a table of {TABLE_SIZE} entries!"
+        );
+
+        let base = tss as *const TaskStateSegment as u64;
+        let limit = (size_of::<TaskStateSegment>() - 1) as u64;
+
+        // Type 0x9 (available 64-bit TSS) and present
+        self.0[loc] = (limit & 0xFFFF)
+            | ((base & 0xFF_FFFF) << 16)
+            | (0x89 << 40)
+            | (((limit >> 16) & 0xF) << 48)
+            | (((base >> 24) & 0xFF) << 56);
+        self.0[loc + 1] = base >> 32;
+    }
+
     pub fn pack(&'static self) -> GdtPointer {
         GdtPointer {
             limit: (TABLE_SIZE * size_of::<u64>() - 1) as u16,
@@ -61,6 +82,28 @@ impl GdtPointer {
     pub unsafe fn load(self) {
         asm!("lgdt [{}]", in(reg) &self);
     }
+
+    /// # Current
+    /// Read the GDT pointer that is currently loaded on this CPU (`sgdt`).
+    pub fn current() -> Self {
+        let mut ptr = Self {
+            limit: 0,
+            base: core::ptr::null(),
+        };
+
+        unsafe { asm!("sgdt [{}]", in(reg) &raw mut ptr, options(nostack, preserves_flags)) };
+        ptr
+    }
+
+    /// # Entries
+    /// Get the raw entries of the table this pointer points to.
+    ///
+    /// # Safety
+    /// The pointer must point to a valid, currently mapped, GDT.
+    pub unsafe fn entries(&self) -> &'static [u64] {
+        let entries = (self.limit as usize + 1) / size_of::<u64>();
+        unsafe { core::slice::from_raw_parts(self.base, entries) }
+    }
 }
 
 #[make_hw(
@@ -112,6 +155,16 @@ impl DataSegmentDesc {
     pub const fn new64() -> Self {
         Self(0).set_user_segment_flag(true)
     }
+
+    /// # New 32
+    /// A flat 4GiB 32-bit segment.
+    pub const fn new32() -> Self {
+        // Limit of 0xFFFFF pages
+        Self(0x000F_0000_0000_FFFF)
+            .set_user_segment_flag(true)
+            .set_big_flag(true)
+            .set_granularity_flag(true)
+    }
 }
 
 impl CodeSegmentDesc {
@@ -121,6 +174,17 @@ impl CodeSegmentDesc {
             .set_code_segment_flag(true)
             .set_long_mode_flag(true)
     }
+
+    /// # New 32
+    /// A flat 4GiB 32-bit segment.
+    pub const fn new32() -> Self {
+        // Limit of 0xFFFFF pages
+        Self(0x000F_0000_0000_FFFF)
+            .set_user_segment_flag(true)
+            .set_code_segment_flag(true)
+            .set_big_flag(true)
+            .set_granularity_flag(true)
+    }
 }
 
 pub trait SegmentEntry {
@@ -138,3 +202,39 @@ impl SegmentEntry for DataSegmentDesc {
         self.0
     }
 }
+
+/// # Task State Segment
+/// The 64-bit TSS, which holds the stacks the CPU switches to when changing privilege
+/// level or taking an interrupt with an IST index.
+#[repr(C, packed(4))]
+#[derive(Clone, Copy)]
+pub struct TaskStateSegment {
+    reserved0: u32,
+    pub privilege_stacks: [u64; 3],
+    reserved1: u64,
+    pub interrupt_stacks: [u64; 7],
+    reserved2: u64,
+    reserved3: u16,
+    pub iomap_base: u16,
+}
+
+impl TaskStateSegment {
+    pub const fn new() -> Self {
+        Self {
+            reserved0: 0,
+            privilege_stacks: [0; 3],
+            reserved1: 0,
+            interrupt_stacks: [0; 7],
+            reserved2: 0,
+            reserved3: 0,
+            // No I/O permission bitmap
+            iomap_base: size_of::<Self>() as u16,
+        }
+    }
+}
+
+impl Default for TaskStateSegment {
+    fn default() -> Self {
+        Self::new()
+    }
+}
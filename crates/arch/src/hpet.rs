@@ -0,0 +1,436 @@
+/*
+  ____                 __               __   _ __
+ / __ \__ _____ ____  / /___ ____ _    / /  (_) /
+/ /_/ / // / _ `/ _ \/ __/ // /  ' \  / /__/ / _ \
+\___\_\_,_/\_,_/_//_/\__/\_,_/_/_/_/ /____/_/_.__/
+    Part of the Quantum OS Project
+
+Copyright 2024 Gavin Kellam
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and
+associated documentation files (the "Software"), to deal in the Software without restriction,
+including without limitation the rights to use, copy, modify, merge, publish, distribute,
+sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial
+portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT
+NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
+OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+use crate::time::{ClockSource, Instant};
+use core::time::Duration;
+use hw::make_hw;
+
+/// # Hpet Error
+/// Errors from finding or programming the HPET.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HpetError {
+    /// The ACPI table isn't an HPET table.
+    BadSignature,
+    /// The ACPI table's checksum doesn't add up to zero.
+    BadChecksum,
+    /// The HPET's registers are not in memory space.
+    NotMemoryMapped,
+    /// The HPET doesn't have this timer.
+    NoSuchTimer,
+    /// The timer can't run in periodic mode.
+    PeriodicNotSupported,
+    /// The timer can't be routed to this IOAPIC input.
+    InvalidRoute,
+}
+
+/// # Hpet Table
+/// The parts of the ACPI "HPET" table needed to find the HPET.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HpetTable {
+    /// The physical address of the HPET's registers.
+    pub base_address: u64,
+    pub hpet_number: u8,
+    /// The smallest periodic tick (in main counter ticks) that won't lose
+    /// interrupts.
+    pub min_tick: u16,
+}
+
+impl HpetTable {
+    const SIGNATURE: &'static [u8; 4] = b"HPET";
+    const MIN_LENGTH: usize = 56;
+
+    /// # From Acpi
+    /// Parse the HPET table at `table`.
+    ///
+    /// # Safety
+    /// `table` must point to a mapped ACPI table.
+    pub unsafe fn from_acpi(table: *const u8) -> Result<Self, HpetError> {
+        let header = core::slice::from_raw_parts(table, 8);
+        let length = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as usize;
+
+        if &header[..4] != Self::SIGNATURE || length < Self::MIN_LENGTH {
+            return Err(HpetError::BadSignature);
+        }
+
+        Self::from_bytes(core::slice::from_raw_parts(table, length))
+    }
+
+    /// # From Bytes
+    /// Parse an HPET table that has already been copied into memory.
+    pub fn from_bytes(table: &[u8]) -> Result<Self, HpetError> {
+        if table.len() < Self::MIN_LENGTH || &table[..4] != Self::SIGNATURE {
+            return Err(HpetError::BadSignature);
+        }
+
+        if table.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte)) != 0 {
+            return Err(HpetError::BadChecksum);
+        }
+
+        // The base address is an ACPI generic address, space 0 is memory
+        if table[40] != 0 {
+            return Err(HpetError::NotMemoryMapped);
+        }
+
+        let mut address = [0; 8];
+        address.copy_from_slice(&table[44..52]);
+
+        Ok(Self {
+            base_address: u64::from_le_bytes(address),
+            hpet_number: table[52],
+            min_tick: u16::from_le_bytes([table[53], table[54]]),
+        })
+    }
+}
+
+#[make_hw(
+    field(RO, 0..8, pub revision),
+    field(RO, 8..13, pub last_timer),
+    field(RO, 13, pub counter_64bit),
+    field(RO, 15, pub legacy_replacement),
+    field(RO, 16..32, pub vendor_id),
+    field(RO, 32..64, pub period_femtoseconds)
+)]
+#[derive(Clone, Copy, Debug)]
+pub struct HpetCapabilities(u64);
+
+#[make_hw(
+    field(RW, 1, pub level_triggered),
+    field(RW, 2, pub interrupt_enable),
+    field(RW, 3, pub periodic),
+    field(RO, 4, pub periodic_capable),
+    field(RO, 5, pub comparator_64bit),
+    field(RW, 6, pub set_accumulator),
+    field(RW, 8, pub force_32bit),
+    field(RW, 9..14, pub ioapic_route),
+    field(RW, 14, pub fsb_enable),
+    field(RO, 15, pub fsb_capable),
+    field(RO, 32..64, pub route_capabilities)
+)]
+#[derive(Clone, Copy, Debug)]
+pub struct HpetTimerConfig(u64);
+
+/// # Hpet Timer Mode
+/// How a HPET timer fires.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HpetTimerMode {
+    /// Fire once when the main counter reaches the comparator.
+    OneShot,
+    /// Fire every period.
+    Periodic,
+}
+
+/// # Hpet
+/// The High Precision Event Timer.
+pub struct Hpet {
+    mmio: *mut u64,
+}
+
+impl Hpet {
+    const REG_CAPABILITIES: usize = 0x000;
+    const REG_CONFIG: usize = 0x010;
+    const REG_INTERRUPT_STATUS: usize = 0x020;
+    const REG_MAIN_COUNTER: usize = 0x0F0;
+
+    const CONFIG_ENABLE: u64 = 1 << 0;
+
+    const FEMTOS_PER_NANO: u64 = 1_000_000;
+
+    /// # New
+    /// Access the HPET whose registers are mapped at `mmio`.
+    ///
+    /// # Safety
+    /// `mmio` must point to the HPET's registers ([`HpetTable::base_address`])
+    /// mapped as uncacheable memory.
+    pub unsafe fn new(mmio: *mut u64) -> Self {
+        Self { mmio }
+    }
+
+    fn read(&self, offset: usize) -> u64 {
+        unsafe { core::ptr::read_volatile(self.mmio.byte_add(offset)) }
+    }
+
+    fn write(&mut self, offset: usize, value: u64) {
+        unsafe { core::ptr::write_volatile(self.mmio.byte_add(offset), value) }
+    }
+
+    const fn timer_config_reg(timer: u8) -> usize {
+        0x100 + 0x20 * timer as usize
+    }
+
+    const fn timer_comparator_reg(timer: u8) -> usize {
+        0x108 + 0x20 * timer as usize
+    }
+
+    /// # Capabilities
+    /// What this HPET can do.
+    pub fn capabilities(&self) -> HpetCapabilities {
+        HpetCapabilities(self.read(Self::REG_CAPABILITIES))
+    }
+
+    /// # Timers
+    /// The number of timers (comparators) this HPET has.
+    pub fn timers(&self) -> u8 {
+        self.capabilities().read_last_timer() + 1
+    }
+
+    /// # Frequency
+    /// How many times per second the main counter ticks.
+    pub fn frequency(&self) -> u64 {
+        1_000_000_000_000_000 / (self.capabilities().read_period_femtoseconds() as u64).max(1)
+    }
+
+    /// # Set Enabled
+    /// Start or stop the main counter, timers only fire while its running.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        let config = self.read(Self::REG_CONFIG);
+        let config = if enabled {
+            config | Self::CONFIG_ENABLE
+        } else {
+            config & !Self::CONFIG_ENABLE
+        };
+
+        self.write(Self::REG_CONFIG, config);
+    }
+
+    /// # Main Counter
+    /// The current value of the main counter.
+    pub fn main_counter(&self) -> u64 {
+        self.read(Self::REG_MAIN_COUNTER)
+    }
+
+    /// # Reset Main Counter
+    /// Set the main counter back to zero, the HPET must be disabled.
+    pub fn reset_main_counter(&mut self) {
+        self.write(Self::REG_MAIN_COUNTER, 0);
+    }
+
+    /// # Duration To Ticks
+    /// Convert a duration into main counter ticks.
+    pub fn duration_to_ticks(&self, duration: Duration) -> u64 {
+        let period = self.capabilities().read_period_femtoseconds() as u128;
+        let femtos = duration.as_nanos() * Self::FEMTOS_PER_NANO as u128;
+
+        (femtos / period.max(1)) as u64
+    }
+
+    /// # Timer Config
+    /// Read the config (and capabilities) of a timer.
+    pub fn timer_config(&self, timer: u8) -> Result<HpetTimerConfig, HpetError> {
+        if timer >= self.timers() {
+            return Err(HpetError::NoSuchTimer);
+        }
+
+        Ok(HpetTimerConfig(self.read(Self::timer_config_reg(timer))))
+    }
+
+    /// # Start Timer
+    /// Fire IOAPIC input `route` after `delay`, and then every `delay` for
+    /// [`HpetTimerMode::Periodic`].
+    ///
+    /// # Safety
+    /// The IOAPIC input must be routed to a vector the IDT can handle.
+    pub unsafe fn start_timer(
+        &mut self,
+        timer: u8,
+        mode: HpetTimerMode,
+        route: u8,
+        delay: Duration,
+    ) -> Result<(), HpetError> {
+        let mut config = self.timer_config(timer)?;
+
+        if mode == HpetTimerMode::Periodic && !config.is_periodic_capable_set() {
+            return Err(HpetError::PeriodicNotSupported);
+        }
+
+        if route >= 32 || config.read_route_capabilities() & (1 << route) == 0 {
+            return Err(HpetError::InvalidRoute);
+        }
+
+        let ticks = self.duration_to_ticks(delay).max(1);
+        let periodic = mode == HpetTimerMode::Periodic;
+
+        let config = config
+            .set_level_triggered_flag(false)
+            .set_fsb_enable_flag(false)
+            .set_ioapic_route(route)
+            .set_periodic_flag(periodic)
+            .set_set_accumulator_flag(periodic)
+            .set_interrupt_enable_flag(true);
+
+        self.write(Self::timer_config_reg(timer), config.0);
+        self.write(
+            Self::timer_comparator_reg(timer),
+            self.main_counter().wrapping_add(ticks),
+        );
+
+        // After the first write, a second write in periodic mode sets the
+        // period that gets added to the comparator each time it fires.
+        if periodic {
+            self.write(Self::timer_comparator_reg(timer), ticks);
+        }
+
+        Ok(())
+    }
+
+    /// # Stop Timer
+    /// Stop a timer from firing.
+    pub fn stop_timer(&mut self, timer: u8) -> Result<(), HpetError> {
+        let config = self
+            .timer_config(timer)?
+            .set_interrupt_enable_flag(false)
+            .set_periodic_flag(false);
+
+        self.write(Self::timer_config_reg(timer), config.0);
+        Ok(())
+    }
+
+    /// # Acknowledge
+    /// Clear the interrupt status of a level triggered timer.
+    pub fn acknowledge(&mut self, timer: u8) -> Result<(), HpetError> {
+        if timer >= self.timers() {
+            return Err(HpetError::NoSuchTimer);
+        }
+
+        self.write(Self::REG_INTERRUPT_STATUS, 1 << timer);
+        Ok(())
+    }
+}
+
+impl ClockSource for Hpet {
+    fn now(&self) -> Instant {
+        let period = self.capabilities().read_period_femtoseconds() as u128;
+        let femtos = self.main_counter() as u128 * period;
+
+        Instant::from_nanos((femtos / Self::FEMTOS_PER_NANO as u128) as u64)
+    }
+
+    fn resolution(&self) -> Duration {
+        let period = self.capabilities().read_period_femtoseconds() as u64;
+        Duration::from_nanos((period / Self::FEMTOS_PER_NANO).max(1))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn hpet_table() -> [u8; 56] {
+        let mut table = [0u8; 56];
+        table[..4].copy_from_slice(b"HPET");
+        table[4] = 56;
+        table[44..52].copy_from_slice(&0xFED0_0000u64.to_le_bytes());
+        table[52] = 0;
+        table[53..55].copy_from_slice(&128u16.to_le_bytes());
+
+        let sum = table.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte));
+        table[9] = 0u8.wrapping_sub(sum);
+        table
+    }
+
+    #[test]
+    fn test_parse_table() {
+        let table = hpet_table();
+
+        assert_eq!(
+            unsafe { HpetTable::from_acpi(table.as_ptr()) },
+            Ok(HpetTable {
+                base_address: 0xFED0_0000,
+                hpet_number: 0,
+                min_tick: 128,
+            })
+        );
+    }
+
+    #[test]
+    fn test_bad_table() {
+        let mut table = hpet_table();
+        table[20] ^= 0xFF;
+        assert_eq!(HpetTable::from_bytes(&table), Err(HpetError::BadChecksum));
+
+        let mut table = hpet_table();
+        table[0] = b'X';
+        assert_eq!(HpetTable::from_bytes(&table), Err(HpetError::BadSignature));
+    }
+
+    /// Fake HPET with 3 timers ticking at 10MHz, timer 0 is periodic capable
+    /// and can be routed to inputs 2 and 8.
+    fn fake_hpet() -> [u64; 0x180 / 8] {
+        let mut regs = [0u64; 0x180 / 8];
+        regs[0] = (100_000_000 << 32) | (2 << 8) | 1;
+        regs[0x100 / 8] = (((1 << 2) | (1 << 8)) << 32) | (1 << 4);
+        regs
+    }
+
+    #[test]
+    fn test_capabilities() {
+        let mut regs = fake_hpet();
+        let hpet = unsafe { Hpet::new(regs.as_mut_ptr()) };
+
+        assert_eq!(hpet.timers(), 3);
+        assert_eq!(hpet.frequency(), 10_000_000);
+        assert_eq!(hpet.duration_to_ticks(Duration::from_millis(1)), 10_000);
+        assert_eq!(hpet.resolution(), Duration::from_nanos(100));
+    }
+
+    #[test]
+    fn test_start_timer() {
+        let mut regs = fake_hpet();
+        regs[0xF0 / 8] = 500;
+        let mut hpet = unsafe { Hpet::new(regs.as_mut_ptr()) };
+
+        assert_eq!(
+            unsafe { hpet.start_timer(0, HpetTimerMode::OneShot, 8, Duration::from_micros(10)) },
+            Ok(())
+        );
+        assert_eq!(regs[0x108 / 8], 600);
+
+        let config = HpetTimerConfig(regs[0x100 / 8]);
+        assert_eq!(config.get_ioapic_route(), 8);
+        assert!(config.is_interrupt_enable_set());
+        assert!(!config.is_periodic_set());
+    }
+
+    #[test]
+    fn test_timer_errors() {
+        let mut regs = fake_hpet();
+        let mut hpet = unsafe { Hpet::new(regs.as_mut_ptr()) };
+        let delay = Duration::from_millis(1);
+
+        assert_eq!(
+            unsafe { hpet.start_timer(5, HpetTimerMode::OneShot, 2, delay) },
+            Err(HpetError::NoSuchTimer)
+        );
+        assert_eq!(
+            unsafe { hpet.start_timer(0, HpetTimerMode::OneShot, 3, delay) },
+            Err(HpetError::InvalidRoute)
+        );
+        assert_eq!(
+            unsafe { hpet.start_timer(1, HpetTimerMode::Periodic, 2, delay) },
+            Err(HpetError::PeriodicNotSupported)
+        );
+        assert_eq!(hpet.acknowledge(3), Err(HpetError::NoSuchTimer));
+        assert_eq!(hpet.acknowledge(2), Ok(()));
+    }
+}
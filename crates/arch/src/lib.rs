@@ -25,10 +25,20 @@ OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWA
 
 #![no_std]
 
+pub mod apic;
+pub mod cpuid;
+pub mod debug;
+pub mod fpu;
 pub mod gdt;
+pub mod hpet;
 pub mod io;
+pub mod msr;
 pub mod paging64;
+pub mod pmu;
 pub mod registers;
+pub mod smp;
+pub mod time;
+pub mod tlb;
 
 pub mod interrupts {
     #[inline(always)]
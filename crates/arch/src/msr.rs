@@ -0,0 +1,221 @@
+/*
+  ____                 __               __   _ __
+ / __ \__ _____ ____  / /___ ____ _    / /  (_) /
+/ /_/ / // / _ `/ _ \/ __/ // /  ' \  / /__/ / _ \
+\___\_\_,_/\_,_/_//_/\__/\_,_/_/_/_/ /____/_/_.__/
+    Part of the Quantum OS Project
+
+Copyright 2024 Gavin Kellam
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and
+associated documentation files (the "Software"), to deal in the Software without restriction,
+including without limitation the rights to use, copy, modify, merge, publish, distribute,
+sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial
+portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT
+NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
+OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+use hw::make_hw;
+
+/// # Rdmsr
+/// Read a model specific register.
+///
+/// # Safety
+/// `msr_number` must be a MSR the CPU supports, and we must be in ring 0.
+#[inline(always)]
+pub unsafe fn rdmsr(msr_number: u32) -> u64 {
+    let lo: u32;
+    let hi: u32;
+
+    unsafe {
+        core::arch::asm!("rdmsr",
+            in("ecx") msr_number,
+            out("eax") lo,
+            out("edx") hi
+        )
+    }
+
+    lo as u64 | ((hi as u64) << 32)
+}
+
+/// # Wrmsr
+/// Write a model specific register.
+///
+/// # Safety
+/// `msr_number` must be a MSR the CPU supports, and we must be in ring 0.
+/// Writing MSRs can change almost anything about how the CPU runs.
+#[inline(always)]
+pub unsafe fn wrmsr(msr_number: u32, value: u64) {
+    let lo = value as u32;
+    let hi = (value >> 32) as u32;
+
+    core::arch::asm!("wrmsr",
+        in("ecx") msr_number,
+        in("eax") lo,
+        in("edx") hi
+    )
+}
+
+#[make_hw(
+    field(RW, 0, pub syscall_extensions),
+    field(RW, 8, pub long_mode_enable),
+    field(RW, 10, pub long_mode_active),
+    field(RW, 11, pub no_execute),
+    field(RW, 12, pub secure_virtual_machine),
+    field(RW, 13, pub long_mode_segment_limit),
+    field(RW, 14, pub fast_fxsave),
+    field(RW, 15, pub translation_cache)
+)]
+pub mod ia32_efer {
+    use super::{rdmsr, wrmsr};
+
+    pub const MSR: u32 = 0xC0000080;
+
+    #[inline(always)]
+    pub fn read() -> u64 {
+        unsafe { rdmsr(MSR) }
+    }
+
+    /// # Safety
+    /// Changing EFER changes which CPU modes and paging features are enabled.
+    #[inline(always)]
+    pub unsafe fn write(value: u64) {
+        wrmsr(MSR, value);
+    }
+}
+
+#[make_hw(
+    field(RO, 8, pub bootstrap_processor),
+    field(RW, 10, pub x2apic_mode),
+    field(RW, 11, pub apic_global_enable),
+    field(RWNS, 12..52, pub apic_base)
+)]
+pub mod ia32_apic_base {
+    use super::{rdmsr, wrmsr};
+
+    pub const MSR: u32 = 0x1B;
+
+    #[inline(always)]
+    pub fn read() -> u64 {
+        unsafe { rdmsr(MSR) }
+    }
+
+    /// # Safety
+    /// Changing the APIC base or enable bits changes how interrupts are delivered.
+    #[inline(always)]
+    pub unsafe fn write(value: u64) {
+        wrmsr(MSR, value);
+    }
+}
+
+#[make_hw(
+    field(RW, 0..32, pub legacy_syscall_target),
+    /// The kernel CS for `syscall`, SS is loaded with this value + 8.
+    field(RW, 32..48, pub syscall_selector),
+    /// The base selector for `sysret`, 64-bit CS is this value + 16 and SS is
+    /// this value + 8.
+    field(RW, 48..64, pub sysret_selector)
+)]
+pub mod ia32_star {
+    use super::{rdmsr, wrmsr};
+
+    pub const MSR: u32 = 0xC0000081;
+
+    #[inline(always)]
+    pub fn read() -> u64 {
+        unsafe { rdmsr(MSR) }
+    }
+
+    /// # Safety
+    /// The selectors must point to valid code/data segments in the GDT.
+    #[inline(always)]
+    pub unsafe fn write(value: u64) {
+        wrmsr(MSR, value);
+    }
+}
+
+/// The address `syscall` jumps to in 64-bit mode.
+pub mod ia32_lstar {
+    use super::{rdmsr, wrmsr};
+
+    pub const MSR: u32 = 0xC0000082;
+
+    #[inline(always)]
+    pub fn read() -> u64 {
+        unsafe { rdmsr(MSR) }
+    }
+
+    /// # Safety
+    /// `value` must be the address of a valid syscall entry point.
+    #[inline(always)]
+    pub unsafe fn write(value: u64) {
+        wrmsr(MSR, value);
+    }
+}
+
+#[make_hw(
+    /// Every RFLAGS bit set here is cleared when `syscall` is run.
+    field(RW, 0..32, pub rflags_mask)
+)]
+pub mod ia32_fmask {
+    use super::{rdmsr, wrmsr};
+
+    pub const MSR: u32 = 0xC0000084;
+
+    #[inline(always)]
+    pub fn read() -> u64 {
+        unsafe { rdmsr(MSR) }
+    }
+
+    /// # Safety
+    /// Not masking the interrupt flag lets interrupts fire before the
+    /// syscall entry has switched stacks.
+    #[inline(always)]
+    pub unsafe fn write(value: u64) {
+        wrmsr(MSR, value);
+    }
+}
+
+#[make_hw(
+    field(RW, 0..3, pub pat0),
+    field(RW, 8..11, pub pat1),
+    field(RW, 16..19, pub pat2),
+    field(RW, 24..27, pub pat3),
+    field(RW, 32..35, pub pat4),
+    field(RW, 40..43, pub pat5),
+    field(RW, 48..51, pub pat6),
+    field(RW, 56..59, pub pat7)
+)]
+pub mod ia32_pat {
+    use super::{rdmsr, wrmsr};
+
+    pub const MSR: u32 = 0x277;
+
+    pub const UNCACHEABLE: u8 = 0x00;
+    pub const WRITE_COMBINING: u8 = 0x01;
+    pub const WRITE_THROUGH: u8 = 0x04;
+    pub const WRITE_PROTECTED: u8 = 0x05;
+    pub const WRITE_BACK: u8 = 0x06;
+    /// Uncacheable, but can be overridden by write combining MTRRs.
+    pub const UNCACHED: u8 = 0x07;
+
+    #[inline(always)]
+    pub fn read() -> u64 {
+        unsafe { rdmsr(MSR) }
+    }
+
+    /// # Safety
+    /// Changing the PAT changes the memory type of every page that uses it.
+    #[inline(always)]
+    pub unsafe fn write(value: u64) {
+        wrmsr(MSR, value);
+    }
+}
@@ -0,0 +1,501 @@
+/*
+  ____                 __               __   _ __
+ / __ \__ _____ ____  / /___ ____ _    / /  (_) /
+/ /_/ / // / _ `/ _ \/ __/ // /  ' \  / /__/ / _ \
+\___\_\_,_/\_,_/_//_/\__/\_,_/_/_/_/ /____/_/_.__/
+    Part of the Quantum OS Project
+
+Copyright 2024 Gavin Kellam
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and
+associated documentation files (the "Software"), to deal in the Software without restriction,
+including without limitation the rights to use, copy, modify, merge, publish, distribute,
+sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial
+portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT
+NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
+OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+use crate::apic::{LocalApic, LocalApicReg};
+use crate::cpuid::{checked_cpuid, CpuidResult};
+use crate::msr::{rdmsr, wrmsr};
+use hw::make_hw;
+
+const IA32_PMC0: u32 = 0xC1;
+const IA32_PERFEVTSEL0: u32 = 0x186;
+const IA32_FIXED_CTR0: u32 = 0x309;
+const IA32_FIXED_CTR_CTRL: u32 = 0x38D;
+const IA32_PERF_GLOBAL_STATUS: u32 = 0x38E;
+const IA32_PERF_GLOBAL_CTRL: u32 = 0x38F;
+const IA32_PERF_GLOBAL_OVF_CTRL: u32 = 0x390;
+
+/// # Pmu Error
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PmuError {
+    /// The CPU doesn't have this counter.
+    NoSuchCounter,
+    /// The CPU can't count this event.
+    EventNotSupported,
+    /// The global status and control MSRs only exist from version 2.
+    NoGlobalControl,
+}
+
+/// # Arch Event
+/// The architectural events every CPU with performance monitoring can count
+/// (unless leaf `0xA` says otherwise).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArchEvent {
+    CoreCycles,
+    InstructionsRetired,
+    ReferenceCycles,
+    LlcReferences,
+    LlcMisses,
+    BranchesRetired,
+    BranchMisses,
+}
+
+impl ArchEvent {
+    /// # Event And Umask
+    /// The event select and unit mask that count this event.
+    pub const fn event_and_umask(self) -> (u8, u8) {
+        match self {
+            ArchEvent::CoreCycles => (0x3C, 0x00),
+            ArchEvent::InstructionsRetired => (0xC0, 0x00),
+            ArchEvent::ReferenceCycles => (0x3C, 0x01),
+            ArchEvent::LlcReferences => (0x2E, 0x4F),
+            ArchEvent::LlcMisses => (0x2E, 0x41),
+            ArchEvent::BranchesRetired => (0xC4, 0x00),
+            ArchEvent::BranchMisses => (0xC5, 0x00),
+        }
+    }
+
+    /// The bit in leaf `0xA`'s `ebx` that is set when this event is missing.
+    const fn unavailable_bit(self) -> u32 {
+        match self {
+            ArchEvent::CoreCycles => 0,
+            ArchEvent::InstructionsRetired => 1,
+            ArchEvent::ReferenceCycles => 2,
+            ArchEvent::LlcReferences => 3,
+            ArchEvent::LlcMisses => 4,
+            ArchEvent::BranchesRetired => 5,
+            ArchEvent::BranchMisses => 6,
+        }
+    }
+}
+
+/// # Fixed Counter
+/// The fixed function counters, each can only count one event.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FixedCounter {
+    InstructionsRetired = 0,
+    CoreCycles = 1,
+    ReferenceCycles = 2,
+}
+
+/// # Count Privilege
+/// Which privilege levels a counter counts in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CountPrivilege {
+    Kernel,
+    User,
+    Both,
+}
+
+impl CountPrivilege {
+    const fn kernel(self) -> bool {
+        matches!(self, CountPrivilege::Kernel | CountPrivilege::Both)
+    }
+
+    const fn user(self) -> bool {
+        matches!(self, CountPrivilege::User | CountPrivilege::Both)
+    }
+}
+
+/// # Event Select
+/// The `IA32_PERFEVTSELx` register of a general purpose counter.
+#[make_hw(
+    field(RW, 0..8, pub event),
+    field(RW, 8..16, pub umask),
+    field(RW, 16, pub user_mode),
+    field(RW, 17, pub kernel_mode),
+    field(RW, 18, pub edge_detect),
+    field(RW, 20, pub interrupt),
+    field(RW, 22, pub enable),
+    field(RW, 23, pub invert),
+    field(RW, 24..32, pub counter_mask)
+)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EventSelect(u64);
+
+impl EventSelect {
+    /// # For Event
+    /// Count `event` in `privilege`, optionally raising an interrupt when the
+    /// counter overflows.
+    pub fn for_event(event: ArchEvent, privilege: CountPrivilege, interrupt: bool) -> Self {
+        let (event, umask) = event.event_and_umask();
+
+        Self(0)
+            .set_event(event)
+            .set_umask(umask)
+            .set_user_mode_flag(privilege.user())
+            .set_kernel_mode_flag(privilege.kernel())
+            .set_interrupt_flag(interrupt)
+            .set_enable_flag(true)
+    }
+
+    pub const fn value(&self) -> u64 {
+        self.0
+    }
+}
+
+/// # Pmu Info
+/// The performance monitoring this CPU supports, from `cpuid` leaf `0xA`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PmuInfo {
+    pub version: u8,
+    pub general_counters: u8,
+    /// Bit width of the general purpose counters.
+    pub general_width: u8,
+    pub fixed_counters: u8,
+    /// Bit width of the fixed counters.
+    pub fixed_width: u8,
+    unavailable_events: u32,
+    event_vector_length: u8,
+}
+
+impl PmuInfo {
+    /// # Read
+    /// Read this CPU's performance monitoring support, or `None` if it has none.
+    pub fn read() -> Option<Self> {
+        Self::from_leaf(checked_cpuid(0xA, 0))
+    }
+
+    /// # From Leaf
+    /// Decode the result of `cpuid` leaf `0xA`.
+    pub const fn from_leaf(leaf: CpuidResult) -> Option<Self> {
+        let version = leaf.eax as u8;
+        if version == 0 {
+            return None;
+        }
+
+        // Fixed counters are only enumerated from version 2
+        let (fixed_counters, fixed_width) = if version >= 2 {
+            ((leaf.edx & 0x1F) as u8, ((leaf.edx >> 5) & 0xFF) as u8)
+        } else {
+            (0, 0)
+        };
+
+        Some(Self {
+            version,
+            general_counters: (leaf.eax >> 8) as u8,
+            general_width: (leaf.eax >> 16) as u8,
+            fixed_counters,
+            fixed_width,
+            unavailable_events: leaf.ebx,
+            event_vector_length: (leaf.eax >> 24) as u8,
+        })
+    }
+
+    /// # Supports
+    /// Check if this CPU can count `event`.
+    pub const fn supports(&self, event: ArchEvent) -> bool {
+        let bit = event.unavailable_bit();
+        bit < self.event_vector_length as u32 && self.unavailable_events & (1 << bit) == 0
+    }
+
+    /// # Overflow Preload
+    /// The value to start a `width` bit counter at so it overflows after
+    /// `events` more events.
+    pub const fn overflow_preload(width: u8, events: u64) -> u64 {
+        let mask = if width >= 64 {
+            u64::MAX
+        } else {
+            (1 << width) - 1
+        };
+
+        0u64.wrapping_sub(events) & mask
+    }
+}
+
+/// # Pmu
+/// Programs this CPU's performance counters.
+pub struct Pmu {
+    info: PmuInfo,
+}
+
+impl Pmu {
+    /// # New
+    /// Use the counters described by `info`, which must have been read on this CPU.
+    pub const fn new(info: PmuInfo) -> Self {
+        Self { info }
+    }
+
+    pub const fn info(&self) -> &PmuInfo {
+        &self.info
+    }
+
+    /// # Start General
+    /// Start general purpose counter `index` counting `event` from `initial`.
+    ///
+    /// # Safety
+    /// Must be run in ring 0. With `interrupt` set, an overflow handler must
+    /// be routed (see [`Pmu::route_overflow_interrupt`]).
+    pub unsafe fn start_general(
+        &mut self,
+        index: u8,
+        event: ArchEvent,
+        privilege: CountPrivilege,
+        interrupt: bool,
+        initial: u64,
+    ) -> Result<(), PmuError> {
+        if index >= self.info.general_counters {
+            return Err(PmuError::NoSuchCounter);
+        }
+        if !self.info.supports(event) {
+            return Err(PmuError::EventNotSupported);
+        }
+
+        let select = EventSelect::for_event(event, privilege, interrupt);
+
+        unsafe {
+            wrmsr(IA32_PERFEVTSEL0 + index as u32, 0);
+            wrmsr(
+                IA32_PMC0 + index as u32,
+                initial & Self::mask(self.info.general_width),
+            );
+            wrmsr(IA32_PERFEVTSEL0 + index as u32, select.value());
+            self.set_global_enable(index as u32, true);
+        }
+
+        Ok(())
+    }
+
+    /// # Stop General
+    /// Stop general purpose counter `index`, returning its final count.
+    ///
+    /// # Safety
+    /// Must be run in ring 0.
+    pub unsafe fn stop_general(&mut self, index: u8) -> Result<u64, PmuError> {
+        if index >= self.info.general_counters {
+            return Err(PmuError::NoSuchCounter);
+        }
+
+        unsafe {
+            wrmsr(IA32_PERFEVTSEL0 + index as u32, 0);
+            self.set_global_enable(index as u32, false);
+            Ok(rdmsr(IA32_PMC0 + index as u32))
+        }
+    }
+
+    /// # Read General
+    /// Read general purpose counter `index`.
+    ///
+    /// # Safety
+    /// Must be run in ring 0.
+    pub unsafe fn read_general(&self, index: u8) -> Result<u64, PmuError> {
+        if index >= self.info.general_counters {
+            return Err(PmuError::NoSuchCounter);
+        }
+
+        Ok(unsafe { rdmsr(IA32_PMC0 + index as u32) })
+    }
+
+    /// # Start Fixed
+    /// Start a fixed counter from zero.
+    ///
+    /// # Safety
+    /// Must be run in ring 0. With `interrupt` set, an overflow handler must
+    /// be routed (see [`Pmu::route_overflow_interrupt`]).
+    pub unsafe fn start_fixed(
+        &mut self,
+        counter: FixedCounter,
+        privilege: CountPrivilege,
+        interrupt: bool,
+    ) -> Result<(), PmuError> {
+        let index = counter as u32;
+        if index >= self.info.fixed_counters as u32 {
+            return Err(PmuError::NoSuchCounter);
+        }
+
+        let control = (privilege.kernel() as u64)
+            | ((privilege.user() as u64) << 1)
+            | ((interrupt as u64) << 3);
+
+        unsafe {
+            let all = rdmsr(IA32_FIXED_CTR_CTRL) & !(0xF << (index * 4));
+            wrmsr(IA32_FIXED_CTR_CTRL, all);
+            wrmsr(IA32_FIXED_CTR0 + index, 0);
+            wrmsr(IA32_FIXED_CTR_CTRL, all | (control << (index * 4)));
+            self.set_global_enable(32 + index, true);
+        }
+
+        Ok(())
+    }
+
+    /// # Stop Fixed
+    /// Stop a fixed counter, returning its final count.
+    ///
+    /// # Safety
+    /// Must be run in ring 0.
+    pub unsafe fn stop_fixed(&mut self, counter: FixedCounter) -> Result<u64, PmuError> {
+        let index = counter as u32;
+        if index >= self.info.fixed_counters as u32 {
+            return Err(PmuError::NoSuchCounter);
+        }
+
+        unsafe {
+            let all = rdmsr(IA32_FIXED_CTR_CTRL) & !(0xF << (index * 4));
+            wrmsr(IA32_FIXED_CTR_CTRL, all);
+            self.set_global_enable(32 + index, false);
+            Ok(rdmsr(IA32_FIXED_CTR0 + index))
+        }
+    }
+
+    /// # Read Fixed
+    ///
+    /// # Safety
+    /// Must be run in ring 0.
+    pub unsafe fn read_fixed(&self, counter: FixedCounter) -> Result<u64, PmuError> {
+        let index = counter as u32;
+        if index >= self.info.fixed_counters as u32 {
+            return Err(PmuError::NoSuchCounter);
+        }
+
+        Ok(unsafe { rdmsr(IA32_FIXED_CTR0 + index) })
+    }
+
+    /// # Overflow Status
+    /// Which counters have overflowed, general purpose counters are bits `0..`
+    /// and fixed counters are bits `32..`.
+    ///
+    /// # Safety
+    /// Must be run in ring 0.
+    pub unsafe fn overflow_status(&self) -> Result<u64, PmuError> {
+        if self.info.version < 2 {
+            return Err(PmuError::NoGlobalControl);
+        }
+
+        Ok(unsafe { rdmsr(IA32_PERF_GLOBAL_STATUS) })
+    }
+
+    /// # Clear Overflow
+    /// Clear the overflow bits in `mask` (same layout as [`Pmu::overflow_status`]).
+    ///
+    /// # Safety
+    /// Must be run in ring 0.
+    pub unsafe fn clear_overflow(&mut self, mask: u64) -> Result<(), PmuError> {
+        if self.info.version < 2 {
+            return Err(PmuError::NoGlobalControl);
+        }
+
+        unsafe { wrmsr(IA32_PERF_GLOBAL_OVF_CTRL, mask) };
+        Ok(())
+    }
+
+    /// # Route Overflow Interrupt
+    /// Deliver counter overflow interrupts to `vector` on this CPU.
+    ///
+    /// The local APIC masks this interrupt every time it's delivered, so the
+    /// handler must call this again to unmask it.
+    ///
+    /// # Safety
+    /// Must be run in ring 0, with a handler installed for `vector`.
+    pub unsafe fn route_overflow_interrupt(&self, lapic: &mut LocalApic, vector: u8) {
+        unsafe { lapic.write(LocalApicReg::LvtPerformanceCounter, vector as u32) };
+    }
+
+    /// # Mask Overflow Interrupt
+    ///
+    /// # Safety
+    /// Must be run in ring 0.
+    pub unsafe fn mask_overflow_interrupt(&self, lapic: &mut LocalApic) {
+        unsafe { lapic.write(LocalApicReg::LvtPerformanceCounter, 1 << 16) };
+    }
+
+    const fn mask(width: u8) -> u64 {
+        PmuInfo::overflow_preload(width, 1)
+    }
+
+    /// `IA32_PERF_GLOBAL_CTRL` only exists from version 2, before that the
+    /// enable bit in each event select is all there is.
+    unsafe fn set_global_enable(&mut self, bit: u32, enabled: bool) {
+        if self.info.version < 2 {
+            return;
+        }
+
+        unsafe {
+            let global = rdmsr(IA32_PERF_GLOBAL_CTRL);
+            let global = if enabled {
+                global | (1 << bit)
+            } else {
+                global & !(1 << bit)
+            };
+            wrmsr(IA32_PERF_GLOBAL_CTRL, global);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const fn leaf(eax: u32, ebx: u32, edx: u32) -> CpuidResult {
+        CpuidResult {
+            eax,
+            ebx,
+            ecx: 0,
+            edx,
+        }
+    }
+
+    #[test]
+    fn test_pmu_info_decoding() {
+        // Version 4, 8 counters of 48 bits, 7 events with LLC misses missing,
+        // 3 fixed counters of 48 bits
+        let info = PmuInfo::from_leaf(leaf(0x0730_0804, 1 << 4, (48 << 5) | 3)).unwrap();
+
+        assert_eq!(info.version, 4);
+        assert_eq!(info.general_counters, 8);
+        assert_eq!(info.general_width, 48);
+        assert_eq!(info.fixed_counters, 3);
+        assert_eq!(info.fixed_width, 48);
+        assert!(info.supports(ArchEvent::InstructionsRetired));
+        assert!(!info.supports(ArchEvent::LlcMisses));
+
+        assert_eq!(PmuInfo::from_leaf(leaf(0, 0, 0)), None);
+    }
+
+    #[test]
+    fn test_short_event_vector() {
+        // Only the first two events are enumerated
+        let info = PmuInfo::from_leaf(leaf(0x0228_0201, 0, 0xFFFF)).unwrap();
+
+        assert!(info.supports(ArchEvent::CoreCycles));
+        assert!(!info.supports(ArchEvent::BranchMisses));
+        assert_eq!(info.fixed_counters, 0, "Version 1 has no fixed counters");
+    }
+
+    #[test]
+    fn test_event_select() {
+        let select = EventSelect::for_event(ArchEvent::LlcMisses, CountPrivilege::User, true);
+
+        assert_eq!(
+            select.value(),
+            0x2E | (0x41 << 8) | (1 << 16) | (1 << 20) | (1 << 22)
+        );
+    }
+
+    #[test]
+    fn test_overflow_preload() {
+        assert_eq!(PmuInfo::overflow_preload(48, 1000), (1 << 48) - 1000);
+        assert_eq!(PmuInfo::overflow_preload(64, 1), u64::MAX);
+        assert_eq!(PmuInfo::overflow_preload(48, 0), 0);
+    }
+}
@@ -0,0 +1,571 @@
+/*
+  ____                 __               __   _ __
+ / __ \__ _____ ____  / /___ ____ _    / /  (_) /
+/ /_/ / // / _ `/ _ \/ __/ // /  ' \  / /__/ / _ \
+\___\_\_,_/\_,_/_//_/\__/\_,_/_/_/_/ /____/_/_.__/
+    Part of the Quantum OS Project
+
+Copyright 2024 Gavin Kellam
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and
+associated documentation files (the "Software"), to deal in the Software without restriction,
+including without limitation the rights to use, copy, modify, merge, publish, distribute,
+sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial
+portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT
+NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
+OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+use crate::apic::{IpiKind, LocalApic};
+use core::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+
+#[cfg(target_arch = "x86_64")]
+use crate::{
+    gdt::{
+        CodeSegmentDesc, DataSegmentDesc, GlobalDescriptorTable, SegmentEntry, TaskStateSegment,
+    },
+    msr::ia32_efer,
+    registers::cr3,
+};
+#[cfg(target_arch = "x86_64")]
+use core::{cell::UnsafeCell, mem::offset_of};
+
+/// The max number of CPUs that can be tracked.
+pub const MAX_CPUS: usize = 64;
+
+/// # Smp Error
+/// Errors from starting an application processor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SmpError {
+    /// The trampoline must be page aligned and below 1MiB.
+    InvalidTrampoline,
+    /// The APs enable paging in 32-bit mode, so the page tables must be below 4GiB.
+    InvalidPageTables,
+    /// This APIC id cannot be tracked.
+    InvalidApicId,
+    /// The CPU never called [`mark_started`].
+    Timeout,
+    /// [`MAX_CPUS`] CPUs have already been started.
+    TooManyCpus,
+}
+
+static STARTED_COUNT: AtomicUsize = AtomicUsize::new(0);
+static STARTED_IDS: [AtomicU32; MAX_CPUS] = [const { AtomicU32::new(0) }; MAX_CPUS];
+
+/// # Mark Started
+/// Record that the CPU with `apic_id` is running, should be called by every
+/// CPU (including the BSP) once its entry code has finished setting up.
+pub fn mark_started(apic_id: u32) -> Result<(), SmpError> {
+    // The id is offset by one so a zeroed slot is never a valid id
+    let stored_id = apic_id.checked_add(1).ok_or(SmpError::InvalidApicId)?;
+
+    if is_started(apic_id) {
+        return Ok(());
+    }
+
+    let index = STARTED_COUNT.fetch_add(1, Ordering::AcqRel);
+    if index >= MAX_CPUS {
+        STARTED_COUNT.fetch_sub(1, Ordering::AcqRel);
+        return Err(SmpError::TooManyCpus);
+    }
+
+    STARTED_IDS[index].store(stored_id, Ordering::Release);
+    Ok(())
+}
+
+/// # Started Cpus
+/// The number of CPUs that are running.
+pub fn started_cpus() -> usize {
+    STARTED_COUNT.load(Ordering::Acquire).min(MAX_CPUS)
+}
+
+/// # Is Started
+/// Check if the CPU with `apic_id` is running.
+pub fn is_started(apic_id: u32) -> bool {
+    let mut started = false;
+    for_each_started_cpu(|id| started |= id == apic_id);
+
+    started
+}
+
+/// # For Each Started Cpu
+/// Call `f` with the APIC id of every running CPU.
+pub fn for_each_started_cpu(mut f: impl FnMut(u32)) {
+    STARTED_IDS[..started_cpus()]
+        .iter()
+        .map(|id| id.load(Ordering::Acquire))
+        // A CPU that is still writing its id
+        .filter(|&id| id != 0)
+        .for_each(|id| f(id - 1));
+}
+
+/// # Ap Entry
+/// Where an application processor starts running Rust code, it is given its APIC
+/// id and must call [`mark_started`].
+pub type ApEntry = extern "C" fn(apic_id: u32) -> !;
+
+/// Selectors of the GDT each AP is given.
+#[cfg(target_arch = "x86_64")]
+const AP_CODE_SELECTOR: u16 = 0x08;
+#[cfg(target_arch = "x86_64")]
+const AP_DATA_SELECTOR: u16 = 0x10;
+#[cfg(target_arch = "x86_64")]
+const AP_TSS_SELECTOR: u16 = 0x18;
+
+/// Selectors of the GDT the trampoline uses on its way to long mode.
+#[cfg(target_arch = "x86_64")]
+const TRAMPOLINE_CODE32_SELECTOR: u16 = 0x08;
+#[cfg(target_arch = "x86_64")]
+const TRAMPOLINE_DATA32_SELECTOR: u16 = 0x10;
+#[cfg(target_arch = "x86_64")]
+const TRAMPOLINE_CODE64_SELECTOR: u16 = 0x18;
+
+/// # Trampoline Params
+/// What the trampoline needs to bring up an AP, kept inside the trampoline page.
+///
+/// Far pointers and GDT pointers are stored as `u16`s so they have no padding.
+#[cfg(target_arch = "x86_64")]
+#[repr(C)]
+struct TrampolineParams {
+    protected_gdt: [u64; 4],
+    cr3: u64,
+    efer: u64,
+    stack_top: u64,
+    entry: u64,
+    apic_id: u64,
+    /// Limit and 32-bit base of `protected_gdt`, filled in by the trampoline
+    protected_gdt_ptr: [u16; 3],
+    /// 32-bit offset and selector of the protected mode code, filled in by the trampoline
+    protected_jump: [u16; 3],
+    /// 32-bit offset and selector of the long mode code, filled in by the trampoline
+    long_jump: [u16; 3],
+    /// Limit and 64-bit base of the AP's own GDT
+    gdt_ptr: [u16; 5],
+}
+
+// The 16-bit real mode entry, it only knows where it was copied to from `cs`. It
+// goes through 32-bit protected mode (with the GDT in `protected_gdt`) to turn on
+// paging with the BSP's page tables, then loads the AP's own GDT, TSS and stack in
+// long mode before calling the entry point.
+#[cfg(target_arch = "x86_64")]
+core::arch::global_asm!(
+    r#"
+    .pushsection .rodata.smp_trampoline, "a"
+    .global __smp_trampoline_start
+    .global __smp_trampoline_params
+    .global __smp_trampoline_end
+    .set SMP_PARAMS, __smp_trampoline_params - __smp_trampoline_start
+
+    .code16
+__smp_trampoline_start:
+    cli
+    cld
+    jmp 2f
+
+    .balign 8
+__smp_trampoline_params:
+    .skip {params_size}
+
+2:
+    mov %cs, %ax
+    mov %ax, %ds
+    xor %ebx, %ebx
+    mov %ax, %bx
+    shl $4, %ebx
+
+    lea (SMP_PARAMS + {protected_gdt})(%ebx), %eax
+    movw ${protected_gdt_limit}, (SMP_PARAMS + {protected_gdt_ptr})
+    mov %eax, (SMP_PARAMS + {protected_gdt_ptr} + 2)
+    lea (3f - __smp_trampoline_start)(%ebx), %eax
+    mov %eax, (SMP_PARAMS + {protected_jump})
+    movw ${code32}, (SMP_PARAMS + {protected_jump} + 4)
+    lea (4f - __smp_trampoline_start)(%ebx), %eax
+    mov %eax, (SMP_PARAMS + {long_jump})
+    movw ${code64}, (SMP_PARAMS + {long_jump} + 4)
+
+    lgdtl (SMP_PARAMS + {protected_gdt_ptr})
+    mov %cr0, %eax
+    or $1, %eax
+    mov %eax, %cr0
+    ljmpl *(SMP_PARAMS + {protected_jump})
+
+    .code32
+3:
+    mov ${data32}, %ax
+    mov %ax, %ds
+    mov %ax, %es
+    mov %ax, %ss
+
+    # PAE
+    mov %cr4, %eax
+    or $(1 << 5), %eax
+    mov %eax, %cr4
+
+    mov (SMP_PARAMS + {cr3})(%ebx), %eax
+    mov %eax, %cr3
+
+    mov $0xC0000080, %ecx
+    mov (SMP_PARAMS + {efer})(%ebx), %eax
+    mov (SMP_PARAMS + {efer} + 4)(%ebx), %edx
+    wrmsr
+
+    # Paging (and so long mode)
+    mov %cr0, %eax
+    or $0x80000000, %eax
+    mov %eax, %cr0
+    ljmp *(SMP_PARAMS + {long_jump})(%ebx)
+
+    .code64
+4:
+    # The upper half of rbx is undefined after changing modes
+    mov %ebx, %ebx
+
+    lgdt (SMP_PARAMS + {gdt_ptr})(%rbx)
+    mov ${data64}, %ax
+    mov %ax, %ds
+    mov %ax, %es
+    mov %ax, %ss
+    xor %eax, %eax
+    mov %ax, %fs
+    mov %ax, %gs
+    mov ${tss}, %ax
+    ltr %ax
+
+    mov (SMP_PARAMS + {stack_top})(%rbx), %rsp
+    mov (SMP_PARAMS + {apic_id})(%rbx), %edi
+    mov (SMP_PARAMS + {entry})(%rbx), %rax
+
+    # Reload cs from the AP's own GDT
+    pushq ${code64_ap}
+    lea 5f(%rip), %rcx
+    push %rcx
+    lretq
+5:
+    xor %ebp, %ebp
+    call *%rax
+6:
+    cli
+    hlt
+    jmp 6b
+__smp_trampoline_end:
+    .popsection
+    "#,
+    params_size = const size_of::<TrampolineParams>(),
+    protected_gdt = const offset_of!(TrampolineParams, protected_gdt),
+    protected_gdt_limit = const size_of::<[u64; 4]>() - 1,
+    protected_gdt_ptr = const offset_of!(TrampolineParams, protected_gdt_ptr),
+    protected_jump = const offset_of!(TrampolineParams, protected_jump),
+    long_jump = const offset_of!(TrampolineParams, long_jump),
+    cr3 = const offset_of!(TrampolineParams, cr3),
+    efer = const offset_of!(TrampolineParams, efer),
+    gdt_ptr = const offset_of!(TrampolineParams, gdt_ptr),
+    stack_top = const offset_of!(TrampolineParams, stack_top),
+    apic_id = const offset_of!(TrampolineParams, apic_id),
+    entry = const offset_of!(TrampolineParams, entry),
+    code32 = const TRAMPOLINE_CODE32_SELECTOR,
+    data32 = const TRAMPOLINE_DATA32_SELECTOR,
+    code64 = const TRAMPOLINE_CODE64_SELECTOR,
+    data64 = const AP_DATA_SELECTOR,
+    tss = const AP_TSS_SELECTOR,
+    code64_ap = const AP_CODE_SELECTOR,
+    options(att_syntax)
+);
+
+#[cfg(target_arch = "x86_64")]
+unsafe extern "C" {
+    static __smp_trampoline_start: u8;
+    static __smp_trampoline_params: u8;
+    static __smp_trampoline_end: u8;
+}
+
+/// # Trampoline Code
+/// The trampoline, as it is copied into its page.
+#[cfg(target_arch = "x86_64")]
+fn trampoline_code() -> &'static [u8] {
+    let start = &raw const __smp_trampoline_start;
+    let end = &raw const __smp_trampoline_end;
+
+    unsafe { core::slice::from_raw_parts(start, end as usize - start as usize) }
+}
+
+#[cfg(target_arch = "x86_64")]
+fn trampoline_params_offset() -> usize {
+    &raw const __smp_trampoline_params as usize - &raw const __smp_trampoline_start as usize
+}
+
+/// # Trampoline
+/// The real mode code application processors start in, copied into a page below 1MiB.
+#[cfg(target_arch = "x86_64")]
+pub struct Trampoline {
+    page: u64,
+}
+
+#[cfg(target_arch = "x86_64")]
+impl Trampoline {
+    /// # Install
+    /// Copy the trampoline into the page at `page`.
+    ///
+    /// # Safety
+    /// `page` must be free memory that stays identity mapped (in the page tables
+    /// that are active when APs are started) for as long as APs are being started.
+    pub unsafe fn install(page: u64) -> Result<Self, SmpError> {
+        if !page.is_multiple_of(4096) || page >= 0x100000 {
+            return Err(SmpError::InvalidTrampoline);
+        }
+
+        let code = trampoline_code();
+        unsafe { core::ptr::copy_nonoverlapping(code.as_ptr(), page as *mut u8, code.len()) };
+
+        let mut trampoline = Self { page };
+        trampoline.params().protected_gdt = [
+            0,
+            CodeSegmentDesc::new32()
+                .set_writable_flag(true)
+                .set_present_flag(true)
+                .into_entry(),
+            DataSegmentDesc::new32()
+                .set_writable_flag(true)
+                .set_present_flag(true)
+                .into_entry(),
+            CodeSegmentDesc::new64()
+                .set_writable_flag(true)
+                .set_present_flag(true)
+                .into_entry(),
+        ];
+
+        Ok(trampoline)
+    }
+
+    /// # Vector
+    /// The SIPI vector that starts a CPU at this trampoline.
+    pub const fn vector(&self) -> u8 {
+        (self.page >> 12) as u8
+    }
+
+    fn params(&mut self) -> &mut TrampolineParams {
+        unsafe {
+            &mut *((self.page as usize + trampoline_params_offset()) as *mut TrampolineParams)
+        }
+    }
+}
+
+/// # Cpu Tables
+/// The GDT (with its TSS) an application processor loads once it reaches long mode.
+#[cfg(target_arch = "x86_64")]
+struct CpuTables {
+    gdt: UnsafeCell<GlobalDescriptorTable<5>>,
+    tss: UnsafeCell<TaskStateSegment>,
+}
+
+// Each `CpuTables` is only written before its CPU is started
+#[cfg(target_arch = "x86_64")]
+unsafe impl Sync for CpuTables {}
+
+#[cfg(target_arch = "x86_64")]
+static CPU_TABLES: [CpuTables; MAX_CPUS] = [const {
+    CpuTables {
+        gdt: UnsafeCell::new(GlobalDescriptorTable::new()),
+        tss: UnsafeCell::new(TaskStateSegment::new()),
+    }
+}; MAX_CPUS];
+
+#[cfg(target_arch = "x86_64")]
+static CPU_TABLES_USED: AtomicUsize = AtomicUsize::new(0);
+
+/// # Setup Cpu Tables
+/// Claim the next unused GDT and TSS, with `stack_top` as the TSS's ring 0 stack.
+///
+/// Returns the index of the claimed tables along with the GDT.
+#[cfg(target_arch = "x86_64")]
+fn setup_cpu_tables(
+    stack_top: u64,
+) -> Result<(usize, &'static GlobalDescriptorTable<5>), SmpError> {
+    let index = CPU_TABLES_USED.fetch_add(1, Ordering::AcqRel);
+    let Some(tables) = CPU_TABLES.get(index) else {
+        CPU_TABLES_USED.fetch_sub(1, Ordering::AcqRel);
+        return Err(SmpError::TooManyCpus);
+    };
+
+    let tss = unsafe { &mut *tables.tss.get() };
+    tss.privilege_stacks[0] = stack_top;
+
+    let gdt = unsafe { &mut *tables.gdt.get() };
+    gdt.store(
+        AP_CODE_SELECTOR as usize / 8,
+        CodeSegmentDesc::new64()
+            .set_accessed_flag(true)
+            .set_present_flag(true)
+            .set_writable_flag(true),
+    );
+    gdt.store(
+        AP_DATA_SELECTOR as usize / 8,
+        DataSegmentDesc::new64()
+            .set_accessed_flag(true)
+            .set_present_flag(true)
+            .set_writable_flag(true),
+    );
+    gdt.store_tss(AP_TSS_SELECTOR as usize / 8, unsafe { &*tables.tss.get() });
+
+    Ok((index, unsafe { &*tables.gdt.get() }))
+}
+
+/// # Release Cpu Tables
+/// Give back the tables at `index` after their CPU failed to start, so retrying
+/// doesn't run out of tables. Only the most recently claimed tables can be given
+/// back, which is always the case as APs are started one at a time.
+#[cfg(target_arch = "x86_64")]
+fn release_cpu_tables(index: usize) {
+    let _ = CPU_TABLES_USED.compare_exchange(index + 1, index, Ordering::AcqRel, Ordering::Acquire);
+}
+
+/// # Start Application Processor
+/// Wake up the CPU with `apic_id` using the INIT-SIPI-SIPI sequence. The CPU starts
+/// in `trampoline`, switches to long mode with the calling CPU's page tables, loads
+/// its own GDT and TSS, and calls `entry` on the stack ending at `stack_top`.
+///
+/// `delay_us` must wait (at least) the given number of microseconds, it is
+/// used for the delays the startup sequence requires.
+///
+/// # Safety
+/// `entry` must call [`mark_started`], `stack_top` must be the end of a stack only
+/// this CPU uses, and the CPU must not already be running. APs must be started one
+/// at a time, as they share the trampoline.
+#[cfg(target_arch = "x86_64")]
+pub unsafe fn start_application_processor(
+    lapic: &mut LocalApic,
+    apic_id: u32,
+    trampoline: &mut Trampoline,
+    stack_top: u64,
+    entry: ApEntry,
+    mut delay_us: impl FnMut(u32),
+) -> Result<(), SmpError> {
+    const INIT_DELAY_US: u32 = 10_000;
+    const SIPI_DELAY_US: u32 = 200;
+    const POLL_DELAY_US: u32 = 100;
+    const STARTUP_TIMEOUT_US: u32 = 1_000_000;
+
+    let page_tables = cr3::read() & !0xFFF;
+    if page_tables >= (1 << 32) {
+        return Err(SmpError::InvalidPageTables);
+    }
+
+    let (tables_index, gdt) = setup_cpu_tables(stack_top)?;
+    let gdt_base = gdt as *const GlobalDescriptorTable<5> as u64;
+
+    let params = trampoline.params();
+    params.cr3 = page_tables;
+    // Long mode active is set by the CPU once it gets there
+    params.efer = ia32_efer::read() & !(1 << 10);
+    // Keep the stack aligned for the call into `entry`
+    params.stack_top = stack_top & !0xF;
+    params.entry = entry as usize as u64;
+    params.apic_id = apic_id as u64;
+    params.gdt_ptr = [
+        (size_of::<GlobalDescriptorTable<5>>() - 1) as u16,
+        gdt_base as u16,
+        (gdt_base >> 16) as u16,
+        (gdt_base >> 32) as u16,
+        (gdt_base >> 48) as u16,
+    ];
+
+    lapic.send_ipi(apic_id, IpiKind::Init);
+    delay_us(INIT_DELAY_US);
+
+    // The second SIPI is only needed if the CPU missed the first one
+    for _ in 0..2 {
+        lapic.send_ipi(apic_id, IpiKind::Startup(trampoline.vector()));
+        delay_us(SIPI_DELAY_US);
+
+        if is_started(apic_id) {
+            return Ok(());
+        }
+    }
+
+    for _ in 0..(STARTUP_TIMEOUT_US / POLL_DELAY_US) {
+        if is_started(apic_id) {
+            return Ok(());
+        }
+
+        delay_us(POLL_DELAY_US);
+    }
+
+    release_cpu_tables(tables_index);
+    Err(SmpError::Timeout)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_started_cpus() {
+        assert_eq!(mark_started(0), Ok(()));
+        assert_eq!(mark_started(3), Ok(()));
+        assert_eq!(mark_started(3), Ok(()));
+
+        assert_eq!(started_cpus(), 2);
+        assert!(is_started(0));
+        assert!(is_started(3));
+        assert!(!is_started(1));
+
+        let mut ids = [u32::MAX; 2];
+        let mut index = 0;
+        for_each_started_cpu(|id| {
+            ids[index] = id;
+            index += 1;
+        });
+        assert_eq!(ids, [0, 3]);
+
+        // Ids are stored offset by one
+        assert_eq!(mark_started(u32::MAX), Err(SmpError::InvalidApicId));
+        assert_eq!(mark_started(255), Ok(()));
+        assert!(is_started(255));
+        assert_eq!(started_cpus(), 3);
+    }
+
+    #[test]
+    #[cfg(target_arch = "x86_64")]
+    fn test_trampoline_layout() {
+        let code = trampoline_code();
+
+        // cli, cld
+        assert_eq!(code[..2], [0xFA, 0xFC]);
+        assert!(code.len() <= 4096);
+        assert!(trampoline_params_offset() + size_of::<TrampolineParams>() < code.len());
+        assert!(trampoline_params_offset().is_multiple_of(8));
+    }
+
+    #[test]
+    #[cfg(target_arch = "x86_64")]
+    fn test_cpu_tables() {
+        let (index, gdt) = setup_cpu_tables(0x1234_5000).unwrap();
+        let entries = unsafe { gdt.pack().entries() };
+        let tss = CPU_TABLES[0].tss.get();
+
+        assert_eq!(entries.len(), 5);
+        assert_eq!(entries[0], 0);
+        // Present, and code or data
+        assert_ne!(entries[1] & (1 << 47), 0);
+        assert_ne!(entries[1] & (1 << 43), 0);
+        assert_ne!(entries[2] & (1 << 47), 0);
+        assert_eq!(entries[2] & (1 << 43), 0);
+
+        let tss_base = ((entries[3] >> 16) & 0xFF_FFFF)
+            | (((entries[3] >> 56) & 0xFF) << 24)
+            | (entries[4] << 32);
+        assert_eq!(tss_base, tss as u64);
+        assert_eq!((entries[3] >> 40) & 0xFF, 0x89);
+        assert_eq!({ unsafe { (*tss).privilege_stacks } }[0], 0x1234_5000);
+
+        // A CPU that failed to start gives its tables to the next one
+        release_cpu_tables(index);
+        assert_eq!(setup_cpu_tables(0x1234_6000).unwrap().0, index);
+    }
+}
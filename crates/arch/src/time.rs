@@ -0,0 +1,321 @@
+/*
+  ____                 __               __   _ __
+ / __ \__ _____ ____  / /___ ____ _    / /  (_) /
+/ /_/ / // / _ `/ _ \/ __/ // /  ' \  / /__/ / _ \
+\___\_\_,_/\_,_/_//_/\__/\_,_/_/_/_/ /____/_/_.__/
+    Part of the Quantum OS Project
+
+Copyright 2024 Gavin Kellam
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and
+associated documentation files (the "Software"), to deal in the Software without restriction,
+including without limitation the rights to use, copy, modify, merge, publish, distribute,
+sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial
+portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT
+NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
+OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+use crate::{
+    cpuid::{self, CpuFeatures, CpuidResult},
+    io::IOPort,
+};
+use core::{ops::Add, time::Duration};
+
+/// # Rdtsc
+/// Read the CPU's time stamp counter.
+#[inline(always)]
+pub fn rdtsc() -> u64 {
+    let lo: u32;
+    let hi: u32;
+
+    unsafe { core::arch::asm!("rdtsc", out("eax") lo, out("edx") hi, options(nomem, nostack)) };
+
+    lo as u64 | ((hi as u64) << 32)
+}
+
+/// # Instant
+/// A point in monotonic time, measured in nanoseconds since its clock started.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Instant(u64);
+
+impl Instant {
+    pub const fn from_nanos(nanos: u64) -> Self {
+        Self(nanos)
+    }
+
+    pub const fn as_nanos(&self) -> u64 {
+        self.0
+    }
+
+    /// # Duration Since
+    /// The time between `earlier` and `self`, or zero if `earlier` is later.
+    pub const fn duration_since(&self, earlier: Instant) -> Duration {
+        Duration::from_nanos(self.0.saturating_sub(earlier.0))
+    }
+}
+
+impl Add<Duration> for Instant {
+    type Output = Instant;
+
+    fn add(self, rhs: Duration) -> Self::Output {
+        Self(self.0.saturating_add(rhs.as_nanos() as u64))
+    }
+}
+
+/// # Clock Source
+/// Something that can tell the current monotonic time.
+pub trait ClockSource {
+    /// The current time.
+    fn now(&self) -> Instant;
+
+    /// The smallest step this clock can measure.
+    fn resolution(&self) -> Duration;
+
+    /// # Elapsed
+    /// The time since `earlier`.
+    fn elapsed(&self, earlier: Instant) -> Duration {
+        self.now().duration_since(earlier)
+    }
+}
+
+/// # Tsc Frequency From Cpuid
+/// Get the TSC frequency (in Hz) reported by `cpuid` leaf `0x15`, if the CPU
+/// reports the crystal clock.
+pub fn tsc_frequency_from_cpuid() -> Option<u64> {
+    if !cpuid::is_supported() || cpuid::cpuid(0, 0).eax < 0x15 {
+        return None;
+    }
+
+    tsc_frequency_from_leaf15(cpuid::cpuid(0x15, 0))
+}
+
+/// # Tsc Frequency From Leaf 15
+/// Decode the TSC frequency from the result of `cpuid` leaf `0x15`.
+pub const fn tsc_frequency_from_leaf15(leaf: CpuidResult) -> Option<u64> {
+    let (denominator, numerator, crystal_hz) = (leaf.eax, leaf.ebx, leaf.ecx);
+
+    if denominator == 0 || numerator == 0 || crystal_hz == 0 {
+        return None;
+    }
+
+    Some(crystal_hz as u64 * numerator as u64 / denominator as u64)
+}
+
+/// The frequency the PIT runs at.
+pub const PIT_FREQUENCY_HZ: u64 = 1_193_182;
+
+/// How many times the PIT output is polled before giving up on it. Each poll is
+/// a port read (around a microsecond), so this is far longer than the PIT can count.
+const PIT_OUTPUT_POLLS: usize = 10_000_000;
+
+/// # Tsc Frequency From Pit
+/// Measure the TSC frequency (in Hz) by counting TSC ticks while the PIT's
+/// channel 2 counts down `millis` milliseconds.
+///
+/// Returns `None` if `millis` is zero, since nothing can be measured, or if the
+/// PIT never finishes counting. Periods longer than the PIT can count (about 54ms)
+/// are shortened to fit.
+///
+/// # Safety
+/// Uses PIT channel 2 and the PC speaker gate (port `0x61`), nothing else
+/// may be using them.
+pub unsafe fn tsc_frequency_from_pit(millis: u16) -> Option<u64> {
+    const CHANNEL_2: IOPort = IOPort::new(0x42);
+    const COMMAND: IOPort = IOPort::new(0x43);
+    const SPEAKER_GATE: IOPort = IOPort::new(0x61);
+
+    const GATE: u8 = 1 << 0;
+    const SPEAKER: u8 = 1 << 1;
+    const OUTPUT: u8 = 1 << 5;
+
+    let count = pit_count(millis)?;
+
+    // Gate channel 2 on, but keep the speaker disconnected
+    let gate = SPEAKER_GATE.read_byte() & !(GATE | SPEAKER);
+    SPEAKER_GATE.write_byte(gate);
+
+    // Channel 2, lobyte/hibyte, mode 0 (interrupt on terminal count)
+    COMMAND.write_byte(0b1011_0000);
+    CHANNEL_2.write_byte(count as u8);
+    CHANNEL_2.write_byte((count >> 8) as u8);
+
+    // Raising the gate starts the countdown
+    SPEAKER_GATE.write_byte(gate | GATE);
+    let start = rdtsc();
+
+    let mut polls = 0;
+    while SPEAKER_GATE.read_byte() & OUTPUT == 0 {
+        if polls == PIT_OUTPUT_POLLS {
+            SPEAKER_GATE.write_byte(gate);
+            return None;
+        }
+
+        polls += 1;
+        core::hint::spin_loop();
+    }
+
+    let end = rdtsc();
+    SPEAKER_GATE.write_byte(gate);
+
+    Some(ticks_to_frequency(
+        end - start,
+        count as u64,
+        PIT_FREQUENCY_HZ,
+    ))
+}
+
+/// The PIT count for `millis` milliseconds, clamped to what the PIT can count.
+fn pit_count(millis: u16) -> Option<u16> {
+    match PIT_FREQUENCY_HZ * millis as u64 / 1000 {
+        0 => None,
+        count => Some(count.min(u16::MAX as u64) as u16),
+    }
+}
+
+/// Scale `ticks` counted over `reference_ticks` of a `reference_hz` clock
+/// into the frequency of the counted clock.
+const fn ticks_to_frequency(ticks: u64, reference_ticks: u64, reference_hz: u64) -> u64 {
+    (ticks as u128 * reference_hz as u128 / reference_ticks as u128) as u64
+}
+
+/// # Tsc Clock
+/// A monotonic clock driven by the CPU's time stamp counter.
+#[derive(Clone, Copy, Debug)]
+pub struct TscClock {
+    frequency_hz: u64,
+    start: u64,
+}
+
+impl TscClock {
+    /// How long to count the PIT for when calibrating.
+    const CALIBRATION_MILLIS: u16 = 10;
+
+    /// # Calibrate
+    /// Find the TSC's frequency, using `cpuid` when the CPU reports it and
+    /// measuring it against the PIT otherwise.
+    ///
+    /// # Safety
+    /// See [`tsc_frequency_from_pit`].
+    pub unsafe fn calibrate() -> Self {
+        let frequency_hz = tsc_frequency_from_cpuid()
+            .or_else(|| tsc_frequency_from_pit(Self::CALIBRATION_MILLIS))
+            .expect("TSC calibration period cannot be zero");
+
+        Self::with_frequency(frequency_hz)
+    }
+
+    /// # With Frequency
+    /// Make a clock for a TSC with a known frequency, starting at zero now.
+    pub fn with_frequency(frequency_hz: u64) -> Self {
+        assert_ne!(frequency_hz, 0, "TSC frequency cannot be zero");
+
+        Self {
+            frequency_hz,
+            start: rdtsc(),
+        }
+    }
+
+    /// # Frequency
+    /// The TSC's frequency in Hz.
+    pub const fn frequency(&self) -> u64 {
+        self.frequency_hz
+    }
+
+    /// # Is Invariant
+    /// Check if the TSC keeps a constant rate in every power state, if not
+    /// this clock can drift when the CPU changes frequency.
+    pub fn is_invariant() -> bool {
+        CpuFeatures::read().invariant_tsc
+    }
+
+    /// # Ticks To Instant
+    /// Convert a raw TSC value into an instant on this clock.
+    pub const fn ticks_to_instant(&self, tsc: u64) -> Instant {
+        let ticks = tsc.saturating_sub(self.start) as u128;
+        Instant((ticks * 1_000_000_000 / self.frequency_hz as u128) as u64)
+    }
+}
+
+impl ClockSource for TscClock {
+    fn now(&self) -> Instant {
+        self.ticks_to_instant(rdtsc())
+    }
+
+    fn resolution(&self) -> Duration {
+        Duration::from_nanos((1_000_000_000 / self.frequency_hz).max(1))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_leaf15() {
+        // 24MHz crystal with a 2:168 ratio
+        let leaf = CpuidResult {
+            eax: 2,
+            ebx: 168,
+            ecx: 24_000_000,
+            edx: 0,
+        };
+        assert_eq!(tsc_frequency_from_leaf15(leaf), Some(2_016_000_000));
+
+        let no_crystal = CpuidResult { ecx: 0, ..leaf };
+        assert_eq!(tsc_frequency_from_leaf15(no_crystal), None);
+    }
+
+    #[test]
+    fn test_ticks_to_frequency() {
+        // Half a second of PIT ticks while 1.5G TSC ticks passed is a 3GHz TSC
+        assert_eq!(
+            ticks_to_frequency(1_500_000_000, PIT_FREQUENCY_HZ / 2, PIT_FREQUENCY_HZ),
+            3_000_000_000
+        );
+    }
+
+    #[test]
+    fn test_pit_count() {
+        assert_eq!(pit_count(0), None);
+        assert_eq!(pit_count(10), Some(11931));
+        // Longer than the PIT can count
+        assert_eq!(pit_count(100), Some(u16::MAX));
+    }
+
+    #[test]
+    fn test_ticks_to_instant() {
+        let clock = TscClock {
+            frequency_hz: 2_000_000_000,
+            start: 1000,
+        };
+
+        assert_eq!(clock.ticks_to_instant(500), Instant::from_nanos(0));
+        assert_eq!(clock.ticks_to_instant(3000), Instant::from_nanos(1000));
+        assert_eq!(clock.resolution(), Duration::from_nanos(1));
+    }
+
+    #[test]
+    fn test_instant() {
+        let a = Instant::from_nanos(100);
+        let b = a + Duration::from_nanos(50);
+
+        assert_eq!(b.duration_since(a), Duration::from_nanos(50));
+        assert_eq!(a.duration_since(b), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_clock_is_monotonic() {
+        let clock = TscClock::with_frequency(1_000_000_000);
+        let first = clock.now();
+
+        assert!(clock.now() >= first);
+    }
+}
@@ -0,0 +1,145 @@
+/*
+  ____                 __               __   _ __
+ / __ \__ _____ ____  / /___ ____ _    / /  (_) /
+/ /_/ / // / _ `/ _ \/ __/ // /  ' \  / /__/ / _ \
+\___\_\_,_/\_,_/_//_/\__/\_,_/_/_/_/ /____/_/_.__/
+    Part of the Quantum OS Project
+
+Copyright 2024 Gavin Kellam
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and
+associated documentation files (the "Software"), to deal in the Software without restriction,
+including without limitation the rights to use, copy, modify, merge, publish, distribute,
+sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial
+portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT
+NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
+OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+use crate::registers::cr3;
+
+/// # Flush Page
+/// Invalidate the TLB entry for the page containing `address` in the current
+/// address space.
+///
+/// # Safety
+/// Must be run in ring 0.
+#[inline(always)]
+pub unsafe fn flush_page(address: usize) {
+    unsafe { core::arch::asm!("invlpg [{0}]", in(reg) address, options(nostack)) };
+}
+
+/// # Flush All
+/// Invalidate all non-global TLB entries by reloading CR3.
+///
+/// With PCIDs enabled this only flushes the current PCID.
+///
+/// # Safety
+/// Must be run in ring 0.
+#[inline(always)]
+pub unsafe fn flush_all() {
+    unsafe { cr3::write(cr3::read()) };
+}
+
+/// # Invpcid Kind
+/// Which TLB entries an `invpcid` instruction should invalidate.
+#[cfg(target_arch = "x86_64")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InvpcidKind {
+    /// A single page in the given PCID.
+    Address { pcid: u16, address: u64 },
+    /// Every non-global entry in the given PCID.
+    SingleContext { pcid: u16 },
+    /// Every entry in every PCID, including global pages.
+    AllIncludingGlobal,
+    /// Every non-global entry in every PCID.
+    AllExcludingGlobal,
+}
+
+#[cfg(target_arch = "x86_64")]
+#[repr(C, align(16))]
+struct InvpcidDescriptor {
+    pcid: u64,
+    address: u64,
+}
+
+/// # Invpcid
+/// Invalidate TLB entries without needing to switch into the target address
+/// space.
+///
+/// # Safety
+/// The CPU must support `invpcid` (see [`crate::cpuid::CpuFeatures`]).
+#[cfg(target_arch = "x86_64")]
+#[inline(always)]
+pub unsafe fn invpcid(kind: InvpcidKind) {
+    let (kind, descriptor) = match kind {
+        InvpcidKind::Address { pcid, address } => (
+            0u64,
+            InvpcidDescriptor {
+                pcid: pcid as u64,
+                address,
+            },
+        ),
+        InvpcidKind::SingleContext { pcid } => (
+            1,
+            InvpcidDescriptor {
+                pcid: pcid as u64,
+                address: 0,
+            },
+        ),
+        InvpcidKind::AllIncludingGlobal => (
+            2,
+            InvpcidDescriptor {
+                pcid: 0,
+                address: 0,
+            },
+        ),
+        InvpcidKind::AllExcludingGlobal => (
+            3,
+            InvpcidDescriptor {
+                pcid: 0,
+                address: 0,
+            },
+        ),
+    };
+
+    unsafe {
+        core::arch::asm!(
+            "invpcid {0}, [{1}]",
+            in(reg) kind,
+            in(reg) &descriptor,
+            options(nostack, readonly)
+        )
+    };
+}
+
+/// # Flush Pcid Range
+/// Invalidate each 4K page in `start..end` for the given PCID.
+///
+/// Large ranges flush the whole PCID instead, as its cheaper than one
+/// `invpcid` per page.
+///
+/// # Safety
+/// The CPU must support `invpcid` (see [`crate::cpuid::CpuFeatures`]).
+#[cfg(target_arch = "x86_64")]
+pub unsafe fn flush_pcid_range(pcid: u16, start: u64, end: u64) {
+    const PAGE_SIZE: u64 = 4096;
+    const MAX_SINGLE_FLUSHES: u64 = 32;
+
+    let start = start & !(PAGE_SIZE - 1);
+    if end.saturating_sub(start) / PAGE_SIZE > MAX_SINGLE_FLUSHES {
+        unsafe { invpcid(InvpcidKind::SingleContext { pcid }) };
+        return;
+    }
+
+    for address in (start..end).step_by(PAGE_SIZE as usize) {
+        unsafe { invpcid(InvpcidKind::Address { pcid, address }) };
+    }
+}
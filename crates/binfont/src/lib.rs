@@ -356,6 +356,6 @@ impl BinFont {
     /// # Get Glyph
     /// Gets the glyph data for a given input char.
     pub fn get_glyph(c: char) -> Option<&'static [u8; Self::HEIGHT]> {
-        BUILT_IN_FONT.get(c as usize - 32)
+        BUILT_IN_FONT.get((c as usize).checked_sub(32)?)
     }
 }
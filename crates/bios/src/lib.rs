@@ -35,7 +35,7 @@ pub const INVALID_BIOS_CALL_AX: u16 = 0x80;
 pub const NOT_SUPPORTED_CALL_AX: u16 = 0x86;
 
 #[must_use = "BiosStatus must be used"]
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum BiosStatus {
     Success,
     InvalidInput,
@@ -195,8 +195,229 @@ pub unsafe fn int_0x15(reg: &mut Regs32, es: u16) -> BiosStatus {
     }
 }
 
+/// # Real Mode Ptr
+/// A real mode `segment:offset` pointer, which addresses `segment * 16 + offset`.
+///
+/// Laid out like the far pointers the BIOS stores in its tables (offset first).
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RealModePtr {
+    offset: u16,
+    segment: u16,
+}
+
+impl RealModePtr {
+    /// # Addressable End
+    /// The first linear address real mode code can't reach (without A20 tricks).
+    pub const ADDRESSABLE_END: u32 = 0x10_0000;
+
+    pub const fn new(segment: u16, offset: u16) -> Self {
+        Self { offset, segment }
+    }
+
+    /// # From Linear
+    /// Build a normalized pointer (offset below 16) to `address`, or `None` if
+    /// it isn't below [`Self::ADDRESSABLE_END`].
+    pub const fn from_linear(address: u32) -> Option<Self> {
+        if address >= Self::ADDRESSABLE_END {
+            return None;
+        }
+
+        Some(Self::new((address >> 4) as u16, (address & 0xF) as u16))
+    }
+
+    /// # From Ptr
+    /// Build a pointer to the same memory as `ptr`, if it's reachable from real mode.
+    pub fn from_ptr<T>(ptr: *const T) -> Option<Self> {
+        Self::from_linear(u32::try_from(ptr as usize).ok()?)
+    }
+
+    pub const fn segment(&self) -> u16 {
+        self.segment
+    }
+
+    pub const fn offset(&self) -> u16 {
+        self.offset
+    }
+
+    /// # Linear
+    /// The linear address this points to.
+    pub const fn linear(&self) -> u32 {
+        ((self.segment as u32) << 4) + self.offset as u32
+    }
+
+    /// # As Ptr
+    /// This pointer as a (linear) Rust pointer, valid in 16-bit and unreal mode.
+    pub const fn as_ptr<T>(&self) -> *const T {
+        self.linear() as usize as *const T
+    }
+
+    /// # Fits
+    /// Check if `len` values of `T` starting here are aligned and end below
+    /// [`Self::ADDRESSABLE_END`].
+    pub const fn fits<T>(&self, len: usize) -> bool {
+        let start = self.linear() as usize;
+        let room = (Self::ADDRESSABLE_END as usize).saturating_sub(start);
+
+        if !start.is_multiple_of(align_of::<T>()) {
+            return false;
+        }
+
+        match len.checked_mul(size_of::<T>()) {
+            Some(bytes) => bytes <= room,
+            None => false,
+        }
+    }
+
+    /// # Slice
+    /// Make a slice of `len` values of `T` starting here, or `None` if they
+    /// don't [`fit`](Self::fits) in real mode memory.
+    ///
+    /// # Safety
+    /// The memory must hold `len` valid values of `T`, and must be identity
+    /// mapped (true in 16-bit and unreal mode).
+    pub unsafe fn slice<'a, T>(&self, len: usize) -> Option<&'a [T]> {
+        if !self.fits::<T>(len) {
+            return None;
+        }
+
+        Some(unsafe { core::slice::from_raw_parts(self.as_ptr(), len) })
+    }
+}
+
+/// # Bios Services
+/// The BIOS calls the rest of this crate is built on.
+///
+/// [`RealBios`] makes the actual calls, everything else can be given a mock
+/// implementation so it can be tested on the host.
+pub trait BiosServices {
+    /// # Disk Read
+    /// Run an extended disk read (int 0x13, ah=0x42) with `packet`.
+    ///
+    /// # Safety
+    /// The packet's buffer must be valid for the number of sectors it reads.
+    unsafe fn disk_read(&mut self, disk_id: u16, packet: &disk::DiskAccessPacket) -> BiosStatus;
+
+    /// # Memory Map Entry
+    /// Read one entry of the memory map (int 0x15, eax=0xE820) into `entry`.
+    ///
+    /// Returns the continuation to pass for the next entry, which is zero
+    /// after the last entry.
+    fn memory_map_entry(
+        &mut self,
+        entry: &mut memory::MemoryEntry,
+        continuation: u32,
+    ) -> Result<u32, BiosStatus>;
+
+    /// # Vesa Info
+    /// Read the VBE controller info (int 0x10, ax=0x4F00) into `info`.
+    fn vesa_info(&mut self, info: &mut video::Vesa) -> BiosStatus;
+
+    /// # Vesa Mode Info
+    /// Read the info of `mode` (int 0x10, ax=0x4F01) into `info`.
+    fn vesa_mode_info(&mut self, mode: video::VesaModeId, info: &mut video::VesaMode)
+        -> BiosStatus;
+
+    /// # Vesa Set Mode
+    /// Switch to `mode` (int 0x10, ax=0x4F02).
+    fn vesa_set_mode(&mut self, mode: video::VesaModeId) -> BiosStatus;
+}
+
+/// # Real Bios
+/// Makes the BIOS calls for real, only usable from 16-bit (or unreal) mode.
+pub struct RealBios;
+
+impl RealBios {
+    /// The value VBE functions leave in `ax` when they succeed.
+    const VBE_SUCCESS: u16 = 0x004F;
+
+    fn vbe_status(ax: u16) -> BiosStatus {
+        if ax == Self::VBE_SUCCESS {
+            BiosStatus::Success
+        } else {
+            BiosStatus::Failed
+        }
+    }
+}
+
+impl BiosServices for RealBios {
+    unsafe fn disk_read(&mut self, disk_id: u16, packet: &disk::DiskAccessPacket) -> BiosStatus {
+        use core::ptr::addr_of;
+
+        assert!(addr_of!(*packet) as u32 & 0xFFFF == addr_of!(*packet) as u32);
+
+        BiosStatus::from_ax(bios_call! {
+            int: 13,
+            ax: disk::DISK_DAP_READ,
+            dx: disk_id,
+            si: addr_of!(*packet) as u16
+        })
+    }
+
+    fn memory_map_entry(
+        &mut self,
+        entry: &mut memory::MemoryEntry,
+        continuation: u32,
+    ) -> Result<u32, BiosStatus> {
+        let ptr = RealModePtr::from_ptr(entry).ok_or(BiosStatus::InvalidInput)?;
+
+        let mut regs = Regs32 {
+            eax: 0xE820,
+            ebx: continuation,
+            ecx: 24,
+            edx: 0x534D4150,
+            edi: ptr.offset() as u32,
+            ..Regs32::default()
+        };
+
+        match unsafe { int_0x15(&mut regs, ptr.segment()) } {
+            BiosStatus::Success => Ok(regs.ebx),
+            err => Err(err),
+        }
+    }
+
+    fn vesa_info(&mut self, info: &mut video::Vesa) -> BiosStatus {
+        let Some(ptr) = RealModePtr::from_ptr(info) else {
+            return BiosStatus::InvalidInput;
+        };
+
+        Self::vbe_status(bios_call!(
+            int: 10,
+            ax: 0x4F00,
+            es: ptr.segment(),
+            di: ptr.offset(),
+        ))
+    }
+
+    fn vesa_mode_info(
+        &mut self,
+        mode: video::VesaModeId,
+        info: &mut video::VesaMode,
+    ) -> BiosStatus {
+        let Some(ptr) = RealModePtr::from_ptr(info) else {
+            return BiosStatus::InvalidInput;
+        };
+
+        Self::vbe_status(bios_call!(
+            int: 10,
+            ax: 0x4F01,
+            cx: mode.get_id(),
+            es: ptr.segment(),
+            di: ptr.offset(),
+        ))
+    }
+
+    fn vesa_set_mode(&mut self, mode: video::VesaModeId) -> BiosStatus {
+        Self::vbe_status(bios_call!(
+            int: 10,
+            ax: 0x4F02,
+            bx: mode.get_id(),
+        ))
+    }
+}
+
 pub mod video {
-    use core::ptr::addr_of;
+    use crate::{BiosServices, BiosStatus, RealBios, RealModePtr};
     const TELETYPE_OUTPUT_CHAR: u16 = 0x0E00;
 
     #[inline]
@@ -257,6 +478,15 @@ pub mod video {
         reserved2: [u8; 206],
     }
 
+    impl VesaMode {
+        /// # Is Supported
+        /// Check if this mode has a linear framebuffer with packed or direct color
+        /// pixels, the only kind of mode the bootloader can draw to.
+        pub fn is_supported(&self) -> bool {
+            self.attributes & 0x90 == 0x90 && (self.memory_model == 4 || self.memory_model == 6)
+        }
+    }
+
     impl core::fmt::Debug for VesaMode {
         fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
             f.debug_struct("VesaMode")
@@ -271,9 +501,9 @@ pub mod video {
     pub struct Vesa {
         pub signature: [u8; 4],
         pub version: u16,
-        pub oem_string_ptr: (u16, u16),
+        pub oem_string_ptr: RealModePtr,
         pub capabilities: [u8; 4],
-        pub video_mode_ptr: (u16, u16),
+        pub video_mode_ptr: RealModePtr,
         pub size_64k_blocks: u16,
     }
 
@@ -299,128 +529,184 @@ pub mod video {
         }
 
         pub fn querry(self) -> Result<VesaMode, VesaErrorKind> {
-            let uninit_mode: VesaMode = unsafe { core::mem::zeroed() };
-
-            bios_call!(
-                int: 10,
-                ax: 0x4F01,
-                cx: self.0,
-                es: (addr_of!(uninit_mode) as u32 / 0x10) as u16,
-                di: (addr_of!(uninit_mode) as u32 % 0x10) as u16,
-            );
+            self.querry_with(&mut RealBios)
+        }
 
-            if uninit_mode.attributes & 0x90 != 0x90 {
-                // We don't currently want to deal with non-linear framebuffer support
-                return Err(VesaErrorKind::NotSupported);
+        /// # Querry With
+        /// Read this mode's info using `bios`, only modes the bootloader
+        /// supports are returned (see [`VesaMode::is_supported`]).
+        pub fn querry_with(self, bios: &mut impl BiosServices) -> Result<VesaMode, VesaErrorKind> {
+            let mut mode: VesaMode = unsafe { core::mem::zeroed() };
+
+            if bios.vesa_mode_info(self, &mut mode) != BiosStatus::Success {
+                return Err(VesaErrorKind::Failed);
             }
 
-            if uninit_mode.memory_model != 4 && uninit_mode.memory_model != 6 {
-                // same with non-packed pixel modes
+            if !mode.is_supported() {
+                // We don't currently want to deal with non-linear or non-packed pixel modes
                 return Err(VesaErrorKind::NotSupported);
             }
 
-            Ok(uninit_mode)
+            Ok(mode)
         }
 
         pub fn set(self) -> Result<(), VesaErrorKind> {
-            bios_call!(
-                int: 10,
-                ax: 0x4F02,
-                bx: self.0,
-            );
+            self.set_with(&mut RealBios)
+        }
 
-            Ok(())
+        /// # Set With
+        /// Switch to this mode using `bios`.
+        pub fn set_with(self, bios: &mut impl BiosServices) -> Result<(), VesaErrorKind> {
+            match bios.vesa_set_mode(self) {
+                BiosStatus::Success => Ok(()),
+                BiosStatus::NotSupported => Err(VesaErrorKind::NotSupported),
+                _ => Err(VesaErrorKind::Failed),
+            }
         }
     }
 
     impl Vesa {
         pub fn quarry() -> Result<Self, VesaErrorKind> {
-            let uninit_self: Self = Default::default();
+            Self::quarry_with(&mut RealBios)
+        }
 
-            bios_call!(
-                int: 10,
-                ax: 0x4F00,
-                es: (addr_of!(uninit_self) as u32 / 0x10) as u16,
-                di: (addr_of!(uninit_self) as u32 % 0x10) as u16,
-            );
+        /// # Quarry With
+        /// Read the VBE controller info using `bios`, only VBE 3.0 is accepted.
+        pub fn quarry_with(bios: &mut impl BiosServices) -> Result<Self, VesaErrorKind> {
+            let mut info: Self = Default::default();
+
+            if bios.vesa_info(&mut info) != BiosStatus::Success {
+                return Err(VesaErrorKind::Failed);
+            }
 
-            if &uninit_self.signature == b"VESA" && uninit_self.version == 0x0300 {
-                Ok(uninit_self)
+            if &info.signature == b"VESA" && info.version == 0x0300 {
+                Ok(info)
             } else {
                 Err(VesaErrorKind::Invalid)
             }
         }
 
         pub fn modes(&self) -> impl Iterator<Item = VesaModeId> {
-            let modes_loc = (self.video_mode_ptr.1 as u32 * 0x10) + self.video_mode_ptr.0 as u32;
-            let modes_ptr = modes_loc as *const VesaModeId;
+            let modes_ptr: *const VesaModeId = self.video_mode_ptr.as_ptr();
 
+            // The list ends with 0xFFFF, but never trust it to end before real mode memory does
             let mut mode_len = 0;
-            loop {
-                unsafe {
-                    if (*modes_ptr.add(mode_len)).0 == 0xFFFF {
-                        break;
-                    }
-
-                    mode_len += 1;
-                }
+            while self.video_mode_ptr.fits::<VesaModeId>(mode_len + 1)
+                && unsafe { (*modes_ptr.add(mode_len)).0 } != 0xFFFF
+            {
+                mode_len += 1;
             }
 
-            unsafe { core::slice::from_raw_parts(modes_ptr, mode_len) }
-                .into_iter()
+            unsafe { self.video_mode_ptr.slice::<VesaModeId>(mode_len) }
+                .unwrap_or(&[])
+                .iter()
                 .copied()
         }
     }
+
+    /// # Closest Mode
+    /// Pick the mode with `bpp` bits per pixel that is closest to `want_x` by
+    /// `want_y`. A mode only replaces the current pick if it is closer in both
+    /// width and height.
+    pub fn closest_mode(
+        modes: impl Iterator<Item = (VesaModeId, VesaMode)>,
+        (want_x, want_y): (u16, u16),
+        bpp: u8,
+    ) -> Option<(VesaModeId, VesaMode)> {
+        modes
+            .filter(|(_, mode)| mode.bpp == bpp)
+            .reduce(|closest_mode, (id, mode)| {
+                if closest_mode.1.width.abs_diff(want_x) > mode.width.abs_diff(want_x)
+                    && closest_mode.1.height.abs_diff(want_y) > mode.height.abs_diff(want_y)
+                {
+                    (id, mode)
+                } else {
+                    closest_mode
+                }
+            })
+    }
 }
 
 pub mod disk {
-    use crate::BiosStatus;
-    use core::ptr::addr_of;
+    use crate::{BiosServices, BiosStatus, RealBios, RealModePtr};
+
+    pub(crate) const DISK_DAP_READ: u16 = 0x4200;
 
-    const DISK_DAP_READ: u16 = 0x4200;
+    /// # Max Sectors Per Read
+    /// The most sectors one extended read is allowed to transfer, some BIOSes
+    /// reject anything larger.
+    pub const MAX_SECTORS_PER_READ: usize = 127;
 
+    /// # Disk Access Packet
+    /// Describes an extended disk read for int 0x13.
     #[repr(C)]
-    struct DiskAccessPacket {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct DiskAccessPacket {
         packet_size: u8,
         always_zero: u8,
         sectors: u16,
-        base_ptr: u16,
-        base_segment: u16,
+        buffer: RealModePtr,
         lba: u64,
     }
 
     impl DiskAccessPacket {
-        fn new(sectors: u16, lba: u64, ptr: u32) -> Self {
-            let base_segment = (ptr >> 4) as u16;
-            let base_ptr = ptr as u16 & 0xF;
-
+        pub fn new(sectors: u16, lba: u64, buffer: RealModePtr) -> Self {
             Self {
                 packet_size: 0x10,
                 always_zero: 0,
                 sectors,
-                base_ptr,
-                base_segment,
+                buffer,
                 lba,
             }
         }
+
+        pub const fn sectors(&self) -> u16 {
+            self.sectors
+        }
+
+        pub const fn lba(&self) -> u64 {
+            self.lba
+        }
+
+        /// # Buffer
+        /// Where the sectors are read into.
+        pub const fn buffer(&self) -> RealModePtr {
+            self.buffer
+        }
     }
 
     pub unsafe fn raw_read(disk_id: u16, lba: u64, count: usize, ptr: *mut u8) -> BiosStatus {
-        let package = DiskAccessPacket::new(count as u16, lba, ptr as u32);
+        read_with(&mut RealBios, disk_id, lba, count, ptr)
+    }
 
-        assert!(addr_of!(package) as u32 & 0xFFFF == addr_of!(package) as u32);
+    /// # Read With
+    /// Read `count` sectors starting at `lba` into `ptr` using `bios`.
+    ///
+    /// # Safety
+    /// `ptr` must be valid for `count` sectors, and (like all BIOS buffers) be
+    /// below 1MiB.
+    pub unsafe fn read_with(
+        bios: &mut impl BiosServices,
+        disk_id: u16,
+        lba: u64,
+        count: usize,
+        ptr: *mut u8,
+    ) -> BiosStatus {
+        if count == 0 || count > MAX_SECTORS_PER_READ {
+            return BiosStatus::InvalidInput;
+        }
 
-        BiosStatus::from_ax(bios_call! {
-            int: 13,
-            ax: DISK_DAP_READ,
-            dx: disk_id,
-            si: addr_of!(package) as u16
-        })
+        let Some(buffer) = RealModePtr::from_ptr(ptr) else {
+            return BiosStatus::InvalidInput;
+        };
+
+        let package = DiskAccessPacket::new(count as u16, lba, buffer);
+        bios.disk_read(disk_id, &package)
     }
 }
 
 pub mod memory {
-    use crate::BiosStatus;
+    use crate::{BiosServices, BiosStatus, RealBios};
 
     #[repr(C)]
     #[derive(Clone, Copy, Debug)]
@@ -436,30 +722,6 @@ pub mod memory {
         pub const REGION_FREE: u32 = 0x1;
     }
 
-    // FIXME: We should not be returning a Result with BiosStatus as the error, but instead
-    //        it should be a type containing the error kind.
-    unsafe fn read_region(ptr: *mut MemoryEntry, ebx: u32) -> Result<u32, BiosStatus> {
-        use crate::int_0x15;
-        use arch::registers::Regs32;
-
-        let low_ptr = (ptr as u32) % 0x10;
-        let high_ptr = ((ptr as u32) / 0x10) as u16;
-
-        let mut regs = Regs32 {
-            eax: 0xE820,
-            ebx,
-            ecx: 24,
-            edx: 0x534D4150,
-            edi: low_ptr,
-            ..Regs32::default()
-        };
-
-        match int_0x15(&mut regs, high_ptr) {
-            BiosStatus::Success => Ok(regs.ebx),
-            err => Err(err),
-        }
-    }
-
     /// # Read Mapping
     /// Reads the computer's memory map using Bios-Call-0x15's 0xE820 command.
     ///
@@ -470,12 +732,21 @@ pub mod memory {
     /// provided buffer. If there are more regions than will fit in the buffer
     /// this function will simply return and return the size of the buffer.
     pub fn read_mapping(memory: &mut [MemoryEntry]) -> Result<usize, BiosStatus> {
+        read_mapping_with(&mut RealBios, memory)
+    }
+
+    // FIXME: We should not be returning a Result with BiosStatus as the error, but instead
+    //        it should be a type containing the error kind.
+    /// # Read Mapping With
+    /// Same as [`read_mapping`], but using `bios` to read each entry.
+    pub fn read_mapping_with(
+        bios: &mut impl BiosServices,
+        memory: &mut [MemoryEntry],
+    ) -> Result<usize, BiosStatus> {
         let mut ebx = 0;
 
         for (en, entry) in memory.iter_mut().enumerate() {
-            let entry_ptr = entry as *mut MemoryEntry;
-
-            ebx = unsafe { read_region(entry_ptr, ebx) }?;
+            ebx = bios.memory_map_entry(entry, ebx)?;
 
             if ebx == 0 {
                 return Ok(en + 1);
@@ -485,3 +756,227 @@ pub mod memory {
         Ok(memory.len())
     }
 }
+
+#[cfg(test)]
+mod test {
+    extern crate std;
+    use super::*;
+    use disk::DiskAccessPacket;
+    use memory::MemoryEntry;
+    use std::vec::Vec;
+    use video::{Vesa, VesaErrorKind, VesaMode, VesaModeId};
+
+    #[derive(Default)]
+    struct MockBios {
+        reads: Vec<(u16, DiskAccessPacket)>,
+        memory_map: Vec<MemoryEntry>,
+        modes: Vec<(u16, VesaMode)>,
+        current_mode: Option<u16>,
+    }
+
+    impl BiosServices for MockBios {
+        unsafe fn disk_read(&mut self, disk_id: u16, packet: &DiskAccessPacket) -> BiosStatus {
+            self.reads.push((disk_id, *packet));
+            BiosStatus::Success
+        }
+
+        fn memory_map_entry(
+            &mut self,
+            entry: &mut MemoryEntry,
+            continuation: u32,
+        ) -> Result<u32, BiosStatus> {
+            let index = continuation as usize;
+            *entry = *self.memory_map.get(index).ok_or(BiosStatus::Failed)?;
+
+            if index + 1 == self.memory_map.len() {
+                Ok(0)
+            } else {
+                Ok(index as u32 + 1)
+            }
+        }
+
+        fn vesa_info(&mut self, info: &mut Vesa) -> BiosStatus {
+            info.signature = *b"VESA";
+            info.version = 0x0300;
+            BiosStatus::Success
+        }
+
+        fn vesa_mode_info(&mut self, mode: VesaModeId, info: &mut VesaMode) -> BiosStatus {
+            match self.modes.iter().find(|(id, _)| *id == mode.get_id()) {
+                Some((_, found)) => {
+                    *info = *found;
+                    BiosStatus::Success
+                }
+                None => BiosStatus::Failed,
+            }
+        }
+
+        fn vesa_set_mode(&mut self, mode: VesaModeId) -> BiosStatus {
+            self.current_mode = Some(mode.get_id());
+            BiosStatus::Success
+        }
+    }
+
+    fn region(base_address: u64, region_length: u64) -> MemoryEntry {
+        MemoryEntry {
+            base_address,
+            region_length,
+            region_type: MemoryEntry::REGION_FREE,
+            acpi_attributes: 0,
+        }
+    }
+
+    fn mode(width: u16, height: u16, bpp: u8, linear: bool) -> VesaMode {
+        let mut mode: VesaMode = unsafe { core::mem::zeroed() };
+        mode.attributes = if linear { 0x9B } else { 0x1B };
+        mode.memory_model = 6;
+        mode.width = width;
+        mode.height = height;
+        mode.bpp = bpp;
+        mode
+    }
+
+    #[test]
+    fn test_disk_packet_construction() {
+        let mut bios = MockBios::default();
+
+        let status = unsafe { disk::read_with(&mut bios, 0x80, 1234, 16, 0x2_0010 as *mut u8) };
+        assert_eq!(status, BiosStatus::Success);
+
+        let (disk_id, packet) = bios.reads[0];
+        assert_eq!(disk_id, 0x80);
+        assert_eq!(packet.sectors(), 16);
+        assert_eq!(packet.lba(), 1234);
+        assert_eq!(packet.buffer(), RealModePtr::new(0x2001, 0x0));
+        assert_eq!(packet.buffer().linear(), 0x2_0010);
+    }
+
+    #[test]
+    fn test_disk_read_rejects_bad_requests() {
+        let mut bios = MockBios::default();
+
+        for (count, ptr) in [(0, 0x8000), (128, 0x8000), (1, 0x10_0000)] {
+            let status = unsafe { disk::read_with(&mut bios, 0x80, 0, count, ptr as *mut u8) };
+            assert_eq!(status, BiosStatus::InvalidInput);
+        }
+
+        assert!(bios.reads.is_empty());
+    }
+
+    #[test]
+    fn test_memory_map_reads_every_entry() {
+        let mut bios = MockBios {
+            memory_map: [region(0, 0x9F000), region(0x10_0000, 0x100_0000)].into(),
+            ..Default::default()
+        };
+
+        let mut entries = [region(0, 0); 4];
+        assert_eq!(memory::read_mapping_with(&mut bios, &mut entries), Ok(2));
+        assert_eq!(entries[1].base_address, 0x10_0000);
+    }
+
+    #[test]
+    fn test_memory_map_stops_when_full() {
+        let mut bios = MockBios {
+            memory_map: [region(0, 1), region(1, 1), region(2, 1)].into(),
+            ..Default::default()
+        };
+
+        let mut entries = [region(0, 0); 2];
+        assert_eq!(memory::read_mapping_with(&mut bios, &mut entries), Ok(2));
+
+        bios.memory_map.clear();
+        assert_eq!(
+            memory::read_mapping_with(&mut bios, &mut entries),
+            Err(BiosStatus::Failed)
+        );
+    }
+
+    #[test]
+    fn test_querry_filters_unsupported_modes() {
+        let mut bios = MockBios {
+            modes: [
+                (0x100, mode(800, 600, 32, true)),
+                (0x101, mode(800, 600, 32, false)),
+            ]
+            .into(),
+            ..Default::default()
+        };
+
+        assert!(Vesa::quarry_with(&mut bios).is_ok());
+        assert!(unsafe { VesaModeId::force_mode(0x100) }
+            .querry_with(&mut bios)
+            .is_ok());
+        assert!(matches!(
+            unsafe { VesaModeId::force_mode(0x101) }.querry_with(&mut bios),
+            Err(VesaErrorKind::NotSupported)
+        ));
+        assert!(matches!(
+            unsafe { VesaModeId::force_mode(0x102) }.querry_with(&mut bios),
+            Err(VesaErrorKind::Failed)
+        ));
+
+        unsafe { VesaModeId::force_mode(0x100) }
+            .set_with(&mut bios)
+            .unwrap();
+        assert_eq!(bios.current_mode, Some(0x100));
+    }
+
+    #[test]
+    fn test_closest_mode() {
+        let modes = [
+            (0x100, mode(640, 480, 32, true)),
+            (0x101, mode(1024, 768, 16, true)),
+            (0x102, mode(1024, 768, 32, true)),
+            (0x103, mode(1920, 1080, 32, true)),
+        ];
+        let modes = || {
+            modes
+                .iter()
+                .map(|&(id, mode)| (unsafe { VesaModeId::force_mode(id) }, mode))
+        };
+
+        let (id, _) = video::closest_mode(modes(), (1000, 700), 32).unwrap();
+        assert_eq!(id.get_id(), 0x102);
+
+        let (id, _) = video::closest_mode(modes(), (1000, 700), 16).unwrap();
+        assert_eq!(id.get_id(), 0x101);
+
+        assert!(video::closest_mode(modes(), (1000, 700), 8).is_none());
+    }
+
+    #[test]
+    fn test_real_mode_ptr_linear() {
+        let ptr = RealModePtr::from_linear(0x7C00).unwrap();
+        assert_eq!((ptr.segment(), ptr.offset()), (0x07C0, 0));
+        assert_eq!(ptr.linear(), 0x7C00);
+
+        assert_eq!(RealModePtr::new(0x0000, 0x7C00).linear(), 0x7C00);
+        assert_eq!(RealModePtr::new(0xFFFF, 0xFFFF).linear(), 0x10_FFEF);
+        assert_eq!(
+            RealModePtr::from_linear(0xF_FFFF),
+            Some(RealModePtr::new(0xFFFF, 0xF))
+        );
+        assert_eq!(RealModePtr::from_linear(0x10_0000), None);
+    }
+
+    #[test]
+    fn test_real_mode_ptr_fits() {
+        let ptr = RealModePtr::new(0xFFFF, 0x0);
+        assert!(ptr.fits::<u8>(16));
+        assert!(!ptr.fits::<u8>(17));
+        assert!(!ptr.fits::<u8>(usize::MAX));
+
+        assert!(RealModePtr::new(0x1000, 0x2).fits::<u16>(1));
+        assert!(!RealModePtr::new(0x1000, 0x1).fits::<u16>(1));
+        assert!(!RealModePtr::new(0xFFFF, 0xFFF0).fits::<u8>(1));
+    }
+
+    #[test]
+    fn test_real_mode_ptr_layout() {
+        let ptr = RealModePtr::new(0x1234, 0x5678);
+        let bytes: [u8; 4] = unsafe { core::mem::transmute(ptr) };
+
+        assert_eq!(bytes, [0x78, 0x56, 0x34, 0x12]);
+    }
+}
@@ -29,6 +29,9 @@ use core::ops::RangeBounds;
 /// # Bit Manipulation
 /// A Simple trait to help with setting and un-setting bits in types.
 pub trait BitManipulation: Sized {
+    /// The signed type with the same number of bits as this type.
+    type Signed;
+
     /// # Set Bit
     /// Set a single bit in the given type.
     fn set_bit<B>(&mut self, bit: B, set: bool) -> &mut Self
@@ -53,6 +56,96 @@ pub trait BitManipulation: Sized {
     fn get_bit_range<R>(&self, bit: R) -> Self
     where
         R: RangeBounds<Self>;
+
+    /// # Try Set Bit Range
+    /// Set a range of bits in the given type, or return an error if the range (or the
+    /// value being set) does not fit.
+    fn try_set_bit_range<R, B>(&mut self, bit: R, set: B) -> Result<&mut Self, BitRangeError>
+    where
+        R: RangeBounds<Self>,
+        B: Into<Self>;
+
+    /// # Try Get Bit Range
+    /// Get a range of bits in the given type, or return an error if the range does not fit.
+    fn try_get_bit_range<R>(&self, bit: R) -> Result<Self, BitRangeError>
+    where
+        R: RangeBounds<Self>;
+
+    /// # Get Bit Range Signed
+    /// Get a range of bits in the given type, treating the highest bit of the range as a
+    /// sign bit and sign-extending the result.
+    fn get_bit_range_signed<R>(&self, bit: R) -> Self::Signed
+    where
+        R: RangeBounds<Self>;
+
+    /// # Extract
+    /// Get `LEN` bits starting at bit `START`, the range is checked at compile time.
+    ///
+    /// See the [`extract`] module for versions of this that can be used in const contexts.
+    fn extract<const START: u32, const LEN: u32>(&self) -> Self;
+}
+
+/// # Bit Range Error
+/// Why a bit range operation could not be done.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitRangeError {
+    /// The range (`start..end`) goes past the last bit of the type.
+    OutOfBounds { start: u32, end: u32, bits: u32 },
+    /// The range's start comes after its end.
+    Reversed { start: u32, end: u32 },
+    /// The value being set has more bits than the range can hold.
+    ValueTooLarge { range_bits: u32 },
+}
+
+impl core::fmt::Display for BitRangeError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            BitRangeError::OutOfBounds { start, end, bits } => write!(
+                f,
+                "Bit Range '{start}..{end}' is larger then type's total bits of '{bits}'!"
+            ),
+            BitRangeError::Reversed { start, end } => {
+                write!(f, "Bit Start '{start}' must be less then Bit End '{end}'!")
+            }
+            BitRangeError::ValueTooLarge { range_bits } => write!(
+                f,
+                "The setting bits cannot be more bits then amount specified {range_bits}!"
+            ),
+        }
+    }
+}
+
+/// Convert `bit` into a `start..end` (end exclusive) pair, checking that it fits in a
+/// type with `bits` total bits.
+fn bit_range_bounds<T, R>(bit: R, bits: u32) -> Result<(u32, u32), BitRangeError>
+where
+    T: Copy + TryInto<u32>,
+    R: RangeBounds<T>,
+{
+    // Negative or huge values can never be in bounds
+    let to_bit = |value: T| value.try_into().unwrap_or(u32::MAX);
+
+    let start = match bit.start_bound() {
+        core::ops::Bound::Included(&value) => to_bit(value),
+        core::ops::Bound::Excluded(&value) => to_bit(value).saturating_add(1),
+        core::ops::Bound::Unbounded => 0,
+    };
+
+    let end = match bit.end_bound() {
+        core::ops::Bound::Included(&value) => to_bit(value).saturating_add(1),
+        core::ops::Bound::Excluded(&value) => to_bit(value),
+        core::ops::Bound::Unbounded => bits,
+    };
+
+    if start > bits || end > bits {
+        return Err(BitRangeError::OutOfBounds { start, end, bits });
+    }
+
+    if start > end {
+        return Err(BitRangeError::Reversed { start, end });
+    }
+
+    Ok((start, end))
 }
 
 /// # Bit Manipulation `Impl`
@@ -60,8 +153,10 @@ pub trait BitManipulation: Sized {
 /// FIXME: We should use something like `PrimInt` from the num-traits create
 ///        to provide a `impl<T: PrimInt> BitManipulation for T {}`.
 macro_rules! bit_manipulation_impl {
-    ($($t:ty)*) => ($(
+    ($($t:ident => $signed:ident)*) => ($(
      impl BitManipulation for $t {
+        type Signed = $signed;
+
         /// # Set Bit
         /// Set a single bit in the given type.
         fn set_bit<B>(&mut self, bit: B, set: bool) -> &mut Self
@@ -107,100 +202,280 @@ macro_rules! bit_manipulation_impl {
         where
             R: RangeBounds<Self>,
         {
-            let self_bits = (core::mem::size_of::<Self>() * 8) as Self;
-            let true_bit_start = match bit.start_bound() {
-                core::ops::Bound::Included(&value) => value,
-                core::ops::Bound::Excluded(&value) => value + 1,
-                core::ops::Bound::Unbounded => 0 as Self,
-            };
+            match self.try_get_bit_range(bit) {
+                Ok(value) => value,
+                Err(err) => panic!("{err}"),
+            }
+        }
 
-            let true_bit_end = match bit.end_bound() {
-                core::ops::Bound::Included(&value) => value + 1,
-                core::ops::Bound::Excluded(&value) => value,
-                core::ops::Bound::Unbounded => self_bits,
-            };
+        /// # Set Bit Range
+        /// Set a range of bits in the given type.
+        fn set_bit_range<R, B>(&mut self, bit: R, set: B) -> &mut Self
+        where
+            R: RangeBounds<Self>,
+            B: Into<Self>,
+        {
+            if let Err(err) = self.try_set_bit_range(bit, set) {
+                panic!("{err}");
+            }
 
-            debug_assert!(
-                true_bit_start <= self_bits,
-                "Bit Start '{true_bit_start}' is larger then type's total bits of '{self_bits}'!"
-            );
+            self
+        }
 
-            debug_assert!(
-                true_bit_end <= self_bits,
-                "Bit End '{true_bit_end}' is larger then type's total bits of '{self_bits}'!"
-            );
+        /// # Try Get Bit Range
+        /// Get a range of bits in the given type, or return an error if the range does not fit.
+        fn try_get_bit_range<R>(&self, bit: R) -> Result<Self, BitRangeError>
+        where
+            R: RangeBounds<Self>,
+        {
+            let (start, end) = bit_range_bounds(bit, Self::BITS)?;
 
-            debug_assert!(
-                true_bit_end >= true_bit_start,
-                "Bit Start '{true_bit_start}' must be less then Bit End '{true_bit_end}'!"
-            );
+            if start == end {
+                return Ok(0);
+            }
+
+            let bits = *self << (Self::BITS - end) >> (Self::BITS - end);
+            Ok(bits >> start)
+        }
+
+        /// # Get Bit Range Signed
+        /// Get a range of bits in the given type, treating the highest bit of the range as a
+        /// sign bit and sign-extending the result.
+        fn get_bit_range_signed<R>(&self, bit: R) -> Self::Signed
+        where
+            R: RangeBounds<Self>,
+        {
+            let (start, end) = match bit_range_bounds(bit, Self::BITS) {
+                Ok(bounds) => bounds,
+                Err(err) => panic!("{err}"),
+            };
 
-            let bits = *self << (self_bits - true_bit_end) >> (self_bits - true_bit_end);
+            if start == end {
+                return 0;
+            }
 
-            bits >> true_bit_start
+            (*self as $signed) << (Self::BITS - end) >> (Self::BITS - end + start)
         }
 
-        /// # Set Bit Range
-        /// Set a range of bits in the given type.
-        fn set_bit_range<R, B>(&mut self, bit: R, set: B) -> &mut Self
+        /// # Extract
+        /// Get `LEN` bits starting at bit `START`, the range is checked at compile time.
+        fn extract<const START: u32, const LEN: u32>(&self) -> Self {
+            extract::$t::<START, LEN>(*self)
+        }
+
+        /// # Try Set Bit Range
+        /// Set a range of bits in the given type, or return an error if the range (or the
+        /// value being set) does not fit.
+        fn try_set_bit_range<R, B>(&mut self, bit: R, set: B) -> Result<&mut Self, BitRangeError>
         where
             R: RangeBounds<Self>,
             B: Into<Self>,
         {
             let set_bits: Self = set.into();
-            let self_bits = (core::mem::size_of::<Self>() * 8) as Self;
-            let true_bit_start = match bit.start_bound() {
-                core::ops::Bound::Included(&value) => value,
-                core::ops::Bound::Excluded(&value) => value + 1,
-                core::ops::Bound::Unbounded => 0 as Self,
+            let (start, end) = bit_range_bounds(bit, Self::BITS)?;
+            let range_bits = end - start;
+
+            let mask: Self = if range_bits == Self::BITS {
+                !0
+            } else {
+                (1 as Self).wrapping_shl(range_bits).wrapping_sub(1)
             };
 
-            let true_bit_end = match bit.end_bound() {
-                core::ops::Bound::Included(&value) => value,
-                core::ops::Bound::Excluded(&value) => value - 1,
-                core::ops::Bound::Unbounded => self_bits,
+            if set_bits & !mask != 0 {
+                return Err(BitRangeError::ValueTooLarge { range_bits });
+            }
+
+            *self = (*self & !(mask.wrapping_shl(start))) | set_bits.wrapping_shl(start);
+            Ok(self)
+        }
+    }
+    )*)
+}
+
+bit_manipulation_impl! {
+    u8 => i8 u16 => i16 u32 => i32 u64 => i64 u128 => i128 usize => isize
+    i8 => i8 i16 => i16 i32 => i32 i64 => i64 i128 => i128 isize => isize
+}
+
+/// # Extract
+/// `const` functions for getting a compile time known range of bits out of a value.
+///
+/// Each function is named after the type it extracts from, signed types sign-extend
+/// the extracted bits.
+///
+/// ```
+/// const FIELD: u32 = bits::extract::u32::<4, 8>(0xABCD);
+/// assert_eq!(FIELD, 0xBC);
+///
+/// const SIGNED: i32 = bits::extract::i32::<0, 4>(0b1110);
+/// assert_eq!(SIGNED, -2);
+/// ```
+pub mod extract {
+    macro_rules! extract_impl {
+        ($($t:ident)*) => ($(
+        /// # Extract
+        /// Get `LEN` bits starting at bit `START`, the range is checked at compile time.
+        pub const fn $t<const START: u32, const LEN: u32>(value: $t) -> $t {
+            const {
+                assert!(
+                    START as u64 + LEN as u64 <= $t::BITS as u64,
+                    "Extracted bit range is larger then the type's total bits!"
+                )
             };
 
-            let true_bit_diff = true_bit_end - true_bit_start;
+            if LEN == 0 {
+                return 0;
+            }
 
-            debug_assert!(
-                true_bit_start <= self_bits,
-                "Bit Start '{true_bit_start}' is larger then type's total bits of '{self_bits}'!"
-            );
+            value << ($t::BITS - START - LEN) >> ($t::BITS - LEN)
+        }
+        )*)
+    }
 
-            debug_assert!(
-                true_bit_end <= self_bits,
-                "Bit End '{true_bit_end}' is larger then type's total bits of '{self_bits}'!"
-            );
+    extract_impl! { u8 u16 u32 u64 u128 usize i8 i16 i32 i64 i128 isize }
+}
 
-            debug_assert!(
-                true_bit_end >= true_bit_start,
-                "Bit Start '{true_bit_start}' must be less then Bit End '{true_bit_end}'!"
-            );
+/// # Bit Slice Manipulation
+/// Treat a slice of integers as one long string of bits.
+///
+/// Bit `n` of the slice is bit `n % BITS` of element `n / BITS`, so bit `0` is the
+/// lowest bit of the first element.
+pub trait BitSliceManipulation {
+    /// # Bit Len
+    /// The total number of bits in this slice.
+    fn bit_len(&self) -> usize;
 
-            debug_assert!(
-                set_bits >> true_bit_diff <= 1,
-                "The setting bits '0b{set_bits:0b}' cannot be more bits then amount specified {true_bit_diff}!"
-            );
+    /// # Set Bit
+    /// Set a single bit in the slice.
+    fn set_bit(&mut self, bit: usize, set: bool) -> &mut Self;
+
+    /// # Get Bit
+    /// Get a single bit in the slice.
+    fn get_bit(&self, bit: usize) -> bool;
+
+    /// # Set Bit Range
+    /// Set a range of bits (at most 64) in the slice, the range may cross elements.
+    fn set_bit_range<R>(&mut self, bit: R, set: u64) -> &mut Self
+    where
+        R: RangeBounds<usize>;
+
+    /// # Get Bit Range
+    /// Get a range of bits (at most 64) in the slice, the range may cross elements.
+    fn get_bit_range<R>(&self, bit: R) -> u64
+    where
+        R: RangeBounds<usize>;
+}
+
+/// Convert `bit` into a `start..end` pair, checking it fits in `bit_len` bits and
+/// is at most 64 bits long.
+fn slice_bit_bounds<R: RangeBounds<usize>>(bit: R, bit_len: usize) -> (usize, usize) {
+    let true_bit_start = match bit.start_bound() {
+        core::ops::Bound::Included(&value) => value,
+        core::ops::Bound::Excluded(&value) => value + 1,
+        core::ops::Bound::Unbounded => 0,
+    };
+
+    let true_bit_end = match bit.end_bound() {
+        core::ops::Bound::Included(&value) => value + 1,
+        core::ops::Bound::Excluded(&value) => value,
+        core::ops::Bound::Unbounded => bit_len,
+    };
+
+    assert!(
+        true_bit_start <= true_bit_end && true_bit_end <= bit_len,
+        "Bit Range '{true_bit_start}..{true_bit_end}' is out of bounds of slice's total bits of '{bit_len}'!"
+    );
+
+    assert!(
+        true_bit_end - true_bit_start <= 64,
+        "Bit Range '{true_bit_start}..{true_bit_end}' is larger then 64 bits!"
+    );
+
+    (true_bit_start, true_bit_end)
+}
 
-            // Generate Mask bits
-            // TODO: There must be a better way of doing this?
-            let mut mask = 1;
-            for _ in 0..true_bit_diff {
-                mask <<= 1;
-                mask |= 1;
+/// # Bit Slice Manipulation `Impl`
+/// Implement `BitSliceManipulation` for slices of many types.
+macro_rules! bit_slice_manipulation_impl {
+    ($($t:ty)*) => ($(
+    impl BitSliceManipulation for [$t] {
+        fn bit_len(&self) -> usize {
+            self.len() * <$t>::BITS as usize
+        }
+
+        fn set_bit(&mut self, bit: usize, set: bool) -> &mut Self {
+            let element_bits = <$t>::BITS as usize;
+            self[bit / element_bits].set_bit((bit % element_bits) as u8, set);
+
+            self
+        }
+
+        fn get_bit(&self, bit: usize) -> bool {
+            let element_bits = <$t>::BITS as usize;
+            self[bit / element_bits].get_bit((bit % element_bits) as u8)
+        }
+
+        fn set_bit_range<R>(&mut self, bit: R, set: u64) -> &mut Self
+        where
+            R: RangeBounds<usize>,
+        {
+            let element_bits = <$t>::BITS as usize;
+            let (start, end) = slice_bit_bounds(bit, self.bit_len());
+
+            if end - start < 64 && set >> (end - start) != 0 {
+                panic!(
+                    "{}",
+                    BitRangeError::ValueTooLarge {
+                        range_bits: (end - start) as u32
+                    }
+                );
+            }
+
+            let mut done = 0;
+            while start + done < end {
+                let bit = start + done;
+                let offset = bit % element_bits;
+                let amount = (element_bits - offset).min(end - bit);
+
+                let chunk = set.checked_shr(done as u32).unwrap_or(0)
+                    & (u64::MAX >> (64 - amount));
+
+                self[bit / element_bits]
+                    .set_bit_range((offset as $t)..((offset + amount) as $t), chunk as $t);
+                done += amount;
             }
-            mask <<= true_bit_start;
 
-            // Combine everything together
-            *self = (*self & !mask) | (set_bits << true_bit_start);
             self
         }
+
+        fn get_bit_range<R>(&self, bit: R) -> u64
+        where
+            R: RangeBounds<usize>,
+        {
+            let element_bits = <$t>::BITS as usize;
+            let (start, end) = slice_bit_bounds(bit, self.bit_len());
+
+            let mut value = 0;
+            let mut done = 0;
+            while start + done < end {
+                let bit = start + done;
+                let offset = bit % element_bits;
+                let amount = (element_bits - offset).min(end - bit);
+
+                let chunk = self[bit / element_bits]
+                    .get_bit_range((offset as $t)..((offset + amount) as $t));
+
+                value |= (chunk as u64) << done;
+                done += amount;
+            }
+
+            value
+        }
     }
     )*)
 }
 
-bit_manipulation_impl! { u8 u16 u32 u64 u128 i8 i16 i32 i64 i128 usize isize }
+bit_slice_manipulation_impl! { u8 u16 u32 u64 }
 
 #[cfg(test)]
 mod test {
@@ -274,6 +549,123 @@ mod test {
         assert_eq!(*0b00000000_u8.set_bit_range(0..=7, 0xFF), 0b11111111_u8);
     }
 
+    #[test]
+    fn test_slice_get_set_bit() {
+        let mut bytes = [0u8; 4];
+        bytes.set_bit(0, true).set_bit(9, true).set_bit(31, true);
+
+        assert_eq!(bytes, [0b00000001, 0b00000010, 0, 0b10000000]);
+        assert!(bytes.get_bit(9));
+        assert!(!bytes.get_bit(10));
+        assert_eq!(bytes.bit_len(), 32);
+    }
+
+    #[test]
+    fn test_slice_bit_range_across_elements() {
+        let mut bytes = [0u8; 3];
+        bytes.set_bit_range(4..20, 0xABCD);
+
+        assert_eq!(bytes, [0xD0, 0xBC, 0x0A]);
+        assert_eq!(bytes.get_bit_range(4..20), 0xABCD);
+        assert_eq!(bytes.get_bit_range(8..=15), 0xBC);
+    }
+
+    #[test]
+    fn test_slice_bit_range_full_u64() {
+        let mut words = [0u64; 2];
+        words.set_bit_range(32..96, u64::MAX);
+
+        assert_eq!(words, [0xFFFFFFFF_00000000, 0x00000000_FFFFFFFF]);
+        assert_eq!(words.get_bit_range(32..96), u64::MAX);
+        assert_eq!(words.get_bit_range(..32), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot be more bits then amount specified 4")]
+    fn test_slice_bit_range_value_too_large() {
+        let mut bytes = [0u8; 2];
+        bytes.set_bit_range(6..10, 0x10);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_slice_bit_range_out_of_bounds() {
+        let bytes = [0u8; 2];
+        bytes.get_bit_range(8..17);
+    }
+
+    #[test]
+    #[allow(clippy::reversed_empty_ranges)]
+    fn test_try_bit_range_errors() {
+        assert_eq!(
+            0u8.try_get_bit_range(4..9),
+            Err(BitRangeError::OutOfBounds {
+                start: 4,
+                end: 9,
+                bits: 8
+            })
+        );
+        assert_eq!(
+            0u32.try_get_bit_range(6..2),
+            Err(BitRangeError::Reversed { start: 6, end: 2 })
+        );
+        assert_eq!(
+            0u16.try_set_bit_range(0..4, 0x10_u16).copied(),
+            Err(BitRangeError::ValueTooLarge { range_bits: 4 })
+        );
+        assert_eq!(
+            0i8.try_get_bit_range(-1..4),
+            Err(BitRangeError::OutOfBounds {
+                start: u32::MAX,
+                end: 4,
+                bits: 8
+            })
+        );
+    }
+
+    #[test]
+    fn test_try_bit_range_ok() {
+        assert_eq!(0xABCD_u16.try_get_bit_range(4..12), Ok(0xBC));
+        assert_eq!(u64::MAX.try_get_bit_range(..), Ok(u64::MAX));
+        assert_eq!(0_u64.try_set_bit_range(.., u64::MAX).copied(), Ok(u64::MAX));
+        assert_eq!(
+            0xFF_u8.try_set_bit_range(2..6, 0b0110_u8).copied(),
+            Ok(0b11011011)
+        );
+        assert_eq!(0_i8.try_set_bit_range(0..8, -1_i8).copied(), Ok(-1));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_bit_range_panics_on_value_too_large() {
+        0u8.set_bit_range(0..2, 0b100);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_bit_range_panics_out_of_bounds() {
+        0u8.get_bit_range(0..9);
+    }
+
+    #[test]
+    fn test_get_bit_range_signed() {
+        assert_eq!(0b1110_0000_u8.get_bit_range_signed(4..8), -2_i8);
+        assert_eq!(0b0110_0000_u8.get_bit_range_signed(4..8), 6_i8);
+        assert_eq!(0xFFF0_0000_u32.get_bit_range_signed(20..32), -1_i32);
+        assert_eq!(0x0800_u64.get_bit_range_signed(0..12), -2048_i64);
+        assert_eq!(0x0800_u64.get_bit_range_signed(0..13), 2048_i64);
+    }
+
+    #[test]
+    fn test_extract() {
+        const FIELD: u64 = extract::u64::<12, 40>(0x000F_FFFF_FFFF_F123);
+        assert_eq!(FIELD, 0xFF_FFFF_FFFF);
+        assert_eq!(0xABCD_u16.extract::<4, 8>(), 0xBC);
+        assert_eq!(0xABCD_u16.extract::<0, 16>(), 0xABCD);
+        assert_eq!(0xABCD_u16.extract::<3, 0>(), 0);
+        assert_eq!(extract::i16::<8, 8>(0x80FF_u16 as i16), -128);
+    }
+
     #[test]
     fn test_set_bit_same_as_shift_ore() {
         assert_eq!(*0b00000u8.set_bit(0, true), 1 << 0);
@@ -0,0 +1,187 @@
+/*
+  ____                 __               __   _ __
+ / __ \__ _____ ____  / /___ ____ _    / /  (_) /
+/ /_/ / // / _ `/ _ \/ __/ // /  ' \  / /__/ / _ \
+\___\_\_,_/\_,_/_//_/\__/\_,_/_/_/_/ /____/_/_.__/
+    Part of the Quantum OS Project
+
+Copyright 2024 Gavin Kellam
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and
+associated documentation files (the "Software"), to deal in the Software without restriction,
+including without limitation the rights to use, copy, modify, merge, publish, distribute,
+sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial
+portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT
+NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
+OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+use crate::raw;
+use bits::BitSliceManipulation;
+use core::ops::Range;
+
+/// # Bool Array
+/// A fixed size, densely packed, array of bools that does not need an allocator.
+///
+/// Holds `WORDS * 64` bits, and has the same API as `BoolVec` so it can be used
+/// before the heap is ready (and converted into a `BoolVec` once it is).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BoolArray<const WORDS: usize> {
+    words: [u64; WORDS],
+}
+
+impl<const WORDS: usize> BoolArray<WORDS> {
+    /// # New
+    /// Create a new `BoolArray` with all bits set to `false`.
+    pub const fn new() -> Self {
+        Self { words: [0; WORDS] }
+    }
+
+    /// # New Filled
+    /// Create a new `BoolArray` with all bits set to `state`.
+    pub const fn new_filled(state: bool) -> Self {
+        Self {
+            words: [if state { u64::MAX } else { 0 }; WORDS],
+        }
+    }
+
+    /// # Len
+    /// The number of bits in this array.
+    pub const fn len(&self) -> usize {
+        WORDS * raw::WORD_BITS
+    }
+
+    /// # Is Empty
+    /// Check if this array has no bits.
+    pub const fn is_empty(&self) -> bool {
+        WORDS == 0
+    }
+
+    /// # Get
+    /// Get the bit at `index`.
+    pub fn get(&self, index: usize) -> bool {
+        assert!(
+            index < self.len(),
+            "Index '{index}' is out of bounds of BoolArray with len '{}'!",
+            self.len()
+        );
+
+        self.words.get_bit(index)
+    }
+
+    /// # Set
+    /// Set the bit at `index` to `state`.
+    pub fn set(&mut self, index: usize, state: bool) {
+        assert!(
+            index < self.len(),
+            "Index '{index}' is out of bounds of BoolArray with len '{}'!",
+            self.len()
+        );
+
+        self.words.set_bit(index, state);
+    }
+
+    /// # Set Range
+    /// Set every bit in `range` to `state`.
+    pub fn set_range(&mut self, range: Range<usize>, state: bool) {
+        assert!(
+            range.start <= range.end && range.end <= self.len(),
+            "Range '{}..{}' is out of bounds of BoolArray with len '{}'!",
+            range.start,
+            range.end,
+            self.len()
+        );
+
+        raw::set_range(&mut self.words, range, state);
+    }
+
+    /// # Iter
+    /// Iterate over every bit in this array.
+    pub fn iter(&self) -> impl Iterator<Item = bool> + '_ {
+        (0..self.len()).map(|index| self.words.get_bit(index))
+    }
+
+    /// # Count Ones
+    /// The number of bits set to `true`.
+    pub fn count_ones(&self) -> usize {
+        raw::count_ones(&self.words)
+    }
+
+    /// # Count Zeros
+    /// The number of bits set to `false`.
+    pub fn count_zeros(&self) -> usize {
+        self.len() - self.count_ones()
+    }
+
+    /// # Find First Of
+    /// Find the index of the first bit set to `state`.
+    pub fn find_first_of(&self, state: bool) -> Option<usize> {
+        self.find_first_of_from(state, 0)
+    }
+
+    /// # Find First Of From
+    /// Find the index of the first bit set to `state`, starting the search at `start_index`.
+    pub fn find_first_of_from(&self, state: bool, start_index: usize) -> Option<usize> {
+        raw::find_first_of_from(&self.words, self.len(), state, start_index)
+    }
+
+    /// # Find Contiguous
+    /// Find the index of the first run of `run_len` bits all set to `state`, where the
+    /// run starts at an index that is a multiple of `alignment`.
+    pub fn find_contiguous(&self, state: bool, run_len: usize, alignment: usize) -> Option<usize> {
+        raw::find_contiguous(&self.words, self.len(), state, run_len, alignment)
+    }
+
+    /// # As Words
+    /// Get the raw words backing this array.
+    pub const fn as_words(&self) -> &[u64; WORDS] {
+        &self.words
+    }
+}
+
+impl<const WORDS: usize> Default for BoolArray<WORDS> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_const_new() {
+        static ARRAY: BoolArray<2> = BoolArray::new();
+
+        assert_eq!(ARRAY.len(), 128);
+        assert_eq!(ARRAY.count_zeros(), 128);
+        assert_eq!(BoolArray::<2>::new_filled(true).count_ones(), 128);
+    }
+
+    #[test]
+    fn test_same_api_as_vec() {
+        let mut array = BoolArray::<4>::new();
+        array.set_range(10..20, true);
+        array.set(100, true);
+
+        assert!(array.get(10));
+        assert!(!array.get(20));
+        assert_eq!(array.count_ones(), 11);
+        assert_eq!(array.find_first_of_from(true, 20), Some(100));
+        assert_eq!(array.find_contiguous(false, 64, 64), Some(128));
+        assert_eq!(array.iter().filter(|bit| *bit).count(), 11);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_set_out_of_bounds() {
+        BoolArray::<1>::new().set(64, true);
+    }
+}
@@ -0,0 +1,127 @@
+/*
+  ____                 __               __   _ __
+ / __ \__ _____ ____  / /___ ____ _    / /  (_) /
+/ /_/ / // / _ `/ _ \/ __/ // /  ' \  / /__/ / _ \
+\___\_\_,_/\_,_/_//_/\__/\_,_/_/_/_/ /____/_/_.__/
+    Part of the Quantum OS Project
+
+Copyright 2024 Gavin Kellam
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and
+associated documentation files (the "Software"), to deal in the Software without restriction,
+including without limitation the rights to use, copy, modify, merge, publish, distribute,
+sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial
+portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT
+NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
+OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+use core::ops::Range;
+
+/// The number of bits in each word of storage.
+pub const WORD_BITS: usize = u64::BITS as usize;
+
+/// The number of words needed to store `len` bits.
+#[cfg(feature = "alloc")]
+pub const fn words_for(len: usize) -> usize {
+    len.div_ceil(WORD_BITS)
+}
+
+/// A mask of the bits `start..end` in a word.
+const fn word_mask(start: usize, end: usize) -> u64 {
+    if end - start == WORD_BITS {
+        u64::MAX
+    } else {
+        ((1 << (end - start)) - 1) << start
+    }
+}
+
+/// Set the bits in `range` to `state`, one word at a time.
+pub fn set_range(words: &mut [u64], range: Range<usize>, state: bool) {
+    let mut index = range.start;
+
+    while index < range.end {
+        let offset = index % WORD_BITS;
+        let amount = (WORD_BITS - offset).min(range.end - index);
+        let mask = word_mask(offset, offset + amount);
+
+        if state {
+            words[index / WORD_BITS] |= mask;
+        } else {
+            words[index / WORD_BITS] &= !mask;
+        }
+
+        index += amount;
+    }
+}
+
+/// Clear every bit after `len`, so whole-word operations don't see stale bits.
+#[cfg(feature = "alloc")]
+pub fn clear_unused(words: &mut [u64], len: usize) {
+    let end = words.len() * WORD_BITS;
+    set_range(words, len..end, false);
+}
+
+/// Count the bits set in `words`, bits past the end must be cleared.
+pub fn count_ones(words: &[u64]) -> usize {
+    words.iter().map(|word| word.count_ones() as usize).sum()
+}
+
+/// Find the first bit at or after `start` that is `state`.
+pub fn find_first_of_from(words: &[u64], len: usize, state: bool, start: usize) -> Option<usize> {
+    let mut index = start;
+
+    while index < len {
+        let word_index = index / WORD_BITS;
+        let word = if state {
+            words[word_index]
+        } else {
+            !words[word_index]
+        };
+
+        let word = word & (u64::MAX << (index % WORD_BITS));
+        if word != 0 {
+            let found = (word_index * WORD_BITS) + word.trailing_zeros() as usize;
+            return (found < len).then_some(found);
+        }
+
+        index = (word_index + 1) * WORD_BITS;
+    }
+
+    None
+}
+
+/// Find the first run of `run_len` bits that are all `state`, starting at an index
+/// aligned to `alignment`.
+pub fn find_contiguous(
+    words: &[u64],
+    len: usize,
+    state: bool,
+    run_len: usize,
+    alignment: usize,
+) -> Option<usize> {
+    assert!(alignment != 0, "Alignment must be non-zero!");
+
+    let mut search_start = 0;
+    loop {
+        let candidate =
+            find_first_of_from(words, len, state, search_start)?.next_multiple_of(alignment);
+
+        if candidate.checked_add(run_len)? > len {
+            return None;
+        }
+
+        // Only a bit of the opposite state can break this run, so skip right past it
+        match find_first_of_from(words, candidate + run_len, !state, candidate) {
+            Some(break_index) => search_start = break_index + 1,
+            None => return Some(candidate),
+        }
+    }
+}
@@ -0,0 +1,295 @@
+/*
+  ____                 __               __   _ __
+ / __ \__ _____ ____  / /___ ____ _    / /  (_) /
+/ /_/ / // / _ `/ _ \/ __/ // /  ' \  / /__/ / _ \
+\___\_\_,_/\_,_/_//_/\__/\_,_/_/_/_/ /____/_/_.__/
+    Part of the Quantum OS Project
+
+Copyright 2024 Gavin Kellam
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and
+associated documentation files (the "Software"), to deal in the Software without restriction,
+including without limitation the rights to use, copy, modify, merge, publish, distribute,
+sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial
+portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT
+NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
+OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+use crate::{raw, BoolArray};
+use alloc::vec::Vec;
+use bits::BitSliceManipulation;
+use core::ops::Range;
+
+/// # Bool Vec
+/// A growable, densely packed, vector of bools.
+///
+/// Each bool is stored as one bit, 64 to a word.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BoolVec {
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl BoolVec {
+    /// # New
+    /// Create a new empty `BoolVec`.
+    pub const fn new() -> Self {
+        Self {
+            words: Vec::new(),
+            len: 0,
+        }
+    }
+
+    /// # With Len
+    /// Create a new `BoolVec` with `len` bits all set to `state`.
+    pub fn with_len(len: usize, state: bool) -> Self {
+        let mut vec = Self::new();
+        vec.resize(len, state);
+        vec
+    }
+
+    /// # Len
+    /// The number of bits in this vector.
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// # Is Empty
+    /// Check if this vector has no bits.
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// # Resize
+    /// Grow or shrink this vector to `len` bits, new bits are set to `state`.
+    pub fn resize(&mut self, len: usize, state: bool) {
+        let old_len = self.len;
+
+        self.words.resize(raw::words_for(len), 0);
+        self.len = len;
+
+        if len > old_len {
+            raw::set_range(&mut self.words, old_len..len, state);
+        } else {
+            raw::clear_unused(&mut self.words, len);
+        }
+    }
+
+    /// # Push
+    /// Add a bit to the end of this vector.
+    pub fn push(&mut self, state: bool) {
+        self.resize(self.len + 1, state);
+    }
+
+    /// # Get
+    /// Get the bit at `index`.
+    pub fn get(&self, index: usize) -> bool {
+        assert!(
+            index < self.len,
+            "Index '{index}' is out of bounds of BoolVec with len '{}'!",
+            self.len
+        );
+
+        self.words.get_bit(index)
+    }
+
+    /// # Set
+    /// Set the bit at `index` to `state`.
+    pub fn set(&mut self, index: usize, state: bool) {
+        assert!(
+            index < self.len,
+            "Index '{index}' is out of bounds of BoolVec with len '{}'!",
+            self.len
+        );
+
+        self.words.set_bit(index, state);
+    }
+
+    /// # Set Range
+    /// Set every bit in `range` to `state`.
+    pub fn set_range(&mut self, range: Range<usize>, state: bool) {
+        assert!(
+            range.start <= range.end && range.end <= self.len,
+            "Range '{}..{}' is out of bounds of BoolVec with len '{}'!",
+            range.start,
+            range.end,
+            self.len
+        );
+
+        raw::set_range(&mut self.words, range, state);
+    }
+
+    /// # Iter
+    /// Iterate over every bit in this vector.
+    pub fn iter(&self) -> impl Iterator<Item = bool> + '_ {
+        (0..self.len).map(|index| self.words.get_bit(index))
+    }
+
+    /// # Count Ones
+    /// The number of bits set to `true`.
+    pub fn count_ones(&self) -> usize {
+        raw::count_ones(&self.words)
+    }
+
+    /// # Count Zeros
+    /// The number of bits set to `false`.
+    pub fn count_zeros(&self) -> usize {
+        self.len - self.count_ones()
+    }
+
+    /// # Find First Of
+    /// Find the index of the first bit set to `state`.
+    pub fn find_first_of(&self, state: bool) -> Option<usize> {
+        self.find_first_of_from(state, 0)
+    }
+
+    /// # Find First Of From
+    /// Find the index of the first bit set to `state`, starting the search at `start_index`.
+    pub fn find_first_of_from(&self, state: bool, start_index: usize) -> Option<usize> {
+        raw::find_first_of_from(&self.words, self.len, state, start_index)
+    }
+
+    /// # Find Contiguous
+    /// Find the index of the first run of `run_len` bits all set to `state`, where the
+    /// run starts at an index that is a multiple of `alignment`.
+    pub fn find_contiguous(&self, state: bool, run_len: usize, alignment: usize) -> Option<usize> {
+        raw::find_contiguous(&self.words, self.len, state, run_len, alignment)
+    }
+}
+
+impl<const WORDS: usize> From<BoolArray<WORDS>> for BoolVec {
+    fn from(value: BoolArray<WORDS>) -> Self {
+        Self {
+            words: value.as_words().to_vec(),
+            len: value.len(),
+        }
+    }
+}
+
+impl FromIterator<bool> for BoolVec {
+    fn from_iter<T: IntoIterator<Item = bool>>(iter: T) -> Self {
+        let mut vec = Self::new();
+        iter.into_iter().for_each(|state| vec.push(state));
+        vec
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn test_push_and_get() {
+        let vec: BoolVec = [true, false, true, true].into_iter().collect();
+
+        assert_eq!(vec.len(), 4);
+        assert_eq!(
+            vec.iter().collect::<Vec<_>>(),
+            vec![true, false, true, true]
+        );
+    }
+
+    #[test]
+    fn test_count() {
+        let mut vec = BoolVec::with_len(130, true);
+        vec.set(5, false);
+        vec.set(129, false);
+
+        assert_eq!(vec.count_ones(), 128);
+        assert_eq!(vec.count_zeros(), 2);
+    }
+
+    #[test]
+    fn test_shrink_clears_bits() {
+        let mut vec = BoolVec::with_len(100, true);
+        vec.resize(10, true);
+        vec.resize(100, false);
+
+        assert_eq!(vec.count_ones(), 10);
+    }
+
+    #[test]
+    fn test_set_range() {
+        let mut vec = BoolVec::with_len(200, false);
+        vec.set_range(60..140, true);
+
+        assert_eq!(vec.count_ones(), 80);
+        assert!(!vec.get(59));
+        assert!(vec.get(60));
+        assert!(vec.get(139));
+        assert!(!vec.get(140));
+
+        vec.set_range(64..128, false);
+        assert_eq!(vec.count_ones(), 16);
+    }
+
+    #[test]
+    fn test_find_first_of_from() {
+        let mut vec = BoolVec::with_len(300, false);
+        vec.set(3, true);
+        vec.set(250, true);
+
+        assert_eq!(vec.find_first_of(true), Some(3));
+        assert_eq!(vec.find_first_of_from(true, 4), Some(250));
+        assert_eq!(vec.find_first_of_from(true, 251), None);
+        assert_eq!(vec.find_first_of_from(false, 3), Some(4));
+
+        vec.set_range(0..300, true);
+        assert_eq!(vec.find_first_of(false), None);
+    }
+
+    #[test]
+    fn test_find_contiguous() {
+        let mut vec = BoolVec::with_len(256, true);
+        vec.set_range(10..20, false);
+        vec.set_range(40..200, false);
+
+        assert_eq!(vec.find_contiguous(false, 10, 1), Some(10));
+        assert_eq!(vec.find_contiguous(false, 11, 1), Some(40));
+        assert_eq!(vec.find_contiguous(false, 8, 16), Some(48));
+        assert_eq!(vec.find_contiguous(false, 64, 64), Some(64));
+        assert_eq!(vec.find_contiguous(false, 128, 64), Some(64));
+        assert_eq!(vec.find_contiguous(false, 160, 8), Some(40));
+        assert_eq!(vec.find_contiguous(false, 161, 1), None);
+        assert_eq!(vec.find_contiguous(true, 56, 1), Some(200));
+        assert_eq!(vec.find_contiguous(true, 57, 1), None);
+    }
+
+    #[test]
+    fn test_find_contiguous_at_end() {
+        let mut vec = BoolVec::with_len(70, false);
+        vec.set_range(66..70, true);
+
+        assert_eq!(vec.find_contiguous(true, 4, 2), Some(66));
+        assert_eq!(vec.find_contiguous(true, 4, 4), None);
+        assert_eq!(vec.find_contiguous(true, 0, 1), Some(66));
+    }
+
+    #[test]
+    fn test_from_bool_array() {
+        let mut array = BoolArray::<2>::new();
+        array.set_range(60..70, true);
+
+        let mut vec = BoolVec::from(array);
+        assert_eq!(vec.len(), 128);
+        assert_eq!(vec.count_ones(), 10);
+
+        vec.push(true);
+        assert_eq!(vec.find_first_of_from(true, 70), Some(128));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_get_out_of_bounds() {
+        BoolVec::with_len(10, false).get(10);
+    }
+}
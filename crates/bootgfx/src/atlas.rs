@@ -0,0 +1,220 @@
+/*
+  ____                 __               __   _ __
+ / __ \__ _____ ____  / /___ ____ _    / /  (_) /
+/ /_/ / // / _ `/ _ \/ __/ // /  ' \  / /__/ / _ \
+\___\_\_,_/\_,_/_//_/\__/\_,_/_/_/_/ /____/_/_.__/
+    Part of the Quantum OS Project
+
+Copyright 2024 Gavin Kellam
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and
+associated documentation files (the "Software"), to deal in the Software without restriction,
+including without limitation the rights to use, copy, modify, merge, publish, distribute,
+sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial
+portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT
+NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
+OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+use crate::Color;
+use binfont::BinFont;
+
+const FIRST_CHAR: u32 = 32;
+const GLYPHS: usize = 96;
+
+#[derive(Clone, Copy)]
+struct AtlasSlot {
+    foreground: u32,
+    background: u32,
+    /// One bit per glyph that has been rendered in these colors.
+    rendered: u128,
+    last_used: u64,
+    cells: [[Color; GlyphAtlas::CELL_PIXELS]; GLYPHS],
+}
+
+impl AtlasSlot {
+    const fn empty() -> Self {
+        Self {
+            foreground: 0,
+            background: 0,
+            rendered: 0,
+            last_used: 0,
+            cells: [[Color(0); GlyphAtlas::CELL_PIXELS]; GLYPHS],
+        }
+    }
+}
+
+/// # Glyph Atlas
+/// A cache of pre-rendered glyph cells, so drawing text becomes a copy of each
+/// row instead of testing every pixel of the font.
+///
+/// Glyphs are rendered the first time they are used with a pair of colors,
+/// and a few color pairs are kept at once so switching colors doesn't throw
+/// the whole cache away.
+///
+/// # Note
+/// This is around 100KiB, so it should be put in a `static`.
+pub struct GlyphAtlas {
+    slots: [AtlasSlot; Self::SLOTS],
+    clock: u64,
+}
+
+impl GlyphAtlas {
+    /// The width of a rendered cell in pixels.
+    pub const CELL_WIDTH: usize = BinFont::WIDTH;
+    /// The height of a rendered cell in pixels, rows below the glyph are
+    /// filled with the background color.
+    pub const CELL_HEIGHT: usize = 16;
+    pub const CELL_PIXELS: usize = Self::CELL_WIDTH * Self::CELL_HEIGHT;
+
+    /// The number of color pairs that are cached at once.
+    pub const SLOTS: usize = 2;
+
+    pub const fn new() -> Self {
+        Self {
+            slots: [AtlasSlot::empty(); Self::SLOTS],
+            clock: 0,
+        }
+    }
+
+    /// # Cell
+    /// Get the rendered cell for `c`, or `None` if the font doesn't have it.
+    pub fn cell(
+        &mut self,
+        c: char,
+        foreground: Color,
+        background: Color,
+    ) -> Option<&[Color; Self::CELL_PIXELS]> {
+        let glyph = BinFont::get_glyph(c)?;
+        let index = (c as u32 - FIRST_CHAR) as usize;
+        self.clock += 1;
+
+        let slot_index = self
+            .slots
+            .iter()
+            .position(|slot| {
+                slot.rendered != 0
+                    && slot.foreground == foreground.0
+                    && slot.background == background.0
+            })
+            .unwrap_or_else(|| {
+                // Evict the least recently used colors
+                let lru = self
+                    .slots
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|(_, slot)| slot.last_used)
+                    .map(|(index, _)| index)
+                    .unwrap_or(0);
+
+                let slot = &mut self.slots[lru];
+                slot.foreground = foreground.0;
+                slot.background = background.0;
+                slot.rendered = 0;
+
+                lru
+            });
+
+        let slot = &mut self.slots[slot_index];
+        slot.last_used = self.clock;
+
+        if slot.rendered & (1 << index) == 0 {
+            Self::render(&mut slot.cells[index], glyph, foreground, background);
+            slot.rendered |= 1 << index;
+        }
+
+        Some(&slot.cells[index])
+    }
+
+    fn render(
+        cell: &mut [Color; Self::CELL_PIXELS],
+        glyph: &[u8; BinFont::HEIGHT],
+        foreground: Color,
+        background: Color,
+    ) {
+        cell.fill(background);
+
+        // The font is stored bottom row first
+        for (row, bits) in glyph.iter().rev().enumerate() {
+            for bit in 0..Self::CELL_WIDTH {
+                if (bits >> (7 - bit)) & 1 != 0 {
+                    cell[row * Self::CELL_WIDTH + bit] = foreground;
+                }
+            }
+        }
+    }
+}
+
+impl Default for GlyphAtlas {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate std;
+
+    use super::*;
+    use crate::Framebuffer;
+    use std::{boxed::Box, vec};
+
+    #[test]
+    fn test_cell_matches_draw_glyph() {
+        let mut atlas = Box::new(GlyphAtlas::new());
+        let mut buffer = vec![0u32; GlyphAtlas::CELL_PIXELS];
+        let mut framebuffer = unsafe {
+            Framebuffer::new_linear(
+                buffer.as_mut_ptr(),
+                32,
+                GlyphAtlas::CELL_HEIGHT,
+                GlyphAtlas::CELL_WIDTH,
+            )
+        };
+
+        framebuffer.draw_rec(0, 0, 8, 16, Color(1));
+        framebuffer.draw_glyph(0, 0, 'Q', Color(2));
+
+        let cell = atlas.cell('Q', Color(2), Color(1)).unwrap();
+        assert!(cell.iter().zip(buffer.iter()).all(|(a, &b)| a.0 == b));
+    }
+
+    #[test]
+    fn test_missing_glyph() {
+        let mut atlas = Box::new(GlyphAtlas::new());
+
+        assert!(atlas.cell('é', Color(2), Color(1)).is_none());
+        assert!(atlas.cell('\n', Color(2), Color(1)).is_none());
+    }
+
+    #[test]
+    fn test_color_slots() {
+        let mut atlas = Box::new(GlyphAtlas::new());
+
+        atlas.cell('a', Color(1), Color(0));
+        atlas.cell('a', Color(2), Color(0));
+        atlas.cell('b', Color(1), Color(0));
+
+        // Color 2 is the least recently used, so color 3 replaces it
+        atlas.cell('a', Color(3), Color(0));
+        assert!(atlas.slots.iter().any(|slot| slot.foreground == 1));
+        assert!(atlas.slots.iter().any(|slot| slot.foreground == 3));
+        assert_eq!(
+            atlas
+                .slots
+                .iter()
+                .find(|s| s.foreground == 1)
+                .unwrap()
+                .rendered
+                .count_ones(),
+            2
+        );
+    }
+}
@@ -0,0 +1,344 @@
+/*
+  ____                 __               __   _ __
+ / __ \__ _____ ____  / /___ ____ _    / /  (_) /
+/ /_/ / // / _ `/ _ \/ __/ // /  ' \  / /__/ / _ \
+\___\_\_,_/\_,_/_//_/\__/\_,_/_/_/_/ /____/_/_.__/
+    Part of the Quantum OS Project
+
+Copyright 2024 Gavin Kellam
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and
+associated documentation files (the "Software"), to deal in the Software without restriction,
+including without limitation the rights to use, copy, modify, merge, publish, distribute,
+sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial
+portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT
+NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
+OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+use crate::Framebuffer;
+use arch::io::IOPort;
+
+/// # Dispi Error
+/// Errors from the Bochs/QEMU DISPI interface.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DispiError {
+    /// No DISPI compatible display was found.
+    NotPresent,
+    /// The display can't do the requested mode.
+    UnsupportedMode,
+}
+
+/// # Dispi Mode
+/// A video mode of the DISPI display.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DispiMode {
+    pub width: u16,
+    pub height: u16,
+    pub bits_per_pixel: u16,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u16)]
+enum DispiReg {
+    Id = 0x0,
+    XRes = 0x1,
+    YRes = 0x2,
+    Bpp = 0x3,
+    Enable = 0x4,
+    VirtWidth = 0x6,
+    VirtHeight = 0x7,
+    XOffset = 0x8,
+    YOffset = 0x9,
+    VideoMemory64K = 0xA,
+}
+
+#[derive(Clone, Copy)]
+enum DispiAccess {
+    IoPort,
+    Mmio(*mut u16),
+}
+
+/// # Dispi
+/// The Bochs VBE extensions ("DISPI") used by QEMU's std-vga and
+/// bochs-display devices. Modes can be set at any time without going
+/// through the BIOS.
+pub struct Dispi {
+    access: DispiAccess,
+}
+
+impl Dispi {
+    const INDEX_PORT: IOPort = IOPort::new(0x1CE);
+    const DATA_PORT: IOPort = IOPort::new(0x1CF);
+
+    /// Offset of the DISPI registers into the MMIO BAR.
+    pub const MMIO_OFFSET: usize = 0x500;
+    /// Where QEMU places the linear framebuffer when the BAR hasn't been moved.
+    pub const DEFAULT_LFB_ADDRESS: u64 = 0xE000_0000;
+
+    const ID_MIN: u16 = 0xB0C0;
+    const ID_MAX: u16 = 0xB0C5;
+
+    const ENABLED: u16 = 0x01;
+    const GET_CAPS: u16 = 0x02;
+    const LFB_ENABLED: u16 = 0x40;
+    const NO_CLEAR_MEM: u16 = 0x80;
+
+    /// # Io Port
+    /// Access the DISPI registers through IO ports `0x1CE`/`0x1CF`.
+    ///
+    /// # Safety
+    /// Must be able to access IO ports, and nothing else may be using the
+    /// DISPI registers.
+    pub unsafe fn io_port() -> Result<Self, DispiError> {
+        Self {
+            access: DispiAccess::IoPort,
+        }
+        .probe()
+    }
+
+    /// # Mmio
+    /// Access the DISPI registers through the device's MMIO BAR (BAR 2),
+    /// `bar` is where that BAR is mapped.
+    ///
+    /// # Safety
+    /// `bar` must be the mapped MMIO BAR of a bochs-display or std-vga device.
+    pub unsafe fn mmio(bar: *mut u8) -> Result<Self, DispiError> {
+        Self {
+            access: DispiAccess::Mmio(bar.add(Self::MMIO_OFFSET).cast()),
+        }
+        .probe()
+    }
+
+    fn probe(self) -> Result<Self, DispiError> {
+        match self.version() {
+            Self::ID_MIN..=Self::ID_MAX => Ok(self),
+            _ => Err(DispiError::NotPresent),
+        }
+    }
+
+    fn read(&self, reg: DispiReg) -> u16 {
+        match self.access {
+            DispiAccess::IoPort => unsafe {
+                Self::INDEX_PORT.write_word(reg as u16);
+                Self::DATA_PORT.read_word()
+            },
+            DispiAccess::Mmio(regs) => unsafe { core::ptr::read_volatile(regs.add(reg as usize)) },
+        }
+    }
+
+    fn write(&mut self, reg: DispiReg, value: u16) {
+        match self.access {
+            DispiAccess::IoPort => unsafe {
+                Self::INDEX_PORT.write_word(reg as u16);
+                Self::DATA_PORT.write_word(value);
+            },
+            DispiAccess::Mmio(regs) => unsafe {
+                core::ptr::write_volatile(regs.add(reg as usize), value)
+            },
+        }
+    }
+
+    /// # Version
+    /// The DISPI interface version (`0xB0C0` to `0xB0C5`).
+    pub fn version(&self) -> u16 {
+        self.read(DispiReg::Id)
+    }
+
+    /// # Video Memory
+    /// The amount of video memory in bytes.
+    pub fn video_memory(&self) -> usize {
+        self.read(DispiReg::VideoMemory64K) as usize * 64 * 1024
+    }
+
+    /// # Max Mode
+    /// The largest resolution and color depth the display supports.
+    pub fn max_mode(&mut self) -> DispiMode {
+        let enable = self.read(DispiReg::Enable);
+        self.write(DispiReg::Enable, enable | Self::GET_CAPS);
+
+        let max = DispiMode {
+            width: self.read(DispiReg::XRes),
+            height: self.read(DispiReg::YRes),
+            bits_per_pixel: self.read(DispiReg::Bpp),
+        };
+
+        self.write(DispiReg::Enable, enable);
+        max
+    }
+
+    /// # Mode
+    /// The current video mode, or `None` if the display is disabled.
+    pub fn mode(&self) -> Option<DispiMode> {
+        if self.read(DispiReg::Enable) & Self::ENABLED == 0 {
+            return None;
+        }
+
+        Some(DispiMode {
+            width: self.read(DispiReg::XRes),
+            height: self.read(DispiReg::YRes),
+            bits_per_pixel: self.read(DispiReg::Bpp),
+        })
+    }
+
+    /// # Set Mode
+    /// Switch to a new video mode with the linear framebuffer enabled.
+    pub fn set_mode(&mut self, mode: DispiMode, clear: bool) -> Result<(), DispiError> {
+        let max = self.max_mode();
+        let bytes = mode.width as usize * mode.height as usize * mode.bits_per_pixel as usize / 8;
+
+        if mode.width == 0
+            || mode.height == 0
+            || mode.width > max.width
+            || mode.height > max.height
+            || !matches!(mode.bits_per_pixel, 8 | 15 | 16 | 24 | 32)
+            || mode.bits_per_pixel > max.bits_per_pixel
+            || bytes > self.video_memory()
+        {
+            return Err(DispiError::UnsupportedMode);
+        }
+
+        let mut enable = Self::ENABLED | Self::LFB_ENABLED;
+        if !clear {
+            enable |= Self::NO_CLEAR_MEM;
+        }
+
+        // The display must be disabled while changing the mode
+        self.write(DispiReg::Enable, 0);
+        self.write(DispiReg::XRes, mode.width);
+        self.write(DispiReg::YRes, mode.height);
+        self.write(DispiReg::Bpp, mode.bits_per_pixel);
+        self.write(DispiReg::VirtWidth, mode.width);
+        self.write(DispiReg::XOffset, 0);
+        self.write(DispiReg::YOffset, 0);
+        self.write(DispiReg::Enable, enable);
+
+        Ok(())
+    }
+
+    /// # Virtual Height
+    /// The number of rows in the framebuffer, which can be larger than the
+    /// visible height when there is enough video memory.
+    pub fn virtual_height(&self) -> u16 {
+        self.read(DispiReg::VirtHeight)
+    }
+
+    /// # Set Display Offset
+    /// Change which part of the virtual framebuffer is shown, used for
+    /// scrolling and page flipping.
+    pub fn set_display_offset(&mut self, x: u16, y: u16) {
+        self.write(DispiReg::XOffset, x);
+        self.write(DispiReg::YOffset, y);
+    }
+
+    /// # Framebuffer
+    /// Make a [`Framebuffer`] for the current 32-bit mode, `lfb` is where
+    /// the linear framebuffer is mapped.
+    ///
+    /// # Safety
+    /// `lfb` must point to the mapped linear framebuffer (BAR 0).
+    pub unsafe fn framebuffer(&self, lfb: *mut u32) -> Option<Framebuffer> {
+        let mode = self.mode()?;
+
+        if mode.bits_per_pixel as usize != Framebuffer::ALLOWED_BPP {
+            return None;
+        }
+
+        Some(Framebuffer::new_linear(
+            lfb,
+            mode.bits_per_pixel as u8,
+            mode.height as usize,
+            mode.width as usize,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Fake MMIO BAR where registers read back what was last written.
+    fn fake_bar() -> [u16; 0x300] {
+        let mut bar = [0u16; 0x300];
+        let regs = &mut bar[Dispi::MMIO_OFFSET / 2..];
+
+        regs[DispiReg::Id as usize] = 0xB0C5;
+        regs[DispiReg::XRes as usize] = 1024;
+        regs[DispiReg::YRes as usize] = 768;
+        regs[DispiReg::Bpp as usize] = 32;
+        regs[DispiReg::VideoMemory64K as usize] = 256;
+
+        bar
+    }
+
+    #[test]
+    fn test_probe() {
+        let mut bar = fake_bar();
+        let dispi = unsafe { Dispi::mmio(bar.as_mut_ptr().cast()) }.unwrap();
+        assert_eq!(dispi.version(), 0xB0C5);
+        assert_eq!(dispi.video_memory(), 16 * 1024 * 1024);
+
+        let mut empty = [0u16; 0x300];
+        assert_eq!(
+            unsafe { Dispi::mmio(empty.as_mut_ptr().cast()) }.err(),
+            Some(DispiError::NotPresent)
+        );
+    }
+
+    #[test]
+    fn test_set_mode() {
+        let mut bar = fake_bar();
+        let mut dispi = unsafe { Dispi::mmio(bar.as_mut_ptr().cast()) }.unwrap();
+
+        assert_eq!(dispi.mode(), None);
+
+        let mode = DispiMode {
+            width: 800,
+            height: 600,
+            bits_per_pixel: 32,
+        };
+        assert_eq!(dispi.set_mode(mode, false), Ok(()));
+        assert_eq!(dispi.mode(), Some(mode));
+        assert_eq!(
+            dispi.read(DispiReg::Enable),
+            Dispi::ENABLED | Dispi::LFB_ENABLED | Dispi::NO_CLEAR_MEM
+        );
+    }
+
+    #[test]
+    fn test_unsupported_mode() {
+        let mut bar = fake_bar();
+        let mut dispi = unsafe { Dispi::mmio(bar.as_mut_ptr().cast()) }.unwrap();
+
+        assert_eq!(
+            dispi.set_mode(
+                DispiMode {
+                    width: 1920,
+                    height: 1080,
+                    bits_per_pixel: 32,
+                },
+                true
+            ),
+            Err(DispiError::UnsupportedMode)
+        );
+        assert_eq!(
+            dispi.set_mode(
+                DispiMode {
+                    width: 640,
+                    height: 480,
+                    bits_per_pixel: 12,
+                },
+                true
+            ),
+            Err(DispiError::UnsupportedMode)
+        );
+        assert_eq!(dispi.mode(), None);
+    }
+}
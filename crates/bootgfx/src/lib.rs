@@ -29,6 +29,8 @@ use core::ptr::write_volatile;
 
 use binfont::BinFont;
 
+pub mod atlas;
+pub mod dispi;
 pub mod terminal;
 
 /// # Color
@@ -39,6 +41,38 @@ pub struct Color(pub u32);
 impl Color {
     pub const WHITE: Self = Self(0xFFFFFFFF);
     pub const QUANTUM_BACKGROUND: Self = Self(0xFF121212);
+
+    /// # From RGB
+    /// Make an opaque color from its red, green, and blue parts (0-255).
+    pub const fn from_rgb(red: u32, green: u32, blue: u32) -> Self {
+        Self(0xFF000000 | ((red & 0xFF) << 16) | ((green & 0xFF) << 8) | (blue & 0xFF))
+    }
+}
+
+/// # Dirty Rect
+/// The area of the framebuffer that has changed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DirtyRect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl DirtyRect {
+    /// # Union
+    /// The smallest rect that covers both `self` and `other`.
+    pub fn union(self, other: Self) -> Self {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+
+        Self {
+            x,
+            y,
+            width: (self.x + self.width).max(other.x + other.width) - x,
+            height: (self.y + self.height).max(other.y + other.height) - y,
+        }
+    }
 }
 
 /// # Framebuffer
@@ -47,6 +81,7 @@ pub struct Framebuffer {
     buffer: *mut Color,
     height: usize,
     width: usize,
+    dirty: Option<DirtyRect>,
 }
 
 impl Framebuffer {
@@ -70,36 +105,87 @@ impl Framebuffer {
             buffer: buffer.cast(),
             height,
             width,
+            dirty: None,
         }
     }
 
-    /// # Draw Pixel
-    /// Draw a pixel of a color onto the framebuffer.
-    pub fn draw_pixel(&mut self, x: usize, y: usize, color: Color) {
-        if x > self.width || y > self.height {
-            return;
+    /// Add the visible part of this area to the dirty rect, returning the
+    /// visible part (if any).
+    fn mark_dirty(&mut self, x: usize, y: usize, width: usize, height: usize) -> Option<DirtyRect> {
+        if x >= self.width || y >= self.height || width == 0 || height == 0 {
+            return None;
         }
 
-        let verticality_to_linearity = y * self.width;
+        let rect = DirtyRect {
+            x,
+            y,
+            width: width.min(self.width - x),
+            height: height.min(self.height - y),
+        };
+
+        self.dirty = Some(match self.dirty {
+            Some(dirty) => dirty.union(rect),
+            None => rect,
+        });
+
+        Some(rect)
+    }
+
+    /// # Take Dirty
+    /// Get the area that has been drawn to since the last call, and reset it.
+    pub fn take_dirty(&mut self) -> Option<DirtyRect> {
+        self.dirty.take()
+    }
+
+    /// Write a pixel that is known to be on screen.
+    fn write_pixel(&mut self, x: usize, y: usize, color: Color) {
         unsafe {
-            write_volatile(self.buffer.add(verticality_to_linearity + x), color);
+            write_volatile(self.buffer.add(y * self.width + x), color);
         };
     }
 
+    /// # Draw Pixel
+    /// Draw a pixel of a color onto the framebuffer.
+    pub fn draw_pixel(&mut self, x: usize, y: usize, color: Color) {
+        if self.mark_dirty(x, y, 1, 1).is_some() {
+            self.write_pixel(x, y, color);
+        }
+    }
+
     /// # Draw Rectangle
     /// Draw a rectangle of a color onto the framebuffer.
     pub fn draw_rec(&mut self, x: usize, y: usize, length: usize, height: usize, color: Color) {
-        // TODO: Use memory functions to speed this up. However, this may never
-        //       be used so I don't want to optimize it until it gets used out-
-        //       side the bootloader.
+        let Some(rect) = self.mark_dirty(x, y, length, height) else {
+            return;
+        };
 
-        for y in y..(y + height) {
-            for x in x..(x + length) {
-                self.draw_pixel(x, y, color);
+        for y in rect.y..(rect.y + rect.height) {
+            for x in rect.x..(rect.x + rect.width) {
+                self.write_pixel(x, y, color);
             }
         }
     }
 
+    /// # Blit
+    /// Copy `pixels`, an image `width` pixels wide, onto the framebuffer one
+    /// row at a time.
+    pub fn blit(&mut self, x: usize, y: usize, width: usize, pixels: &[Color]) {
+        let height = pixels.len() / width.max(1);
+        let Some(rect) = self.mark_dirty(x, y, width, height) else {
+            return;
+        };
+
+        for (row, row_pixels) in pixels.chunks_exact(width).take(rect.height).enumerate() {
+            unsafe {
+                core::ptr::copy_nonoverlapping(
+                    row_pixels.as_ptr(),
+                    self.buffer.add((rect.y + row) * self.width + rect.x),
+                    rect.width,
+                )
+            };
+        }
+    }
+
     /// # Draw Glyph
     /// Draw a glyph at some position on the screen.
     pub fn draw_glyph(&mut self, x: usize, y: usize, c: char, color: Color) {
@@ -107,15 +193,38 @@ impl Framebuffer {
             return;
         };
 
+        let Some(rect) = self.mark_dirty(x, y, BinFont::WIDTH, BinFont::HEIGHT) else {
+            return;
+        };
+
         for (y_offset, y_char) in glyph.iter().copied().rev().enumerate() {
             for bit in 0..8 {
-                if (y_char >> (7 - bit)) & 1 != 0 {
-                    self.draw_pixel(x + bit, y + y_offset, color);
+                if (y_char >> (7 - bit)) & 1 != 0 && bit < rect.width && y_offset < rect.height {
+                    self.write_pixel(x + bit, y + y_offset, color);
                 }
             }
         }
     }
 
+    /// # Scroll Up
+    /// Move the entire framebuffer up by `pixels` rows, filling the newly
+    /// exposed rows at the bottom with `fill`.
+    pub fn scroll_up(&mut self, pixels: usize, fill: Color) {
+        let pixels = pixels.min(self.height);
+        let moved_rows = self.height - pixels;
+
+        unsafe {
+            core::ptr::copy(
+                self.buffer.add(pixels * self.width),
+                self.buffer,
+                moved_rows * self.width,
+            )
+        };
+
+        self.draw_rec(0, moved_rows, self.width, pixels, fill);
+        self.mark_dirty(0, 0, self.width, self.height);
+    }
+
     /// # Height
     /// Get the height of the framebuffer.
     pub const fn height(&self) -> usize {
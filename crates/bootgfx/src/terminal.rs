@@ -22,3 +22,912 @@ NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FO
 DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
 OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
 */
+
+use crate::{atlas::GlyphAtlas, Color, Framebuffer};
+use binfont::BinFont;
+use core::fmt::Write;
+
+/// The max number of parameters a single CSI sequence can carry, extra
+/// parameters are ignored.
+pub const MAX_CSI_PARAMS: usize = 16;
+
+/// # Csi Params
+/// The numeric parameters of a CSI sequence (`ESC [ 1 ; 2 m`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CsiParams {
+    values: [u16; MAX_CSI_PARAMS],
+    len: usize,
+}
+
+impl CsiParams {
+    const fn new() -> Self {
+        Self {
+            values: [0; MAX_CSI_PARAMS],
+            len: 0,
+        }
+    }
+
+    /// # Get
+    /// Get the param at `index`, or `default` if its missing or zero.
+    pub fn get(&self, index: usize, default: u16) -> u16 {
+        match self.values[..self.len].get(index) {
+            Some(&value) if value != 0 => value,
+            _ => default,
+        }
+    }
+
+    /// # Len
+    /// The number of params in this sequence.
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// # Is Empty
+    /// Check if this sequence had no params.
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// # Iter
+    /// Iterate over all the params in this sequence.
+    pub fn iter(&self) -> impl Iterator<Item = u16> + '_ {
+        self.values[..self.len].iter().copied()
+    }
+}
+
+/// # Erase Mode
+/// Which part of the display or line an erase sequence should clear.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EraseMode {
+    /// From the cursor to the end.
+    ToEnd,
+    /// From the start to the cursor.
+    ToStart,
+    /// Everything.
+    All,
+}
+
+impl EraseMode {
+    fn from_param(param: u16) -> Option<Self> {
+        match param {
+            0 => Some(Self::ToEnd),
+            1 => Some(Self::ToStart),
+            2 | 3 => Some(Self::All),
+            _ => None,
+        }
+    }
+}
+
+/// # Ansi Action
+/// What the terminal should do after being fed a char.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnsiAction {
+    /// Draw this char at the cursor.
+    Print(char),
+    /// A C0 control char like `\n`, `\r`, `\t` or backspace.
+    Execute(char),
+    CursorUp(u16),
+    CursorDown(u16),
+    CursorForward(u16),
+    CursorBack(u16),
+    /// Move the cursor to a zero-based row and column.
+    CursorPosition {
+        row: u16,
+        col: u16,
+    },
+    EraseDisplay(EraseMode),
+    EraseLine(EraseMode),
+    /// Select Graphic Rendition (colors and attributes).
+    SelectGraphics(CsiParams),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ParserState {
+    Ground,
+    Escape,
+    Csi,
+    /// A sequence we don't understand, eat chars until its final byte.
+    CsiIgnore,
+}
+
+/// # Ansi Parser
+/// A state machine that turns a stream of chars into [`AnsiAction`]s.
+///
+/// Only the CSI sequences a simple console needs are understood, every other
+/// escape sequence is consumed and dropped so it never ends up on screen.
+#[derive(Debug, Clone, Copy)]
+pub struct AnsiParser {
+    state: ParserState,
+    params: CsiParams,
+    current: Option<u16>,
+}
+
+impl AnsiParser {
+    pub const fn new() -> Self {
+        Self {
+            state: ParserState::Ground,
+            params: CsiParams::new(),
+            current: None,
+        }
+    }
+
+    /// # Advance
+    /// Feed the next char into the parser.
+    pub fn advance(&mut self, c: char) -> Option<AnsiAction> {
+        match self.state {
+            ParserState::Ground => match c {
+                '\x1b' => {
+                    self.state = ParserState::Escape;
+                    None
+                }
+                c if c.is_control() => Some(AnsiAction::Execute(c)),
+                c => Some(AnsiAction::Print(c)),
+            },
+            ParserState::Escape => {
+                match c {
+                    '[' => {
+                        self.params = CsiParams::new();
+                        self.current = None;
+                        self.state = ParserState::Csi;
+                    }
+                    // Intermediate bytes (like `ESC ( B`), wait for the final byte
+                    '\x20'..='\x2f' => (),
+                    // Other escape sequences are not supported
+                    _ => self.state = ParserState::Ground,
+                }
+
+                None
+            }
+            ParserState::Csi => self.csi(c),
+            ParserState::CsiIgnore => {
+                if Self::is_final(c) {
+                    self.state = ParserState::Ground;
+                }
+
+                None
+            }
+        }
+    }
+
+    const fn is_final(c: char) -> bool {
+        matches!(c, '\x40'..='\x7e')
+    }
+
+    fn push_param(&mut self) {
+        if self.params.len < MAX_CSI_PARAMS {
+            self.params.values[self.params.len] = self.current.unwrap_or(0);
+            self.params.len += 1;
+        }
+
+        self.current = None;
+    }
+
+    fn csi(&mut self, c: char) -> Option<AnsiAction> {
+        match c {
+            '0'..='9' => {
+                let digit = c as u16 - '0' as u16;
+                self.current = Some(
+                    self.current
+                        .unwrap_or(0)
+                        .saturating_mul(10)
+                        .saturating_add(digit),
+                );
+
+                None
+            }
+            ';' => {
+                self.push_param();
+                None
+            }
+            // Cancel the sequence
+            '\x18' | '\x1a' => {
+                self.state = ParserState::Ground;
+                None
+            }
+            '\x1b' => {
+                self.state = ParserState::Escape;
+                None
+            }
+            c if Self::is_final(c) => {
+                if self.current.is_some() || !self.params.is_empty() {
+                    self.push_param();
+                }
+
+                self.state = ParserState::Ground;
+                self.dispatch(c)
+            }
+            // Private markers ('?') and intermediate bytes, we don't support any
+            // of these sequences.
+            _ => {
+                self.state = ParserState::CsiIgnore;
+                None
+            }
+        }
+    }
+
+    fn dispatch(&self, c: char) -> Option<AnsiAction> {
+        let params = &self.params;
+
+        match c {
+            'A' => Some(AnsiAction::CursorUp(params.get(0, 1))),
+            'B' => Some(AnsiAction::CursorDown(params.get(0, 1))),
+            'C' => Some(AnsiAction::CursorForward(params.get(0, 1))),
+            'D' => Some(AnsiAction::CursorBack(params.get(0, 1))),
+            'H' | 'f' => Some(AnsiAction::CursorPosition {
+                row: params.get(0, 1) - 1,
+                col: params.get(1, 1) - 1,
+            }),
+            'J' => EraseMode::from_param(params.get(0, 0)).map(AnsiAction::EraseDisplay),
+            'K' => EraseMode::from_param(params.get(0, 0)).map(AnsiAction::EraseLine),
+            'm' => Some(AnsiAction::SelectGraphics(*params)),
+            _ => None,
+        }
+    }
+}
+
+impl Default for AnsiParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// # Utf8 Decoder
+/// Decodes a stream of bytes into chars one byte at a time, so input that is
+/// split across writes still decodes correctly.
+///
+/// Invalid, overlong, or truncated sequences decode into
+/// [`char::REPLACEMENT_CHARACTER`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Utf8Decoder {
+    codepoint: u32,
+    remaining: u8,
+    min: u32,
+}
+
+impl Utf8Decoder {
+    pub const fn new() -> Self {
+        Self {
+            codepoint: 0,
+            remaining: 0,
+            min: 0,
+        }
+    }
+
+    /// # Push
+    /// Feed the next byte into the decoder, calling `emit` for every char
+    /// that is completed.
+    pub fn push(&mut self, byte: u8, mut emit: impl FnMut(char)) {
+        if self.remaining != 0 {
+            if byte & 0xC0 == 0x80 {
+                self.codepoint = (self.codepoint << 6) | (byte & 0x3F) as u32;
+                self.remaining -= 1;
+
+                if self.remaining == 0 {
+                    emit(self.finish());
+                }
+
+                return;
+            }
+
+            // The sequence was cut short, the byte still needs decoding below
+            self.remaining = 0;
+            emit(char::REPLACEMENT_CHARACTER);
+        }
+
+        let (remaining, codepoint, min) = match byte {
+            0x00..=0x7F => return emit(byte as char),
+            0xC0..=0xDF => (1, byte & 0x1F, 0x80),
+            0xE0..=0xEF => (2, byte & 0x0F, 0x800),
+            0xF0..=0xF7 => (3, byte & 0x07, 0x10000),
+            _ => return emit(char::REPLACEMENT_CHARACTER),
+        };
+
+        self.remaining = remaining;
+        self.codepoint = codepoint as u32;
+        self.min = min;
+    }
+
+    fn finish(&self) -> char {
+        if self.codepoint < self.min {
+            return char::REPLACEMENT_CHARACTER;
+        }
+
+        char::from_u32(self.codepoint).unwrap_or(char::REPLACEMENT_CHARACTER)
+    }
+}
+
+/// # Char Width
+/// The number of terminal cells this char takes up on screen.
+///
+/// East Asian wide and fullwidth chars (and most emoji) take two cells, every
+/// other char takes one.
+pub const fn char_width(c: char) -> usize {
+    match c as u32 {
+        0x1100..=0x115F
+        | 0x2E80..=0x303E
+        | 0x3041..=0x33FF
+        | 0x3400..=0x4DBF
+        | 0x4E00..=0x9FFF
+        | 0xA000..=0xA4CF
+        | 0xAC00..=0xD7A3
+        | 0xF900..=0xFAFF
+        | 0xFE30..=0xFE4F
+        | 0xFF00..=0xFF60
+        | 0xFFE0..=0xFFE6
+        | 0x1F300..=0x1F64F
+        | 0x1F900..=0x1F9FF
+        | 0x20000..=0x3FFFD => 2,
+        _ => 1,
+    }
+}
+
+/// The standard 16 color palette, the first 8 are the normal colors and the
+/// last 8 are the 'bright' colors.
+pub const PALETTE: [Color; 16] = [
+    Color(0xFF000000),
+    Color(0xFFCD3131),
+    Color(0xFF0DBC79),
+    Color(0xFFE5E510),
+    Color(0xFF2472C8),
+    Color(0xFFBC3FBC),
+    Color(0xFF11A8CD),
+    Color(0xFFE5E5E5),
+    Color(0xFF666666),
+    Color(0xFFF14C4C),
+    Color(0xFF23D18B),
+    Color(0xFFF5F543),
+    Color(0xFF3B8EEA),
+    Color(0xFFD670D6),
+    Color(0xFF29B8DB),
+    Color(0xFFFFFFFF),
+];
+
+/// Convert an xterm 256 color index into a color.
+fn color_256(index: u16) -> Color {
+    match index {
+        0..=15 => PALETTE[index as usize],
+        16..=231 => {
+            let index = index - 16;
+            let level = |v: u16| if v == 0 { 0 } else { 55 + v as u32 * 40 };
+
+            Color::from_rgb(level(index / 36), level((index / 6) % 6), level(index % 6))
+        }
+        _ => {
+            let gray = 8 + (index.min(255) as u32 - 232) * 10;
+            Color::from_rgb(gray, gray, gray)
+        }
+    }
+}
+
+/// # Terminal
+/// A text console drawn onto a [`Framebuffer`] that understands ANSI escape
+/// sequences for cursor movement, erasing and colors.
+pub struct Terminal<'a> {
+    framebuffer: &'a mut Framebuffer,
+    atlas: Option<&'a mut GlyphAtlas>,
+    parser: AnsiParser,
+    utf8: Utf8Decoder,
+    cursor_row: usize,
+    cursor_col: usize,
+    foreground: Color,
+    background: Color,
+    bold: bool,
+}
+
+impl<'a> Terminal<'a> {
+    /// The width of each char cell in pixels.
+    pub const CELL_WIDTH: usize = GlyphAtlas::CELL_WIDTH;
+    /// The height of each char cell in pixels.
+    pub const CELL_HEIGHT: usize = GlyphAtlas::CELL_HEIGHT;
+    /// The width of a tab stop in cells.
+    pub const TAB_WIDTH: usize = 8;
+    /// The glyph drawn for chars that are missing from the font.
+    pub const REPLACEMENT_GLYPH: char = '?';
+
+    pub const DEFAULT_FOREGROUND: Color = Color::WHITE;
+    pub const DEFAULT_BACKGROUND: Color = Color::QUANTUM_BACKGROUND;
+
+    /// # New
+    /// Make a new terminal on this framebuffer, this does not clear the screen.
+    pub fn new(framebuffer: &'a mut Framebuffer) -> Self {
+        Self {
+            framebuffer,
+            atlas: None,
+            parser: AnsiParser::new(),
+            utf8: Utf8Decoder::new(),
+            cursor_row: 0,
+            cursor_col: 0,
+            foreground: Self::DEFAULT_FOREGROUND,
+            background: Self::DEFAULT_BACKGROUND,
+            bold: false,
+        }
+    }
+
+    /// # With Atlas
+    /// Make a new terminal that draws text through a glyph atlas, which is
+    /// much faster than drawing each glyph pixel by pixel.
+    pub fn with_atlas(framebuffer: &'a mut Framebuffer, atlas: &'a mut GlyphAtlas) -> Self {
+        Self {
+            atlas: Some(atlas),
+            ..Self::new(framebuffer)
+        }
+    }
+
+    /// # Rows
+    /// The number of text rows that fit on the screen.
+    pub fn rows(&self) -> usize {
+        (self.framebuffer.height() / Self::CELL_HEIGHT).max(1)
+    }
+
+    /// # Cols
+    /// The number of text columns that fit on the screen.
+    pub fn cols(&self) -> usize {
+        (self.framebuffer.width() / Self::CELL_WIDTH).max(1)
+    }
+
+    /// # Cursor
+    /// Get the current `(row, col)` of the cursor.
+    pub const fn cursor(&self) -> (usize, usize) {
+        (self.cursor_row, self.cursor_col)
+    }
+
+    /// # Clear
+    /// Clear the entire screen and move the cursor to the top left.
+    pub fn clear(&mut self) {
+        self.erase_display(EraseMode::All);
+        self.cursor_row = 0;
+        self.cursor_col = 0;
+    }
+
+    /// # Write Char
+    /// Feed a single char into the terminal.
+    pub fn write_char(&mut self, c: char) {
+        if let Some(action) = self.parser.advance(c) {
+            self.apply(action);
+        }
+    }
+
+    /// # Write Bytes
+    /// Feed raw UTF-8 bytes into the terminal, a char split across two calls
+    /// is still decoded correctly.
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        let mut decoder = self.utf8;
+        for &byte in bytes {
+            decoder.push(byte, |c| self.write_char(c));
+        }
+
+        self.utf8 = decoder;
+    }
+
+    /// # Apply
+    /// Apply a parsed action to the screen.
+    pub fn apply(&mut self, action: AnsiAction) {
+        let last_row = self.rows().saturating_sub(1);
+        let last_col = self.cols().saturating_sub(1);
+
+        match action {
+            AnsiAction::Print(c) => self.print(c),
+            AnsiAction::Execute('\n') => self.newline(),
+            AnsiAction::Execute('\r') => self.cursor_col = 0,
+            AnsiAction::Execute('\x08') => self.cursor_col = self.cursor_col.saturating_sub(1),
+            AnsiAction::Execute('\t') => {
+                let next = (self.cursor_col / Self::TAB_WIDTH + 1) * Self::TAB_WIDTH;
+                self.cursor_col = next.min(last_col);
+            }
+            AnsiAction::Execute(_) => (),
+            AnsiAction::CursorUp(n) => {
+                self.cursor_row = self.cursor_row.saturating_sub(n as usize);
+            }
+            AnsiAction::CursorDown(n) => {
+                self.cursor_row = (self.cursor_row + n as usize).min(last_row);
+            }
+            AnsiAction::CursorForward(n) => {
+                self.cursor_col = (self.cursor_col + n as usize).min(last_col);
+            }
+            AnsiAction::CursorBack(n) => {
+                self.cursor_col = self.cursor_col.saturating_sub(n as usize);
+            }
+            AnsiAction::CursorPosition { row, col } => {
+                self.cursor_row = (row as usize).min(last_row);
+                self.cursor_col = (col as usize).min(last_col);
+            }
+            AnsiAction::EraseDisplay(mode) => self.erase_display(mode),
+            AnsiAction::EraseLine(mode) => self.erase_line(mode),
+            AnsiAction::SelectGraphics(params) => self.select_graphics(&params),
+        }
+    }
+
+    fn print(&mut self, c: char) {
+        let width = char_width(c);
+
+        if self.cursor_col + width > self.cols() {
+            self.newline();
+        }
+
+        let x = self.cursor_col * Self::CELL_WIDTH;
+        let y = self.cursor_row * Self::CELL_HEIGHT;
+        let foreground = if self.bold {
+            Self::brighten(self.foreground)
+        } else {
+            self.foreground
+        };
+
+        // Chars our font doesn't have are drawn as a replacement glyph
+        let glyph = if BinFont::get_glyph(c).is_some() {
+            c
+        } else {
+            Self::REPLACEMENT_GLYPH
+        };
+
+        let cell = self
+            .atlas
+            .as_deref_mut()
+            .and_then(|atlas| atlas.cell(glyph, foreground, self.background));
+
+        match cell {
+            Some(cell) => {
+                self.framebuffer.blit(x, y, Self::CELL_WIDTH, cell);
+                self.framebuffer.draw_rec(
+                    x + Self::CELL_WIDTH,
+                    y,
+                    Self::CELL_WIDTH * (width - 1),
+                    Self::CELL_HEIGHT,
+                    self.background,
+                );
+            }
+            None => {
+                self.framebuffer.draw_rec(
+                    x,
+                    y,
+                    Self::CELL_WIDTH * width,
+                    Self::CELL_HEIGHT,
+                    self.background,
+                );
+                self.framebuffer.draw_glyph(x, y, glyph, foreground);
+            }
+        }
+
+        self.cursor_col += width;
+    }
+
+    fn newline(&mut self) {
+        self.cursor_col = 0;
+
+        if self.cursor_row + 1 >= self.rows() {
+            self.framebuffer
+                .scroll_up(Self::CELL_HEIGHT, Self::DEFAULT_BACKGROUND);
+        } else {
+            self.cursor_row += 1;
+        }
+    }
+
+    fn clear_cells(&mut self, row: usize, cols: core::ops::Range<usize>) {
+        self.framebuffer.draw_rec(
+            cols.start * Self::CELL_WIDTH,
+            row * Self::CELL_HEIGHT,
+            cols.len() * Self::CELL_WIDTH,
+            Self::CELL_HEIGHT,
+            self.background,
+        );
+    }
+
+    fn erase_line(&mut self, mode: EraseMode) {
+        let cols = self.cols();
+        let range = match mode {
+            EraseMode::ToEnd => self.cursor_col..cols,
+            EraseMode::ToStart => 0..(self.cursor_col + 1).min(cols),
+            EraseMode::All => 0..cols,
+        };
+
+        self.clear_cells(self.cursor_row, range);
+    }
+
+    fn erase_display(&mut self, mode: EraseMode) {
+        let (rows, cols) = (self.rows(), self.cols());
+        let row_range = match mode {
+            EraseMode::ToEnd => (self.cursor_row + 1)..rows,
+            EraseMode::ToStart => 0..self.cursor_row,
+            EraseMode::All => 0..rows,
+        };
+
+        for row in row_range {
+            self.clear_cells(row, 0..cols);
+        }
+
+        if mode != EraseMode::All {
+            self.erase_line(mode);
+        }
+    }
+
+    fn brighten(color: Color) -> Color {
+        match PALETTE[..8].iter().position(|c| c.0 == color.0) {
+            Some(index) => PALETTE[index + 8],
+            None => color,
+        }
+    }
+
+    fn select_graphics(&mut self, params: &CsiParams) {
+        // `ESC [ m` is the same as `ESC [ 0 m`
+        if params.is_empty() {
+            self.reset_graphics();
+            return;
+        }
+
+        let mut iter = params.iter();
+        while let Some(param) = iter.next() {
+            match param {
+                0 => self.reset_graphics(),
+                1 => self.bold = true,
+                22 => self.bold = false,
+                30..=37 => self.foreground = PALETTE[(param - 30) as usize],
+                39 => self.foreground = Self::DEFAULT_FOREGROUND,
+                40..=47 => self.background = PALETTE[(param - 40) as usize],
+                49 => self.background = Self::DEFAULT_BACKGROUND,
+                90..=97 => self.foreground = PALETTE[(param - 90 + 8) as usize],
+                100..=107 => self.background = PALETTE[(param - 100 + 8) as usize],
+                38 | 48 => {
+                    let color = match iter.next() {
+                        Some(5) => iter.next().map(color_256),
+                        Some(2) => match (iter.next(), iter.next(), iter.next()) {
+                            // Out of range parts are clamped, not wrapped
+                            (Some(r), Some(g), Some(b)) => Some(Color::from_rgb(
+                                r.min(255) as u32,
+                                g.min(255) as u32,
+                                b.min(255) as u32,
+                            )),
+                            _ => None,
+                        },
+                        _ => None,
+                    };
+
+                    match (param, color) {
+                        (38, Some(color)) => self.foreground = color,
+                        (_, Some(color)) => self.background = color,
+                        _ => (),
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+
+    fn reset_graphics(&mut self) {
+        self.foreground = Self::DEFAULT_FOREGROUND;
+        self.background = Self::DEFAULT_BACKGROUND;
+        self.bold = false;
+    }
+}
+
+impl Write for Terminal<'_> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        for c in s.chars() {
+            Terminal::write_char(self, c);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate std;
+
+    use super::*;
+    use std::{vec, vec::Vec};
+
+    fn parse(input: &str) -> Vec<AnsiAction> {
+        let mut parser = AnsiParser::new();
+        input.chars().filter_map(|c| parser.advance(c)).collect()
+    }
+
+    #[test]
+    fn test_plain_text() {
+        assert_eq!(
+            parse("hi\n"),
+            [
+                AnsiAction::Print('h'),
+                AnsiAction::Print('i'),
+                AnsiAction::Execute('\n')
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cursor_movement() {
+        assert_eq!(
+            parse("\x1b[A\x1b[3B\x1b[10;20H\x1b[H"),
+            [
+                AnsiAction::CursorUp(1),
+                AnsiAction::CursorDown(3),
+                AnsiAction::CursorPosition { row: 9, col: 19 },
+                AnsiAction::CursorPosition { row: 0, col: 0 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_erase() {
+        assert_eq!(
+            parse("\x1b[2J\x1b[K\x1b[1K"),
+            [
+                AnsiAction::EraseDisplay(EraseMode::All),
+                AnsiAction::EraseLine(EraseMode::ToEnd),
+                AnsiAction::EraseLine(EraseMode::ToStart),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sgr_params() {
+        let actions = parse("\x1b[1;31;48;5;200mx");
+        assert_eq!(actions.len(), 2);
+
+        let AnsiAction::SelectGraphics(params) = actions[0] else {
+            panic!("Expected SGR, got {:?}", actions[0]);
+        };
+
+        assert_eq!(params.iter().collect::<Vec<_>>(), [1, 31, 48, 5, 200]);
+        assert_eq!(actions[1], AnsiAction::Print('x'));
+    }
+
+    #[test]
+    fn test_sgr_empty_param() {
+        let actions = parse("\x1b[;1m");
+        let AnsiAction::SelectGraphics(params) = actions[0] else {
+            panic!("Expected SGR, got {:?}", actions[0]);
+        };
+
+        assert_eq!(params.iter().collect::<Vec<_>>(), [0, 1]);
+    }
+
+    #[test]
+    fn test_unknown_sequences_are_dropped() {
+        assert_eq!(
+            parse("\x1b[?25la\x1b[5Zb\x1b(c"),
+            [AnsiAction::Print('a'), AnsiAction::Print('b')]
+        );
+    }
+
+    fn with_terminal(width: usize, height: usize, f: impl FnOnce(&mut Terminal)) -> Vec<u32> {
+        let mut buffer = vec![0u32; width * height];
+        let mut framebuffer =
+            unsafe { Framebuffer::new_linear(buffer.as_mut_ptr(), 32, height, width) };
+
+        f(&mut Terminal::new(&mut framebuffer));
+        buffer
+    }
+
+    #[test]
+    fn test_terminal_cursor() {
+        with_terminal(80, 64, |term| {
+            assert_eq!((term.rows(), term.cols()), (4, 10));
+
+            write!(term, "abc\x1b[2;5H").unwrap();
+            assert_eq!(term.cursor(), (1, 4));
+
+            write!(term, "\x1b[99B\x1b[99C").unwrap();
+            assert_eq!(term.cursor(), (3, 9));
+
+            write!(term, "\r\t").unwrap();
+            assert_eq!(term.cursor(), (3, 8));
+        });
+    }
+
+    #[test]
+    fn test_terminal_colors() {
+        let buffer = with_terminal(16, 16, |term| {
+            write!(term, "\x1b[41m \x1b[0m ").unwrap();
+        });
+
+        assert_eq!(buffer[0], PALETTE[1].0);
+        assert_eq!(buffer[8], Terminal::DEFAULT_BACKGROUND.0);
+    }
+
+    #[test]
+    fn test_terminal_true_color() {
+        let buffer = with_terminal(16, 16, |term| {
+            write!(term, "\x1b[48;2;10;20;30m \x1b[48;2;300;0;256m ").unwrap();
+        });
+
+        assert_eq!(buffer[0], Color::from_rgb(10, 20, 30).0);
+        assert_eq!(buffer[8], Color::from_rgb(255, 0, 255).0);
+    }
+
+    #[test]
+    fn test_terminal_scrolls() {
+        let buffer = with_terminal(8, 32, |term| {
+            write!(term, "\x1b[42m \n\x1b[0m\n").unwrap();
+            assert_eq!(term.cursor(), (1, 0));
+        });
+
+        // The green cell scrolled off the top of the screen
+        assert!(buffer
+            .iter()
+            .all(|&p| p == 0 || p == Terminal::DEFAULT_BACKGROUND.0));
+    }
+
+    #[test]
+    fn test_color_256() {
+        assert_eq!(color_256(1).0, PALETTE[1].0);
+        assert_eq!(color_256(16).0, 0xFF000000);
+        assert_eq!(color_256(231).0, 0xFFFFFFFF);
+        assert_eq!(color_256(232).0, 0xFF080808);
+    }
+
+    fn decode(bytes: &[u8]) -> std::string::String {
+        let mut decoder = Utf8Decoder::new();
+        let mut out = std::string::String::new();
+        for &byte in bytes {
+            decoder.push(byte, |c| out.push(c));
+        }
+
+        out
+    }
+
+    #[test]
+    fn test_utf8_decode() {
+        assert_eq!(decode("héllo 世界 🦀".as_bytes()), "héllo 世界 🦀");
+    }
+
+    #[test]
+    fn test_utf8_invalid() {
+        // Truncated sequence followed by ascii
+        assert_eq!(decode(b"\xE4\xB8a"), "\u{FFFD}a");
+        // Stray continuation byte
+        assert_eq!(decode(b"\x80b"), "\u{FFFD}b");
+        // Overlong encoding of '/'
+        assert_eq!(decode(b"\xC0\xAF"), "\u{FFFD}");
+        // Surrogate half
+        assert_eq!(decode(b"\xED\xA0\x80"), "\u{FFFD}");
+    }
+
+    #[test]
+    fn test_char_width() {
+        assert_eq!(char_width('a'), 1);
+        assert_eq!(char_width('é'), 1);
+        assert_eq!(char_width('世'), 2);
+        assert_eq!(char_width('🦀'), 2);
+    }
+
+    #[test]
+    fn test_terminal_utf8() {
+        with_terminal(80, 32, |term| {
+            let bytes = "a世".as_bytes();
+            term.write_bytes(&bytes[..2]);
+            term.write_bytes(&bytes[2..]);
+            assert_eq!(term.cursor(), (0, 3));
+
+            // A wide char that doesn't fit on the line wraps
+            write!(term, "\x1b[1;10H世").unwrap();
+            assert_eq!(term.cursor(), (1, 2));
+        });
+    }
+
+    #[test]
+    fn test_terminal_atlas_matches() {
+        let text = "\x1b[1;32mhello\x1b[0m world\n\x1b[44m世 ok\x1b[2;3H\x1b[K";
+        let plain = with_terminal(64, 48, |term| write!(term, "{}", text).unwrap());
+
+        let mut atlas = std::boxed::Box::new(GlyphAtlas::new());
+        let mut buffer = vec![0u32; 64 * 48];
+        let mut framebuffer = unsafe { Framebuffer::new_linear(buffer.as_mut_ptr(), 32, 48, 64) };
+        write!(
+            Terminal::with_atlas(&mut framebuffer, &mut atlas),
+            "{}",
+            text
+        )
+        .unwrap();
+
+        assert_eq!(plain, buffer);
+        assert_eq!(
+            framebuffer.take_dirty(),
+            Some(crate::DirtyRect {
+                x: 0,
+                y: 0,
+                width: 64,
+                height: 48
+            })
+        );
+        assert_eq!(framebuffer.take_dirty(), None);
+    }
+}
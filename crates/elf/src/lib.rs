@@ -27,6 +27,7 @@ OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWA
 
 use lldebug::logln;
 
+pub mod stream;
 pub mod tables;
 
 #[derive(Clone, Copy, Debug)]
@@ -35,6 +36,7 @@ pub enum ElfErrorKind {
     NotAligned,
     IncorrectBitMode,
     Invalid,
+    ReadError,
 }
 
 pub type Result<T> = core::result::Result<T, ElfErrorKind>;
@@ -0,0 +1,348 @@
+/*
+  ____                 __               __   _ __
+ / __ \__ _____ ____  / /___ ____ _    / /  (_) /
+/ /_/ / // / _ `/ _ \/ __/ // /  ' \  / /__/ / _ \
+\___\_\_,_/\_,_/_//_/\__/\_,_/_/_/_/ /____/_/_.__/
+    Part of the Quantum OS Project
+
+Copyright 2024 Gavin Kellam
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and
+associated documentation files (the "Software"), to deal in the Software without restriction,
+including without limitation the rights to use, copy, modify, merge, publish, distribute,
+sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial
+portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT
+NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
+OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+use crate::{
+    ElfErrorKind, Result,
+    tables::{
+        Elf32Header, Elf64Header, ElfGenProgramHeader, ElfInitHeader, ProgramHeader32,
+        ProgramHeader64, SegmentKind,
+    },
+};
+
+/// # Read At
+/// A source of bytes that can be read from any offset, like a file on disk.
+///
+/// This lets an elf be loaded without first copying the whole file into memory.
+pub trait ReadAt {
+    /// # Read At
+    /// Fill all of `buf` with the bytes starting at `offset`.
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> Result<()>;
+}
+
+impl ReadAt for &[u8] {
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> Result<()> {
+        let start = usize::try_from(offset).map_err(|_| ElfErrorKind::NotEnoughBytes)?;
+        let end = start
+            .checked_add(buf.len())
+            .ok_or(ElfErrorKind::NotEnoughBytes)?;
+        let bytes = self.get(start..end).ok_or(ElfErrorKind::NotEnoughBytes)?;
+
+        buf.copy_from_slice(bytes);
+        Ok(())
+    }
+}
+
+/// Headers are read into this so they can be cast into their tables.
+#[repr(C, align(8))]
+struct AlignedBuffer<const N: usize>([u8; N]);
+
+/// # Elf Reader
+/// Parses an elf straight from a [`ReadAt`] source, reading only the headers
+/// and segments that are asked for.
+pub struct ElfReader<R: ReadAt> {
+    reader: R,
+    init_header: ElfInitHeader,
+    entry_point: u64,
+    program_header_offset: u64,
+    program_header_count: usize,
+    program_header_size: usize,
+}
+
+impl<R: ReadAt> ElfReader<R> {
+    /// # New
+    /// Read and validate the elf header from `reader`.
+    pub fn new(mut reader: R) -> Result<Self> {
+        let mut header = AlignedBuffer([0; size_of::<Elf64Header>()]);
+        reader.read_at(0, &mut header.0[..size_of::<Elf32Header>()])?;
+
+        let init_header: &ElfInitHeader = header.0.as_slice().try_into()?;
+        if !init_header.is_valid() {
+            return Err(ElfErrorKind::Invalid);
+        }
+        let init_header = *init_header;
+
+        let (entry_point, program_header_offset, program_header_count, program_header_size) =
+            if init_header.is_64bit() {
+                reader.read_at(0, &mut header.0)?;
+                let header: &Elf64Header = header.0.as_slice().try_into()?;
+
+                (
+                    header.entry_point(),
+                    header.program_header_offset(),
+                    header.program_header_count(),
+                    header.program_header_size(),
+                )
+            } else {
+                let header: &Elf32Header = header.0.as_slice().try_into()?;
+
+                (
+                    header.entry_point() as u64,
+                    header.program_header_offset() as u64,
+                    header.program_header_count(),
+                    header.program_header_size(),
+                )
+            };
+
+        let expected_size = if init_header.is_64bit() {
+            size_of::<ProgramHeader64>()
+        } else {
+            size_of::<ProgramHeader32>()
+        };
+
+        if program_header_count != 0 && program_header_size < expected_size {
+            return Err(ElfErrorKind::Invalid);
+        }
+
+        Ok(Self {
+            reader,
+            init_header,
+            entry_point,
+            program_header_offset,
+            program_header_count,
+            program_header_size,
+        })
+    }
+
+    /// # Init Header
+    /// The bit-mode, endian and arch of this elf.
+    pub const fn init_header(&self) -> &ElfInitHeader {
+        &self.init_header
+    }
+
+    /// # Entry Point
+    /// The virtual address execution should start at.
+    pub const fn entry_point(&self) -> u64 {
+        self.entry_point
+    }
+
+    /// # Program Header Count
+    pub const fn program_header_count(&self) -> usize {
+        self.program_header_count
+    }
+
+    /// # Program Header
+    /// Read the program header at `index`.
+    pub fn program_header(&mut self, index: usize) -> Result<ElfGenProgramHeader> {
+        if index >= self.program_header_count {
+            return Err(ElfErrorKind::NotEnoughBytes);
+        }
+
+        let offset = index
+            .checked_mul(self.program_header_size)
+            .and_then(|relative| self.program_header_offset.checked_add(relative as u64))
+            .ok_or(ElfErrorKind::NotEnoughBytes)?;
+        let mut buffer = AlignedBuffer([0; size_of::<ProgramHeader64>()]);
+
+        if self.init_header.is_64bit() {
+            self.reader.read_at(offset, &mut buffer.0)?;
+            let header: &ProgramHeader64 = buffer.0.as_slice().try_into()?;
+            Ok(header.into())
+        } else {
+            let bytes = &mut buffer.0[..size_of::<ProgramHeader32>()];
+            self.reader.read_at(offset, bytes)?;
+            let header: &ProgramHeader32 = (&*bytes).try_into()?;
+            Ok(header.into())
+        }
+    }
+
+    /// # Program Headers
+    /// Iterate over every program header in this elf.
+    pub fn program_headers(&mut self) -> impl Iterator<Item = Result<ElfGenProgramHeader>> + '_ {
+        (0..self.program_header_count).map(|index| self.program_header(index))
+    }
+
+    /// # Load Size
+    /// The number of bytes needed to hold every loadable segment, when each one
+    /// starts on an `alignment` boundary.
+    ///
+    /// An `alignment` of zero, or a total that does not fit in a `usize`, is
+    /// reported as `Invalid`.
+    pub fn load_size(&mut self, alignment: usize) -> Result<usize> {
+        self.program_headers().try_fold(0usize, |size, header| {
+            let header = header?;
+
+            if header.segment_kind() != SegmentKind::Load {
+                return Ok(size);
+            }
+
+            header
+                .in_mem_size()
+                .checked_next_multiple_of(alignment)
+                .and_then(|segment_size| size.checked_add(segment_size))
+                .ok_or(ElfErrorKind::Invalid)
+        })
+    }
+
+    /// # Load Into
+    /// Read every segment `loader_fn` gives a buffer for directly from the
+    /// source, zeroing the part of the buffer the file does not cover.
+    ///
+    /// Returns the entry point of the elf.
+    pub fn load_into<F>(&mut self, mut loader_fn: F) -> Result<u64>
+    where
+        F: FnMut(&ElfGenProgramHeader) -> Option<&'static mut [u8]>,
+    {
+        for index in 0..self.program_header_count {
+            let header = self.program_header(index)?;
+
+            let Some(mem_buffer) = loader_fn(&header) else {
+                continue;
+            };
+
+            if header.in_elf_size() > mem_buffer.len() {
+                return Err(ElfErrorKind::Invalid);
+            }
+
+            let (file_bytes, zeroed_bytes) = mem_buffer.split_at_mut(header.in_elf_size());
+            self.reader
+                .read_at(header.in_elf_offset() as u64, file_bytes)?;
+            zeroed_bytes.fill(0);
+        }
+
+        Ok(self.entry_point)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate std;
+    use super::*;
+    use std::{boxed::Box, vec, vec::Vec};
+
+    fn push_program_header(elf: &mut Vec<u8>, offset: u64, vaddr: u64, file: u64, mem: u64) {
+        elf.extend_from_slice(&1u32.to_le_bytes());
+        elf.extend_from_slice(&6u32.to_le_bytes());
+        elf.extend_from_slice(&offset.to_le_bytes());
+        elf.extend_from_slice(&vaddr.to_le_bytes());
+        elf.extend_from_slice(&vaddr.to_le_bytes());
+        elf.extend_from_slice(&file.to_le_bytes());
+        elf.extend_from_slice(&mem.to_le_bytes());
+        elf.extend_from_slice(&0x1000u64.to_le_bytes());
+    }
+
+    /// An elf64 with two load segments, the second has 4 bytes of bss.
+    fn test_elf() -> Vec<u8> {
+        let mut elf = vec![0x7F, b'E', b'L', b'F', 2, 1, 1, 0];
+        elf.extend_from_slice(&[0; 8]);
+        elf.extend_from_slice(&2u16.to_le_bytes());
+        elf.extend_from_slice(&0x3eu16.to_le_bytes());
+        elf.extend_from_slice(&1u32.to_le_bytes());
+        elf.extend_from_slice(&0x1000_0000u64.to_le_bytes());
+        elf.extend_from_slice(&64u64.to_le_bytes());
+        elf.extend_from_slice(&0u64.to_le_bytes());
+        elf.extend_from_slice(&0u32.to_le_bytes());
+        elf.extend_from_slice(&64u16.to_le_bytes());
+        elf.extend_from_slice(&56u16.to_le_bytes());
+        elf.extend_from_slice(&2u16.to_le_bytes());
+        elf.extend_from_slice(&[0; 6]);
+
+        push_program_header(&mut elf, 176, 0x1000_0000, 4, 4);
+        push_program_header(&mut elf, 180, 0x1000_1000, 4, 8);
+
+        elf.extend_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8]);
+        elf
+    }
+
+    #[test]
+    fn test_reading_headers() {
+        let elf = test_elf();
+        let mut reader = ElfReader::new(elf.as_slice()).unwrap();
+
+        assert!(reader.init_header().is_64bit());
+        assert_eq!(reader.entry_point(), 0x1000_0000);
+        assert_eq!(reader.program_header_count(), 2);
+
+        let second = reader.program_header(1).unwrap();
+        assert_eq!(second.segment_kind(), SegmentKind::Load);
+        assert_eq!(second.expected_vaddr(), 0x1000_1000);
+        assert_eq!(second.in_mem_size(), 8);
+        assert!(reader.program_header(2).is_err());
+    }
+
+    #[test]
+    fn test_load_size() {
+        let elf = test_elf();
+        let mut reader = ElfReader::new(elf.as_slice()).unwrap();
+
+        assert_eq!(reader.load_size(4096).unwrap(), 8192);
+        assert_eq!(reader.load_size(1).unwrap(), 12);
+        assert!(matches!(reader.load_size(0), Err(ElfErrorKind::Invalid)));
+    }
+
+    #[test]
+    fn test_malformed_sizes() {
+        // A program header table that starts right before the end of the address space
+        let mut elf = test_elf();
+        elf[32..40].copy_from_slice(&(u64::MAX - 8).to_le_bytes());
+        let mut reader = ElfReader::new(elf.as_slice()).unwrap();
+        assert!(matches!(
+            reader.program_header(1),
+            Err(ElfErrorKind::NotEnoughBytes)
+        ));
+
+        // Two segments whose memory sizes add up past `usize::MAX`
+        let mut elf = test_elf();
+        elf[104..112].copy_from_slice(&(u64::MAX - 2).to_le_bytes());
+        elf[160..168].copy_from_slice(&(u64::MAX - 2).to_le_bytes());
+        let mut reader = ElfReader::new(elf.as_slice()).unwrap();
+        assert!(matches!(reader.load_size(1), Err(ElfErrorKind::Invalid)));
+        assert!(matches!(reader.load_size(4096), Err(ElfErrorKind::Invalid)));
+    }
+
+    #[test]
+    fn test_scatter_load() {
+        let elf = test_elf();
+        let mut reader = ElfReader::new(elf.as_slice()).unwrap();
+
+        let first: &'static mut [u8] = Box::leak(Box::new([0xFF; 4]));
+        let second: &'static mut [u8] = Box::leak(Box::new([0xFF; 8]));
+        let first_ptr = first.as_ptr();
+        let second_ptr = second.as_ptr();
+        let mut buffers = [Some(first), Some(second)].into_iter();
+
+        assert_eq!(
+            reader.load_into(|_| buffers.next().flatten()).unwrap(),
+            0x1000_0000
+        );
+
+        assert_eq!(unsafe { core::slice::from_raw_parts(first_ptr, 4) }, &[
+            1, 2, 3, 4
+        ]);
+        assert_eq!(unsafe { core::slice::from_raw_parts(second_ptr, 8) }, &[
+            5, 6, 7, 8, 0, 0, 0, 0
+        ]);
+    }
+
+    #[test]
+    fn test_invalid_magic() {
+        let mut elf = test_elf();
+        elf[0] = 0;
+
+        assert!(matches!(
+            ElfReader::new(elf.as_slice()),
+            Err(ElfErrorKind::Invalid)
+        ));
+    }
+}
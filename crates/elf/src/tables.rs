@@ -464,6 +464,10 @@ impl ElfGenProgramHeader {
         self.bits == 1
     }
 
+    pub const fn flags(&self) -> u32 {
+        self.flags
+    }
+
     pub fn segment_kind(&self) -> SegmentKind {
         self.segment_kind.into()
     }
@@ -0,0 +1,485 @@
+/*
+  ____                 __               __   _ __
+ / __ \__ _____ ____  / /___ ____ _    / /  (_) /
+/ /_/ / // / _ `/ _ \/ __/ // /  ' \  / /__/ / _ \
+\___\_\_,_/\_,_/_//_/\__/\_,_/_/_/_/ /____/_/_.__/
+    Part of the Quantum OS Project
+
+Copyright 2024 Gavin Kellam
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and
+associated documentation files (the "Software"), to deal in the Software without restriction,
+including without limitation the rights to use, copy, modify, merge, publish, distribute,
+sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial
+portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT
+NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
+OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+use crate::{
+    error::{FsError, Result},
+    io::{Read, Seek, SeekFrom, Write},
+    read_block::{read_smooth_from_block_device, BlockDevice},
+};
+
+/// # Write Policy
+/// When writes to a `CachedBlockDevice` reach the device underneath it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WritePolicy {
+    /// Every write goes straight to the device, cached blocks are never dirty.
+    WriteThrough,
+    /// Writes only go to the cache, and reach the device once the block is
+    /// evicted or the cache is flushed.
+    WriteBack,
+}
+
+/// # Cache Stats
+/// How well the cache has been doing since it was created (or last reset).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Block reads that were answered from the cache.
+    pub hits: u64,
+    /// Block reads that had to go to the device.
+    pub misses: u64,
+    /// Dirty blocks written back to the device.
+    pub writebacks: u64,
+}
+
+struct CacheSlot<const SIZE: usize> {
+    block: Option<u64>,
+    last_used: u64,
+    dirty: bool,
+    data: [u8; SIZE],
+}
+
+/// # Cached Block Device
+/// Keeps the `CAPACITY` most recently used blocks of `device` in memory, and
+/// evicts the least recently used block once the cache is full.
+///
+/// `SIZE` must be the same as the device's `BLOCK_SIZE`.
+///
+/// # Note
+/// Batched `read_blocks` calls bigger than half the cache (like reading a whole
+/// file) skip it, so one big read doesn't push out all the FAT and directory
+/// sectors.
+pub struct CachedBlockDevice<D: BlockDevice, const SIZE: usize = 512, const CAPACITY: usize = 16> {
+    device: D,
+    policy: WritePolicy,
+    slots: [CacheSlot<SIZE>; CAPACITY],
+    clock: u64,
+    stats: CacheStats,
+    seek: u64,
+}
+
+impl<D: BlockDevice, const SIZE: usize, const CAPACITY: usize>
+    CachedBlockDevice<D, SIZE, CAPACITY>
+{
+    /// # New
+    /// Cache the blocks of `device`, writing them back with `policy`.
+    pub fn new(device: D, policy: WritePolicy) -> Result<Self> {
+        if SIZE != D::BLOCK_SIZE || CAPACITY == 0 {
+            return Err(FsError::InvalidInput);
+        }
+
+        Ok(Self {
+            device,
+            policy,
+            slots: core::array::from_fn(|_| CacheSlot {
+                block: None,
+                last_used: 0,
+                dirty: false,
+                data: [0; SIZE],
+            }),
+            clock: 0,
+            stats: CacheStats::default(),
+            seek: 0,
+        })
+    }
+
+    /// # Into Inner
+    /// Write back any dirty blocks, and get the device back out of the cache.
+    pub fn into_inner(mut self) -> Result<D> {
+        self.flush()?;
+        Ok(self.device)
+    }
+
+    pub const fn policy(&self) -> WritePolicy {
+        self.policy
+    }
+
+    pub const fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.stats = CacheStats::default();
+    }
+
+    /// # Flush
+    /// Write every dirty block back to the device.
+    pub fn flush(&mut self) -> Result<()> {
+        for index in 0..CAPACITY {
+            self.write_back(index)?;
+        }
+
+        Ok(())
+    }
+
+    fn slot_of(&self, block: u64) -> Option<usize> {
+        self.slots.iter().position(|slot| slot.block == Some(block))
+    }
+
+    fn touch(&mut self, index: usize) {
+        self.clock += 1;
+        self.slots[index].last_used = self.clock;
+    }
+
+    fn write_back(&mut self, index: usize) -> Result<()> {
+        let slot = &mut self.slots[index];
+
+        if let (Some(block), true) = (slot.block, slot.dirty) {
+            self.device.write_block(block, &slot.data)?;
+            slot.dirty = false;
+            self.stats.writebacks += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Empty the least recently used slot (or an unused one) for `block`.
+    fn claim_slot(&mut self, block: u64) -> Result<usize> {
+        let index = self
+            .slots
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, slot)| slot.block.map(|_| slot.last_used))
+            .map(|(index, _)| index)
+            .unwrap();
+
+        self.write_back(index)?;
+        self.slots[index].block = Some(block);
+
+        Ok(index)
+    }
+}
+
+impl<D: BlockDevice, const SIZE: usize, const CAPACITY: usize> BlockDevice
+    for CachedBlockDevice<D, SIZE, CAPACITY>
+{
+    const BLOCK_SIZE: usize = SIZE;
+
+    fn read_block(&mut self, block_offset: u64) -> Result<&[u8]> {
+        let index = match self.slot_of(block_offset) {
+            Some(index) => {
+                self.stats.hits += 1;
+                index
+            }
+            None => {
+                self.stats.misses += 1;
+                let index = self.claim_slot(block_offset)?;
+
+                match self.device.read_block(block_offset) {
+                    Ok(data) => self.slots[index].data.copy_from_slice(&data[..SIZE]),
+                    Err(err) => {
+                        self.slots[index].block = None;
+                        return Err(err);
+                    }
+                }
+
+                index
+            }
+        };
+
+        self.touch(index);
+        Ok(&self.slots[index].data)
+    }
+
+    fn read_blocks(&mut self, block_offset: u64, buf: &mut [u8]) -> Result<usize> {
+        let blocks = buf.len() / SIZE;
+
+        if blocks <= (CAPACITY / 2).max(1) {
+            let (chunks, _) = buf.as_chunks_mut::<SIZE>();
+            for (block, data) in chunks.iter_mut().enumerate() {
+                data.copy_from_slice(self.read_block(block_offset + block as u64)?);
+            }
+
+            return Ok(blocks * SIZE);
+        }
+
+        let read = self.device.read_blocks(block_offset, buf)?;
+        let blocks = block_offset..block_offset + (read / SIZE) as u64;
+
+        // The device doesn't have our dirty blocks yet
+        for slot in self.slots.iter().filter(|slot| slot.dirty) {
+            if let Some(block) = slot.block.filter(|block| blocks.contains(block)) {
+                let start = (block - block_offset) as usize * SIZE;
+                buf[start..start + SIZE].copy_from_slice(&slot.data);
+            }
+        }
+
+        Ok(read)
+    }
+
+    fn write_block(&mut self, block_offset: u64, data: &[u8]) -> Result<()> {
+        if data.len() != SIZE {
+            return Err(FsError::InvalidInput);
+        }
+
+        let index = match self.policy {
+            WritePolicy::WriteThrough => {
+                self.device.write_block(block_offset, data)?;
+
+                // Only keep the cached copy up to date, don't cache new blocks
+                let Some(index) = self.slot_of(block_offset) else {
+                    return Ok(());
+                };
+                index
+            }
+            WritePolicy::WriteBack => {
+                let index = match self.slot_of(block_offset) {
+                    Some(index) => index,
+                    None => self.claim_slot(block_offset)?,
+                };
+
+                self.slots[index].dirty = true;
+                index
+            }
+        };
+
+        self.slots[index].data.copy_from_slice(data);
+        self.touch(index);
+
+        Ok(())
+    }
+}
+
+impl<D: BlockDevice, const SIZE: usize, const CAPACITY: usize> Read
+    for CachedBlockDevice<D, SIZE, CAPACITY>
+{
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let read = read_smooth_from_block_device(self, self.seek, buf)?;
+        self.seek += read as u64;
+
+        Ok(read)
+    }
+}
+
+impl<D: BlockDevice, const SIZE: usize, const CAPACITY: usize> Write
+    for CachedBlockDevice<D, SIZE, CAPACITY>
+{
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let mut written = 0;
+
+        while written < buf.len() {
+            let block = self.seek / SIZE as u64;
+            let in_block = (self.seek % SIZE as u64) as usize;
+            let len = (SIZE - in_block).min(buf.len() - written);
+
+            // Partial blocks have to keep the bytes we aren't writing
+            let mut data = [0; SIZE];
+            if len != SIZE {
+                data.copy_from_slice(self.read_block(block)?);
+            }
+
+            data[in_block..in_block + len].copy_from_slice(&buf[written..written + len]);
+            self.write_block(block, &data)?;
+
+            written += len;
+            self.seek += len as u64;
+        }
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        CachedBlockDevice::flush(self)
+    }
+}
+
+impl<D: BlockDevice, const SIZE: usize, const CAPACITY: usize> Seek
+    for CachedBlockDevice<D, SIZE, CAPACITY>
+{
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+        self.seek = match pos {
+            SeekFrom::Start(start) => start,
+            SeekFrom::Current(delta) => self
+                .seek
+                .checked_add_signed(delta)
+                .ok_or(FsError::InvalidInput)?,
+            // Block devices don't know how big they are
+            SeekFrom::End(_) => return Err(FsError::NotSupported),
+        };
+
+        Ok(self.seek)
+    }
+
+    fn stream_position(&mut self) -> u64 {
+        self.seek
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate std;
+
+    use super::*;
+    use std::vec::Vec;
+
+    /// A disk that counts how often it is actually used.
+    struct CountingDisk {
+        image: Vec<u8>,
+        block: [u8; 16],
+        reads: usize,
+        writes: usize,
+    }
+
+    impl CountingDisk {
+        fn new(blocks: usize) -> Self {
+            Self {
+                image: (0..blocks * 16).map(|i| i as u8).collect(),
+                block: [0; 16],
+                reads: 0,
+                writes: 0,
+            }
+        }
+    }
+
+    impl BlockDevice for CountingDisk {
+        const BLOCK_SIZE: usize = 16;
+
+        fn read_block(&mut self, block_offset: u64) -> Result<&[u8]> {
+            let start = block_offset as usize * 16;
+            self.reads += 1;
+            self.block.copy_from_slice(
+                self.image
+                    .get(start..start + 16)
+                    .ok_or(FsError::EndOfFile)?,
+            );
+
+            Ok(&self.block)
+        }
+
+        fn write_block(&mut self, block_offset: u64, data: &[u8]) -> Result<()> {
+            let start = block_offset as usize * 16;
+            self.writes += 1;
+            self.image[start..start + 16].copy_from_slice(data);
+
+            Ok(())
+        }
+    }
+
+    type Cache<const CAPACITY: usize> = CachedBlockDevice<CountingDisk, 16, CAPACITY>;
+
+    #[test]
+    fn test_cache_hits() {
+        let mut cache = Cache::<4>::new(CountingDisk::new(8), WritePolicy::WriteThrough).unwrap();
+
+        assert_eq!(cache.read_block(3).unwrap()[0], 48);
+        assert_eq!(cache.read_block(3).unwrap()[0], 48);
+        assert_eq!(
+            cache.stats(),
+            CacheStats {
+                hits: 1,
+                misses: 1,
+                writebacks: 0
+            }
+        );
+
+        assert!(matches!(cache.read_block(8), Err(FsError::EndOfFile)));
+        assert_eq!(cache.into_inner().unwrap().reads, 2);
+    }
+
+    #[test]
+    fn test_lru_eviction() {
+        let mut cache = Cache::<2>::new(CountingDisk::new(8), WritePolicy::WriteThrough).unwrap();
+
+        cache.read_block(0).unwrap();
+        cache.read_block(1).unwrap();
+        cache.read_block(0).unwrap();
+
+        // 1 is the least recently used, so it gets evicted for 2
+        cache.read_block(2).unwrap();
+        cache.reset_stats();
+
+        cache.read_block(0).unwrap();
+        cache.read_block(2).unwrap();
+        assert_eq!(cache.stats().hits, 2);
+
+        cache.read_block(1).unwrap();
+        assert_eq!(cache.stats().misses, 1);
+    }
+
+    #[test]
+    fn test_write_through() {
+        let mut cache = Cache::<2>::new(CountingDisk::new(8), WritePolicy::WriteThrough).unwrap();
+
+        cache.read_block(1).unwrap();
+        cache.write_block(1, &[0xAA; 16]).unwrap();
+        cache.write_block(5, &[0xBB; 16]).unwrap();
+
+        assert_eq!(cache.read_block(1).unwrap(), &[0xAA; 16]);
+        assert_eq!(cache.stats().hits, 1);
+
+        let disk = cache.into_inner().unwrap();
+        assert_eq!(disk.writes, 2);
+        assert_eq!(&disk.image[16..32], &[0xAA; 16]);
+        assert_eq!(&disk.image[80..96], &[0xBB; 16]);
+    }
+
+    #[test]
+    fn test_write_back() {
+        let mut cache = Cache::<2>::new(CountingDisk::new(8), WritePolicy::WriteBack).unwrap();
+
+        cache.write_block(1, &[0xAA; 16]).unwrap();
+        cache.write_block(1, &[0xCC; 16]).unwrap();
+        cache.write_block(2, &[0xBB; 16]).unwrap();
+        assert_eq!(cache.device.writes, 0);
+
+        // Batched reads skip the cache, but still see dirty blocks
+        let mut buf = [0; 64];
+        cache.read_blocks(0, &mut buf).unwrap();
+        assert_eq!(&buf[..16], &core::array::from_fn::<u8, 16, _>(|i| i as u8));
+        assert_eq!(&buf[16..32], &[0xCC; 16]);
+        assert_eq!(&buf[32..48], &[0xBB; 16]);
+
+        // Evicting the dirty block 1 writes it back
+        cache.read_block(6).unwrap();
+        assert_eq!(cache.device.writes, 1);
+        assert_eq!(&cache.device.image[16..32], &[0xCC; 16]);
+
+        let disk = cache.into_inner().unwrap();
+        assert_eq!(disk.writes, 2);
+        assert_eq!(&disk.image[32..48], &[0xBB; 16]);
+    }
+
+    #[test]
+    fn test_read_write_stream() {
+        let mut cache = Cache::<4>::new(CountingDisk::new(8), WritePolicy::WriteBack).unwrap();
+
+        cache.seek(SeekFrom::Start(10)).unwrap();
+        cache.write(&[0xFF; 20]).unwrap();
+        assert_eq!(cache.stream_position(), 30);
+
+        let mut buf = [0; 24];
+        cache.seek(SeekFrom::Current(-22)).unwrap();
+        cache.read(&mut buf).unwrap();
+
+        let mut expected: Vec<u8> = (8..32).collect();
+        expected[2..22].fill(0xFF);
+        assert_eq!(&buf[..], &expected[..]);
+
+        assert!(cache.seek(SeekFrom::End(0)).is_err());
+        assert!(CachedBlockDevice::<CountingDisk, 512>::new(
+            CountingDisk::new(1),
+            WritePolicy::WriteBack
+        )
+        .is_err());
+    }
+}
@@ -27,9 +27,15 @@ OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWA
 pub enum FsError {
     EndOfFile,
     ReadError,
+    WriteError,
     InvalidInput,
     NotFound,
     NotSupported,
+    /// The data doesn't match its checksum.
+    IntegrityError,
+    /// There is no free space left to write to.
+    NoSpace,
+    AlreadyExists,
 }
 
 pub type Result<T> = core::result::Result<T, FsError>;
@@ -0,0 +1,112 @@
+/*
+  ____                 __               __   _ __
+ / __ \__ _____ ____  / /___ ____ _    / /  (_) /
+/ /_/ / // / _ `/ _ \/ __/ // /  ' \  / /__/ / _ \
+\___\_\_,_/\_,_/_//_/\__/\_,_/_/_/_/ /____/_/_.__/
+    Part of the Quantum OS Project
+
+Copyright 2024 Gavin Kellam
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and
+associated documentation files (the "Software"), to deal in the Software without restriction,
+including without limitation the rights to use, copy, modify, merge, publish, distribute,
+sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial
+portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT
+NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
+OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+use super::{u16_at, u32_at};
+
+/// The root directory is always inode 2.
+pub const ROOT_INODE: u32 = 2;
+
+/// `i_block` holds 12 direct blocks, then a singly, doubly and triply indirect block.
+pub const DIRECT_BLOCKS: usize = 12;
+pub const BLOCK_POINTERS: usize = DIRECT_BLOCKS + 3;
+
+const MODE_TYPE_MASK: u16 = 0xF000;
+const MODE_FILE: u16 = 0x8000;
+const MODE_DIRECTORY: u16 = 0x4000;
+const MODE_SYMLINK: u16 = 0xA000;
+
+/// Symlink targets shorter than this are stored in `i_block` instead of a block.
+pub const FAST_SYMLINK_MAX: u64 = (BLOCK_POINTERS * 4) as u64;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Ext2Kind {
+    File,
+    Directory,
+    Symlink,
+    /// Devices, fifos and sockets.
+    Other,
+}
+
+/// # Ext2 Inode
+/// A file, directory or other object on an ext2 filesystem.
+#[derive(Clone, Copy, Debug)]
+pub struct Ext2Inode {
+    number: u32,
+    mode: u16,
+    size: u64,
+    /// The number of 512 byte sectors used by this inode, including indirect blocks.
+    sectors: u32,
+    pub(super) blocks: [u32; BLOCK_POINTERS],
+}
+
+impl Ext2Inode {
+    pub(super) fn parse(number: u32, bytes: &[u8]) -> Self {
+        let mode = u16_at(bytes, 0);
+        let size_low = u32_at(bytes, 4) as u64;
+
+        // Only regular files use the high half of the size, on directories
+        // this field is an ACL.
+        let size_high = match mode & MODE_TYPE_MASK {
+            MODE_FILE => u32_at(bytes, 108) as u64,
+            _ => 0,
+        };
+
+        Self {
+            number,
+            mode,
+            size: size_low | (size_high << 32),
+            sectors: u32_at(bytes, 28),
+            blocks: core::array::from_fn(|index| u32_at(bytes, 40 + index * 4)),
+        }
+    }
+
+    pub const fn number(&self) -> u32 {
+        self.number
+    }
+
+    pub const fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// # Mode
+    /// The permission bits of this inode.
+    pub const fn mode(&self) -> u32 {
+        (self.mode & !MODE_TYPE_MASK) as u32
+    }
+
+    pub const fn kind(&self) -> Ext2Kind {
+        match self.mode & MODE_TYPE_MASK {
+            MODE_FILE => Ext2Kind::File,
+            MODE_DIRECTORY => Ext2Kind::Directory,
+            MODE_SYMLINK => Ext2Kind::Symlink,
+            _ => Ext2Kind::Other,
+        }
+    }
+
+    /// Short symlinks keep their target in the block pointers instead of a block.
+    pub(super) fn is_fast_symlink(&self) -> bool {
+        self.kind() == Ext2Kind::Symlink && self.size < FAST_SYMLINK_MAX && self.sectors == 0
+    }
+}
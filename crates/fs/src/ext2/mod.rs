@@ -0,0 +1,589 @@
+/*
+  ____                 __               __   _ __
+ / __ \__ _____ ____  / /___ ____ _    / /  (_) /
+/ /_/ / // / _ `/ _ \/ __/ // /  ' \  / /__/ / _ \
+\___\_\_,_/\_,_/_//_/\__/\_,_/_/_/_/ /____/_/_.__/
+    Part of the Quantum OS Project
+
+Copyright 2024 Gavin Kellam
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and
+associated documentation files (the "Software"), to deal in the Software without restriction,
+including without limitation the rights to use, copy, modify, merge, publish, distribute,
+sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial
+portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT
+NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
+OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+use crate::{
+    error::{FsError, Result},
+    read_block::{read_smooth_from_block_device, BlockDevice},
+    vfs::{DirEntry, FileKind, Filesystem, Metadata},
+};
+
+mod inode;
+mod superblock;
+
+pub use inode::{Ext2Inode, Ext2Kind, ROOT_INODE};
+pub use superblock::Superblock;
+
+use inode::{DIRECT_BLOCKS, FAST_SYMLINK_MAX};
+use superblock::{SUPERBLOCK_OFFSET, SUPERBLOCK_SIZE};
+
+/// Block group descriptors are 32 bytes, the inode table's block is 8 bytes in.
+const GROUP_DESCRIPTOR_SIZE: u64 = 32;
+const GROUP_INODE_TABLE: u64 = 8;
+
+/// Each directory entry starts with `inode u32, rec_len u16, name_len u8, file_type u8`.
+const DIR_ENTRY_HEADER: usize = 8;
+const MAX_NAME_LEN: usize = 255;
+
+fn u16_at(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+/// # Ext2
+/// A read-only ext2 filesystem on a block device.
+pub struct Ext2<D: BlockDevice> {
+    device: D,
+    superblock: Superblock,
+}
+
+impl<D: BlockDevice> Ext2<D> {
+    /// # New
+    /// Read the superblock from `device`, and make sure we can read this filesystem.
+    pub fn new(mut device: D) -> Result<Self> {
+        let mut bytes = [0; SUPERBLOCK_SIZE];
+        read_smooth_from_block_device(&mut device, SUPERBLOCK_OFFSET, &mut bytes)?;
+
+        let superblock = Superblock::parse(&bytes)?;
+        Ok(Self { device, superblock })
+    }
+
+    /// # Into Inner
+    /// Get the block device back out of this filesystem.
+    pub fn into_inner(self) -> D {
+        self.device
+    }
+
+    pub const fn superblock(&self) -> &Superblock {
+        &self.superblock
+    }
+
+    pub fn volume_name(&self) -> &str {
+        self.superblock.volume_name()
+    }
+
+    fn block_size(&self) -> u64 {
+        self.superblock.block_size as u64
+    }
+
+    fn read_bytes(&mut self, offset: u64, buf: &mut [u8]) -> Result<()> {
+        read_smooth_from_block_device(&mut self.device, offset, buf)?;
+        Ok(())
+    }
+
+    /// Read the `index`th block pointer out of the indirect block `block`.
+    fn read_pointer(&mut self, block: u32, index: u64) -> Result<u32> {
+        let mut pointer = [0; 4];
+        self.read_bytes(block as u64 * self.block_size() + index * 4, &mut pointer)?;
+
+        Ok(u32::from_le_bytes(pointer))
+    }
+
+    /// # Inode
+    /// Read the inode `number`, inodes are numbered from 1.
+    pub fn inode(&mut self, number: u32) -> Result<Ext2Inode> {
+        if number == 0 || number > self.superblock.inodes_count {
+            return Err(FsError::InvalidInput);
+        }
+
+        let group = ((number - 1) / self.superblock.inodes_per_group) as u64;
+        let index = ((number - 1) % self.superblock.inodes_per_group) as u64;
+
+        let descriptor = self.superblock.group_table_block() as u64 * self.block_size()
+            + group * GROUP_DESCRIPTOR_SIZE;
+
+        let mut inode_table = [0; 4];
+        self.read_bytes(descriptor + GROUP_INODE_TABLE, &mut inode_table)?;
+        let inode_table = u32::from_le_bytes(inode_table) as u64;
+
+        let mut bytes = [0; 128];
+        let inode_size = self.superblock.inode_size as u64;
+        self.read_bytes(
+            inode_table * self.block_size() + index * inode_size,
+            &mut bytes,
+        )?;
+
+        Ok(Ext2Inode::parse(number, &bytes))
+    }
+
+    /// Find the disk block holding the `logical`th block of `inode`, `0` means the
+    /// block is a hole and reads as zeros.
+    fn block_of(&mut self, inode: &Ext2Inode, logical: u64) -> Result<u32> {
+        if logical < DIRECT_BLOCKS as u64 {
+            return Ok(inode.blocks[logical as usize]);
+        }
+
+        let pointers = self.block_size() / 4;
+        let mut logical = logical - DIRECT_BLOCKS as u64;
+        let mut level_blocks = 1;
+
+        // The singly, doubly and triply indirect blocks each cover `pointers`
+        // times as many blocks as the level before them.
+        for level in 0..3 {
+            level_blocks *= pointers;
+
+            if logical >= level_blocks {
+                logical -= level_blocks;
+                continue;
+            }
+
+            let mut block = inode.blocks[DIRECT_BLOCKS + level];
+            let mut step = level_blocks;
+
+            for _ in 0..=level {
+                if block == 0 {
+                    return Ok(0);
+                }
+
+                step /= pointers;
+                block = self.read_pointer(block, logical / step)?;
+                logical %= step;
+            }
+
+            return Ok(block);
+        }
+
+        Err(FsError::InvalidInput)
+    }
+
+    /// # Read At
+    /// Read `inode` starting at `offset` into `buf`, returning how many bytes
+    /// were read (`0` at the end of the file).
+    pub fn read_at(&mut self, inode: &Ext2Inode, offset: u64, buf: &mut [u8]) -> Result<usize> {
+        let block_size = self.block_size();
+        let mut read = 0;
+
+        while read < buf.len() {
+            let position = offset + read as u64;
+            if position >= inode.size() {
+                break;
+            }
+
+            let in_block = position % block_size;
+            let len = ((block_size - in_block).min(inode.size() - position) as usize)
+                .min(buf.len() - read);
+
+            match self.block_of(inode, position / block_size)? {
+                0 => buf[read..read + len].fill(0),
+                block => self.read_bytes(
+                    block as u64 * block_size + in_block,
+                    &mut buf[read..read + len],
+                )?,
+            }
+
+            read += len;
+        }
+
+        Ok(read)
+    }
+
+    /// # Read Link
+    /// Read the target of the symlink `inode` into `buf`, returning its length.
+    pub fn read_link(&mut self, inode: &Ext2Inode, buf: &mut [u8]) -> Result<usize> {
+        if inode.kind() != Ext2Kind::Symlink {
+            return Err(FsError::InvalidInput);
+        }
+
+        if !inode.is_fast_symlink() {
+            return self.read_at(inode, 0, buf);
+        }
+
+        let mut target = [0; FAST_SYMLINK_MAX as usize];
+        for (bytes, block) in target.chunks_mut(4).zip(inode.blocks) {
+            bytes.copy_from_slice(&block.to_le_bytes());
+        }
+
+        let len = (inode.size() as usize).min(buf.len());
+        buf[..len].copy_from_slice(&target[..len]);
+
+        Ok(len)
+    }
+
+    /// Call `visit` with the name and inode number of each entry in the directory
+    /// `dir`, until it returns `Some`.
+    fn walk_dir<T>(
+        &mut self,
+        dir: &Ext2Inode,
+        mut visit: impl FnMut(&mut Self, &str, u32) -> Result<Option<T>>,
+    ) -> Result<Option<T>> {
+        if dir.kind() != Ext2Kind::Directory {
+            return Err(FsError::InvalidInput);
+        }
+
+        let mut offset = 0;
+        while offset < dir.size() {
+            let mut header = [0; DIR_ENTRY_HEADER];
+            self.read_at(dir, offset, &mut header)?;
+
+            let inode = u32_at(&header, 0);
+            let rec_len = u16_at(&header, 4) as u64;
+            let name_len = header[6] as usize;
+
+            if rec_len < DIR_ENTRY_HEADER as u64 {
+                return Err(FsError::InvalidInput);
+            }
+
+            // Unused entries have an inode of 0
+            if inode != 0 {
+                let mut name = [0; MAX_NAME_LEN];
+                self.read_at(dir, offset + DIR_ENTRY_HEADER as u64, &mut name[..name_len])?;
+
+                // Names that aren't UTF-8 can't be looked up anyway
+                if let Ok(name) = core::str::from_utf8(&name[..name_len]) {
+                    if let Some(found) = visit(self, name, inode)? {
+                        return Ok(Some(found));
+                    }
+                }
+            }
+
+            offset += rec_len;
+        }
+
+        Ok(None)
+    }
+
+    /// # Lookup
+    /// Find the inode at `path`, starting from the root directory. Symlinks
+    /// are not followed.
+    pub fn lookup(&mut self, path: &str) -> Result<Ext2Inode> {
+        let mut inode = self.inode(ROOT_INODE)?;
+
+        for part in path.split('/').filter(|part| !part.is_empty()) {
+            if inode.kind() != Ext2Kind::Directory {
+                return Err(FsError::NotFound);
+            }
+
+            let number = self
+                .walk_dir(&inode, |_, name, number| {
+                    Ok((name == part).then_some(number))
+                })?
+                .ok_or(FsError::NotFound)?;
+
+            inode = self.inode(number)?;
+        }
+
+        Ok(inode)
+    }
+
+    /// # Read Dir
+    /// Call `visit` with the name and inode of everything in the directory
+    /// `dir` (not including `.` and `..`).
+    pub fn read_dir(
+        &mut self,
+        dir: &Ext2Inode,
+        mut visit: impl FnMut(&str, &Ext2Inode),
+    ) -> Result<()> {
+        self.walk_dir(dir, |ext2, name, number| {
+            if name != "." && name != ".." {
+                visit(name, &ext2.inode(number)?);
+            }
+
+            Ok(None::<()>)
+        })?;
+
+        Ok(())
+    }
+}
+
+impl<D: BlockDevice> Filesystem for Ext2<D> {
+    fn metadata(&mut self, path: &str) -> Result<Metadata> {
+        Ok(self.lookup(path)?.into())
+    }
+
+    fn read_at(&mut self, path: &str, offset: u64, buf: &mut [u8]) -> Result<usize> {
+        let inode = self.lookup(path)?;
+        if inode.kind() != Ext2Kind::File {
+            return Err(FsError::InvalidInput);
+        }
+
+        Ext2::read_at(self, &inode, offset, buf)
+    }
+
+    fn read_dir(&mut self, path: &str, visit: &mut dyn FnMut(DirEntry)) -> Result<()> {
+        let dir = self.lookup(path)?;
+
+        Ext2::read_dir(self, &dir, |name, inode| {
+            visit(DirEntry {
+                name,
+                metadata: (*inode).into(),
+            })
+        })
+    }
+}
+
+impl From<Ext2Inode> for Metadata {
+    fn from(inode: Ext2Inode) -> Self {
+        Metadata {
+            kind: match inode.kind() {
+                Ext2Kind::File => FileKind::File,
+                Ext2Kind::Directory => FileKind::Directory,
+                Ext2Kind::Symlink | Ext2Kind::Other => FileKind::Other,
+            },
+            size: inode.size(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate std;
+
+    use super::*;
+    use crate::ramdisk::RamDisk;
+    use std::{string::String, vec, vec::Vec};
+
+    const BLOCK_SIZE: usize = 1024;
+    const INODE_TABLE: usize = 5;
+    const INODE_SIZE: usize = 128;
+
+    /// Logical block 5 of `big.bin` is left as a hole.
+    const BIG_BLOCKS: usize = 300;
+    const BIG_HOLE: usize = 5;
+
+    fn put_u16(image: &mut [u8], offset: usize, value: u16) {
+        image[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn put_u32(image: &mut [u8], offset: usize, value: u32) {
+        image[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn put_inode(image: &mut [u8], number: usize, mode: u16, size: u32, blocks: &[u32]) {
+        let offset = INODE_TABLE * BLOCK_SIZE + (number - 1) * INODE_SIZE;
+
+        put_u16(image, offset, mode);
+        put_u32(image, offset + 4, size);
+        for (index, &block) in blocks.iter().enumerate() {
+            put_u32(image, offset + 40 + index * 4, block);
+        }
+    }
+
+    fn put_dir(image: &mut [u8], block: usize, entries: &[(u32, &str)]) {
+        let mut offset = block * BLOCK_SIZE;
+
+        for (index, (inode, name)) in entries.iter().enumerate() {
+            let rec_len = if index + 1 == entries.len() {
+                (block + 1) * BLOCK_SIZE - offset
+            } else {
+                (DIR_ENTRY_HEADER + name.len()).next_multiple_of(4)
+            };
+
+            put_u32(image, offset, *inode);
+            put_u16(image, offset + 4, rec_len as u16);
+            image[offset + 6] = name.len() as u8;
+            image[offset + 8..offset + 8 + name.len()].copy_from_slice(name.as_bytes());
+
+            offset += rec_len;
+        }
+    }
+
+    fn big_byte(position: usize) -> u8 {
+        match position / BLOCK_SIZE {
+            BIG_HOLE => 0,
+            block => ((block * 7 + position) % 251) as u8,
+        }
+    }
+
+    /// Build a one group, 1 KiB block image by hand.
+    ///
+    /// | 0 boot | 1 superblock | 2 group descriptors | 3-4 bitmaps |
+    /// | 5-8 inode table | 10+ directories and small files | 100+ big.bin |
+    #[allow(clippy::needless_range_loop)]
+    fn build_image() -> Vec<u8> {
+        let mut image = vec![0; 512 * BLOCK_SIZE];
+
+        let superblock = 1024;
+        put_u32(&mut image, superblock, 32);
+        put_u32(&mut image, superblock + 4, 512);
+        put_u32(&mut image, superblock + 20, 1);
+        put_u32(&mut image, superblock + 24, 0);
+        put_u32(&mut image, superblock + 32, 8192);
+        put_u32(&mut image, superblock + 40, 32);
+        put_u16(&mut image, superblock + 56, superblock::MAGIC);
+        put_u32(&mut image, superblock + 76, 1);
+        put_u16(&mut image, superblock + 88, INODE_SIZE as u16);
+        put_u32(&mut image, superblock + 96, superblock::INCOMPAT_FILETYPE);
+        image[superblock + 120..superblock + 127].copy_from_slice(b"quantum");
+
+        put_u32(&mut image, 2 * BLOCK_SIZE + 8, INODE_TABLE as u32);
+
+        put_inode(&mut image, 2, 0x41ED, BLOCK_SIZE as u32, &[10]);
+        put_dir(
+            &mut image,
+            10,
+            &[
+                (2, "."),
+                (2, ".."),
+                (12, "hello.txt"),
+                (13, "docs"),
+                (14, "big.bin"),
+                (15, "link"),
+            ],
+        );
+
+        put_inode(&mut image, 12, 0x81A4, 13, &[13]);
+        image[13 * BLOCK_SIZE..13 * BLOCK_SIZE + 13].copy_from_slice(b"Hello, ext2!\n");
+
+        put_inode(&mut image, 13, 0x41ED, BLOCK_SIZE as u32, &[11]);
+        put_dir(&mut image, 11, &[(13, "."), (2, ".."), (16, "notes.txt")]);
+
+        put_inode(&mut image, 16, 0x81A4, 5, &[12]);
+        image[12 * BLOCK_SIZE..12 * BLOCK_SIZE + 5].copy_from_slice(b"notes");
+
+        // A fast symlink keeps its target in the block pointers
+        put_inode(&mut image, 15, 0xA1FF, 9, &[]);
+        image[INODE_TABLE * BLOCK_SIZE + 14 * INODE_SIZE + 40..][..9].copy_from_slice(b"hello.txt");
+
+        // big.bin goes through the singly (block 20) and doubly (block 21 -> 22)
+        // indirect blocks.
+        let pointers = BLOCK_SIZE / 4;
+        let mut direct = [0u32; DIRECT_BLOCKS + 2];
+        for logical in 0..BIG_BLOCKS {
+            let block = 100 + logical;
+            let pointer = match logical {
+                BIG_HOLE => 0,
+                _ => block as u32,
+            };
+
+            match logical {
+                0..DIRECT_BLOCKS => direct[logical] = pointer,
+                _ if logical < DIRECT_BLOCKS + pointers => put_u32(
+                    &mut image,
+                    20 * BLOCK_SIZE + (logical - DIRECT_BLOCKS) * 4,
+                    pointer,
+                ),
+                _ => {
+                    let index = logical - DIRECT_BLOCKS - pointers;
+                    assert!(index < pointers);
+                    put_u32(&mut image, 22 * BLOCK_SIZE + index * 4, pointer);
+                }
+            }
+
+            if pointer != 0 {
+                for offset in 0..BLOCK_SIZE {
+                    image[block * BLOCK_SIZE + offset] = big_byte(logical * BLOCK_SIZE + offset);
+                }
+            }
+        }
+
+        direct[DIRECT_BLOCKS] = 20;
+        direct[DIRECT_BLOCKS + 1] = 21;
+        put_u32(&mut image, 21 * BLOCK_SIZE, 22);
+        put_inode(
+            &mut image,
+            14,
+            0x81A4,
+            (BIG_BLOCKS * BLOCK_SIZE) as u32,
+            &direct,
+        );
+
+        image
+    }
+
+    #[test]
+    fn test_superblock() {
+        let image = build_image();
+        let ext2 = Ext2::new(RamDisk::<512>::new(&image).unwrap()).unwrap();
+
+        assert_eq!(ext2.volume_name(), "quantum");
+        assert_eq!(ext2.superblock().block_size, 1024);
+        assert!(Ext2::new(RamDisk::<512>::new(&[0; 4096]).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_read_file() {
+        let image = build_image();
+        let mut ext2 = Ext2::new(RamDisk::<512>::new(&image).unwrap()).unwrap();
+
+        let hello = ext2.lookup("/hello.txt").unwrap();
+        assert_eq!(hello.kind(), Ext2Kind::File);
+        assert_eq!(hello.mode(), 0o644);
+
+        let mut buf = [0; 32];
+        let len = ext2.read_at(&hello, 0, &mut buf).unwrap();
+        assert_eq!(&buf[..len], b"Hello, ext2!\n");
+
+        let notes = ext2.lookup("docs/../docs/notes.txt").unwrap();
+        let len = ext2.read_at(&notes, 2, &mut buf).unwrap();
+        assert_eq!(&buf[..len], b"tes");
+
+        assert!(matches!(ext2.lookup("hello.txt/x"), Err(FsError::NotFound)));
+        assert!(matches!(ext2.lookup("missing"), Err(FsError::NotFound)));
+    }
+
+    #[test]
+    fn test_indirect_blocks() {
+        let image = build_image();
+        let mut ext2 = Ext2::new(RamDisk::<512>::new(&image).unwrap()).unwrap();
+
+        let big = ext2.lookup("big.bin").unwrap();
+        let mut data = vec![0xFF; BIG_BLOCKS * BLOCK_SIZE + 100];
+        let len = ext2.read_at(&big, 0, &mut data).unwrap();
+
+        assert_eq!(len, BIG_BLOCKS * BLOCK_SIZE);
+        for (position, &byte) in data[..len].iter().enumerate() {
+            assert_eq!(byte, big_byte(position), "Byte {position} of big.bin");
+        }
+
+        // Across the end of the singly indirect blocks
+        let start = (DIRECT_BLOCKS + 256) * BLOCK_SIZE - 10;
+        let mut buf = [0; 20];
+        ext2.read_at(&big, start as u64, &mut buf).unwrap();
+        assert!(buf
+            .iter()
+            .enumerate()
+            .all(|(index, &byte)| byte == big_byte(start + index)));
+    }
+
+    #[test]
+    fn test_read_dir_and_link() {
+        let image = build_image();
+        let mut ext2 = Ext2::new(RamDisk::<512>::new(&image).unwrap()).unwrap();
+
+        let root = ext2.lookup("/").unwrap();
+        let mut names = Vec::new();
+        ext2.read_dir(&root, |name, inode| {
+            names.push((String::from(name), inode.kind()))
+        })
+        .unwrap();
+
+        assert_eq!(
+            names,
+            [
+                (String::from("hello.txt"), Ext2Kind::File),
+                (String::from("docs"), Ext2Kind::Directory),
+                (String::from("big.bin"), Ext2Kind::File),
+                (String::from("link"), Ext2Kind::Symlink),
+            ]
+        );
+
+        let link = ext2.lookup("link").unwrap();
+        let mut target = [0; 16];
+        let len = ext2.read_link(&link, &mut target).unwrap();
+        assert_eq!(&target[..len], b"hello.txt");
+    }
+}
@@ -0,0 +1,119 @@
+/*
+  ____                 __               __   _ __
+ / __ \__ _____ ____  / /___ ____ _    / /  (_) /
+/ /_/ / // / _ `/ _ \/ __/ // /  ' \  / /__/ / _ \
+\___\_\_,_/\_,_/_//_/\__/\_,_/_/_/_/ /____/_/_.__/
+    Part of the Quantum OS Project
+
+Copyright 2024 Gavin Kellam
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and
+associated documentation files (the "Software"), to deal in the Software without restriction,
+including without limitation the rights to use, copy, modify, merge, publish, distribute,
+sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial
+portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT
+NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
+OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+use super::{u16_at, u32_at};
+use crate::error::{FsError, Result};
+
+/// The superblock is always 1024 bytes into the disk, whatever the block size.
+pub const SUPERBLOCK_OFFSET: u64 = 1024;
+pub const SUPERBLOCK_SIZE: usize = 1024;
+pub const MAGIC: u16 = 0xEF53;
+
+/// Directory entries store the kind of file they point to.
+pub const INCOMPAT_FILETYPE: u32 = 0x0002;
+
+/// Any other incompatible feature changes the layout in ways we can't read.
+const SUPPORTED_INCOMPAT: u32 = INCOMPAT_FILETYPE;
+
+/// Blocks above 4 KiB aren't supported, so one always fits on the stack.
+pub const MAX_BLOCK_SIZE: usize = 4096;
+
+/// Revision 0 has fixed 128 byte inodes.
+const GOOD_OLD_INODE_SIZE: u16 = 128;
+
+/// # Superblock
+/// The parts of the ext2 superblock needed to read the filesystem.
+#[derive(Clone, Copy, Debug)]
+pub struct Superblock {
+    pub inodes_count: u32,
+    pub blocks_count: u32,
+    pub first_data_block: u32,
+    pub block_size: u32,
+    pub inodes_per_group: u32,
+    pub inode_size: u16,
+    pub rev_level: u32,
+    volume_name: [u8; 16],
+}
+
+impl Superblock {
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < SUPERBLOCK_SIZE || u16_at(bytes, 56) != MAGIC {
+            return Err(FsError::InvalidInput);
+        }
+
+        let log_block_size = u32_at(bytes, 24);
+        if log_block_size > (MAX_BLOCK_SIZE / 1024).ilog2() {
+            return Err(FsError::NotSupported);
+        }
+
+        let rev_level = u32_at(bytes, 76);
+        let (inode_size, incompat) = match rev_level {
+            0 => (GOOD_OLD_INODE_SIZE, 0),
+            _ => (u16_at(bytes, 88), u32_at(bytes, 96)),
+        };
+
+        if incompat & !SUPPORTED_INCOMPAT != 0 {
+            return Err(FsError::NotSupported);
+        }
+
+        let superblock = Self {
+            inodes_count: u32_at(bytes, 0),
+            blocks_count: u32_at(bytes, 4),
+            first_data_block: u32_at(bytes, 20),
+            block_size: 1024 << log_block_size,
+            inodes_per_group: u32_at(bytes, 40),
+            inode_size,
+            rev_level,
+            volume_name: bytes[120..136].try_into().unwrap(),
+        };
+
+        if superblock.inodes_per_group == 0
+            || superblock.inode_size < GOOD_OLD_INODE_SIZE
+            || !superblock.inode_size.is_power_of_two()
+            || superblock.inode_size as u32 > superblock.block_size
+        {
+            return Err(FsError::InvalidInput);
+        }
+
+        Ok(superblock)
+    }
+
+    /// # Volume Name
+    /// The label of the filesystem, empty if it doesn't have one.
+    pub fn volume_name(&self) -> &str {
+        let len = self
+            .volume_name
+            .iter()
+            .position(|&c| c == 0)
+            .unwrap_or(self.volume_name.len());
+
+        core::str::from_utf8(&self.volume_name[..len]).unwrap_or("")
+    }
+
+    /// The block holding the table of block group descriptors.
+    pub fn group_table_block(&self) -> u32 {
+        self.first_data_block + 1
+    }
+}
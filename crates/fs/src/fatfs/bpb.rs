@@ -64,7 +64,7 @@ struct Bpb16 {
 struct Bpb32 {
     fat_size: u32,
     ext_flags: u16,
-    fat_version: u32,
+    fat_version: u16,
     root_cluster: u32,
     fs_info: u16,
     boot_sector: u16,
@@ -129,7 +129,7 @@ impl Bpb {
         }
     }
 
-    fn fat_sectors(&self) -> usize {
+    pub fn fat_sectors(&self) -> usize {
         if self.fat_sectors_fat16 != 0 {
             self.fat_sectors_fat16 as usize
         } else {
@@ -137,7 +137,7 @@ impl Bpb {
         }
     }
 
-    fn clusters(&self) -> usize {
+    pub fn clusters(&self) -> usize {
         let data_sectors = self.total_sectors()
             - (self.reserved_sectors as usize
                 + (self.number_fats as usize * self.fat_sectors())
@@ -199,6 +199,29 @@ impl Bpb {
         self.sectors_per_cluster as usize
     }
 
+    pub fn cluster_bytes(&self) -> u64 {
+        (self.cluster_sectors() * self.sector_size()) as u64
+    }
+
+    pub fn fat_count(&self) -> usize {
+        self.number_fats as usize
+    }
+
+    /// The number of entries in the fixed root directory (always 0 on FAT32).
+    pub fn root_entries(&self) -> usize {
+        self.root_entries as usize
+    }
+
+    /// The sector holding the FAT32 FSInfo structure, if there is one.
+    pub fn fs_info_sector(&self) -> Option<u64> {
+        match self.safe_extended() {
+            ExtendedKind::Fat32(ext) if ext.fs_info != 0 && ext.fs_info != u16::MAX => {
+                Some(ext.fs_info as u64)
+            }
+            _ => None,
+        }
+    }
+
     pub fn fat_entry_bytes(&self) -> usize {
         match self.kind() {
             FatKind::Fat12 => todo!("Fat12 not impl"),
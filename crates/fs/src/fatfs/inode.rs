@@ -133,7 +133,317 @@ impl<'a> TryFrom<&'a [u8]> for Inode {
 }
 
 impl DirectoryEntry {
+    pub const ATTR_VOLUME_ID: u8 = 0x08;
+    pub const ATTR_DIRECTORY: u8 = 0x10;
+    pub const ATTR_ARCHIVE: u8 = 0x20;
+
+    /// Entries with this as their first name byte were deleted.
+    pub const DELETED: u8 = 0xE5;
+
+    pub(super) fn new(name: [u8; 11], attributes: u8, cluster: ClusterId) -> Self {
+        let mut entry = Self {
+            name,
+            attributes,
+            reserved: 0,
+            time_tenth: 0,
+            creation_time: 0,
+            creation_date: 0,
+            last_access_date: 0,
+            cluster_high: 0,
+            modified_time: 0,
+            modified_date: 0,
+            cluster_low: 0,
+            file_size: 0,
+        };
+
+        entry.set_cluster_id(cluster);
+        entry
+    }
+
     pub fn cluster_id(&self) -> ClusterId {
         self.cluster_low as u32 | ((self.cluster_high as u32) << 16)
     }
+
+    pub(super) fn set_cluster_id(&mut self, id: ClusterId) {
+        self.cluster_low = id as u16;
+        self.cluster_high = (id >> 16) as u16;
+    }
+
+    pub fn file_size(&self) -> u32 {
+        self.file_size
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.attributes & Self::ATTR_DIRECTORY != 0
+    }
+
+    pub fn is_volume_label(&self) -> bool {
+        self.attributes & Self::ATTR_VOLUME_ID != 0
+    }
+
+    /// Compare `name` against this entry's 8.3 name (ignoring case).
+    pub fn short_name_eq(&self, name: &str) -> bool {
+        let base = trim_padding(&self.name[..8]);
+        let ext = trim_padding(&self.name[8..]);
+
+        let name = name.as_bytes();
+        if ext.is_empty() {
+            return name.eq_ignore_ascii_case(base);
+        }
+
+        name.len() == base.len() + 1 + ext.len()
+            && name[..base.len()].eq_ignore_ascii_case(base)
+            && name[base.len()] == b'.'
+            && name[base.len() + 1..].eq_ignore_ascii_case(ext)
+    }
+
+    /// Write this entry's 8.3 name into `buf` as `NAME.EXT`.
+    pub fn short_name<'b>(&self, buf: &'b mut [u8; 12]) -> &'b str {
+        let base = trim_padding(&self.name[..8]);
+        let ext = trim_padding(&self.name[8..]);
+
+        buf[..base.len()].copy_from_slice(base);
+        let mut len = base.len();
+
+        if !ext.is_empty() {
+            buf[len] = b'.';
+            buf[len + 1..len + 1 + ext.len()].copy_from_slice(ext);
+            len += 1 + ext.len();
+        }
+
+        core::str::from_utf8(&buf[..len]).unwrap_or("")
+    }
+
+    /// The checksum of the 8.3 name, which every long file name entry for this
+    /// entry must carry.
+    pub(super) fn checksum(&self) -> u8 {
+        self.name
+            .iter()
+            .fold(0u8, |sum, &byte| sum.rotate_right(1).wrapping_add(byte))
+    }
+
+    pub(super) fn to_bytes(self) -> [u8; size_of::<DirectoryEntry>()] {
+        unsafe { core::mem::transmute(self) }
+    }
+}
+
+impl LongFileName {
+    /// Set on the ordering of the final piece of a name (which comes first on disk).
+    pub const LAST_ENTRY: u8 = 0x40;
+    pub const ATTRIBUTES: u8 = 0x0F;
+
+    /// The number of characters stored in each long file name entry.
+    pub const CHARS: usize = 13;
+
+    /// Make the entry holding `piece` (at most 13 chars) of a name, `ordering` starts at 1.
+    pub(super) fn new(ordering: u8, checksum: u8, piece: &[u8]) -> Self {
+        // The name is null terminated (unless it fills the entry), then padded with 0xFFFF
+        let mut wchars = [0xFFFFu16; Self::CHARS];
+        wchars
+            .iter_mut()
+            .zip(piece.iter().map(|&c| c as u16).chain([0]))
+            .for_each(|(wchar, c)| *wchar = c);
+
+        Self {
+            ordering,
+            wchar_low: wchars[..5].try_into().unwrap(),
+            attributes: Self::ATTRIBUTES,
+            kind: 0,
+            checksum,
+            wchar_mid: wchars[5..11].try_into().unwrap(),
+            reserved: 0,
+            wchar_high: wchars[11..].try_into().unwrap(),
+        }
+    }
+
+    pub(super) fn to_bytes(self) -> [u8; size_of::<LongFileName>()] {
+        unsafe { core::mem::transmute(self) }
+    }
+}
+
+/// # Long Name
+/// Collects the pieces of a long file name as its entries are walked.
+pub(super) struct LongName {
+    chars: [u8; 20 * LongFileName::CHARS],
+    len: usize,
+}
+
+impl LongName {
+    pub const fn new() -> Self {
+        Self {
+            chars: [0; 20 * LongFileName::CHARS],
+            len: 0,
+        }
+    }
+
+    pub fn push(&mut self, lfn: &LongFileName) {
+        let ordering = ((lfn.ordering & !LongFileName::LAST_ENTRY) as usize).saturating_sub(1);
+        let offset = ordering * LongFileName::CHARS;
+
+        if offset + LongFileName::CHARS > self.chars.len() {
+            return;
+        }
+
+        let inode = Inode::LongFileName(*lfn);
+        let piece = inode
+            .name_iter()
+            .take_while(|&c| c != '\0')
+            .filter(|c| c.is_ascii());
+
+        for (index, c) in piece.enumerate() {
+            self.chars[offset + index] = c as u8;
+            self.len = self.len.max(offset + index + 1);
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        core::str::from_utf8(&self.chars[..self.len])
+            .unwrap_or("")
+            .trim()
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+}
+
+/// The largest `~N` tail a short name can have, `~999999` leaves one character
+/// of the name.
+pub(super) const MAX_SHORT_NAME_TAIL: u32 = 999_999;
+
+/// # Short Name
+/// Make the 8.3 name stored for `name`. `tail` adds the `~N` suffix used to tell
+/// apart names that shorten to the same thing.
+///
+/// Returns `None` if `name` can't be stored losslessly as a short name and no
+/// tail was given, or if `tail` is above [`MAX_SHORT_NAME_TAIL`].
+pub(super) fn short_name(name: &str, tail: Option<u32>) -> Option<[u8; 11]> {
+    const SPECIAL: &[u8] = b"!#$%&'()-@^_`{}~";
+    let mut short = [b' '; 11];
+
+    if tail.is_some_and(|tail| tail > MAX_SHORT_NAME_TAIL) {
+        return None;
+    }
+
+    let (base, ext) = match name.rfind('.') {
+        Some(dot) if dot != 0 => (&name[..dot], &name[dot + 1..]),
+        _ => (name, ""),
+    };
+
+    let lossless = tail.is_none()
+        && !name.starts_with('.')
+        && base.len() <= 8
+        && ext.len() <= 3
+        && name
+            .bytes()
+            .filter(|&c| c != b'.')
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || SPECIAL.contains(&c))
+        && name.bytes().filter(|&c| c == b'.').count() <= 1;
+
+    if tail.is_none() && !lossless {
+        return None;
+    }
+
+    let convert = |c: u8| match c {
+        c if c.is_ascii_alphanumeric() || SPECIAL.contains(&c) => Some(c.to_ascii_uppercase()),
+        b' ' | b'.' => None,
+        _ => Some(b'_'),
+    };
+
+    let mut tail_str = [0u8; 8];
+    let tail_len = match tail {
+        Some(mut tail) => {
+            let mut digits = [0u8; 6];
+            let mut digit_count = 0;
+
+            while digit_count == 0 || tail != 0 {
+                digits[digit_count] = b'0' + (tail % 10) as u8;
+                tail /= 10;
+                digit_count += 1;
+            }
+
+            tail_str[0] = b'~';
+            for index in 0..digit_count {
+                tail_str[index + 1] = digits[digit_count - index - 1];
+            }
+
+            digit_count + 1
+        }
+        None => 0,
+    };
+
+    let base_len = base
+        .bytes()
+        .filter_map(convert)
+        .take(8 - tail_len)
+        .zip(short[..8].iter_mut())
+        .map(|(c, short_c)| *short_c = c)
+        .count();
+
+    short[base_len..base_len + tail_len].copy_from_slice(&tail_str[..tail_len]);
+
+    ext.bytes()
+        .filter_map(convert)
+        .take(3)
+        .zip(short[8..].iter_mut())
+        .for_each(|(c, short_c)| *short_c = c);
+
+    // 0xE5 marks a deleted entry, so names starting with it are stored as 0x05
+    if short[0] == DirectoryEntry::DELETED {
+        short[0] = 0x05;
+    }
+
+    Some(short)
+}
+
+/// The `N` of a short name's `~N` tail, if it has one.
+pub(super) fn short_name_tail(short: &[u8; 11]) -> Option<u32> {
+    let base = trim_padding(&short[..8]);
+    let digits = &base[base.iter().rposition(|&c| c == b'~')? + 1..];
+
+    if digits.is_empty() || !digits.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+
+    core::str::from_utf8(digits).ok()?.parse().ok()
+}
+
+fn trim_padding(name: &[u8]) -> &[u8] {
+    let len = name
+        .iter()
+        .rposition(|&c| c != b' ')
+        .map_or(0, |end| end + 1);
+    &name[..len]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_short_name_tails() {
+        assert_eq!(short_name("README.TXT", None), Some(*b"README  TXT"));
+        assert_eq!(short_name("readme.txt", None), None);
+        assert_eq!(
+            short_name("longfilename.txt", Some(12)),
+            Some(*b"LONGF~12TXT")
+        );
+        assert_eq!(
+            short_name("longfilename.txt", Some(MAX_SHORT_NAME_TAIL)),
+            Some(*b"L~999999TXT")
+        );
+        assert_eq!(
+            short_name("longfilename.txt", Some(MAX_SHORT_NAME_TAIL + 1)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_short_name_tail_parsing() {
+        assert_eq!(short_name_tail(b"LONGF~12TXT"), Some(12));
+        assert_eq!(short_name_tail(b"L~999999TXT"), Some(999_999));
+        assert_eq!(short_name_tail(b"README  TXT"), None);
+        assert_eq!(short_name_tail(b"A~B     TXT"), None);
+        assert_eq!(short_name_tail(b"TILDE~  TXT"), None);
+    }
 }
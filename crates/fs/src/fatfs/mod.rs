@@ -29,8 +29,12 @@ use crate::{
     io::SeekFrom,
 };
 use crate::{
-    fatfs::inode::{DirectoryEntry, Inode},
-    io::{Read, Seek},
+    fatfs::inode::{
+        short_name, short_name_tail, DirectoryEntry, Inode, LongFileName, LongName,
+        MAX_SHORT_NAME_TAIL,
+    },
+    io::{Read, Seek, Write},
+    vfs::{DirEntry, FileKind, Filesystem, Metadata},
 };
 use core::{fmt::Debug, mem::size_of};
 
@@ -47,9 +51,16 @@ pub enum FatKind {
 pub trait ReadSeek: Read + Seek {}
 impl<T: Read + Seek> ReadSeek for T {}
 
+pub trait ReadWriteSeek: Read + Write + Seek {}
+impl<T: Read + Write + Seek> ReadWriteSeek for T {}
+
 pub struct Fat<Part: ReadSeek> {
     disk: Part,
     bpb: Bpb,
+    /// Where to start looking for a free cluster.
+    free_hint: ClusterId,
+    /// Set once the FSInfo free cluster count has been marked unknown.
+    fs_info_invalidated: bool,
 }
 
 type ClusterId = u32;
@@ -73,7 +84,9 @@ impl FatEntry {
     const FAT32_MAX: u32 = 0xffffff4;
     const FAT32_RESERVED_END: u32 = 0xffffff6;
     const FAT32_DEFECTIVE: u32 = Self::FAT32_RESERVED_END + 1;
-    const FAT32_EOF: u32 = u32::MAX;
+    const FAT32_EOF: u32 = 0xfffffff;
+    /// The top 4 bits of a FAT32 entry are reserved, and must be kept as-is.
+    const FAT32_MASK: u32 = 0xfffffff;
 
     fn from_fat16(id: ClusterId) -> FatEntry {
         match id {
@@ -81,28 +94,59 @@ impl FatEntry {
             Self::ALLOCATED_CLUSTER_BEGIN..=Self::FAT16_MAX => FatEntry::Next(id),
             ..=Self::FAT16_RESERVED_END => FatEntry::Reserved,
             Self::FAT16_DEFECTIVE => FatEntry::Defective,
-            Self::FAT16_EOF => FatEntry::EOF,
+            ..=Self::FAT16_EOF => FatEntry::EOF,
             _ => unreachable!("ClusterID Unknown"),
         }
     }
 
     fn from_fat32(id: ClusterId) -> FatEntry {
-        match id {
+        match id & Self::FAT32_MASK {
             Self::FREE_CLUSTER => FatEntry::Free,
-            Self::ALLOCATED_CLUSTER_BEGIN..=Self::FAT32_MAX => FatEntry::Next(id),
+            id @ Self::ALLOCATED_CLUSTER_BEGIN..=Self::FAT32_MAX => FatEntry::Next(id),
             ..=Self::FAT32_RESERVED_END => FatEntry::Reserved,
             Self::FAT32_DEFECTIVE => FatEntry::Defective,
-            Self::FAT32_EOF => FatEntry::EOF,
-            _ => unreachable!("ClusterID Unknown"),
+            _ => FatEntry::EOF,
+        }
+    }
+
+    fn into_fat16(self) -> u32 {
+        match self {
+            FatEntry::Free => Self::FREE_CLUSTER,
+            FatEntry::Next(id) => id,
+            FatEntry::EOF => Self::FAT16_EOF,
+            FatEntry::Reserved => Self::FAT16_RESERVED_END,
+            FatEntry::Defective => Self::FAT16_DEFECTIVE,
+        }
+    }
+
+    fn into_fat32(self) -> u32 {
+        match self {
+            FatEntry::Free => Self::FREE_CLUSTER,
+            FatEntry::Next(id) => id,
+            FatEntry::EOF => Self::FAT32_EOF,
+            FatEntry::Reserved => Self::FAT32_RESERVED_END,
+            FatEntry::Defective => Self::FAT32_DEFECTIVE,
         }
     }
 }
 
+const DIR_ENTRY_SIZE: usize = size_of::<DirectoryEntry>();
+
+/// The most entries one file can take up, a 255 char long name and its short name.
+const MAX_NAME_ENTRIES: usize = 255_usize.div_ceil(LongFileName::CHARS) + 1;
+
 pub struct FatFile<'a, Part: ReadSeek> {
     filesize: usize,
     start_cluster: ClusterId,
+    /// Where this file's directory entry is on disk.
+    entry_loc: u64,
+    directory: bool,
     fatfs: &'a mut Fat<Part>,
     seek: u64,
+    /// How many clusters this file has and its last one, once a write has found them.
+    tail: Option<(u64, ClusterId)>,
+    /// The index and id of the cluster the last write ended in.
+    cursor: Option<(u64, ClusterId)>,
 }
 
 impl<'a, Part: ReadSeek> FatFile<'a, Part> {
@@ -111,16 +155,222 @@ impl<'a, Part: ReadSeek> FatFile<'a, Part> {
     }
 }
 
-impl<'a, Part> FatFile<'a, Part> where Part: ReadSeek {}
+impl<'a, Part> FatFile<'a, Part>
+where
+    Part: ReadWriteSeek,
+{
+    /// # Set Len
+    /// Truncate or extend this file to `len` bytes. Extending fills the new
+    /// space with zeros, and truncating gives the freed clusters back.
+    pub fn set_len(&mut self, len: u64) -> Result<()> {
+        if self.directory {
+            return Err(FsError::InvalidInput);
+        }
+
+        let filesize = self.filesize as u64;
+        if len > filesize {
+            let seek = self.seek;
+            self.seek = filesize;
+            self.write_zeros(len - filesize)?;
+            self.seek = seek;
+
+            return self.update_entry();
+        }
+
+        // The chain is about to shrink
+        self.tail = None;
+        self.cursor = None;
+
+        let cluster_bytes = self.fatfs.bpb.cluster_bytes();
+        let keep_clusters = len.div_ceil(cluster_bytes);
+
+        if keep_clusters == 0 {
+            if self.start_cluster != 0 {
+                self.fatfs.free_chain(self.start_cluster)?;
+            }
+
+            self.start_cluster = 0;
+        } else {
+            let (last, _) = self
+                .fatfs
+                .cluster_of_offset(self.start_cluster, (keep_clusters - 1) * cluster_bytes)?;
+
+            if let FatEntry::Next(next) = self.fatfs.read_fat(last)? {
+                self.fatfs.write_fat(last, FatEntry::EOF)?;
+                self.fatfs.free_chain(next)?;
+            }
+        }
+
+        self.filesize = len as usize;
+        self.update_entry()
+    }
+
+    /// Make sure this file has enough clusters to hold `len` bytes.
+    fn reserve(&mut self, len: u64) -> Result<()> {
+        let needed = len.div_ceil(self.fatfs.bpb.cluster_bytes());
+        if needed == 0 {
+            return Ok(());
+        }
+
+        if self.start_cluster == 0 {
+            self.start_cluster = self.fatfs.alloc_cluster(None)?;
+            self.tail = Some((1, self.start_cluster));
+        }
+
+        let (mut clusters, mut cluster) = match self.tail {
+            Some(tail) => tail,
+            None => {
+                let mut cluster = self.start_cluster;
+                let mut clusters = 1;
+
+                loop {
+                    match self.fatfs.read_fat(cluster)? {
+                        FatEntry::Next(next) => {
+                            cluster = next;
+                            clusters += 1;
+                        }
+                        FatEntry::EOF => break,
+                        _ => return Err(FsError::ReadError),
+                    }
+                }
+
+                (clusters, cluster)
+            }
+        };
+
+        while clusters < needed {
+            cluster = self.fatfs.alloc_cluster(Some(cluster))?;
+            clusters += 1;
+        }
+
+        self.tail = Some((clusters, cluster));
+        Ok(())
+    }
+
+    /// The cluster holding byte `offset` of this file, and the offset into it.
+    ///
+    /// This walks forward from the cluster the last write ended in when it can, so
+    /// sequential writes don't walk the whole chain every time.
+    fn locate(&mut self, offset: u64) -> Result<(ClusterId, u64)> {
+        let cluster_bytes = self.fatfs.bpb.cluster_bytes();
+        let index = offset / cluster_bytes;
+
+        let (mut at, mut cluster) = match self.cursor {
+            Some((at, cluster)) if at <= index => (at, cluster),
+            _ => (0, self.start_cluster),
+        };
+
+        while at < index {
+            cluster = match self.fatfs.read_fat(cluster)? {
+                FatEntry::Next(next) => next,
+                FatEntry::EOF => return Err(FsError::EndOfFile),
+                _ => return Err(FsError::ReadError),
+            };
+            at += 1;
+        }
+
+        Ok((cluster, offset % cluster_bytes))
+    }
+
+    fn write_zeros(&mut self, mut len: u64) -> Result<()> {
+        let zeros = [0u8; 512];
+
+        while len != 0 {
+            let chunk = len.min(zeros.len() as u64) as usize;
+            self.write_inner(&zeros[..chunk])?;
+            len -= chunk as u64;
+        }
+
+        Ok(())
+    }
+
+    /// Write `buf` at the seek position without updating the directory entry.
+    fn write_inner(&mut self, buf: &[u8]) -> Result<usize> {
+        // Writing past the end leaves a gap, which must read back as zeros
+        if self.seek > self.filesize as u64 {
+            let gap_end = self.seek;
+            self.seek = self.filesize as u64;
+            self.write_zeros(gap_end - self.seek)?;
+        }
+
+        let write_end = self.seek + buf.len() as u64;
+        if write_end > u32::MAX as u64 {
+            return Err(FsError::NoSpace);
+        }
+
+        self.reserve(write_end)?;
+
+        let cluster_bytes = self.fatfs.bpb.cluster_bytes();
+        let mut bytes_written = 0;
+        let (mut cluster, mut cluster_offset) = self.locate(self.seek)?;
+        let mut index = self.seek / cluster_bytes;
+
+        loop {
+            let bytes = ((cluster_bytes - cluster_offset) as usize).min(buf.len() - bytes_written);
+            let disk_loc = self.fatfs.bpb.cluster_physical_loc(cluster) + cluster_offset;
+
+            self.fatfs.disk.seek(SeekFrom::Start(disk_loc))?;
+            self.fatfs
+                .disk
+                .write(&buf[bytes_written..bytes_written + bytes])?;
+
+            bytes_written += bytes;
+            self.seek += bytes as u64;
+
+            if bytes_written == buf.len() {
+                break;
+            }
+
+            cluster = match self.fatfs.read_fat(cluster)? {
+                FatEntry::Next(next) => next,
+                _ => return Err(FsError::WriteError),
+            };
+            cluster_offset = 0;
+            index += 1;
+        }
+
+        self.cursor = Some((index, cluster));
+
+        if self.seek > self.filesize as u64 {
+            self.filesize = self.seek as usize;
+        }
+
+        Ok(bytes_written)
+    }
+
+    /// Write this file's size and first cluster back into its directory entry.
+    fn update_entry(&mut self) -> Result<()> {
+        let mut entry_bytes = [0u8; DIR_ENTRY_SIZE];
+        self.fatfs.disk.seek(SeekFrom::Start(self.entry_loc))?;
+        self.fatfs.disk.read(&mut entry_bytes)?;
+
+        let mut entry: DirectoryEntry = unsafe { *entry_bytes.as_ptr().cast() };
+        entry.set_cluster_id(self.start_cluster);
+        entry.file_size = self.filesize as u32;
+
+        self.fatfs.disk.seek(SeekFrom::Start(self.entry_loc))?;
+        self.fatfs.disk.write(&entry.to_bytes())?;
+
+        Ok(())
+    }
+}
+
 impl<'a, Part> Seek for FatFile<'a, Part>
 where
     Part: ReadSeek,
 {
     fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
-        match pos {
-            SeekFrom::Start(pos) => self.seek = pos,
-            _ => todo!("SeekFrom is not fully implemented, only Start(x) is implemented"),
-        }
+        self.seek = match pos {
+            SeekFrom::Start(pos) => pos,
+            SeekFrom::End(delta) => (self.filesize as u64)
+                .checked_add_signed(delta)
+                .ok_or(FsError::InvalidInput)?,
+            SeekFrom::Current(delta) => self
+                .seek
+                .checked_add_signed(delta)
+                .ok_or(FsError::InvalidInput)?,
+        };
+
         Ok(self.seek)
     }
 
@@ -128,7 +378,6 @@ where
         self.seek
     }
 }
-
 impl<'a, Part> Read for FatFile<'a, Part>
 where
     Part: ReadSeek,
@@ -138,17 +387,37 @@ where
             (self.fatfs.bpb.cluster_sectors() * self.fatfs.bpb.sector_size()) as u64;
         let mut bytes_read = 0;
 
+        let (mut cluster, mut cluster_offset) = self
+            .fatfs
+            .cluster_of_offset(self.start_cluster, self.seek)?;
+
         loop {
-            let cluster_info = self
-                .fatfs
-                .cluster_of_offset(self.start_cluster, self.seek)?;
+            let bytes_remaining = (buf.len() - bytes_read) as u64;
+            let run_start = cluster;
+            let mut run_bytes = cluster_bytes - cluster_offset;
+            let mut next_cluster = None;
+
+            // Clusters that follow each other on disk are read as one run, so the
+            // disk can fetch them in a single large request.
+            while run_bytes < bytes_remaining {
+                match self.fatfs.read_fat(cluster)? {
+                    FatEntry::Next(next) if next == cluster + 1 => {
+                        cluster = next;
+                        run_bytes += cluster_bytes;
+                    }
+                    FatEntry::Next(next) => {
+                        next_cluster = Some(next);
+                        break;
+                    }
+                    FatEntry::EOF => break,
+                    _ => return Err(FsError::ReadError),
+                }
+            }
 
-            let disk_loc = self.fatfs.bpb.cluster_physical_loc(cluster_info.0) + cluster_info.1;
+            let disk_loc = self.fatfs.bpb.cluster_physical_loc(run_start) + cluster_offset;
+            let bytes_until_read_end = run_bytes.min(bytes_remaining);
 
             self.fatfs.disk.seek(SeekFrom::Start(disk_loc))?;
-            let bytes_until_cluster_end = cluster_bytes - cluster_info.1;
-            let bytes_until_read_end = bytes_until_cluster_end.min((buf.len() - bytes_read) as u64);
-
             self.fatfs
                 .disk
                 .read(&mut buf[bytes_read..bytes_read + bytes_until_read_end as usize])?;
@@ -164,15 +433,60 @@ where
             if bytes_read == buf.len() {
                 return Ok(bytes_read);
             }
+
+            cluster = match next_cluster {
+                Some(next) => next,
+                None => match self.fatfs.read_fat(cluster)? {
+                    FatEntry::Next(next) => next,
+                    FatEntry::EOF => return Err(FsError::EndOfFile),
+                    _ => return Err(FsError::ReadError),
+                },
+            };
+            cluster_offset = 0;
         }
     }
 }
 
+impl<'a, Part> Write for FatFile<'a, Part>
+where
+    Part: ReadWriteSeek,
+{
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        if self.directory {
+            return Err(FsError::InvalidInput);
+        }
+
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let bytes_written = self.write_inner(buf)?;
+        self.update_entry()?;
+
+        Ok(bytes_written)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.fatfs.disk.flush()
+    }
+}
+
 impl<Part: ReadSeek> Fat<Part> {
     pub fn new(mut disk: Part) -> Result<Self> {
         let bpb = Bpb::new(&mut disk)?;
 
-        Ok(Self { disk, bpb })
+        Ok(Self {
+            disk,
+            bpb,
+            free_hint: FatEntry::ALLOCATED_CLUSTER_BEGIN,
+            fs_info_invalidated: false,
+        })
+    }
+
+    /// # Into Inner
+    /// Get the disk back out of this filesystem.
+    pub fn into_inner(self) -> Part {
+        self.disk
     }
 
     fn read_fat(&mut self, id: ClusterId) -> Result<FatEntry> {
@@ -194,11 +508,11 @@ impl<Part: ReadSeek> Fat<Part> {
 
         Ok(match self.bpb.kind() {
             FatKind::Fat16 => FatEntry::from_fat16(unsafe {
-                core::ptr::read_unaligned(sector_array.as_ptr().add(entry_offset * 2))
+                core::ptr::read_unaligned(sector_array.as_ptr().add(entry_offset * 2).cast::<u16>())
             } as ClusterId),
             FatKind::Fat32 => FatEntry::from_fat32(unsafe {
-                core::ptr::read_unaligned(sector_array.as_ptr().add(entry_offset * 4))
-            } as ClusterId),
+                core::ptr::read_unaligned(sector_array.as_ptr().add(entry_offset * 4).cast::<u32>())
+            }),
             FatKind::Fat12 => todo!("Support reading FAT12"),
         })
     }
@@ -228,109 +542,580 @@ impl<Part: ReadSeek> Fat<Part> {
         }
     }
 
+    /// The first cluster of a directory, entries point to the root directory with cluster 0.
+    ///
+    /// On FAT12/16 this stays 0, since the root directory has a fixed region instead of clusters.
+    fn dir_cluster(&self, cluster: ClusterId) -> ClusterId {
+        match cluster {
+            0 => self.bpb.root_cluster(),
+            cluster => cluster,
+        }
+    }
+
+    /// Find where the byte at `offset` into the directory `dir` is on disk, or `None`
+    /// if the directory isn't that large.
+    fn dir_offset_loc(&mut self, dir: ClusterId, offset: u64) -> Result<Option<u64>> {
+        if dir == 0 {
+            let root_bytes = (self.bpb.root_entries() * DIR_ENTRY_SIZE) as u64;
+
+            return Ok((offset < root_bytes).then(|| self.bpb.cluster_physical_loc(0) + offset));
+        }
+
+        match self.cluster_of_offset(dir, offset) {
+            Ok((cluster, cluster_offset)) => Ok(Some(
+                self.bpb.cluster_physical_loc(cluster) + cluster_offset,
+            )),
+            Err(FsError::EndOfFile) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Call `visit` with the disk location and bytes of each entry slot in the directory
+    /// `dir` (including free ones), until it returns `Some`.
+    fn walk_dir<T>(
+        &mut self,
+        dir: ClusterId,
+        mut visit: impl FnMut(u64, &[u8]) -> Option<T>,
+    ) -> Result<Option<T>> {
+        let mut data = [0u8; 512];
+        let mut offset = 0;
+
+        while let Some(disk_loc) = self.dir_offset_loc(dir, offset)? {
+            self.disk.seek(SeekFrom::Start(disk_loc))?;
+            self.disk.read(&mut data)?;
+
+            for (index, slot) in data.chunks(DIR_ENTRY_SIZE).enumerate() {
+                if let Some(found) = visit(disk_loc + (index * DIR_ENTRY_SIZE) as u64, slot) {
+                    return Ok(Some(found));
+                }
+            }
+
+            offset += data.len() as u64;
+        }
+
+        Ok(None)
+    }
+
+    /// Find the entry called `name` in the directory `dir`, and where it is on disk.
+    fn find_in_dir(&mut self, dir: ClusterId, name: &str) -> Result<(DirectoryEntry, u64)> {
+        let mut long_name = LongName::new();
+
+        self.walk_dir(dir, |entry_loc, slot| {
+            match slot[0] {
+                0 => return Some(Err(FsError::NotFound)),
+                DirectoryEntry::DELETED => {
+                    long_name.clear();
+                    return None;
+                }
+                _ => (),
+            }
+
+            let entry = match slot.try_into().ok()? {
+                Inode::LongFileName(lfn) => {
+                    long_name.push(&lfn);
+                    return None;
+                }
+                Inode::Dir(entry) | Inode::File(entry) => entry,
+            };
+
+            let found = !entry.is_volume_label()
+                && (name.eq_ignore_ascii_case(long_name.as_str()) || entry.short_name_eq(name));
+
+            long_name.clear();
+            found.then_some(Ok((entry, entry_loc)))
+        })?
+        .unwrap_or(Err(FsError::NotFound))
+    }
+
+    /// Find the entry at `path`, and where it is on disk.
+    fn lookup(&mut self, path: &str) -> Result<(DirectoryEntry, u64)> {
+        let mut path = path.split('/').filter(|str| !str.is_empty()).peekable();
+        let mut dir = self.bpb.root_cluster();
+
+        loop {
+            let Some(path_part) = path.next() else {
+                // The root directory has no entry of its own
+                return Err(FsError::InvalidInput);
+            };
+
+            let (entry, entry_loc) = self.find_in_dir(dir, path_part.trim())?;
+
+            if path.peek().is_none() {
+                return Ok((entry, entry_loc));
+            }
+
+            // Files cannot have other files after them in the path
+            if !entry.is_dir() {
+                return Err(FsError::NotFound);
+            }
+
+            dir = self.dir_cluster(entry.cluster_id());
+        }
+    }
+
     pub fn volume_label<'a>(&'a self) -> &'a str {
         self.bpb.volume_label()
     }
 
     pub fn open<'a>(&'a mut self, name: &str) -> Result<FatFile<'a, Part>> {
-        let entry_info = self.entry_of(name)?;
+        let (entry_info, entry_loc) = self.lookup(name)?;
 
         Ok(FatFile {
             filesize: entry_info.file_size as usize,
             start_cluster: entry_info.cluster_id(),
+            entry_loc,
+            directory: entry_info.is_dir(),
             fatfs: self,
             seek: 0,
+            tail: None,
+            cursor: None,
         })
     }
 
     pub fn entry_of(&mut self, name: &str) -> Result<DirectoryEntry> {
-        assert_eq!(
-            self.bpb.cluster_sectors(),
-            2,
-            "TODO: Expecting cluster size to be 2 sectors"
-        );
+        self.lookup(name).map(|(entry, _)| entry)
+    }
 
-        let mut path = name.split('/').filter(|str| !str.is_empty()).peekable();
-        let mut inode_cluster = self.bpb.root_cluster();
-        let mut data = [0u8; 1024];
+    /// # Read Dir
+    /// Call `visit` with the name and entry of everything in the directory at
+    /// `path` (not including `.` and `..`), `""` is the root directory.
+    pub fn read_dir(
+        &mut self,
+        path: &str,
+        mut visit: impl FnMut(&str, &DirectoryEntry),
+    ) -> Result<()> {
+        let dir = self.dir_at(path)?;
+        let mut long_name = LongName::new();
 
-        'outer: loop {
-            let Some(path_part) = path.next() else {
-                unreachable!("path_part is somehow none");
+        self.walk_dir(dir, |_, slot| {
+            match slot[0] {
+                0 => return Some(()),
+                DirectoryEntry::DELETED => {
+                    long_name.clear();
+                    return None;
+                }
+                _ => (),
+            }
+
+            let entry = match slot.try_into().ok()? {
+                Inode::LongFileName(lfn) => {
+                    long_name.push(&lfn);
+                    return None;
+                }
+                Inode::Dir(entry) | Inode::File(entry) => entry,
             };
 
-            // Max string size for FAT is 256-chars
-            let mut filename_str = [0u8; 256];
-            let mut filename_len = 0;
+            let mut short = [0u8; 12];
+            let name = match long_name.as_str() {
+                "" => entry.short_name(&mut short),
+                long => long,
+            };
 
-            self.disk.seek(SeekFrom::Start(
-                self.bpb.cluster_physical_loc(inode_cluster),
-            ))?;
-            self.disk.read(&mut data)?;
+            if !entry.is_volume_label() && name != "." && name != ".." {
+                visit(name, &entry);
+            }
 
-            for inode in data
-                .chunks(size_of::<DirectoryEntry>())
-                .map(|slice| slice.try_into())
-                .filter_map(|entry: Result<Inode>| entry.ok())
-            {
-                let filename = core::str::from_utf8(&filename_str[..filename_len])
-                    .unwrap_or("")
-                    .trim();
-
-                match inode {
-                    Inode::LongFileName(lfn) => {
-                        let ordering_number = (lfn.ordering - 1) & (u8::MAX ^ 0x40);
-                        let offset = (ordering_number * 13) as usize;
-
-                        filename_str[offset..(offset + 13)]
-                            .iter_mut()
-                            .zip(
-                                inode
-                                    .name_iter()
-                                    .filter(|lfn_c| lfn_c.is_ascii() && *lfn_c != '\0'),
-                            )
-                            .for_each(|(filename_c, inode_c)| {
-                                *filename_c = inode_c as u8;
-                                filename_len += 1;
-                            });
-                    }
-                    Inode::Dir(entry) => {
-                        if path_part.trim().eq_ignore_ascii_case(filename) {
-                            // more todo
-                            if path.peek().is_some() {
-                                inode_cluster = entry.cluster_id();
-                                continue 'outer;
-                            }
-
-                            return Ok(entry);
-                        }
+            long_name.clear();
+            None
+        })?;
 
-                        filename_str = [0u8; 256];
-                        filename_len = 0;
-                        continue;
-                    }
-                    Inode::File(file) => {
-                        // Files cannot have other files after it in the path:
-                        // So, we must not be the one.
-                        if path.peek().is_some() {
-                            filename_str = [0u8; 256];
-                            filename_len = 0;
-                            continue;
-                        }
+        Ok(())
+    }
 
-                        if path_part.trim().eq_ignore_ascii_case(filename) {
-                            return Ok(file);
-                        }
+    /// The cluster of the directory at `path`, `""` is the root directory.
+    fn dir_at(&mut self, path: &str) -> Result<ClusterId> {
+        if path.split('/').all(|part| part.trim().is_empty()) {
+            return Ok(self.bpb.root_cluster());
+        }
 
-                        filename_str = [0u8; 256];
-                        filename_len = 0;
-                    }
+        let (entry, _) = self.lookup(path)?;
+        if !entry.is_dir() {
+            return Err(FsError::InvalidInput);
+        }
+
+        Ok(self.dir_cluster(entry.cluster_id()))
+    }
+}
+
+impl<Part: ReadSeek> Filesystem for Fat<Part> {
+    fn metadata(&mut self, path: &str) -> Result<Metadata> {
+        if path.is_empty() {
+            return Ok(Metadata::directory());
+        }
+
+        Ok(self.entry_of(path)?.into())
+    }
+
+    fn read_at(&mut self, path: &str, offset: u64, buf: &mut [u8]) -> Result<usize> {
+        let mut file = self.open(path)?;
+        if file.directory {
+            return Err(FsError::InvalidInput);
+        }
+
+        // Reads go until the end of the cluster chain, not the end of the file
+        let len = (file.filesize as u64)
+            .saturating_sub(offset)
+            .min(buf.len() as u64) as usize;
+
+        if len == 0 {
+            return Ok(0);
+        }
+
+        file.seek(SeekFrom::Start(offset))?;
+        file.read(&mut buf[..len])
+    }
+
+    fn read_dir(&mut self, path: &str, visit: &mut dyn FnMut(DirEntry)) -> Result<()> {
+        Fat::read_dir(self, path, |name, entry| {
+            visit(DirEntry {
+                name,
+                metadata: (*entry).into(),
+            })
+        })
+    }
+}
+
+impl From<DirectoryEntry> for Metadata {
+    fn from(entry: DirectoryEntry) -> Self {
+        Metadata {
+            kind: if entry.is_dir() {
+                FileKind::Directory
+            } else {
+                FileKind::File
+            },
+            size: entry.file_size() as u64,
+        }
+    }
+}
+
+impl<Part: ReadWriteSeek> Fat<Part> {
+    /// # Create
+    /// Create the file at `path` (its directory must already exist) and open it
+    /// for writing. If the file already exists it is truncated.
+    pub fn create<'a>(&'a mut self, path: &str) -> Result<FatFile<'a, Part>> {
+        let (dir, name) = self.parent_of(path)?;
+
+        let (start_cluster, entry_loc) = match self.find_in_dir(dir, name) {
+            Ok((entry, _)) if entry.is_dir() => return Err(FsError::AlreadyExists),
+            Ok((entry, entry_loc)) => (entry.cluster_id(), entry_loc),
+            Err(FsError::NotFound) => (
+                0,
+                self.insert_entry(dir, name, DirectoryEntry::ATTR_ARCHIVE, 0)?,
+            ),
+            Err(err) => return Err(err),
+        };
+
+        let mut file = FatFile {
+            filesize: 0,
+            start_cluster,
+            entry_loc,
+            directory: false,
+            fatfs: self,
+            seek: 0,
+            tail: None,
+            cursor: None,
+        };
+
+        file.set_len(0)?;
+        Ok(file)
+    }
+
+    /// # Create Dir
+    /// Create an empty directory at `path`, its parent must already exist.
+    pub fn create_dir(&mut self, path: &str) -> Result<()> {
+        let (dir, name) = self.parent_of(path)?;
+
+        match self.find_in_dir(dir, name) {
+            Ok(_) => return Err(FsError::AlreadyExists),
+            Err(FsError::NotFound) => (),
+            Err(err) => return Err(err),
+        }
+
+        let cluster = self.alloc_cluster(None)?;
+
+        // Entries point to the root directory with cluster 0, even on FAT32
+        let parent_cluster = if dir == self.bpb.root_cluster() {
+            0
+        } else {
+            dir
+        };
+        let mut dot_name = [b' '; 11];
+        dot_name[0] = b'.';
+        let mut dot_dot_name = dot_name;
+        dot_dot_name[1] = b'.';
+
+        self.disk
+            .seek(SeekFrom::Start(self.bpb.cluster_physical_loc(cluster)))?;
+        self.disk.write(
+            &DirectoryEntry::new(dot_name, DirectoryEntry::ATTR_DIRECTORY, cluster).to_bytes(),
+        )?;
+        self.disk.write(
+            &DirectoryEntry::new(dot_dot_name, DirectoryEntry::ATTR_DIRECTORY, parent_cluster)
+                .to_bytes(),
+        )?;
+
+        self.insert_entry(dir, name, DirectoryEntry::ATTR_DIRECTORY, cluster)?;
+        Ok(())
+    }
+
+    /// Split `path` into the cluster of its parent directory and its name.
+    fn parent_of<'b>(&mut self, path: &'b str) -> Result<(ClusterId, &'b str)> {
+        let path = path.trim_matches('/');
+        let (parent, name) = path.rsplit_once('/').unwrap_or(("", path));
+
+        Ok((self.dir_at(parent)?, name.trim()))
+    }
+
+    /// Add an entry called `name` to the directory `dir`, returning where its short
+    /// entry was written.
+    fn insert_entry(
+        &mut self,
+        dir: ClusterId,
+        name: &str,
+        attributes: u8,
+        cluster: ClusterId,
+    ) -> Result<u64> {
+        const INVALID_CHARS: &[u8] = b"\"*/:<>?\\|";
+
+        if name.is_empty()
+            || name == "."
+            || name == ".."
+            || name.len() > 255
+            || !name
+                .bytes()
+                .all(|c| c.is_ascii() && !c.is_ascii_control() && !INVALID_CHARS.contains(&c))
+        {
+            return Err(FsError::InvalidInput);
+        }
+
+        // Names that don't fit in 8.3 are stored as a long name, with a unique short
+        // name made up for them.
+        let (short, long_entries) = match short_name(name, None) {
+            Some(short) => (short, 0),
+            None => {
+                let tail = self.next_short_name_tail(dir, name)?;
+                let short = short_name(name, Some(tail)).ok_or(FsError::InvalidInput)?;
+
+                (short, name.len().div_ceil(LongFileName::CHARS))
+            }
+        };
+
+        let entry = DirectoryEntry::new(short, attributes, cluster);
+        let slots = self.free_slots(dir, long_entries + 1)?;
+
+        for (index, &slot_loc) in slots[..long_entries].iter().enumerate() {
+            let ordering = (long_entries - index) as u8;
+            let piece_start = (ordering as usize - 1) * LongFileName::CHARS;
+            let piece_end = (piece_start + LongFileName::CHARS).min(name.len());
+
+            let ordering = if index == 0 {
+                ordering | LongFileName::LAST_ENTRY
+            } else {
+                ordering
+            };
+
+            self.disk.seek(SeekFrom::Start(slot_loc))?;
+            self.disk.write(
+                &LongFileName::new(
+                    ordering,
+                    entry.checksum(),
+                    &name.as_bytes()[piece_start..piece_end],
+                )
+                .to_bytes(),
+            )?;
+        }
+
+        let entry_loc = slots[long_entries];
+        self.disk.seek(SeekFrom::Start(entry_loc))?;
+        self.disk.write(&entry.to_bytes())?;
+
+        Ok(entry_loc)
+    }
+
+    /// The `~N` tail to give `name`'s short name in `dir`, one above the highest
+    /// tail already used for it. The directory is only read once.
+    fn next_short_name_tail(&mut self, dir: ClusterId, name: &str) -> Result<u32> {
+        let mut highest = 0;
+
+        self.walk_dir(dir, |_, slot| {
+            if slot[0] == 0 {
+                return Some(());
+            }
+
+            let slot: &[u8; 11] = slot[..11].try_into().unwrap();
+            if let Some(tail) = short_name_tail(slot).filter(|&tail| tail > highest) {
+                if short_name(name, Some(tail)).as_ref() == Some(slot) {
+                    highest = tail;
+                }
+            }
+
+            None
+        })?;
+
+        if highest >= MAX_SHORT_NAME_TAIL {
+            return Err(FsError::AlreadyExists);
+        }
+
+        Ok(highest + 1)
+    }
+
+    /// Find `count` free entry slots in a row in the directory `dir`, growing the
+    /// directory if there aren't enough.
+    fn free_slots(&mut self, dir: ClusterId, count: usize) -> Result<[u64; MAX_NAME_ENTRIES]> {
+        loop {
+            let mut run = [0u64; MAX_NAME_ENTRIES];
+            let mut run_len = 0;
+
+            let found = self.walk_dir(dir, |slot_loc, slot| {
+                if slot[0] == 0 || slot[0] == DirectoryEntry::DELETED {
+                    run[run_len] = slot_loc;
+                    run_len += 1;
+                } else {
+                    run_len = 0;
                 }
+
+                (run_len == count).then_some(())
+            })?;
+
+            if found.is_some() {
+                return Ok(run);
+            }
+
+            // The FAT12/16 root directory can't grow
+            if dir == 0 {
+                return Err(FsError::NoSpace);
             }
 
-            return Err(FsError::NotFound);
+            let mut last = dir;
+            while let FatEntry::Next(next) = self.read_fat(last)? {
+                last = next;
+            }
+
+            self.alloc_cluster(Some(last))?;
         }
     }
+
+    fn write_fat(&mut self, id: ClusterId, entry: FatEntry) -> Result<()> {
+        let entry_bytes = self.bpb.fat_entry_bytes();
+        let entry_offset = id as u64 * entry_bytes as u64;
+
+        if entry_offset >= (self.bpb.fat_sectors() * self.bpb.sector_size()) as u64 {
+            return Err(FsError::InvalidInput);
+        }
+
+        // Every copy of the FAT is kept in sync
+        for fat in 0..self.bpb.fat_count() {
+            let fat_loc = (*self.bpb.fat_range().start() + (fat * self.bpb.fat_sectors()) as u64)
+                * self.bpb.sector_size() as u64;
+
+            self.disk.seek(SeekFrom::Start(fat_loc + entry_offset))?;
+
+            match self.bpb.kind() {
+                FatKind::Fat16 => {
+                    self.disk
+                        .write(&(entry.into_fat16() as u16).to_le_bytes())?;
+                }
+                FatKind::Fat32 => {
+                    let mut old = [0u8; 4];
+                    self.disk.read(&mut old)?;
+
+                    let value =
+                        (u32::from_le_bytes(old) & !FatEntry::FAT32_MASK) | entry.into_fat32();
+
+                    self.disk.seek(SeekFrom::Start(fat_loc + entry_offset))?;
+                    self.disk.write(&value.to_le_bytes())?;
+                }
+                FatKind::Fat12 => return Err(FsError::NotSupported),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Take a free cluster (zeroing it), and link it after `prev` if given.
+    fn alloc_cluster(&mut self, prev: Option<ClusterId>) -> Result<ClusterId> {
+        let clusters = self.bpb.clusters() as ClusterId;
+        if clusters == 0 {
+            return Err(FsError::NoSpace);
+        }
+
+        let first = FatEntry::ALLOCATED_CLUSTER_BEGIN;
+        let end = first + clusters;
+        let hint = self.free_hint.clamp(first, end - 1);
+
+        for id in (hint..end).chain(first..hint) {
+            if !matches!(self.read_fat(id)?, FatEntry::Free) {
+                continue;
+            }
+
+            self.write_fat(id, FatEntry::EOF)?;
+            if let Some(prev) = prev {
+                self.write_fat(prev, FatEntry::Next(id))?;
+            }
+
+            let zeros = [0u8; 512];
+            self.disk
+                .seek(SeekFrom::Start(self.bpb.cluster_physical_loc(id)))?;
+            for _ in 0..(self.bpb.cluster_bytes() / zeros.len() as u64) {
+                self.disk.write(&zeros)?;
+            }
+
+            self.free_hint = id + 1;
+            self.invalidate_fs_info()?;
+
+            return Ok(id);
+        }
+
+        Err(FsError::NoSpace)
+    }
+
+    /// Free every cluster in the chain starting at `start`.
+    fn free_chain(&mut self, start: ClusterId) -> Result<()> {
+        let mut cluster = start;
+
+        loop {
+            let entry = self.read_fat(cluster)?;
+            self.write_fat(cluster, FatEntry::Free)?;
+
+            match entry {
+                FatEntry::Next(next) => cluster = next,
+                FatEntry::EOF => break,
+                _ => return Err(FsError::ReadError),
+            }
+        }
+
+        self.free_hint = self.free_hint.min(start);
+        self.invalidate_fs_info()
+    }
+
+    /// The FAT32 FSInfo sector caches the free cluster count, rather than keeping it
+    /// up to date it is marked unknown so other drivers recount it.
+    fn invalidate_fs_info(&mut self) -> Result<()> {
+        const LEAD_SIGNATURE: u32 = 0x41615252;
+        const FREE_COUNT: usize = 488;
+
+        if self.fs_info_invalidated {
+            return Ok(());
+        }
+
+        if let Some(sector) = self.bpb.fs_info_sector() {
+            let sector_loc = sector * self.bpb.sector_size() as u64;
+            let mut signature = [0u8; 4];
+
+            self.disk.seek(SeekFrom::Start(sector_loc))?;
+            self.disk.read(&mut signature)?;
+
+            if u32::from_le_bytes(signature) == LEAD_SIGNATURE {
+                // Both the free count and the next free cluster hint
+                self.disk
+                    .seek(SeekFrom::Start(sector_loc + FREE_COUNT as u64))?;
+                self.disk.write(&[0xFF; 8])?;
+            }
+        }
+
+        self.fs_info_invalidated = true;
+        Ok(())
+    }
 }
 
 impl<Part: ReadSeek> Debug for Fat<Part> {
@@ -347,8 +1132,183 @@ impl<Part: ReadSeek> Debug for Fat<Part> {
 
 #[cfg(test)]
 mod test {
+    extern crate std;
+
+    use super::*;
+    use std::{io::Cursor, vec, vec::Vec};
+
+    /// A disk in memory that counts how many reads were made from it.
+    struct MemDisk {
+        image: Vec<u8>,
+        pos: usize,
+        reads: usize,
+    }
+
+    impl MemDisk {
+        fn new(image: Vec<u8>) -> Self {
+            Self {
+                image,
+                pos: 0,
+                reads: 0,
+            }
+        }
+    }
+
+    impl Read for MemDisk {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            let len = buf.len().min(self.image.len().saturating_sub(self.pos));
+            buf[..len].copy_from_slice(&self.image[self.pos..self.pos + len]);
+            self.pos += len;
+            self.reads += 1;
+
+            Ok(len)
+        }
+    }
+
+    impl Write for MemDisk {
+        fn write(&mut self, buf: &[u8]) -> Result<usize> {
+            let end = self.pos + buf.len();
+            if end > self.image.len() {
+                return Err(FsError::NoSpace);
+            }
+
+            self.image[self.pos..end].copy_from_slice(buf);
+            self.pos = end;
+
+            Ok(buf.len())
+        }
+    }
+
+    impl Seek for MemDisk {
+        fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+            self.pos = match pos {
+                SeekFrom::Start(pos) => pos as usize,
+                SeekFrom::End(delta) => (self.image.len() as i64 + delta) as usize,
+                SeekFrom::Current(delta) => (self.pos as i64 + delta) as usize,
+            };
+
+            Ok(self.pos as u64)
+        }
+
+        fn stream_position(&mut self) -> u64 {
+            self.pos as u64
+        }
+    }
+
+    /// A FAT16 image with 1KiB clusters, made by an independent FAT implementation.
+    fn fat16_image(files: &[&str]) -> Vec<u8> {
+        let mut image = Cursor::new(vec![0u8; 8 * 1024 * 1024]);
+
+        ::fatfs::format_volume(
+            &mut image,
+            ::fatfs::FormatVolumeOptions::new()
+                .bytes_per_cluster(1024)
+                .fat_type(::fatfs::FatType::Fat16),
+        )
+        .unwrap();
+
+        {
+            let fat = ::fatfs::FileSystem::new(&mut image, ::fatfs::FsOptions::new()).unwrap();
+            for name in files {
+                fat.root_dir().create_file(name).unwrap();
+            }
+        }
+
+        image.into_inner()
+    }
+
+    #[test]
+    fn test_fat_entry_encoding() {
+        assert!(matches!(FatEntry::from_fat16(0), FatEntry::Free));
+        assert!(matches!(
+            FatEntry::from_fat16(0x1234),
+            FatEntry::Next(0x1234)
+        ));
+        assert!(matches!(FatEntry::from_fat16(0xfff7), FatEntry::Defective));
+        assert!(matches!(FatEntry::from_fat16(0xffff), FatEntry::EOF));
+        assert_eq!(FatEntry::EOF.into_fat16(), 0xffff);
+
+        // The top 4 bits of a FAT32 entry don't take part in its value
+        assert!(matches!(
+            FatEntry::from_fat32(0xf000_0002),
+            FatEntry::Next(2)
+        ));
+        assert!(matches!(FatEntry::from_fat32(0x0fff_fff8), FatEntry::EOF));
+        assert_eq!(FatEntry::Defective.into_fat32(), 0x0fff_fff7);
+    }
+
+    #[test]
+    fn test_alloc_cluster_without_clusters() {
+        // A volume whose reserved sector, FAT and root directory fill the whole disk
+        let mut image = vec![0u8; 3 * 512];
+        image[0] = 0xEB;
+        image[11..13].copy_from_slice(&512u16.to_le_bytes());
+        image[13] = 1;
+        image[14..16].copy_from_slice(&1u16.to_le_bytes());
+        image[16] = 1;
+        image[17..19].copy_from_slice(&16u16.to_le_bytes());
+        image[19..21].copy_from_slice(&3u16.to_le_bytes());
+        image[22..24].copy_from_slice(&1u16.to_le_bytes());
+
+        let mut fat = Fat::new(MemDisk::new(image)).unwrap();
+        assert_eq!(fat.bpb.clusters(), 0);
+        assert!(matches!(fat.alloc_cluster(None), Err(FsError::NoSpace)));
+    }
+
+    #[test]
+    fn test_appends_dont_rewalk_the_chain() {
+        let mut fat = Fat::new(MemDisk::new(fat16_image(&[]))).unwrap();
+        let chunk = [0x5A; 64];
+
+        let mut file = fat.create("append.bin").unwrap();
+        let reads_for = |file: &mut FatFile<MemDisk>, writes: usize| {
+            let before = file.fatfs.disk.reads;
+            for _ in 0..writes {
+                file.write(&chunk).unwrap();
+            }
+            file.fatfs.disk.reads - before
+        };
+
+        // The file is 64 clusters long by the end, so walking the chain on every
+        // write would make the last writes far slower than the first
+        let early = reads_for(&mut file, 100);
+        reads_for(&mut file, 824);
+        let late = reads_for(&mut file, 100);
+        assert!(late <= early + 10, "early={early} late={late}");
+
+        assert_eq!(file.filesize(), 1024 * 64);
+        file.seek(SeekFrom::Start(0)).unwrap();
+        let mut data = vec![0u8; 1024 * 64];
+        file.read(&mut data).unwrap();
+        assert!(data.iter().all(|&byte| byte == 0x5A));
+    }
+
     #[test]
-    fn test() {
-        assert!(true, "True Should Be True!");
+    fn test_truncate_then_append() {
+        let mut fat = Fat::new(MemDisk::new(fat16_image(&[]))).unwrap();
+
+        let mut file = fat.create("log.txt").unwrap();
+        file.write(&[b'a'; 3000]).unwrap();
+        file.set_len(100).unwrap();
+        file.seek(SeekFrom::End(0)).unwrap();
+        file.write(&[b'b'; 2000]).unwrap();
+        file.set_len(2500).unwrap();
+
+        let mut data = vec![0u8; 2500];
+        file.seek(SeekFrom::Start(0)).unwrap();
+        file.read(&mut data).unwrap();
+        assert!(data[..100].iter().all(|&byte| byte == b'a'));
+        assert!(data[100..2100].iter().all(|&byte| byte == b'b'));
+        assert!(data[2100..].iter().all(|&byte| byte == 0));
+    }
+
+    #[test]
+    fn test_short_name_tail_follows_highest() {
+        let mut fat = Fat::new(MemDisk::new(fat16_image(&["LONGFI~7.TXT"]))).unwrap();
+        fat.create("longfilename.txt").unwrap();
+
+        let mut short = [0u8; 12];
+        let entry = fat.entry_of("longfilename.txt").unwrap();
+        assert_eq!(entry.short_name(&mut short), "LONGFI~8.TXT");
     }
 }
@@ -0,0 +1,481 @@
+/*
+  ____                 __               __   _ __
+ / __ \__ _____ ____  / /___ ____ _    / /  (_) /
+/ /_/ / // / _ `/ _ \/ __/ // /  ' \  / /__/ / _ \
+\___\_\_,_/\_,_/_//_/\__/\_,_/_/_/_/ /____/_/_.__/
+    Part of the Quantum OS Project
+
+Copyright 2024 Gavin Kellam
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and
+associated documentation files (the "Software"), to deal in the Software without restriction,
+including without limitation the rights to use, copy, modify, merge, publish, distribute,
+sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial
+portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT
+NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
+OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+extern crate std;
+
+use crate::{
+    error::{FsError, Result},
+    io::{Read, Seek, SeekFrom, Write},
+    read_block::BlockDevice,
+};
+use std::{fs::File, io, path::Path};
+
+impl From<io::Error> for FsError {
+    fn from(value: io::Error) -> Self {
+        match value.kind() {
+            io::ErrorKind::NotFound => FsError::NotFound,
+            io::ErrorKind::UnexpectedEof => FsError::EndOfFile,
+            io::ErrorKind::InvalidInput => FsError::InvalidInput,
+            io::ErrorKind::Unsupported => FsError::NotSupported,
+            io::ErrorKind::AlreadyExists => FsError::AlreadyExists,
+            io::ErrorKind::StorageFull => FsError::NoSpace,
+            io::ErrorKind::WriteZero => FsError::WriteError,
+            _ => FsError::ReadError,
+        }
+    }
+}
+
+impl From<SeekFrom> for io::SeekFrom {
+    fn from(value: SeekFrom) -> Self {
+        match value {
+            SeekFrom::Start(pos) => io::SeekFrom::Start(pos),
+            SeekFrom::End(pos) => io::SeekFrom::End(pos),
+            SeekFrom::Current(pos) => io::SeekFrom::Current(pos),
+        }
+    }
+}
+
+/// # Std Device
+/// Wraps any `std` stream (a `File`, a `Cursor<Vec<u8>>`, a partition slice,
+/// etc) so it can be used as a disk by this crate.
+pub struct StdDevice<T: io::Read + io::Seek> {
+    inner: T,
+    block: [u8; 512],
+}
+
+/// # File Device
+/// A disk image on the host's filesystem.
+pub type FileDevice = StdDevice<File>;
+
+impl<T: io::Read + io::Seek> StdDevice<T> {
+    /// # New
+    /// Use `inner` as the backing storage for this device.
+    pub const fn new(inner: T) -> Self {
+        Self {
+            inner,
+            block: [0; 512],
+        }
+    }
+
+    /// # Into Inner
+    /// Get the backing storage back out of this device.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl FileDevice {
+    /// # Open
+    /// Open an existing disk image for reading and writing.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self::new(
+            File::options().read(true).write(true).open(path)?,
+        ))
+    }
+
+    /// # Create
+    /// Create (or truncate) a zero filled disk image of `size` bytes.
+    pub fn create(path: impl AsRef<Path>, size: u64) -> Result<Self> {
+        let file = File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        file.set_len(size)?;
+
+        Ok(Self::new(file))
+    }
+}
+
+impl<T: io::Read + io::Seek> Read for StdDevice<T> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        // The fs code expects short reads to never happen, so read the whole buffer.
+        self.inner.read_exact(buf)?;
+        Ok(buf.len())
+    }
+}
+
+impl<T: io::Read + io::Seek + io::Write> Write for StdDevice<T> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.inner.write_all(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(self.inner.flush()?)
+    }
+}
+
+impl<T: io::Read + io::Seek> Seek for StdDevice<T> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+        Ok(self.inner.seek(pos.into())?)
+    }
+
+    fn stream_position(&mut self) -> u64 {
+        self.inner.stream_position().unwrap_or(0)
+    }
+}
+
+impl<T: io::Read + io::Seek> BlockDevice for StdDevice<T> {
+    const BLOCK_SIZE: usize = 512;
+
+    fn read_block(&mut self, block_offset: u64) -> Result<&[u8]> {
+        self.inner
+            .seek(io::SeekFrom::Start(block_offset * Self::BLOCK_SIZE as u64))?;
+        self.inner.read_exact(&mut self.block)?;
+
+        Ok(&self.block)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        cache::{CachedBlockDevice, WritePolicy},
+        fatfs::Fat,
+        read_block::read_smooth_from_block_device,
+    };
+    use std::{
+        format,
+        io::{Cursor, Read as _, Seek as _, Write as _},
+        string::String,
+        vec,
+        vec::Vec,
+    };
+
+    const IMAGE_SECTORS: u32 = ((50 * 1024 * 1024) / 512) + 1;
+
+    /// Build a FAT image the same way `meta` does, using an independent FAT implementation.
+    fn build_image(files: &[(&str, &[u8])]) -> Cursor<Vec<u8>> {
+        build_image_with_clusters(512 * 2, files)
+    }
+
+    /// Smaller clusters give enough of them for the image to be FAT32.
+    fn build_image_with_clusters(cluster_bytes: u32, files: &[(&str, &[u8])]) -> Cursor<Vec<u8>> {
+        let mut image = Cursor::new(vec![0u8; IMAGE_SECTORS as usize * 512]);
+
+        ::fatfs::format_volume(
+            &mut image,
+            ::fatfs::FormatVolumeOptions::new()
+                .bytes_per_sector(512)
+                .bytes_per_cluster(cluster_bytes)
+                .total_sectors(IMAGE_SECTORS)
+                .fats(2)
+                .volume_label(*b"Q-TEST     "),
+        )
+        .unwrap();
+
+        {
+            let fat = ::fatfs::FileSystem::new(&mut image, ::fatfs::FsOptions::new()).unwrap();
+            let root = fat.root_dir();
+
+            for (path, data) in files {
+                if let Some((dir, _)) = path.rsplit_once('/') {
+                    let _ = root.create_dir(dir);
+                }
+
+                root.create_file(path).unwrap().write_all(data).unwrap();
+            }
+        }
+
+        image.set_position(0);
+        image
+    }
+
+    #[test]
+    fn test_volume_label() {
+        let fat = Fat::new(StdDevice::new(build_image(&[]))).unwrap();
+        assert_eq!(fat.volume_label(), "Q-TEST     ");
+    }
+
+    #[test]
+    fn test_read_file() {
+        let contents = b"Hello from the host!";
+        let mut fat = Fat::new(StdDevice::new(build_image(&[("hello.txt", contents)]))).unwrap();
+
+        let mut file = fat.open("hello.txt").unwrap();
+        assert_eq!(file.filesize(), contents.len());
+
+        let mut buf = [0u8; 20];
+        file.read(&mut buf).unwrap();
+        assert_eq!(&buf, contents);
+    }
+
+    #[test]
+    fn test_read_nested_multi_cluster_file() {
+        let contents: Vec<u8> = (0..5000).map(|i| (i % 251) as u8).collect();
+        let mut fat = Fat::new(StdDevice::new(build_image(&[(
+            "bootloader/stage.bin",
+            &contents,
+        )])))
+        .unwrap();
+
+        let mut file = fat.open("bootloader/stage.bin").unwrap();
+        let mut buf = vec![0u8; contents.len()];
+        file.read(&mut buf).unwrap();
+        assert_eq!(buf, contents);
+    }
+
+    #[test]
+    fn test_cached_fat_walks() {
+        let contents: Vec<u8> = (0..5000).map(|i| (i % 251) as u8).collect();
+        let device = StdDevice::new(build_image(&[("bootloader/stage.bin", &contents)]));
+        let cache = CachedBlockDevice::<_>::new(device, WritePolicy::WriteThrough).unwrap();
+        let mut fat = Fat::new(cache).unwrap();
+
+        for _ in 0..2 {
+            let mut file = fat.open("bootloader/stage.bin").unwrap();
+            let mut buf = vec![0u8; contents.len()];
+            file.read(&mut buf).unwrap();
+            assert_eq!(buf, contents);
+        }
+
+        // The second walk to the file should not need the disk
+        let stats = fat.into_inner().stats();
+        assert!(stats.hits >= stats.misses, "{stats:?}");
+    }
+
+    #[test]
+    fn test_missing_file() {
+        let mut fat = Fat::new(StdDevice::new(build_image(&[("a.txt", b"a")]))).unwrap();
+        assert!(matches!(fat.entry_of("b.txt"), Err(FsError::NotFound)));
+    }
+
+    #[test]
+    fn test_file_device_blocks() {
+        let path =
+            std::env::temp_dir().join(std::format!("fs-host-test-{}.img", std::process::id()));
+        let mut device = FileDevice::create(&path, 4 * 512).unwrap();
+        device.inner.seek(io::SeekFrom::Start(510)).unwrap();
+        device.inner.write_all(&[1, 2, 3, 4]).unwrap();
+
+        let mut data = [0u8; 4];
+        read_smooth_from_block_device(&mut device, 510, &mut data).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(data, [1, 2, 3, 4]);
+    }
+
+    /// Read a file back with the independent FAT implementation.
+    fn read_back(image: &mut Cursor<Vec<u8>>, path: &str) -> Vec<u8> {
+        image.set_position(0);
+        let fat = ::fatfs::FileSystem::new(image, ::fatfs::FsOptions::new()).unwrap();
+
+        let mut data = Vec::new();
+        fat.root_dir()
+            .open_file(path)
+            .unwrap()
+            .read_to_end(&mut data)
+            .unwrap();
+
+        data
+    }
+
+    fn free_clusters(image: &mut Cursor<Vec<u8>>) -> u32 {
+        image.set_position(0);
+        let fat = ::fatfs::FileSystem::new(image, ::fatfs::FsOptions::new()).unwrap();
+        fat.stats().unwrap().free_clusters()
+    }
+
+    #[test]
+    fn test_create_and_write() {
+        let mut fat = Fat::new(StdDevice::new(build_image(&[]))).unwrap();
+
+        let mut file = fat.create("hello.txt").unwrap();
+        file.write(b"Hello from QuantumOS!").unwrap();
+        assert_eq!(file.filesize(), 21);
+
+        let mut file = fat.open("HELLO.TXT").unwrap();
+        let mut buf = [0u8; 21];
+        file.read(&mut buf).unwrap();
+        assert_eq!(&buf, b"Hello from QuantumOS!");
+
+        let mut image = fat.into_inner().into_inner();
+        assert_eq!(read_back(&mut image, "hello.txt"), b"Hello from QuantumOS!");
+    }
+
+    #[test]
+    fn test_append_across_clusters() {
+        let contents: Vec<u8> = (0..7000).map(|i| (i % 253) as u8).collect();
+        let mut fat = Fat::new(StdDevice::new(build_image(&[("log.txt", b"start")]))).unwrap();
+
+        let mut file = fat.open("log.txt").unwrap();
+        file.seek(SeekFrom::End(0)).unwrap();
+        for chunk in contents.chunks(300) {
+            file.write(chunk).unwrap();
+        }
+        assert_eq!(file.filesize(), 5 + contents.len());
+
+        let mut image = fat.into_inner().into_inner();
+        let data = read_back(&mut image, "log.txt");
+        assert_eq!(&data[..5], b"start");
+        assert_eq!(&data[5..], &contents[..]);
+    }
+
+    #[test]
+    fn test_truncate_frees_clusters() {
+        let mut image = build_image(&[]);
+        let free_before = free_clusters(&mut image);
+
+        let mut fat = Fat::new(StdDevice::new(image)).unwrap();
+        let mut file = fat.create("big.bin").unwrap();
+        file.write(&vec![0xAA; 10 * 1024]).unwrap();
+        file.set_len(1500).unwrap();
+
+        let mut image = fat.into_inner().into_inner();
+        assert_eq!(read_back(&mut image, "big.bin"), vec![0xAA; 1500]);
+        assert_eq!(free_clusters(&mut image), free_before - 2);
+
+        // Creating an existing file truncates it
+        let mut fat = Fat::new(StdDevice::new(image)).unwrap();
+        assert_eq!(fat.create("big.bin").unwrap().filesize(), 0);
+
+        let mut image = fat.into_inner().into_inner();
+        assert!(read_back(&mut image, "big.bin").is_empty());
+        assert_eq!(free_clusters(&mut image), free_before);
+    }
+
+    #[test]
+    fn test_write_past_end_fills_zeros() {
+        let mut fat = Fat::new(StdDevice::new(build_image(&[("a.bin", b"abc")]))).unwrap();
+
+        let mut file = fat.open("a.bin").unwrap();
+        file.seek(SeekFrom::Start(2000)).unwrap();
+        file.write(b"xyz").unwrap();
+        file.set_len(2010).unwrap();
+
+        let mut image = fat.into_inner().into_inner();
+        let data = read_back(&mut image, "a.bin");
+        assert_eq!(data.len(), 2010);
+        assert_eq!(&data[..3], b"abc");
+        assert!(data[3..2000].iter().all(|&byte| byte == 0));
+        assert_eq!(&data[2000..2003], b"xyz");
+        assert!(data[2003..].iter().all(|&byte| byte == 0));
+    }
+
+    #[test]
+    fn test_create_dir_grows_past_one_cluster() {
+        let mut fat = Fat::new(StdDevice::new(build_image_with_clusters(512, &[]))).unwrap();
+        fat.create_dir("logs").unwrap();
+        assert!(matches!(
+            fat.create_dir("logs"),
+            Err(FsError::AlreadyExists)
+        ));
+
+        // Long names take several entries each, so this needs many clusters
+        let names: Vec<String> = (0..40)
+            .map(|i| format!("logs/boot-log-number-{i}.txt"))
+            .collect();
+        for name in &names {
+            fat.create(name).unwrap().write(name.as_bytes()).unwrap();
+        }
+
+        for name in &names {
+            let mut file = fat.open(name).unwrap();
+            let mut buf = vec![0u8; file.filesize()];
+            file.read(&mut buf).unwrap();
+            assert_eq!(buf, name.as_bytes());
+        }
+
+        let mut image = fat.into_inner().into_inner();
+        for name in &names {
+            assert_eq!(read_back(&mut image, name), name.as_bytes());
+        }
+    }
+
+    #[test]
+    fn test_short_name_collisions() {
+        let mut fat = Fat::new(StdDevice::new(build_image(&[]))).unwrap();
+        fat.create("LONGFILENAME-A.TXT")
+            .unwrap()
+            .write(b"a")
+            .unwrap();
+        fat.create("LONGFILENAME-B.TXT")
+            .unwrap()
+            .write(b"b")
+            .unwrap();
+        fat.create("SHORT.TXT").unwrap().write(b"c").unwrap();
+
+        assert!(matches!(fat.create("bad?.txt"), Err(FsError::InvalidInput)));
+
+        let mut image = fat.into_inner().into_inner();
+        assert_eq!(read_back(&mut image, "LONGFILENAME-A.TXT"), b"a");
+        assert_eq!(read_back(&mut image, "LONGFILENAME-B.TXT"), b"b");
+        assert_eq!(read_back(&mut image, "LONGFI~1.TXT"), b"a");
+        assert_eq!(read_back(&mut image, "LONGFI~2.TXT"), b"b");
+        assert_eq!(read_back(&mut image, "short.txt"), b"c");
+    }
+
+    #[test]
+    #[cfg(feature = "qfs")]
+    fn test_vfs_mounts() {
+        use crate::qfs::{builder::QfsBuilder, Qfs};
+        use crate::vfs::{FileKind, Vfs};
+
+        let mut initfs = QfsBuilder::new(9).unwrap();
+        initfs
+            .add_file("bin/init", 0o755, b"init".as_slice())
+            .unwrap();
+        let initfs = initfs.build().unwrap();
+
+        let mut fat = Fat::new(StdDevice::new(build_image(&[(
+            "bootloader/qconfig.cfg",
+            b"kernel=kernel.elf",
+        )])))
+        .unwrap();
+        let mut qfs = Qfs::new(&initfs).unwrap();
+
+        let mut vfs = Vfs::new();
+        vfs.mount("/", &mut fat).unwrap();
+        vfs.mount("/initfs", &mut qfs).unwrap();
+        vfs.set_cwd("/initfs/bin").unwrap();
+
+        let mut buf = [0u8; 32];
+        let len = vfs
+            .open("/bootloader/qconfig.cfg")
+            .unwrap()
+            .read(&mut buf)
+            .unwrap();
+        assert_eq!(&buf[..len], b"kernel=kernel.elf");
+
+        let len = vfs.open("init").unwrap().read(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"init");
+
+        let mut names = Vec::new();
+        vfs.read_dir("../..", |entry| {
+            names.push((String::from(entry.name), entry.metadata.kind))
+        })
+        .unwrap();
+        assert_eq!(
+            names,
+            [
+                (String::from("bootloader"), FileKind::Directory),
+                (String::from("initfs"), FileKind::Directory)
+            ]
+        );
+    }
+}
@@ -25,9 +25,21 @@ OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWA
 
 #![no_std]
 
+#[cfg(feature = "std")]
+pub mod host;
+
 #[cfg(feature = "fatfs")]
 pub mod fatfs;
 
+#[cfg(feature = "qfs")]
+pub mod qfs;
+
+#[cfg(feature = "ext2")]
+pub mod ext2;
+
+pub mod cache;
 pub mod error;
 pub mod io;
+pub mod ramdisk;
 pub mod read_block;
+pub mod vfs;
@@ -0,0 +1,342 @@
+/*
+  ____                 __               __   _ __
+ / __ \__ _____ ____  / /___ ____ _    / /  (_) /
+/ /_/ / // / _ `/ _ \/ __/ // /  ' \  / /__/ / _ \
+\___\_\_,_/\_,_/_//_/\__/\_,_/_/_/_/ /____/_/_.__/
+    Part of the Quantum OS Project
+
+Copyright 2024 Gavin Kellam
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and
+associated documentation files (the "Software"), to deal in the Software without restriction,
+including without limitation the rights to use, copy, modify, merge, publish, distribute,
+sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial
+portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT
+NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
+OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+extern crate std;
+
+use super::{
+    crc::crc32, lz, normalize_path, BLOCK_COMPRESSED, BLOCK_ENTRY_SIZE, ENTRY_SIZE, KIND_DIRECTORY,
+    KIND_FILE, MAGIC, MAX_BLOCK_SIZE_LOG2, MIN_BLOCK_SIZE_LOG2, SUPERBLOCK_SIZE, VERSION,
+};
+use crate::error::{FsError, Result};
+use std::{collections::BTreeMap, string::String, vec, vec::Vec};
+
+enum PendingEntry {
+    File { mode: u32, data: Vec<u8> },
+    Directory { mode: u32 },
+}
+
+/// # Qfs Builder
+/// Build a Qfs image on the host.
+///
+/// Directories are created for every parent of a path that's added, and
+/// blocks are only stored compressed when that makes them smaller.
+pub struct QfsBuilder {
+    block_size_log2: u16,
+    entries: BTreeMap<String, PendingEntry>,
+}
+
+impl QfsBuilder {
+    /// # New
+    /// Build an image with blocks of `1 << block_size_log2` bytes.
+    pub fn new(block_size_log2: u16) -> Result<Self> {
+        if !(MIN_BLOCK_SIZE_LOG2..=MAX_BLOCK_SIZE_LOG2).contains(&block_size_log2) {
+            return Err(FsError::InvalidInput);
+        }
+
+        Ok(Self {
+            block_size_log2,
+            entries: BTreeMap::new(),
+        })
+    }
+
+    /// # Add Directory
+    pub fn add_directory(&mut self, path: &str, mode: u32) -> Result<()> {
+        let path = normalize_path(path);
+        if path.is_empty() {
+            return Err(FsError::InvalidInput);
+        }
+
+        self.add_parents(path)?;
+        match self.entries.get(path) {
+            Some(PendingEntry::File { .. }) => Err(FsError::InvalidInput),
+            _ => {
+                self.entries
+                    .insert(path.into(), PendingEntry::Directory { mode });
+                Ok(())
+            }
+        }
+    }
+
+    /// # Add File
+    /// Add (or replace) the file at `path`.
+    pub fn add_file(&mut self, path: &str, mode: u32, data: impl Into<Vec<u8>>) -> Result<()> {
+        let path = normalize_path(path);
+        if path.is_empty() {
+            return Err(FsError::InvalidInput);
+        }
+
+        self.add_parents(path)?;
+        if let Some(PendingEntry::Directory { .. }) = self.entries.get(path) {
+            return Err(FsError::InvalidInput);
+        }
+
+        self.entries.insert(
+            path.into(),
+            PendingEntry::File {
+                mode,
+                data: data.into(),
+            },
+        );
+
+        Ok(())
+    }
+
+    fn add_parents(&mut self, path: &str) -> Result<()> {
+        for (split, _) in path.match_indices('/') {
+            match self.entries.get(&path[..split]) {
+                Some(PendingEntry::File { .. }) => return Err(FsError::InvalidInput),
+                Some(PendingEntry::Directory { .. }) => (),
+                None => {
+                    self.entries.insert(
+                        path[..split].into(),
+                        PendingEntry::Directory { mode: 0o755 },
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// # Build
+    /// Lay out and checksum the image.
+    pub fn build(&self) -> Result<Vec<u8>> {
+        let block_size = 1usize << self.block_size_log2;
+        let too_big = |_| FsError::InvalidInput;
+
+        let mut entry_table = Vec::new();
+        let mut block_table = Vec::new();
+        let mut name_table = Vec::new();
+        let mut data = Vec::new();
+
+        // Block offsets are fixed up once the size of the tables is known
+        let mut block_offsets = Vec::new();
+        let mut scratch = vec![0; block_size];
+
+        for (path, entry) in &self.entries {
+            let (kind, mode, contents) = match entry {
+                PendingEntry::File { mode, data } => (KIND_FILE, *mode, data.as_slice()),
+                PendingEntry::Directory { mode } => (KIND_DIRECTORY, *mode, &[][..]),
+            };
+
+            entry_table.extend_from_slice(
+                &u32::try_from(name_table.len())
+                    .map_err(too_big)?
+                    .to_le_bytes(),
+            );
+            entry_table
+                .extend_from_slice(&u16::try_from(path.len()).map_err(too_big)?.to_le_bytes());
+            entry_table.extend_from_slice(&[kind, 0]);
+            entry_table.extend_from_slice(
+                &u32::try_from(block_offsets.len())
+                    .map_err(too_big)?
+                    .to_le_bytes(),
+            );
+            entry_table.extend_from_slice(&mode.to_le_bytes());
+            entry_table.extend_from_slice(&(contents.len() as u64).to_le_bytes());
+            name_table.extend_from_slice(path.as_bytes());
+
+            for chunk in contents.chunks(block_size) {
+                let (stored, flags) = match lz::compress(chunk, &mut scratch[..chunk.len() - 1]) {
+                    Some(len) => (&scratch[..len], BLOCK_COMPRESSED),
+                    None => (chunk, 0),
+                };
+
+                block_offsets.push(data.len());
+                block_table.extend_from_slice(&[0; 4]);
+                block_table.extend_from_slice(&(stored.len() as u32).to_le_bytes());
+                block_table.extend_from_slice(&crc32(chunk).to_le_bytes());
+                block_table.extend_from_slice(&flags.to_le_bytes());
+                data.extend_from_slice(stored);
+            }
+        }
+
+        let data_start = SUPERBLOCK_SIZE + entry_table.len() + block_table.len() + name_table.len();
+        for (index, offset) in block_offsets.iter().enumerate() {
+            let offset = u32::try_from(data_start + offset).map_err(too_big)?;
+            block_table[index * BLOCK_ENTRY_SIZE..][..4].copy_from_slice(&offset.to_le_bytes());
+        }
+
+        let mut image = vec![0; SUPERBLOCK_SIZE];
+        image.extend_from_slice(&entry_table);
+        image.extend_from_slice(&block_table);
+        image.extend_from_slice(&name_table);
+        u32::try_from(image.len() + data.len()).map_err(too_big)?;
+
+        let tables_crc = crc32(&image[SUPERBLOCK_SIZE..]);
+        let superblock = &mut image[..SUPERBLOCK_SIZE];
+
+        superblock[..4].copy_from_slice(&MAGIC);
+        superblock[4..6].copy_from_slice(&VERSION.to_le_bytes());
+        superblock[6..8].copy_from_slice(&self.block_size_log2.to_le_bytes());
+        superblock[8..12].copy_from_slice(&((entry_table.len() / ENTRY_SIZE) as u32).to_le_bytes());
+        superblock[12..16].copy_from_slice(&(block_offsets.len() as u32).to_le_bytes());
+        superblock[16..20].copy_from_slice(&(name_table.len() as u32).to_le_bytes());
+        superblock[20..24].copy_from_slice(&tables_crc.to_le_bytes());
+        let superblock_crc = crc32(&superblock[..24]);
+        superblock[24..28].copy_from_slice(&superblock_crc.to_le_bytes());
+
+        image.extend_from_slice(&data);
+        Ok(image)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::qfs::{Qfs, QfsKind};
+
+    fn text(len: usize) -> Vec<u8> {
+        (0..len).map(|i| b"initfs block data "[i % 18]).collect()
+    }
+
+    fn noise(len: usize) -> Vec<u8> {
+        let mut state = 0xDEAD_BEEFu32;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect()
+    }
+
+    fn sample() -> Vec<u8> {
+        let mut builder = QfsBuilder::new(9).unwrap();
+
+        builder
+            .add_file(
+                "/system/servers/graphics/compositor.elf",
+                0o755,
+                noise(1500),
+            )
+            .unwrap();
+        builder
+            .add_file("system/fonts/small.bin", 0o644, text(3000))
+            .unwrap();
+        builder.add_file("./readme", 0o644, &b"hello"[..]).unwrap();
+        builder.add_file("empty", 0o644, Vec::new()).unwrap();
+        builder.add_directory("var/log/", 0o700).unwrap();
+
+        builder.build().unwrap()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let image = sample();
+        let qfs = Qfs::new(&image).unwrap();
+
+        assert_eq!(qfs.block_size(), 512);
+        qfs.verify().unwrap();
+
+        let compositor = qfs
+            .find("system/servers/graphics/compositor.elf")
+            .unwrap()
+            .unwrap();
+        assert_eq!(compositor.mode(), 0o755);
+        assert_eq!(compositor.name(), "compositor.elf");
+
+        let mut buf = vec![0; 1500];
+        assert_eq!(qfs.read_at(&compositor, 0, &mut buf).unwrap(), 1500);
+        assert_eq!(buf, noise(1500));
+
+        // Reads across blocks and off the end of the file
+        let fonts = qfs.find("/system/fonts/small.bin/").unwrap().unwrap();
+        let mut buf = vec![0; 1000];
+        assert_eq!(qfs.read_at(&fonts, 2500, &mut buf).unwrap(), 500);
+        assert_eq!(&buf[..500], &text(3000)[2500..]);
+        assert_eq!(qfs.read_at(&fonts, 3000, &mut buf).unwrap(), 0);
+
+        let empty = qfs.find("empty").unwrap().unwrap();
+        assert_eq!(qfs.read_at(&empty, 0, &mut buf).unwrap(), 0);
+
+        assert_eq!(
+            qfs.find("var/log").unwrap().unwrap().kind(),
+            QfsKind::Directory
+        );
+        assert_eq!(qfs.find("var/log").unwrap().unwrap().mode(), 0o700);
+        assert!(qfs.find("missing").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_text_is_compressed() {
+        let image = sample();
+
+        // The fonts alone are 3000 bytes uncompressed
+        assert!(image.len() < 1500 + 3000);
+    }
+
+    #[test]
+    fn test_children() {
+        let image = sample();
+        let qfs = Qfs::new(&image).unwrap();
+
+        let names = |path| -> Vec<String> {
+            qfs.children(path)
+                .map(|entry| entry.unwrap().name().into())
+                .collect()
+        };
+
+        assert_eq!(names(""), ["empty", "readme", "system", "var"]);
+        assert_eq!(names("/system/"), ["fonts", "servers"]);
+        assert_eq!(names("system/servers/graphics"), ["compositor.elf"]);
+        assert!(names("readme").is_empty());
+    }
+
+    #[test]
+    fn test_corrupted_block() {
+        let mut image = sample();
+        let last = image.len() - 1;
+        image[last] ^= 0xFF;
+
+        let qfs = Qfs::new(&image).unwrap();
+        assert!(matches!(qfs.verify(), Err(FsError::IntegrityError)));
+    }
+
+    #[test]
+    fn test_corrupted_tables() {
+        let mut image = sample();
+        image[SUPERBLOCK_SIZE + 2] ^= 0x01;
+        assert!(matches!(Qfs::new(&image), Err(FsError::IntegrityError)));
+
+        let mut image = sample();
+        image[8] ^= 0x01;
+        assert!(matches!(Qfs::new(&image), Err(FsError::IntegrityError)));
+
+        assert!(matches!(Qfs::new(&image[..16]), Err(FsError::InvalidInput)));
+    }
+
+    #[test]
+    fn test_file_and_directory_conflicts() {
+        let mut builder = QfsBuilder::new(12).unwrap();
+        builder.add_file("a/b", 0o644, Vec::new()).unwrap();
+
+        assert!(builder.add_file("a", 0o644, Vec::new()).is_err());
+        assert!(builder.add_directory("a/b", 0o755).is_err());
+        assert!(builder.add_file("a/b/c", 0o644, Vec::new()).is_err());
+        assert!(QfsBuilder::new(16).is_err());
+    }
+}
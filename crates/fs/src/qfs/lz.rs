@@ -0,0 +1,251 @@
+/*
+  ____                 __               __   _ __
+ / __ \__ _____ ____  / /___ ____ _    / /  (_) /
+/ /_/ / // / _ `/ _ \/ __/ // /  ' \  / /__/ / _ \
+\___\_\_,_/\_,_/_//_/\__/\_,_/_/_/_/ /____/_/_.__/
+    Part of the Quantum OS Project
+
+Copyright 2024 Gavin Kellam
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and
+associated documentation files (the "Software"), to deal in the Software without restriction,
+including without limitation the rights to use, copy, modify, merge, publish, distribute,
+sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial
+portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT
+NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
+OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+/// Matches shorter than this aren't worth encoding.
+const MIN_MATCH: usize = 4;
+#[cfg(any(test, feature = "std"))]
+const HASH_BITS: u32 = 12;
+
+/// # Compress
+/// Compress `input` into `output` with a small LZ77 (the LZ4 block layout,
+/// without its end of block rules).
+///
+/// Returns `None` if the compressed data doesn't fit in `output`, so passing
+/// an output the size of the input tells you if compressing is worth it.
+///
+/// Each sequence is a token (literal count in the high nibble, match length
+/// minus 4 in the low nibble, with `15` meaning more length bytes follow),
+/// the literals, then a little endian 16-bit offset back to the match. The
+/// last sequence is only literals.
+#[cfg(any(test, feature = "std"))]
+pub fn compress(input: &[u8], output: &mut [u8]) -> Option<usize> {
+    let mut table = [0u32; 1 << HASH_BITS];
+    let mut out = Output {
+        buf: output,
+        len: 0,
+    };
+    let mut anchor = 0;
+    let mut i = 0;
+
+    while i + MIN_MATCH <= input.len() {
+        let word = u32::from_le_bytes(input[i..i + 4].try_into().unwrap());
+        let hash = (word.wrapping_mul(2_654_435_761) >> (32 - HASH_BITS)) as usize;
+
+        // Positions are stored + 1 so 0 can mean empty
+        let candidate = table[hash] as usize;
+        table[hash] = i as u32 + 1;
+
+        if candidate == 0 || i - (candidate - 1) > u16::MAX as usize {
+            i += 1;
+            continue;
+        }
+
+        let candidate = candidate - 1;
+        let match_len = input[candidate..]
+            .iter()
+            .zip(&input[i..])
+            .take_while(|(a, b)| a == b)
+            .count();
+
+        if match_len < MIN_MATCH {
+            i += 1;
+            continue;
+        }
+
+        out.sequence(&input[anchor..i], Some(((i - candidate) as u16, match_len)))?;
+        i += match_len;
+        anchor = i;
+    }
+
+    out.sequence(&input[anchor..], None)?;
+    Some(out.len)
+}
+
+/// # Decompress
+/// Decompress data made by [`compress`] into `output`, returning how many
+/// bytes were written or `None` if the data is malformed or doesn't fit.
+pub fn decompress(input: &[u8], output: &mut [u8]) -> Option<usize> {
+    let mut i = 0;
+    let mut len = 0;
+
+    while i < input.len() {
+        let token = input[i];
+        i += 1;
+
+        let literals = read_length(input, &mut i, (token >> 4) as usize)?;
+        let literal_bytes = input.get(i..i.checked_add(literals)?)?;
+        output
+            .get_mut(len..len + literals)?
+            .copy_from_slice(literal_bytes);
+        i += literals;
+        len += literals;
+
+        // The last sequence has no match
+        if i == input.len() {
+            break;
+        }
+
+        let offset = u16::from_le_bytes(input.get(i..i + 2)?.try_into().ok()?) as usize;
+        i += 2;
+
+        let match_len = read_length(input, &mut i, (token & 0xF) as usize)? + MIN_MATCH;
+        if offset == 0 || offset > len || len + match_len > output.len() {
+            return None;
+        }
+
+        // Matches can overlap what they are writing, so copy byte by byte
+        for _ in 0..match_len {
+            output[len] = output[len - offset];
+            len += 1;
+        }
+    }
+
+    Some(len)
+}
+
+fn read_length(input: &[u8], i: &mut usize, nibble: usize) -> Option<usize> {
+    let mut length = nibble;
+    if nibble != 15 {
+        return Some(length);
+    }
+
+    loop {
+        let byte = *input.get(*i)?;
+        *i += 1;
+        length = length.checked_add(byte as usize)?;
+
+        if byte != 255 {
+            return Some(length);
+        }
+    }
+}
+
+#[cfg(any(test, feature = "std"))]
+struct Output<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+#[cfg(any(test, feature = "std"))]
+impl Output<'_> {
+    fn push(&mut self, byte: u8) -> Option<()> {
+        *self.buf.get_mut(self.len)? = byte;
+        self.len += 1;
+        Some(())
+    }
+
+    fn push_length(&mut self, mut length: usize) -> Option<()> {
+        while length >= 255 {
+            self.push(255)?;
+            length -= 255;
+        }
+
+        self.push(length as u8)
+    }
+
+    fn sequence(&mut self, literals: &[u8], found: Option<(u16, usize)>) -> Option<()> {
+        let match_len = found.map_or(0, |(_, len)| len - MIN_MATCH);
+        self.push(((literals.len().min(15) as u8) << 4) | match_len.min(15) as u8)?;
+
+        if literals.len() >= 15 {
+            self.push_length(literals.len() - 15)?;
+        }
+
+        self.buf
+            .get_mut(self.len..self.len + literals.len())?
+            .copy_from_slice(literals);
+        self.len += literals.len();
+
+        if let Some((offset, _)) = found {
+            for byte in offset.to_le_bytes() {
+                self.push(byte)?;
+            }
+
+            if match_len >= 15 {
+                self.push_length(match_len - 15)?;
+            }
+        }
+
+        Some(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn round_trip(input: &[u8]) -> usize {
+        let mut compressed = [0; 8192];
+        let mut decompressed = [0; 4096];
+
+        let len = compress(input, &mut compressed).unwrap();
+        let out = decompress(&compressed[..len], &mut decompressed).unwrap();
+
+        assert_eq!(&decompressed[..out], input);
+        len
+    }
+
+    #[test]
+    fn test_round_trips() {
+        assert_eq!(round_trip(b""), 1);
+        round_trip(b"abc");
+        round_trip(b"hello hello hello hello, world");
+
+        let text: [u8; 4096] = core::array::from_fn(|i| b"QuantumOS initfs "[i % 17]);
+        assert!(round_trip(&text) < 100);
+
+        // Something that doesn't compress, with literal runs over 270 bytes
+        let mut state = 0x1234_5678u32;
+        let noise: [u8; 4096] = core::array::from_fn(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        });
+        round_trip(&noise);
+    }
+
+    #[test]
+    fn test_incompressible_does_not_fit() {
+        let noise: [u8; 64] = core::array::from_fn(|i| (i as u8).wrapping_mul(97));
+        let mut out = [0; 64];
+
+        assert_eq!(compress(&noise, &mut out), None);
+    }
+
+    #[test]
+    fn test_malformed_input() {
+        let mut out = [0; 64];
+
+        // Offset past the start of the output
+        assert_eq!(decompress(&[0x10, b'a', 0x05, 0x00], &mut out), None);
+        // Truncated offset
+        assert_eq!(decompress(&[0x10, b'a', 0x01], &mut out), None);
+        // Literals past the end of the input
+        assert_eq!(decompress(&[0x50, b'a'], &mut out), None);
+        // Doesn't fit in the output
+        assert_eq!(decompress(&[0x10, b'a', 0x01, 0x00], &mut out[..3]), None);
+    }
+}
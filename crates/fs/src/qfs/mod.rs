@@ -0,0 +1,438 @@
+/*
+  ____                 __               __   _ __
+ / __ \__ _____ ____  / /___ ____ _    / /  (_) /
+/ /_/ / // / _ `/ _ \/ __/ // /  ' \  / /__/ / _ \
+\___\_\_,_/\_,_/_//_/\__/\_,_/_/_/_/ /____/_/_.__/
+    Part of the Quantum OS Project
+
+Copyright 2024 Gavin Kellam
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and
+associated documentation files (the "Software"), to deal in the Software without restriction,
+including without limitation the rights to use, copy, modify, merge, publish, distribute,
+sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial
+portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT
+NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
+OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+use crate::error::{FsError, Result};
+use crate::vfs::{DirEntry, FileKind, Filesystem, Metadata};
+
+pub mod crc;
+mod lz;
+
+#[cfg(feature = "std")]
+pub mod builder;
+
+/// # Qfs Magic
+pub const MAGIC: [u8; 4] = *b"QFS1";
+pub const VERSION: u16 = 1;
+
+/// Blocks are between 512 bytes and 4 KiB, so one always fits on the stack.
+pub const MIN_BLOCK_SIZE_LOG2: u16 = 9;
+pub const MAX_BLOCK_SIZE_LOG2: u16 = 12;
+pub const MAX_BLOCK_SIZE: usize = 1 << MAX_BLOCK_SIZE_LOG2;
+
+pub const SUPERBLOCK_SIZE: usize = 32;
+pub const ENTRY_SIZE: usize = 24;
+pub const BLOCK_ENTRY_SIZE: usize = 16;
+
+/// The block is compressed with [`lz`], otherwise it's stored as is.
+pub const BLOCK_COMPRESSED: u32 = 1 << 0;
+
+const KIND_FILE: u8 = 0;
+const KIND_DIRECTORY: u8 = 1;
+
+// Qfs (Quantum Filesystem) Image Layout
+//
+// A read-only image meant for the initfs. Everything is little endian.
+//
+// | Superblock (32 bytes)                                     |
+// |   magic [u8; 4], version u16, block_size_log2 u16,        |
+// |   entry_count u32, block_count u32, name_table_size u32,  |
+// |   tables_crc u32, superblock_crc u32, reserved u32        |
+// | Entry Table (24 bytes each, sorted by path)               |
+// |   name_offset u32, name_len u16, kind u8, reserved u8,    |
+// |   first_block u32, mode u32, size u64                     |
+// | Block Table (16 bytes each)                               |
+// |   offset u32, stored_len u32, crc u32, flags u32          |
+// | Name Table (full paths, no leading or trailing `/`)       |
+// | Block Data                                                |
+//
+// `superblock_crc` covers the 24 bytes before it, and `tables_crc` covers
+// the entry, block and name tables. Each block's `crc` is of its data after
+// decompressing. A file's blocks are contiguous in the block table starting
+// at `first_block`, and all but the last are a whole block long.
+
+fn u16_at(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+fn u64_at(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+}
+
+/// # Normalize Path
+/// Strip the leading `/` and `./`, and any trailing `/`, which is how paths
+/// are stored in the image.
+pub fn normalize_path(mut path: &str) -> &str {
+    loop {
+        if let Some(rest) = path.strip_prefix("./") {
+            path = rest;
+        } else if let Some(rest) = path.strip_prefix('/') {
+            path = rest;
+        } else {
+            break;
+        }
+    }
+
+    path.trim_end_matches('/')
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QfsKind {
+    File,
+    Directory,
+}
+
+/// # Qfs Entry
+/// A file or directory in the image.
+#[derive(Clone, Copy, Debug)]
+pub struct QfsEntry<'a> {
+    path: &'a str,
+    kind: QfsKind,
+    mode: u32,
+    size: u64,
+    first_block: u32,
+}
+
+impl<'a> QfsEntry<'a> {
+    pub const fn path(&self) -> &'a str {
+        self.path
+    }
+
+    /// # Name
+    /// The last component of the path.
+    pub fn name(&self) -> &'a str {
+        self.path.rsplit('/').next().unwrap_or(self.path)
+    }
+
+    pub const fn kind(&self) -> QfsKind {
+        self.kind
+    }
+
+    pub const fn mode(&self) -> u32 {
+        self.mode
+    }
+
+    pub const fn size(&self) -> u64 {
+        self.size
+    }
+}
+
+/// # Qfs
+/// A Qfs image in memory.
+#[derive(Clone, Copy)]
+pub struct Qfs<'a> {
+    image: &'a [u8],
+    block_size_log2: u16,
+    entry_table: &'a [u8],
+    block_table: &'a [u8],
+    name_table: &'a [u8],
+}
+
+impl<'a> Qfs<'a> {
+    /// # New
+    /// Check the superblock and tables of `image`.
+    ///
+    /// Block data is checked as it's read, or all at once with [`Qfs::verify`].
+    pub fn new(image: &'a [u8]) -> Result<Self> {
+        let superblock = image.get(..SUPERBLOCK_SIZE).ok_or(FsError::InvalidInput)?;
+
+        if superblock[..4] != MAGIC || u16_at(superblock, 4) != VERSION {
+            return Err(FsError::NotSupported);
+        }
+        if crc::crc32(&superblock[..24]) != u32_at(superblock, 24) {
+            return Err(FsError::IntegrityError);
+        }
+
+        let block_size_log2 = u16_at(superblock, 6);
+        if !(MIN_BLOCK_SIZE_LOG2..=MAX_BLOCK_SIZE_LOG2).contains(&block_size_log2) {
+            return Err(FsError::InvalidInput);
+        }
+
+        let entry_end = SUPERBLOCK_SIZE + u32_at(superblock, 8) as usize * ENTRY_SIZE;
+        let block_end = entry_end + u32_at(superblock, 12) as usize * BLOCK_ENTRY_SIZE;
+        let name_end = block_end + u32_at(superblock, 16) as usize;
+
+        let tables = image
+            .get(SUPERBLOCK_SIZE..name_end)
+            .ok_or(FsError::InvalidInput)?;
+        if crc::crc32(tables) != u32_at(superblock, 20) {
+            return Err(FsError::IntegrityError);
+        }
+
+        Ok(Self {
+            image,
+            block_size_log2,
+            entry_table: &image[SUPERBLOCK_SIZE..entry_end],
+            block_table: &image[entry_end..block_end],
+            name_table: &image[block_end..name_end],
+        })
+    }
+
+    pub const fn block_size(&self) -> usize {
+        1 << self.block_size_log2
+    }
+
+    pub const fn entry_count(&self) -> usize {
+        self.entry_table.len() / ENTRY_SIZE
+    }
+
+    const fn block_count(&self) -> usize {
+        self.block_table.len() / BLOCK_ENTRY_SIZE
+    }
+
+    /// # Entry
+    /// Get the entry at `index` in the (sorted) entry table.
+    pub fn entry(&self, index: usize) -> Result<QfsEntry<'a>> {
+        let raw = self
+            .entry_table
+            .get(index * ENTRY_SIZE..(index + 1) * ENTRY_SIZE)
+            .ok_or(FsError::NotFound)?;
+
+        let name_offset = u32_at(raw, 0) as usize;
+        let name_len = u16_at(raw, 4) as usize;
+        let path = self
+            .name_table
+            .get(name_offset..name_offset + name_len)
+            .and_then(|name| core::str::from_utf8(name).ok())
+            .ok_or(FsError::InvalidInput)?;
+
+        let kind = match raw[6] {
+            KIND_FILE => QfsKind::File,
+            KIND_DIRECTORY => QfsKind::Directory,
+            _ => return Err(FsError::InvalidInput),
+        };
+
+        let entry = QfsEntry {
+            path,
+            kind,
+            first_block: u32_at(raw, 8),
+            mode: u32_at(raw, 12),
+            size: u64_at(raw, 16),
+        };
+
+        if entry.first_block as u64 + self.blocks_in(&entry) > self.block_count() as u64 {
+            return Err(FsError::InvalidInput);
+        }
+
+        Ok(entry)
+    }
+
+    /// # Entries
+    /// Iterate every entry, sorted by path.
+    pub fn entries(&self) -> impl Iterator<Item = Result<QfsEntry<'a>>> + '_ {
+        (0..self.entry_count()).map(|index| self.entry(index))
+    }
+
+    /// # Find
+    /// Find the entry at `path`, this is a binary search so it doesn't need
+    /// to walk the image.
+    pub fn find(&self, path: &str) -> Result<Option<QfsEntry<'a>>> {
+        let path = normalize_path(path);
+        let (mut low, mut high) = (0, self.entry_count());
+
+        while low < high {
+            let middle = low + (high - low) / 2;
+            let entry = self.entry(middle)?;
+
+            match entry.path.as_bytes().cmp(path.as_bytes()) {
+                core::cmp::Ordering::Equal => return Ok(Some(entry)),
+                core::cmp::Ordering::Less => low = middle + 1,
+                core::cmp::Ordering::Greater => high = middle,
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// # Children
+    /// Iterate the entries directly inside the directory at `path`, an empty
+    /// path is the root.
+    pub fn children<'p>(&self, path: &'p str) -> impl Iterator<Item = Result<QfsEntry<'a>>> + 'p
+    where
+        'a: 'p,
+    {
+        let path = normalize_path(path);
+        let this = *self;
+
+        (0..self.entry_count())
+            .map(move |index| this.entry(index))
+            .filter(move |entry| {
+                let Ok(entry) = entry else {
+                    return true;
+                };
+
+                let rest = if path.is_empty() {
+                    Some(entry.path)
+                } else {
+                    entry
+                        .path
+                        .strip_prefix(path)
+                        .and_then(|rest| rest.strip_prefix('/'))
+                };
+
+                rest.is_some_and(|rest| !rest.is_empty() && !rest.contains('/'))
+            })
+    }
+
+    /// # Read At
+    /// Read the file `entry` starting at `offset` into `buf`, returning how
+    /// many bytes were read (`0` at the end of the file).
+    ///
+    /// Every block read is checked against its checksum.
+    pub fn read_at(&self, entry: &QfsEntry, offset: u64, buf: &mut [u8]) -> Result<usize> {
+        if entry.kind != QfsKind::File {
+            return Err(FsError::InvalidInput);
+        }
+
+        let block_size = self.block_size() as u64;
+        let mut scratch = [0; MAX_BLOCK_SIZE];
+        let mut read = 0;
+
+        while read < buf.len() {
+            let position = offset + read as u64;
+            if position >= entry.size {
+                break;
+            }
+
+            let block_index = position / block_size;
+            let block = self.read_block(entry, block_index, &mut scratch)?;
+            let in_block = (position % block_size) as usize;
+
+            let len = (block.len() - in_block).min(buf.len() - read);
+            buf[read..read + len].copy_from_slice(&block[in_block..in_block + len]);
+            read += len;
+        }
+
+        Ok(read)
+    }
+
+    /// # Verify
+    /// Check every entry and every file block against its checksum.
+    pub fn verify(&self) -> Result<()> {
+        let mut scratch = [0; MAX_BLOCK_SIZE];
+
+        for entry in self.entries() {
+            let entry = entry?;
+
+            for block in 0..self.blocks_in(&entry) {
+                self.read_block(&entry, block, &mut scratch)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn blocks_in(&self, entry: &QfsEntry) -> u64 {
+        match entry.kind {
+            QfsKind::File => entry.size.div_ceil(self.block_size() as u64),
+            QfsKind::Directory => 0,
+        }
+    }
+
+    /// Get the data of the `index`th block of `entry`, decompressing it into
+    /// `scratch` if needed.
+    fn read_block<'s>(
+        &self,
+        entry: &QfsEntry,
+        index: u64,
+        scratch: &'s mut [u8; MAX_BLOCK_SIZE],
+    ) -> Result<&'s [u8]> {
+        let block_size = self.block_size() as u64;
+        let expected = (entry.size - index * block_size).min(block_size) as usize;
+
+        let table_index = entry.first_block as usize + index as usize;
+        let raw = &self.block_table[table_index * BLOCK_ENTRY_SIZE..][..BLOCK_ENTRY_SIZE];
+
+        let offset = u32_at(raw, 0) as usize;
+        let stored = self
+            .image
+            .get(offset..offset + u32_at(raw, 4) as usize)
+            .ok_or(FsError::InvalidInput)?;
+
+        let data = &mut scratch[..expected];
+        if u32_at(raw, 12) & BLOCK_COMPRESSED != 0 {
+            if lz::decompress(stored, data) != Some(expected) {
+                return Err(FsError::IntegrityError);
+            }
+        } else if stored.len() == expected {
+            data.copy_from_slice(stored);
+        } else {
+            return Err(FsError::IntegrityError);
+        }
+
+        if crc::crc32(data) != u32_at(raw, 8) {
+            return Err(FsError::IntegrityError);
+        }
+
+        Ok(data)
+    }
+}
+
+impl Filesystem for Qfs<'_> {
+    fn metadata(&mut self, path: &str) -> Result<Metadata> {
+        if normalize_path(path).is_empty() {
+            return Ok(Metadata::directory());
+        }
+
+        Ok(self.find(path)?.ok_or(FsError::NotFound)?.into())
+    }
+
+    fn read_at(&mut self, path: &str, offset: u64, buf: &mut [u8]) -> Result<usize> {
+        let entry = self.find(path)?.ok_or(FsError::NotFound)?;
+        Qfs::read_at(self, &entry, offset, buf)
+    }
+
+    fn read_dir(&mut self, path: &str, visit: &mut dyn FnMut(DirEntry)) -> Result<()> {
+        if self.metadata(path)?.kind != FileKind::Directory {
+            return Err(FsError::InvalidInput);
+        }
+
+        for entry in self.children(path) {
+            let entry = entry?;
+
+            visit(DirEntry {
+                name: entry.name(),
+                metadata: entry.into(),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+impl From<QfsEntry<'_>> for Metadata {
+    fn from(entry: QfsEntry<'_>) -> Self {
+        Metadata {
+            kind: match entry.kind {
+                QfsKind::File => FileKind::File,
+                QfsKind::Directory => FileKind::Directory,
+            },
+            size: entry.size,
+        }
+    }
+}
@@ -0,0 +1,124 @@
+/*
+  ____                 __               __   _ __
+ / __ \__ _____ ____  / /___ ____ _    / /  (_) /
+/ /_/ / // / _ `/ _ \/ __/ // /  ' \  / /__/ / _ \
+\___\_\_,_/\_,_/_//_/\__/\_,_/_/_/_/ /____/_/_.__/
+    Part of the Quantum OS Project
+
+Copyright 2024 Gavin Kellam
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and
+associated documentation files (the "Software"), to deal in the Software without restriction,
+including without limitation the rights to use, copy, modify, merge, publish, distribute,
+sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial
+portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT
+NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
+OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+use crate::error::{FsError, Result};
+use crate::read_block::BlockDevice;
+
+/// # Ram Disk
+/// A block device backed by an image in memory (like an `initrd`, or a boot
+/// partition copied into RAM).
+pub struct RamDisk<'a, const SIZE: usize = 512> {
+    image: &'a [u8],
+}
+
+impl<'a, const SIZE: usize> RamDisk<'a, SIZE> {
+    /// # New
+    /// Use `image` as a disk, it must be a whole number of blocks.
+    pub fn new(image: &'a [u8]) -> Result<Self> {
+        if SIZE == 0 || !image.len().is_multiple_of(SIZE) {
+            return Err(FsError::InvalidInput);
+        }
+
+        Ok(Self { image })
+    }
+
+    /// # Block Count
+    /// The number of blocks on this disk.
+    pub fn block_count(&self) -> u64 {
+        (self.image.len() / SIZE) as u64
+    }
+
+    /// # Image
+    /// The whole disk image.
+    pub fn image(&self) -> &'a [u8] {
+        self.image
+    }
+
+    fn block_start(&self, block_offset: u64) -> Result<usize> {
+        if block_offset >= self.block_count() {
+            return Err(FsError::EndOfFile);
+        }
+
+        Ok(block_offset as usize * SIZE)
+    }
+}
+
+impl<const SIZE: usize> BlockDevice for RamDisk<'_, SIZE> {
+    const BLOCK_SIZE: usize = SIZE;
+
+    fn read_block(&mut self, block_offset: u64) -> Result<&[u8]> {
+        let start = self.block_start(block_offset)?;
+        Ok(&self.image[start..start + SIZE])
+    }
+
+    fn read_blocks(&mut self, block_offset: u64, buf: &mut [u8]) -> Result<usize> {
+        let start = self.block_start(block_offset)?;
+        let len = (buf.len() / SIZE * SIZE).min(self.image.len() - start);
+
+        buf[..len].copy_from_slice(&self.image[start..start + len]);
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::read_block::read_smooth_from_block_device;
+
+    fn image() -> [u8; 64] {
+        core::array::from_fn(|i| i as u8)
+    }
+
+    #[test]
+    fn test_ram_disk_blocks() {
+        let image = image();
+        let mut disk = RamDisk::<16>::new(&image).unwrap();
+
+        assert_eq!(disk.block_count(), 4);
+        assert_eq!(disk.read_block(1).unwrap(), &image[16..32]);
+        assert!(matches!(disk.read_block(4), Err(FsError::EndOfFile)));
+
+        // Reads stop at the end of the disk
+        let mut buf = [0; 48];
+        assert_eq!(disk.read_blocks(2, &mut buf).unwrap(), 32);
+        assert_eq!(&buf[..32], &image[32..]);
+    }
+
+    #[test]
+    fn test_ram_disk_smooth_read() {
+        let image = image();
+        let mut disk = RamDisk::<16>::new(&image).unwrap();
+
+        let mut buf = [0; 40];
+        read_smooth_from_block_device(&mut disk, 5, &mut buf).unwrap();
+        assert_eq!(&buf, &image[5..45]);
+    }
+
+    #[test]
+    fn test_partial_block_image() {
+        let image = image();
+        assert!(RamDisk::<16>::new(&image[..40]).is_err());
+    }
+}
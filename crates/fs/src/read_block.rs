@@ -23,7 +23,7 @@ DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE,
 OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
 */
 
-use crate::error::Result;
+use crate::error::{FsError, Result};
 
 /// # Block Device
 /// A device that can only read 'blocks' of bytes at a time.
@@ -47,6 +47,33 @@ pub trait BlockDevice {
     /// it must be up to the programmer to keep track of providing
     /// the bytes this block device had read.
     fn read_block<'a>(&'a mut self, block_offset: u64) -> Result<&'a [u8]>;
+
+    /// # Read Blocks
+    /// Read as many whole blocks as fit into `buf`, starting at `block_offset`,
+    /// and return how many bytes were read.
+    ///
+    /// Devices that can read several blocks with one request (like BIOS disk
+    /// reads) should override this, by default blocks are read one at a time.
+    fn read_blocks(&mut self, block_offset: u64, buf: &mut [u8]) -> Result<usize> {
+        let blocks = buf.len() / Self::BLOCK_SIZE;
+
+        for block in 0..blocks {
+            let start = block * Self::BLOCK_SIZE;
+            buf[start..start + Self::BLOCK_SIZE]
+                .copy_from_slice(self.read_block(block_offset + block as u64)?);
+        }
+
+        Ok(blocks * Self::BLOCK_SIZE)
+    }
+
+    /// # Write Block
+    /// Write one whole block (`data` is `BLOCK_SIZE` bytes long) to the device.
+    ///
+    /// Devices are read-only by default, and return `FsError::NotSupported`.
+    fn write_block(&mut self, block_offset: u64, data: &[u8]) -> Result<()> {
+        let _ = (block_offset, data);
+        Err(FsError::NotSupported)
+    }
 }
 
 pub fn read_smooth_from_block_device<Device: BlockDevice>(
@@ -56,8 +83,6 @@ pub fn read_smooth_from_block_device<Device: BlockDevice>(
 ) -> Result<usize> {
     let mut data_copied = 0;
 
-    // FIXME: We should try to read bytes into our 'data' buffer directly as much as possible
-    //        Instead of a copy from the 'read_block' method.
     loop {
         let block_index = (offset_bytes + data_copied as u64) / Device::BLOCK_SIZE as u64;
         let block_offset =
@@ -77,6 +102,20 @@ pub fn read_smooth_from_block_device<Device: BlockDevice>(
             break;
         }
 
+        // Whole blocks can be read straight into 'data' in one batch
+        let whole_blocks = (data.len() - data_copied) / Device::BLOCK_SIZE;
+        if block_offset == 0 && whole_blocks > 0 {
+            let batch_end = data_copied + (whole_blocks * Device::BLOCK_SIZE);
+            let read = device.read_blocks(block_index, &mut data[data_copied..batch_end])?;
+
+            if read == 0 {
+                return Err(FsError::ReadError);
+            }
+
+            data_copied += read;
+            continue;
+        }
+
         let device_block = device.read_block(block_index)?;
         let reading_bytes = index_end - index_begin;
 
@@ -92,6 +131,7 @@ pub fn read_smooth_from_block_device<Device: BlockDevice>(
 #[cfg(test)]
 mod test {
     use super::{read_smooth_from_block_device, BlockDevice};
+    use crate::error::Result;
 
     struct Dummy {
         buf: [u8; 10],
@@ -211,4 +251,61 @@ mod test {
             "Expected bytes to switch when reading different sector"
         );
     }
+
+    struct Batched {
+        buf: [u8; 10],
+        batches: usize,
+    }
+
+    impl BlockDevice for Batched {
+        const BLOCK_SIZE: usize = 10;
+
+        fn read_block(&mut self, block_offset: u64) -> Result<&[u8]> {
+            self.buf = [block_offset as u8; 10];
+            Ok(&self.buf)
+        }
+
+        fn read_blocks(&mut self, block_offset: u64, buf: &mut [u8]) -> Result<usize> {
+            self.batches += 1;
+
+            for (index, byte) in buf.iter_mut().enumerate() {
+                *byte = block_offset as u8 + (index / Self::BLOCK_SIZE) as u8;
+            }
+
+            Ok(buf.len())
+        }
+    }
+
+    #[test]
+    fn test_default_read_blocks() {
+        let mut dummy = Dummy::new();
+
+        let mut target = [255; 25];
+        assert_eq!(dummy.read_blocks(3, &mut target).unwrap(), 20);
+
+        assert_eq!(&target[..10], &[3; 10]);
+        assert_eq!(&target[10..20], &[4; 10]);
+        assert_eq!(&target[20..], &[255; 5]);
+    }
+
+    #[test]
+    fn test_smooth_reading_batches_whole_blocks() {
+        let mut device = Batched {
+            buf: [0; 10],
+            batches: 0,
+        };
+
+        let mut target = [255; 40];
+        read_smooth_from_block_device(&mut device, 5, &mut target).unwrap();
+
+        assert_eq!(&target[..5], &[0; 5]);
+        assert_eq!(&target[5..15], &[1; 10]);
+        assert_eq!(&target[15..25], &[2; 10]);
+        assert_eq!(&target[25..35], &[3; 10]);
+        assert_eq!(&target[35..], &[4; 5]);
+        assert_eq!(
+            device.batches, 1,
+            "Expected the middle blocks to be one batch"
+        );
+    }
 }
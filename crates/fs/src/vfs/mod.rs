@@ -0,0 +1,474 @@
+/*
+  ____                 __               __   _ __
+ / __ \__ _____ ____  / /___ ____ _    / /  (_) /
+/ /_/ / // / _ `/ _ \/ __/ // /  ' \  / /__/ / _ \
+\___\_\_,_/\_,_/_//_/\__/\_,_/_/_/_/ /____/_/_.__/
+    Part of the Quantum OS Project
+
+Copyright 2024 Gavin Kellam
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and
+associated documentation files (the "Software"), to deal in the Software without restriction,
+including without limitation the rights to use, copy, modify, merge, publish, distribute,
+sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial
+portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT
+NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
+OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+use crate::{
+    error::{FsError, Result},
+    io::{Read, Seek, SeekFrom},
+};
+
+mod path;
+pub use path::{VfsPath, MAX_PATH};
+
+#[cfg(feature = "tar")]
+mod tar;
+
+/// The most filesystems that can be mounted at once.
+pub const MAX_MOUNTS: usize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileKind {
+    File,
+    Directory,
+    /// Links, devices, and anything else that isn't a plain file or directory.
+    Other,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Metadata {
+    pub kind: FileKind,
+    pub size: u64,
+}
+
+impl Metadata {
+    pub const fn directory() -> Self {
+        Self {
+            kind: FileKind::Directory,
+            size: 0,
+        }
+    }
+}
+
+/// # Dir Entry
+/// One entry of a directory, the name is only borrowed for the length of the
+/// `read_dir` callback.
+#[derive(Clone, Copy, Debug)]
+pub struct DirEntry<'n> {
+    pub name: &'n str,
+    pub metadata: Metadata,
+}
+
+/// # Filesystem
+/// A filesystem that can be mounted in a [`Vfs`].
+///
+/// Paths given to a filesystem are relative to where it's mounted, have no
+/// leading `/`, and have no `.` or `..` components. Its root is `""`.
+pub trait Filesystem {
+    fn metadata(&mut self, path: &str) -> Result<Metadata>;
+
+    /// # Read At
+    /// Read the file at `path` starting at `offset`, returning how many bytes
+    /// were read (`0` at the end of the file).
+    fn read_at(&mut self, path: &str, offset: u64, buf: &mut [u8]) -> Result<usize>;
+
+    /// # Read Dir
+    /// Call `visit` with every entry in the directory at `path` (not including
+    /// `.` and `..`).
+    fn read_dir(&mut self, path: &str, visit: &mut dyn FnMut(DirEntry)) -> Result<()>;
+}
+
+struct Mount<'a> {
+    point: VfsPath,
+    fs: &'a mut (dyn Filesystem + 'a),
+}
+
+/// # Vfs
+/// Mounts filesystems at paths, and sends each operation to the filesystem
+/// mounted closest to the path it's on.
+pub struct Vfs<'a> {
+    mounts: [Option<Mount<'a>>; MAX_MOUNTS],
+    cwd: VfsPath,
+}
+
+impl<'a> Vfs<'a> {
+    pub fn new() -> Self {
+        Self {
+            mounts: core::array::from_fn(|_| None),
+            cwd: VfsPath::root(),
+        }
+    }
+
+    /// # Mount
+    /// Mount `fs` at `path`, the path doesn't need to exist in the filesystem
+    /// it's mounted over.
+    pub fn mount(&mut self, path: &str, fs: &'a mut (dyn Filesystem + 'a)) -> Result<()> {
+        let point = self.resolve(path)?;
+
+        if self.mount_index(&point).is_some() {
+            return Err(FsError::AlreadyExists);
+        }
+
+        let slot = self
+            .mounts
+            .iter_mut()
+            .find(|mount| mount.is_none())
+            .ok_or(FsError::NoSpace)?;
+
+        *slot = Some(Mount { point, fs });
+        Ok(())
+    }
+
+    /// # Unmount
+    /// Remove the filesystem mounted at `path`, and give it back.
+    pub fn unmount(&mut self, path: &str) -> Result<&'a mut (dyn Filesystem + 'a)> {
+        let point = self.resolve(path)?;
+        let index = self.mount_index(&point).ok_or(FsError::NotFound)?;
+
+        match self.mounts[index].take() {
+            Some(mount) => Ok(mount.fs),
+            None => Err(FsError::NotFound),
+        }
+    }
+
+    /// # Cwd
+    /// The directory relative paths are resolved from.
+    pub fn cwd(&self) -> &VfsPath {
+        &self.cwd
+    }
+
+    /// # Set Cwd
+    /// Change the current directory, `path` must be a directory.
+    pub fn set_cwd(&mut self, path: &str) -> Result<()> {
+        let path = self.resolve(path)?;
+
+        if self.metadata(path.as_str())?.kind != FileKind::Directory {
+            return Err(FsError::InvalidInput);
+        }
+
+        self.cwd = path;
+        Ok(())
+    }
+
+    /// # Resolve
+    /// Turn `path` into an absolute path, relative paths start from [`Vfs::cwd`].
+    pub fn resolve(&self, path: &str) -> Result<VfsPath> {
+        self.cwd.join(path)
+    }
+
+    pub fn metadata(&mut self, path: &str) -> Result<Metadata> {
+        let path = self.resolve(path)?;
+        let (fs, inner) = self.mount_of(&path)?;
+
+        fs.metadata(inner)
+    }
+
+    /// # Open
+    /// Open the file at `path` for reading.
+    pub fn open(&mut self, path: &str) -> Result<VfsFile<'_, 'a>> {
+        let path = self.resolve(path)?;
+        let (fs, inner) = self.mount_of(&path)?;
+
+        let metadata = fs.metadata(inner)?;
+        if metadata.kind == FileKind::Directory {
+            return Err(FsError::InvalidInput);
+        }
+
+        let inner_start = path.as_str().len() - inner.len();
+
+        Ok(VfsFile {
+            fs,
+            path,
+            inner_start,
+            size: metadata.size,
+            seek: 0,
+        })
+    }
+
+    /// # Read Dir
+    /// Call `visit` with every entry in the directory at `path`, including
+    /// filesystems mounted directly inside of it.
+    pub fn read_dir(&mut self, path: &str, mut visit: impl FnMut(DirEntry)) -> Result<()> {
+        let path = self.resolve(path)?;
+
+        // Filesystems mounted here show up as directories, even if the
+        // filesystem they're mounted over doesn't have them.
+        let mut child_mounts = [None; MAX_MOUNTS];
+        for (child, mount) in child_mounts.iter_mut().zip(self.mounts.iter().flatten()) {
+            if !mount.point.is_root() && mount.point.parent() == path {
+                *child = Some(mount.point);
+            }
+        }
+
+        let is_child_mount = |name: &str| {
+            child_mounts
+                .iter()
+                .flatten()
+                .any(|point| point.file_name() == name)
+        };
+
+        let (fs, inner) = self.mount_of(&path)?;
+        fs.read_dir(inner, &mut |entry| {
+            if !is_child_mount(entry.name) {
+                visit(entry);
+            }
+        })?;
+
+        for point in child_mounts.iter().flatten() {
+            visit(DirEntry {
+                name: point.file_name(),
+                metadata: Metadata::directory(),
+            });
+        }
+
+        Ok(())
+    }
+
+    fn mount_index(&self, point: &VfsPath) -> Option<usize> {
+        self.mounts
+            .iter()
+            .position(|mount| mount.as_ref().is_some_and(|mount| mount.point == *point))
+    }
+
+    /// Find the filesystem mounted closest to `path`, and the rest of the path inside it.
+    fn mount_of<'p>(&mut self, path: &'p VfsPath) -> Result<(&mut (dyn Filesystem + 'a), &'p str)> {
+        let (_, mount, inner) = self
+            .mounts
+            .iter_mut()
+            .flatten()
+            .filter_map(|mount| {
+                let inner = path.strip_prefix(&mount.point)?;
+                Some((mount.point.as_str().len(), mount, inner))
+            })
+            .max_by_key(|(point_len, _, _)| *point_len)
+            .ok_or(FsError::NotFound)?;
+
+        Ok((&mut *mount.fs, inner))
+    }
+}
+
+impl Default for Vfs<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// # Vfs File
+/// A file opened through a [`Vfs`].
+pub struct VfsFile<'v, 'a> {
+    fs: &'v mut (dyn Filesystem + 'a),
+    path: VfsPath,
+    /// Where the path inside the filesystem starts in `path`.
+    inner_start: usize,
+    size: u64,
+    seek: u64,
+}
+
+impl VfsFile<'_, '_> {
+    pub const fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub const fn path(&self) -> &VfsPath {
+        &self.path
+    }
+}
+
+impl Read for VfsFile<'_, '_> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let inner = &self.path.as_str()[self.inner_start..];
+        let read = self.fs.read_at(inner, self.seek, buf)?;
+
+        self.seek += read as u64;
+        Ok(read)
+    }
+}
+
+impl Seek for VfsFile<'_, '_> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+        self.seek = match pos {
+            SeekFrom::Start(pos) => pos,
+            SeekFrom::End(delta) => self
+                .size
+                .checked_add_signed(delta)
+                .ok_or(FsError::InvalidInput)?,
+            SeekFrom::Current(delta) => self
+                .seek
+                .checked_add_signed(delta)
+                .ok_or(FsError::InvalidInput)?,
+        };
+
+        Ok(self.seek)
+    }
+
+    fn stream_position(&mut self) -> u64 {
+        self.seek
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A filesystem of files in memory, directories are implied by the paths.
+    struct MemFs(&'static [(&'static str, &'static [u8])]);
+
+    impl Filesystem for MemFs {
+        fn metadata(&mut self, path: &str) -> Result<Metadata> {
+            if let Some((_, data)) = self.0.iter().find(|(name, _)| *name == path) {
+                return Ok(Metadata {
+                    kind: FileKind::File,
+                    size: data.len() as u64,
+                });
+            }
+
+            let is_dir = path.is_empty()
+                || self.0.iter().any(|(name, _)| {
+                    name.strip_prefix(path)
+                        .is_some_and(|rest| rest.starts_with('/'))
+                });
+
+            is_dir.then(Metadata::directory).ok_or(FsError::NotFound)
+        }
+
+        fn read_at(&mut self, path: &str, offset: u64, buf: &mut [u8]) -> Result<usize> {
+            let (_, data) = self
+                .0
+                .iter()
+                .find(|(name, _)| *name == path)
+                .ok_or(FsError::NotFound)?;
+
+            let data = data.get(offset as usize..).unwrap_or_default();
+            let len = data.len().min(buf.len());
+            buf[..len].copy_from_slice(&data[..len]);
+
+            Ok(len)
+        }
+
+        fn read_dir(&mut self, path: &str, visit: &mut dyn FnMut(DirEntry)) -> Result<()> {
+            for (name, data) in self.0 {
+                let rest = match path {
+                    "" => Some(*name),
+                    path => name
+                        .strip_prefix(path)
+                        .and_then(|rest| rest.strip_prefix('/')),
+                };
+
+                if let Some(rest) = rest.filter(|rest| !rest.contains('/')) {
+                    visit(DirEntry {
+                        name: rest,
+                        metadata: Metadata {
+                            kind: FileKind::File,
+                            size: data.len() as u64,
+                        },
+                    });
+                }
+            }
+
+            Ok(())
+        }
+    }
+
+    fn read_all(vfs: &mut Vfs, path: &str) -> Result<([u8; 64], usize)> {
+        let mut file = vfs.open(path)?;
+        let mut buf = [0; 64];
+        let len = file.read(&mut buf)?;
+
+        Ok((buf, len))
+    }
+
+    #[test]
+    fn test_mount_longest_prefix() {
+        let mut root = MemFs(&[("boot/kernel.elf", b"kernel"), ("dev/null", b"shadowed")]);
+        let mut dev = MemFs(&[("null", b"null device")]);
+
+        let mut vfs = Vfs::new();
+        vfs.mount("/", &mut root).unwrap();
+        vfs.mount("/dev", &mut dev).unwrap();
+
+        let (buf, len) = read_all(&mut vfs, "/boot/kernel.elf").unwrap();
+        assert_eq!(&buf[..len], b"kernel");
+
+        let (buf, len) = read_all(&mut vfs, "/dev/null").unwrap();
+        assert_eq!(&buf[..len], b"null device");
+
+        assert_eq!(vfs.metadata("/dev").unwrap().kind, FileKind::Directory);
+        assert!(matches!(vfs.open("/boot"), Err(FsError::InvalidInput)));
+        assert!(matches!(vfs.open("/boot/missing"), Err(FsError::NotFound)));
+    }
+
+    #[test]
+    fn test_relative_paths() {
+        let mut root = MemFs(&[("boot/kernel.elf", b"kernel"), ("etc/motd", b"hello")]);
+
+        let mut vfs = Vfs::new();
+        vfs.mount("/", &mut root).unwrap();
+        vfs.set_cwd("boot").unwrap();
+        assert_eq!(vfs.cwd().as_str(), "/boot");
+
+        let (buf, len) = read_all(&mut vfs, "./kernel.elf").unwrap();
+        assert_eq!(&buf[..len], b"kernel");
+
+        let (buf, len) = read_all(&mut vfs, "../etc/./motd").unwrap();
+        assert_eq!(&buf[..len], b"hello");
+
+        assert!(matches!(
+            vfs.set_cwd("kernel.elf"),
+            Err(FsError::InvalidInput)
+        ));
+    }
+
+    #[test]
+    fn test_read_dir_includes_mounts() {
+        let mut root = MemFs(&[("a.txt", b"a"), ("initfs", b"file under the mount")]);
+        let mut initfs = MemFs(&[("init", b"init")]);
+
+        let mut vfs = Vfs::new();
+        vfs.mount("/", &mut root).unwrap();
+        vfs.mount("/initfs", &mut initfs).unwrap();
+
+        let expected = [("a.txt", FileKind::File), ("initfs", FileKind::Directory)];
+        let mut count = 0;
+        vfs.read_dir("/", |entry| {
+            assert_eq!((entry.name, entry.metadata.kind), expected[count]);
+            count += 1;
+        })
+        .unwrap();
+
+        assert_eq!(count, expected.len());
+    }
+
+    #[test]
+    fn test_seek_and_unmount() {
+        let mut root = MemFs(&[("log", b"0123456789")]);
+        let mut other = MemFs(&[]);
+
+        let mut vfs = Vfs::new();
+        vfs.mount("/", &mut root).unwrap();
+        assert!(matches!(
+            vfs.mount("/", &mut other),
+            Err(FsError::AlreadyExists)
+        ));
+
+        let mut file = vfs.open("log").unwrap();
+        file.seek(SeekFrom::End(-3)).unwrap();
+
+        let mut buf = [0; 8];
+        assert_eq!(file.read(&mut buf).unwrap(), 3);
+        assert_eq!(&buf[..3], b"789");
+        assert_eq!(file.read(&mut buf).unwrap(), 0);
+
+        vfs.unmount("/").unwrap();
+        assert!(matches!(vfs.metadata("/log"), Err(FsError::NotFound)));
+    }
+}
@@ -0,0 +1,217 @@
+/*
+  ____                 __               __   _ __
+ / __ \__ _____ ____  / /___ ____ _    / /  (_) /
+/ /_/ / // / _ `/ _ \/ __/ // /  ' \  / /__/ / _ \
+\___\_\_,_/\_,_/_//_/\__/\_,_/_/_/_/ /____/_/_.__/
+    Part of the Quantum OS Project
+
+Copyright 2024 Gavin Kellam
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and
+associated documentation files (the "Software"), to deal in the Software without restriction,
+including without limitation the rights to use, copy, modify, merge, publish, distribute,
+sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial
+portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT
+NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
+OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+use crate::error::{FsError, Result};
+use core::fmt::{Debug, Display};
+
+/// The longest path (in bytes) a [`VfsPath`] can hold.
+pub const MAX_PATH: usize = 256;
+
+/// # Vfs Path
+/// An absolute path with no `.`, `..` or empty components, like `/boot/kernel.elf`.
+#[derive(Clone, Copy)]
+pub struct VfsPath {
+    bytes: [u8; MAX_PATH],
+    len: usize,
+}
+
+impl VfsPath {
+    /// # Root
+    /// The path `/`.
+    pub const fn root() -> Self {
+        let mut bytes = [0; MAX_PATH];
+        bytes[0] = b'/';
+
+        Self { bytes, len: 1 }
+    }
+
+    /// # New
+    /// Resolve `path` against `/`.
+    pub fn new(path: &str) -> Result<Self> {
+        Self::root().join(path)
+    }
+
+    pub fn as_str(&self) -> &str {
+        // Only whole `str` components are ever pushed
+        core::str::from_utf8(&self.bytes[..self.len]).unwrap_or("/")
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.len == 1
+    }
+
+    /// # Join
+    /// Resolve `path` against this path. Absolute paths replace it, `.` is
+    /// skipped, and `..` goes up a directory (staying at `/` once there).
+    pub fn join(&self, path: &str) -> Result<Self> {
+        let mut joined = if path.starts_with('/') {
+            Self::root()
+        } else {
+            *self
+        };
+
+        for component in path.split('/') {
+            match component {
+                "" | "." => (),
+                ".." => {
+                    joined.pop();
+                }
+                component => joined.push(component)?,
+            }
+        }
+
+        Ok(joined)
+    }
+
+    fn push(&mut self, component: &str) -> Result<()> {
+        let separator = usize::from(!self.is_root());
+        let end = self.len + separator + component.len();
+
+        if end > MAX_PATH {
+            return Err(FsError::InvalidInput);
+        }
+
+        if separator != 0 {
+            self.bytes[self.len] = b'/';
+        }
+
+        self.bytes[end - component.len()..end].copy_from_slice(component.as_bytes());
+        self.len = end;
+
+        Ok(())
+    }
+
+    /// # Pop
+    /// Remove the last component, returning `false` if this is already `/`.
+    pub fn pop(&mut self) -> bool {
+        if self.is_root() {
+            return false;
+        }
+
+        let last_slash = self.as_str().rfind('/').unwrap_or(0);
+        self.len = last_slash.max(1);
+
+        true
+    }
+
+    /// # Parent
+    /// This path without its last component, `/` is its own parent.
+    pub fn parent(&self) -> Self {
+        let mut parent = *self;
+        parent.pop();
+
+        parent
+    }
+
+    /// # File Name
+    /// The last component, or `""` for `/`.
+    pub fn file_name(&self) -> &str {
+        let path = self.as_str();
+        &path[path.rfind('/').map_or(0, |slash| slash + 1)..]
+    }
+
+    /// # Strip Prefix
+    /// The rest of this path inside `prefix` (without a leading `/`), or `None`
+    /// if this path isn't `prefix` or inside of it.
+    pub fn strip_prefix(&self, prefix: &VfsPath) -> Option<&str> {
+        if prefix.is_root() {
+            return Some(&self.as_str()[1..]);
+        }
+
+        match self.as_str().strip_prefix(prefix.as_str())? {
+            "" => Some(""),
+            rest => rest.strip_prefix('/'),
+        }
+    }
+}
+
+impl PartialEq for VfsPath {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for VfsPath {}
+
+impl Debug for VfsPath {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl Display for VfsPath {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_join() {
+        let path = VfsPath::new("/boot/./qconfig.cfg").unwrap();
+        assert_eq!(path.as_str(), "/boot/qconfig.cfg");
+
+        assert_eq!(
+            path.join("../kernel.elf").unwrap().as_str(),
+            "/boot/kernel.elf"
+        );
+        assert_eq!(path.join("/etc//motd/").unwrap().as_str(), "/etc/motd");
+        assert_eq!(path.join("../../../..").unwrap().as_str(), "/");
+    }
+
+    #[test]
+    fn test_parent_and_name() {
+        let path = VfsPath::new("boot/kernel.elf").unwrap();
+        assert_eq!(path.file_name(), "kernel.elf");
+        assert_eq!(path.parent().as_str(), "/boot");
+        assert_eq!(path.parent().parent().as_str(), "/");
+        assert_eq!(VfsPath::root().file_name(), "");
+    }
+
+    #[test]
+    fn test_strip_prefix() {
+        let dev = VfsPath::new("/dev").unwrap();
+
+        assert_eq!(
+            VfsPath::new("/dev/tty0").unwrap().strip_prefix(&dev),
+            Some("tty0")
+        );
+        assert_eq!(VfsPath::new("/dev").unwrap().strip_prefix(&dev), Some(""));
+        assert_eq!(VfsPath::new("/device").unwrap().strip_prefix(&dev), None);
+        assert_eq!(dev.strip_prefix(&VfsPath::root()), Some("dev"));
+    }
+
+    #[test]
+    fn test_too_long() {
+        let component = [b'a'; 200];
+        let component = core::str::from_utf8(&component).unwrap();
+
+        let path = VfsPath::new(component).unwrap();
+        assert!(matches!(path.join(component), Err(FsError::InvalidInput)));
+    }
+}
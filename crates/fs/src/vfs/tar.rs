@@ -0,0 +1,172 @@
+/*
+  ____                 __               __   _ __
+ / __ \__ _____ ____  / /___ ____ _    / /  (_) /
+/ /_/ / // / _ `/ _ \/ __/ // /  ' \  / /__/ / _ \
+\___\_\_,_/\_,_/_//_/\__/\_,_/_/_/_/ /____/_/_.__/
+    Part of the Quantum OS Project
+
+Copyright 2024 Gavin Kellam
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and
+associated documentation files (the "Software"), to deal in the Software without restriction,
+including without limitation the rights to use, copy, modify, merge, publish, distribute,
+sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial
+portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT
+NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
+OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+use super::{DirEntry, FileKind, Filesystem, Metadata};
+use crate::error::{FsError, Result};
+use tar::{Tar, TarError, TarFile, TarKind};
+
+impl From<TarError> for FsError {
+    fn from(value: TarError) -> Self {
+        match value {
+            TarError::NotEnoughBytes => FsError::EndOfFile,
+            TarError::IntegrityError => FsError::IntegrityError,
+            TarError::WriteError => FsError::WriteError,
+            TarError::InvalidHeader | TarError::NameTooLong | TarError::FieldTooLarge => {
+                FsError::InvalidInput
+            }
+        }
+    }
+}
+
+impl From<&TarFile<'_>> for Metadata {
+    fn from(file: &TarFile<'_>) -> Self {
+        Metadata {
+            kind: match file.kind() {
+                TarKind::File => FileKind::File,
+                TarKind::Directory => FileKind::Directory,
+                _ => FileKind::Other,
+            },
+            size: file.data().len() as u64,
+        }
+    }
+}
+
+/// Tar entries can be named `./boot/`, `/boot`, or `boot`.
+fn entry_path<'n>(file: &'n TarFile) -> &'n str {
+    let mut path = file.name();
+
+    while let Some(rest) = path.strip_prefix("./").or_else(|| path.strip_prefix('/')) {
+        path = rest;
+    }
+
+    path.trim_end_matches('/')
+}
+
+fn find<'a>(tar: &Tar<'a>, path: &str) -> Result<Option<TarFile<'a>>> {
+    for file in tar.iter() {
+        let file = file?;
+
+        if entry_path(&file) == path {
+            return Ok(Some(file));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Directories in a tar archive only show up in [`Filesystem::read_dir`] if
+/// they have their own entry, but any path with entries under it is treated
+/// as a directory.
+impl Filesystem for Tar<'_> {
+    fn metadata(&mut self, path: &str) -> Result<Metadata> {
+        if path.is_empty() {
+            return Ok(Metadata::directory());
+        }
+
+        if let Some(file) = find(self, path)? {
+            return Ok((&file).into());
+        }
+
+        match self.entries_under(path).next() {
+            Some(file) => file.map(|_| Metadata::directory()).map_err(Into::into),
+            None => Err(FsError::NotFound),
+        }
+    }
+
+    fn read_at(&mut self, path: &str, offset: u64, buf: &mut [u8]) -> Result<usize> {
+        let file = find(self, path)?.ok_or(FsError::NotFound)?;
+        if file.kind() != TarKind::File {
+            return Err(FsError::InvalidInput);
+        }
+
+        let data = file.data().get(offset as usize..).unwrap_or_default();
+        let len = data.len().min(buf.len());
+        buf[..len].copy_from_slice(&data[..len]);
+
+        Ok(len)
+    }
+
+    fn read_dir(&mut self, path: &str, visit: &mut dyn FnMut(DirEntry)) -> Result<()> {
+        if self.metadata(path)?.kind != FileKind::Directory {
+            return Err(FsError::InvalidInput);
+        }
+
+        for file in self.entries_under(path) {
+            let file = file?;
+            let name = match path {
+                "" => entry_path(&file),
+                path => &entry_path(&file)[path.len() + 1..],
+            };
+
+            if !name.contains('/') {
+                visit(DirEntry {
+                    name,
+                    metadata: (&file).into(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{io::Read, vfs::Vfs};
+    use tar::builder::{SliceWriter, TarBuilder};
+
+    #[test]
+    fn test_mount_tar() {
+        let mut archive = [0; 8 * 512];
+        let mut builder = TarBuilder::new(SliceWriter::new(&mut archive));
+        builder.append_directory("./etc/", 0o755, 0).unwrap();
+        builder
+            .append_file("./etc/hostname", 0o644, 0, b"quantum")
+            .unwrap();
+        builder.append_file("bin/init", 0o755, 0, b"init").unwrap();
+        builder.finish().unwrap();
+
+        let mut tar = Tar::new(&archive);
+        let mut vfs = Vfs::new();
+        vfs.mount("/", &mut tar).unwrap();
+
+        let mut buf = [0; 16];
+        let len = vfs.open("/etc/hostname").unwrap().read(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"quantum");
+
+        // `bin` has no entry of its own
+        assert_eq!(vfs.metadata("/bin").unwrap().kind, FileKind::Directory);
+
+        let mut entries = 0;
+        vfs.read_dir("/etc", |entry| {
+            assert_eq!(entry.name, "hostname");
+            assert_eq!(entry.metadata.size, 7);
+            entries += 1;
+        })
+        .unwrap();
+        assert_eq!(entries, 1);
+    }
+}
@@ -0,0 +1,219 @@
+/*
+  ____                 __               __   _ __
+ / __ \__ _____ ____  / /___ ____ _    / /  (_) /
+/ /_/ / // / _ `/ _ \/ __/ // /  ' \  / /__/ / _ \
+\___\_\_,_/\_,_/_//_/\__/\_,_/_/_/_/ /____/_/_.__/
+    Part of the Quantum OS Project
+
+Copyright 2025 Gavin Kellam
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and
+associated documentation files (the "Software"), to deal in the Software without restriction,
+including without limitation the rights to use, copy, modify, merge, publish, distribute,
+sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial
+portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT
+NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
+OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+use crate::LogKind;
+use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+/// The most per-module overrides that can be set at once.
+pub const MAX_MODULE_FILTERS: usize = 16;
+
+/// # Level Filter
+/// The most verbose kind of message that is still printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum LevelFilter {
+    Off = 0,
+    Error = 1,
+    Warn = 2,
+    Log = 3,
+}
+
+impl LevelFilter {
+    const fn from_u8(value: u8) -> Self {
+        match value {
+            0 => Self::Off,
+            1 => Self::Error,
+            2 => Self::Warn,
+            _ => Self::Log,
+        }
+    }
+
+    /// # Allows
+    /// Check if messages of `kind` pass this filter.
+    pub const fn allows(self, kind: LogKind) -> bool {
+        let level = match kind {
+            LogKind::Error => LevelFilter::Error,
+            LogKind::Warn => LevelFilter::Warn,
+            LogKind::Log => LevelFilter::Log,
+        };
+
+        level as u8 <= self as u8
+    }
+}
+
+/// Overrides of the max level for a module and everything inside it.
+struct ModuleFilters([Option<(&'static str, LevelFilter)>; MAX_MODULE_FILTERS]);
+
+impl ModuleFilters {
+    const fn new() -> Self {
+        Self([None; MAX_MODULE_FILTERS])
+    }
+
+    fn set(&mut self, module: &'static str, level: LevelFilter) -> bool {
+        let slot = match self
+            .0
+            .iter()
+            .position(|filter| matches!(filter, Some((name, _)) if *name == module))
+        {
+            Some(existing) => existing,
+            None => match self.0.iter().position(|filter| filter.is_none()) {
+                Some(empty) => empty,
+                None => return false,
+            },
+        };
+
+        self.0[slot] = Some((module, level));
+        true
+    }
+
+    fn clear(&mut self, module: &str) {
+        for filter in self.0.iter_mut() {
+            if matches!(filter, Some((name, _)) if *name == module) {
+                *filter = None;
+            }
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.0.iter().all(|filter| filter.is_none())
+    }
+
+    /// The level of the most specific filter covering `module`.
+    fn level_of(&self, module: &str) -> Option<LevelFilter> {
+        self.0
+            .iter()
+            .flatten()
+            .filter(|(name, _)| {
+                module
+                    .strip_prefix(name)
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+            })
+            .max_by_key(|(name, _)| name.len())
+            .map(|(_, level)| *level)
+    }
+}
+
+static MAX_LEVEL: AtomicU8 = AtomicU8::new(LevelFilter::Log as u8);
+static HAS_MODULE_FILTERS: AtomicBool = AtomicBool::new(false);
+static MODULE_FILTERS: crate::sync::Mutex<ModuleFilters> =
+    crate::sync::Mutex::new(ModuleFilters::new());
+
+/// # Set Max Level
+/// Only print messages up to `level`, unless a module has its own level.
+pub fn set_max_level(level: LevelFilter) {
+    MAX_LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+/// # Max Level
+/// The level set with `set_max_level` (everything is printed by default).
+pub fn max_level() -> LevelFilter {
+    LevelFilter::from_u8(MAX_LEVEL.load(Ordering::Relaxed))
+}
+
+/// # Set Module Level
+/// Use `level` instead of the max level for `module` and its children (so
+/// `kernel::paging` also covers `kernel::paging::tables`). The most specific
+/// module wins.
+///
+/// Returns `false` if `MAX_MODULE_FILTERS` modules already have a level.
+pub fn set_module_level(module: &'static str, level: LevelFilter) -> bool {
+    let mut filters = MODULE_FILTERS.lock();
+    let set = filters.set(module, level);
+
+    HAS_MODULE_FILTERS.store(!filters.is_empty(), Ordering::Relaxed);
+    set
+}
+
+/// # Clear Module Level
+/// Remove the level of `module`, so it uses the max level again.
+pub fn clear_module_level(module: &str) {
+    let mut filters = MODULE_FILTERS.lock();
+    filters.clear(module);
+
+    HAS_MODULE_FILTERS.store(!filters.is_empty(), Ordering::Relaxed);
+}
+
+/// # Enabled
+/// Check if a message of `kind` from `module` should be printed.
+pub fn enabled(kind: LogKind, module: &str) -> bool {
+    if HAS_MODULE_FILTERS.load(Ordering::Relaxed) {
+        if let Some(level) = MODULE_FILTERS.lock().level_of(module) {
+            return level.allows(kind);
+        }
+    }
+
+    max_level().allows(kind)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_level_allows() {
+        assert!(LevelFilter::Warn.allows(LogKind::Error));
+        assert!(LevelFilter::Warn.allows(LogKind::Warn));
+        assert!(!LevelFilter::Warn.allows(LogKind::Log));
+        assert!(!LevelFilter::Off.allows(LogKind::Error));
+    }
+
+    #[test]
+    fn test_most_specific_module_wins() {
+        let mut filters = ModuleFilters::new();
+        assert!(filters.set("kernel", LevelFilter::Warn));
+        assert!(filters.set("kernel::paging", LevelFilter::Off));
+
+        assert_eq!(
+            filters.level_of("kernel::paging::tables"),
+            Some(LevelFilter::Off)
+        );
+        assert_eq!(
+            filters.level_of("kernel::scheduler"),
+            Some(LevelFilter::Warn)
+        );
+        assert_eq!(filters.level_of("kernel_tests"), None);
+        assert_eq!(filters.level_of("fs"), None);
+
+        filters.clear("kernel::paging");
+        assert_eq!(filters.level_of("kernel::paging"), Some(LevelFilter::Warn));
+    }
+
+    #[test]
+    fn test_module_filters_fill_up() {
+        const NAMES: [&str; MAX_MODULE_FILTERS] = [
+            "a", "b", "c", "d", "e", "f", "g", "h", "i", "j", "k", "l", "m", "n", "o", "p",
+        ];
+
+        let mut filters = ModuleFilters::new();
+        for name in NAMES {
+            assert!(filters.set(name, LevelFilter::Off));
+        }
+
+        // Changing an existing module still works once full
+        assert!(filters.set("a", LevelFilter::Log));
+        assert!(!filters.set("q", LevelFilter::Log));
+        assert_eq!(filters.level_of("a"), Some(LevelFilter::Log));
+    }
+}
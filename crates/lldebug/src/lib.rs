@@ -32,13 +32,16 @@ pub use lldebug_macro::debug_ready;
 pub use lldebug_macro::make_debug;
 
 pub mod color;
+pub mod filter;
 pub mod hexdump;
+pub mod telemetry;
 
 // Re-exports for spin
 pub mod sync {
     pub use spin::Mutex;
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum LogKind {
     Log,
     Warn,
@@ -47,8 +50,27 @@ pub enum LogKind {
 
 pub type OutputFn = fn(core::fmt::Arguments);
 
+/// # Timestamp Fn
+/// Gets the time since boot (or any other monotonic time) for the log header.
+pub type TimestampFn = fn() -> core::time::Duration;
+
+/// # Context Fn
+/// Gets the CPU and task the current message is being logged from.
+pub type ContextFn = fn() -> LogContext;
+
+/// # Log Context
+/// Where a message was logged from, printed in the log header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LogContext {
+    pub cpu: u32,
+    /// The running task, if there is one (like before the scheduler starts).
+    pub task: Option<u64>,
+}
+
 static REQUIRES_HEADER_PRINT: sync::Mutex<bool> = sync::Mutex::new(true);
 static GLOBAL_PRINT_FN: sync::Mutex<Option<OutputFn>> = sync::Mutex::new(None);
+static GLOBAL_TIMESTAMP_FN: sync::Mutex<Option<TimestampFn>> = sync::Mutex::new(None);
+static GLOBAL_CONTEXT_FN: sync::Mutex<Option<ContextFn>> = sync::Mutex::new(None);
 
 fn raw_print(args: core::fmt::Arguments) {
     match GLOBAL_PRINT_FN.lock().as_ref() {
@@ -61,6 +83,86 @@ pub fn set_global_debug_fn(function: OutputFn) {
     *GLOBAL_PRINT_FN.lock() = Some(function);
 }
 
+/// # Set Global Timestamp Fn
+/// Start every line with the time `function` returns, it must not log anything
+/// itself.
+pub fn set_global_timestamp_fn(function: TimestampFn) {
+    *GLOBAL_TIMESTAMP_FN.lock() = Some(function);
+}
+
+/// # Set Global Context Fn
+/// Start every line with the CPU and task `function` returns, it must not log
+/// anything itself.
+pub fn set_global_context_fn(function: ContextFn) {
+    *GLOBAL_CONTEXT_FN.lock() = Some(function);
+}
+
+/// Forwards everything into the global print function.
+struct RawOutput;
+
+impl core::fmt::Write for RawOutput {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        raw_print(format_args!("{}", s));
+        Ok(())
+    }
+}
+
+fn write_header<W: core::fmt::Write>(
+    out: &mut W,
+    kind: LogKind,
+    crate_name: &str,
+    timestamp: Option<core::time::Duration>,
+    context: Option<LogContext>,
+) -> core::fmt::Result {
+    match kind {
+        LogKind::Log => write!(out, "\n{}+{}", color::LOG_STYLE, color::RESET)?,
+        LogKind::Warn => write!(out, "\n{}-{}", color::WARN_STYLE, color::RESET)?,
+        LogKind::Error => write!(out, "\n{}X{}", color::ERR_STYLE, color::RESET)?,
+    }
+
+    if let Some(timestamp) = timestamp {
+        write!(
+            out,
+            "{}[{:>5}.{:06}]{} ",
+            color::DIM_STYLE,
+            timestamp.as_secs(),
+            timestamp.subsec_micros(),
+            color::RESET
+        )?;
+    }
+
+    match context {
+        Some(LogContext {
+            cpu,
+            task: Some(task),
+        }) => write!(
+            out,
+            "{}cpu{} t{:<4}{} ",
+            color::DIM_STYLE,
+            cpu,
+            task,
+            color::RESET
+        )?,
+        Some(LogContext { cpu, task: None }) => write!(
+            out,
+            "{}cpu{} {:<5}{} ",
+            color::DIM_STYLE,
+            cpu,
+            "-",
+            color::RESET
+        )?,
+        None => (),
+    }
+
+    write!(
+        out,
+        "{}{:<30}{} : ",
+        color::DIM_STYLE,
+        crate_name,
+        color::RESET
+    )
+}
+
 struct PrettyOutput<'a> {
     kind: LogKind,
     crate_name: &'a str,
@@ -83,24 +185,19 @@ impl core::fmt::Write for PrettyOutput<'_> {
 
                 if *req_header {
                     *req_header = false;
-                    match self.kind {
-                        LogKind::Log => {
-                            raw_print(format_args!("\n{}+{}", color::LOG_STYLE, color::RESET))
-                        }
-                        LogKind::Warn => {
-                            raw_print(format_args!("\n{}-{}", color::WARN_STYLE, color::RESET))
-                        }
-                        LogKind::Error => {
-                            raw_print(format_args!("\n{}X{}", color::ERR_STYLE, color::RESET))
-                        }
-                    }
-
-                    raw_print(format_args!(
-                        "{}{:<30}{} : ",
-                        color::DIM_STYLE,
+
+                    let timestamp_fn = *GLOBAL_TIMESTAMP_FN.lock();
+                    let context_fn = *GLOBAL_CONTEXT_FN.lock();
+
+                    let timestamp = timestamp_fn.map(|timestamp| timestamp());
+                    let context = context_fn.map(|context| context());
+                    write_header(
+                        &mut RawOutput,
+                        self.kind,
                         self.crate_name,
-                        color::RESET
-                    ));
+                        timestamp,
+                        context,
+                    )?;
                 }
 
                 raw_print(format_args!("{}", c));
@@ -120,7 +217,9 @@ pub fn priv_print(kind: LogKind, crate_name: &str, args: core::fmt::Arguments) {
 #[macro_export]
 macro_rules! log {
     ($($arg:tt)*) => {{
-        $crate::priv_print(::lldebug::LogKind::Log, ::core::module_path!(), format_args!($($arg)*));
+        if $crate::filter::enabled($crate::LogKind::Log, ::core::module_path!()) {
+            $crate::priv_print(::lldebug::LogKind::Log, ::core::module_path!(), format_args!($($arg)*));
+        }
     }};
 }
 
@@ -129,7 +228,7 @@ macro_rules! log {
 macro_rules! logln {
     () => {{ $crate::log!("\n") }};
     ($($arg:tt)*) => {{
-        $crate::priv_print(::lldebug::LogKind::Log, ::core::module_path!(), format_args!($($arg)*));
+        $crate::log!($($arg)*);
         $crate::log!("\n");
     }};
 }
@@ -138,7 +237,9 @@ macro_rules! logln {
 #[macro_export]
 macro_rules! warn {
     ($($arg:tt)*) => {{
-        $crate::priv_print(::lldebug::LogKind::Warn, ::core::module_path!(), format_args!($($arg)*));
+        if $crate::filter::enabled($crate::LogKind::Warn, ::core::module_path!()) {
+            $crate::priv_print(::lldebug::LogKind::Warn, ::core::module_path!(), format_args!($($arg)*));
+        }
     }};
 }
 
@@ -147,7 +248,7 @@ macro_rules! warn {
 macro_rules! warnln {
     () => {{ $crate::warn!("\n") }};
     ($($arg:tt)*) => {{
-        $crate::priv_print(::lldebug::LogKind::Warn, ::core::module_path!(), format_args!($($arg)*));
+        $crate::warn!($($arg)*);
         $crate::warn!("\n");
     }};
 }
@@ -156,7 +257,9 @@ macro_rules! warnln {
 #[macro_export]
 macro_rules! error {
     ($($arg:tt)*) => {{
-        $crate::priv_print(::lldebug::LogKind::Error, ::core::module_path!(), format_args!($($arg)*));
+        if $crate::filter::enabled($crate::LogKind::Error, ::core::module_path!()) {
+            $crate::priv_print(::lldebug::LogKind::Error, ::core::module_path!(), format_args!($($arg)*));
+        }
     }};
 }
 
@@ -165,7 +268,7 @@ macro_rules! error {
 macro_rules! errorln {
     () => {{ $crate::error!("\n") }};
     ($($arg:tt)*) => {{
-        $crate::priv_print(::lldebug::LogKind::Error, ::core::module_path!(), format_args!($($arg)*));
+        $crate::error!($($arg)*);
         $crate::error!("\n");
     }};
 }
@@ -187,3 +290,63 @@ macro_rules! testing_stdout {
         }
     };
 }
+
+#[cfg(test)]
+mod test {
+    extern crate std;
+    use super::*;
+    use core::time::Duration;
+    use std::{format, string::String};
+
+    fn header(timestamp: Option<Duration>, context: Option<LogContext>) -> String {
+        let mut out = String::new();
+        write_header(&mut out, LogKind::Warn, "kernel", timestamp, context).unwrap();
+        out
+    }
+
+    #[test]
+    fn test_plain_header() {
+        assert_eq!(
+            header(None, None),
+            format!(
+                "\n{}-{}{}{:<30}{} : ",
+                color::WARN_STYLE,
+                color::RESET,
+                color::DIM_STYLE,
+                "kernel",
+                color::RESET
+            )
+        );
+    }
+
+    #[test]
+    fn test_timestamp_and_context_header() {
+        let header = header(
+            Some(Duration::from_micros(12_345_678)),
+            Some(LogContext {
+                cpu: 1,
+                task: Some(42),
+            }),
+        );
+
+        let prefix = format!("\n{}-{}", color::WARN_STYLE, color::RESET);
+        let rest = header.strip_prefix(&prefix).unwrap();
+        assert!(
+            rest.starts_with(&format!(
+                "{}[   12.345678]{} {}cpu1 t42  {} ",
+                color::DIM_STYLE,
+                color::RESET,
+                color::DIM_STYLE,
+                color::RESET
+            )),
+            "{rest:?}"
+        );
+        assert!(rest.ends_with(&format!("{:<30}{} : ", "kernel", color::RESET)));
+    }
+
+    #[test]
+    fn test_context_without_task() {
+        let header = header(None, Some(LogContext { cpu: 0, task: None }));
+        assert!(header.contains("cpu0 -    "), "{header:?}");
+    }
+}
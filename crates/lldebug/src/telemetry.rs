@@ -0,0 +1,193 @@
+/*
+  ____                 __               __   _ __
+ / __ \__ _____ ____  / /___ ____ _    / /  (_) /
+/ /_/ / // / _ `/ _ \/ __/ // /  ' \  / /__/ / _ \
+\___\_\_,_/\_,_/_//_/\__/\_,_/_/_/_/ /____/_/_.__/
+    Part of the Quantum OS Project
+
+Copyright 2025 Gavin Kellam
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and
+associated documentation files (the "Software"), to deal in the Software without restriction,
+including without limitation the rights to use, copy, modify, merge, publish, distribute,
+sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial
+portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT
+NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
+OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+use core::fmt::Write;
+
+/// # Telemetry Event
+/// A structured event that can be sent to a machine readable output (like
+/// QEMU's debugcon) so runs can be turned into reports on the host.
+///
+/// Each event is emitted as one line of JSON.
+pub enum TelemetryEvent<'a> {
+    /// A stage of the boot process has started.
+    BootPhase { phase: &'a str },
+    /// A test has finished running.
+    TestResult { name: &'a str, passed: bool },
+    /// The system has panicked.
+    Panic { message: core::fmt::Arguments<'a> },
+}
+
+static GLOBAL_TELEMETRY_FN: crate::sync::Mutex<Option<crate::OutputFn>> =
+    crate::sync::Mutex::new(None);
+
+/// # Set Global Telemetry Fn
+/// Set the function that all telemetry events are written into.
+pub fn set_global_telemetry_fn(function: crate::OutputFn) {
+    *GLOBAL_TELEMETRY_FN.lock() = Some(function);
+}
+
+/// Adapter to escape everything written into a JSON string.
+struct JsonEscape<W: Write>(W);
+
+impl<W: Write> Write for JsonEscape<W> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        for c in s.chars() {
+            self.write_char(c)?;
+        }
+
+        Ok(())
+    }
+
+    fn write_char(&mut self, c: char) -> core::fmt::Result {
+        match c {
+            '"' => self.0.write_str("\\\""),
+            '\\' => self.0.write_str("\\\\"),
+            '\n' => self.0.write_str("\\n"),
+            '\r' => self.0.write_str("\\r"),
+            '\t' => self.0.write_str("\\t"),
+            c if (c as u32) < 0x20 => self.0.write_fmt(format_args!("\\u{:04x}", c as u32)),
+            c => self.0.write_char(c),
+        }
+    }
+}
+
+/// Forwards everything into the global telemetry function.
+struct TelemetryOutput(crate::OutputFn);
+
+impl Write for TelemetryOutput {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        (self.0)(format_args!("{}", s));
+        Ok(())
+    }
+}
+
+fn write_event<W: Write>(
+    out: &mut W,
+    crate_name: &str,
+    event: &TelemetryEvent,
+) -> core::fmt::Result {
+    out.write_str("{\"source\":\"")?;
+    JsonEscape(&mut *out).write_str(crate_name)?;
+    out.write_str("\",")?;
+
+    match event {
+        TelemetryEvent::BootPhase { phase } => {
+            out.write_str("\"event\":\"boot_phase\",\"phase\":\"")?;
+            JsonEscape(&mut *out).write_str(phase)?;
+            out.write_str("\"")?;
+        }
+        TelemetryEvent::TestResult { name, passed } => {
+            out.write_str("\"event\":\"test_result\",\"name\":\"")?;
+            JsonEscape(&mut *out).write_str(name)?;
+            out.write_fmt(format_args!("\",\"passed\":{}", passed))?;
+        }
+        TelemetryEvent::Panic { message } => {
+            out.write_str("\"event\":\"panic\",\"message\":\"")?;
+            JsonEscape(&mut *out).write_fmt(*message)?;
+            out.write_str("\"")?;
+        }
+    }
+
+    out.write_str("}\n")
+}
+
+#[doc(hidden)]
+pub fn priv_emit(crate_name: &str, event: TelemetryEvent) {
+    let Some(output) = *GLOBAL_TELEMETRY_FN.lock() else {
+        return;
+    };
+
+    let _ = write_event(&mut TelemetryOutput(output), crate_name, &event);
+}
+
+/// Emit a structured telemetry event to the attached telemetry output.
+#[macro_export]
+macro_rules! telemetry {
+    (boot_phase: $phase:expr) => {{
+        $crate::telemetry::priv_emit(
+            ::core::module_path!(),
+            $crate::telemetry::TelemetryEvent::BootPhase { phase: $phase },
+        );
+    }};
+    (test_result: $name:expr, $passed:expr) => {{
+        $crate::telemetry::priv_emit(
+            ::core::module_path!(),
+            $crate::telemetry::TelemetryEvent::TestResult {
+                name: $name,
+                passed: $passed,
+            },
+        );
+    }};
+    (panic: $($arg:tt)*) => {{
+        $crate::telemetry::priv_emit(
+            ::core::module_path!(),
+            $crate::telemetry::TelemetryEvent::Panic {
+                message: format_args!($($arg)*),
+            },
+        );
+    }};
+}
+
+#[cfg(test)]
+mod test {
+    extern crate std;
+    use super::*;
+    use std::string::String;
+
+    fn event_string(event: TelemetryEvent) -> String {
+        let mut out = String::new();
+        write_event(&mut out, "kernel", &event).unwrap();
+        out
+    }
+
+    #[test]
+    fn test_boot_phase_event() {
+        assert_eq!(
+            event_string(TelemetryEvent::BootPhase { phase: "stage32" }),
+            "{\"source\":\"kernel\",\"event\":\"boot_phase\",\"phase\":\"stage32\"}\n"
+        );
+    }
+
+    #[test]
+    fn test_result_event() {
+        assert_eq!(
+            event_string(TelemetryEvent::TestResult {
+                name: "fat_read",
+                passed: false
+            }),
+            "{\"source\":\"kernel\",\"event\":\"test_result\",\"name\":\"fat_read\",\"passed\":false}\n"
+        );
+    }
+
+    #[test]
+    fn test_panic_message_is_escaped() {
+        assert_eq!(
+            event_string(TelemetryEvent::Panic {
+                message: format_args!("bad \"{}\"\n\\", 10)
+            }),
+            "{\"source\":\"kernel\",\"event\":\"panic\",\"message\":\"bad \\\"10\\\"\\n\\\\\"}\n"
+        );
+    }
+}
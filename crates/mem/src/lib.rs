@@ -25,11 +25,18 @@ OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWA
 
 #![no_std]
 
+pub mod paging;
+pub mod pcid;
 pub mod phys;
+pub mod pmm;
+pub mod slab;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum MemoryError {
     ArrayTooSmall,
     EmptySegment,
     InvalidSize,
+    NotAligned,
+    OutOfMemory,
+    DoubleFree,
 }
@@ -0,0 +1,537 @@
+/*
+  ____                 __               __   _ __
+ / __ \__ _____ ____  / /___ ____ _    / /  (_) /
+/ /_/ / // / _ `/ _ \/ __/ // /  ' \  / /__/ / _ \
+\___\_\_,_/\_,_/_//_/\__/\_,_/_/_/_/ /____/_/_.__/
+    Part of the Quantum OS Project
+
+Copyright 2025 Gavin Kellam
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and
+associated documentation files (the "Software"), to deal in the Software without restriction,
+including without limitation the rights to use, copy, modify, merge, publish, distribute,
+sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial
+portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT
+NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
+OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+use bits::BitManipulation;
+use core::ops::Range;
+
+/// # Table Reader
+/// How page table entries are read out of physical memory while walking.
+pub trait TableReader {
+    /// # Read Entry
+    /// Read the entry at `index` of the page table at physical address `table`.
+    fn read_entry(&self, table: u64, index: usize) -> u64;
+}
+
+/// # Table Writer
+/// Page tables that can also be changed while walking.
+pub trait TableWriter: TableReader {
+    /// # Write Entry
+    /// Replace the entry at `index` of the page table at physical address `table`.
+    fn write_entry(&mut self, table: u64, index: usize, entry: u64);
+}
+
+/// # Identity Reader
+/// Reads (and writes) page tables assuming physical memory is identity mapped.
+pub struct IdentityReader(());
+
+impl IdentityReader {
+    /// # New
+    /// Create a new identity reader.
+    ///
+    /// # Safety
+    /// All page tables that will be walked must be identity mapped.
+    pub const unsafe fn new() -> Self {
+        Self(())
+    }
+}
+
+impl TableReader for IdentityReader {
+    fn read_entry(&self, table: u64, index: usize) -> u64 {
+        unsafe { ((table as *const u64).add(index)).read_volatile() }
+    }
+}
+
+impl TableWriter for IdentityReader {
+    fn write_entry(&mut self, table: u64, index: usize, entry: u64) {
+        unsafe { ((table as *mut u64).add(index)).write_volatile(entry) }
+    }
+}
+
+/// # Page Flags
+/// The effective permissions of a mapping, combined from every level of the walk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PageFlags {
+    pub writable: bool,
+    pub user: bool,
+    pub write_through: bool,
+    pub cache_disable: bool,
+    pub global: bool,
+    pub no_execute: bool,
+}
+
+/// # Walk Step
+/// One level of a page table walk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WalkStep {
+    /// The level of the table (4 = PML4, 1 = PT).
+    pub level: u8,
+    /// The physical address of the table.
+    pub table: u64,
+    /// The index into the table used for this address.
+    pub index: usize,
+    /// The raw entry read from the table.
+    pub entry: u64,
+}
+
+/// # Mapping
+/// Where a virtual address ends up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mapping {
+    /// The start of the page containing the address.
+    pub virt_page: u64,
+    /// The physical address of the page.
+    pub phys_page: u64,
+    /// The size of the page (4K, 2M or 1G).
+    pub page_size: u64,
+    pub flags: PageFlags,
+}
+
+/// # Page Walk
+/// The full chain of entries the CPU would use to translate an address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PageWalk {
+    steps: [WalkStep; 4],
+    len: usize,
+    /// The final mapping, or `None` if the walk hit a non-present entry.
+    pub mapping: Option<Mapping>,
+}
+
+impl PageWalk {
+    /// # Steps
+    /// Every table entry visited, starting at the PML4.
+    pub fn steps(&self) -> &[WalkStep] {
+        &self.steps[..self.len]
+    }
+
+    /// # Covered Size
+    /// The amount of virtual memory the last visited entry covers, either mapped or
+    /// not mapped.
+    pub fn covered_size(&self) -> u64 {
+        let level = self.steps().last().map(|step| step.level).unwrap_or(4);
+        level_page_size(level)
+    }
+}
+
+/// The bytes of memory an entry at `level` covers.
+const fn level_page_size(level: u8) -> u64 {
+    4096 << (9 * (level as u64 - 1))
+}
+
+const PRESENT_BIT: u8 = 0;
+const WRITABLE_BIT: u8 = 1;
+const USER_BIT: u8 = 2;
+const WRITE_THROUGH_BIT: u8 = 3;
+const CACHE_DISABLE_BIT: u8 = 4;
+const DIRTY_BIT: u8 = 6;
+const HUGE_PAGE_BIT: u8 = 7;
+const GLOBAL_BIT: u8 = 8;
+const NO_EXECUTE_BIT: u8 = 63;
+
+/// The end of the lower half of the address space.
+pub const LOWER_HALF_END: u64 = 0x0000_8000_0000_0000;
+
+/// The start of the higher half of the address space, everything from
+/// [`LOWER_HALF_END`] up to here is the non-canonical hole.
+pub const HIGHER_HALF_START: u64 = 0xFFFF_8000_0000_0000;
+
+/// # Is Canonical
+/// Check that `virt_addr` has bits `48..64` all matching bit 47, the only addresses
+/// 4-level paging can translate.
+pub const fn is_canonical(virt_addr: u64) -> bool {
+    virt_addr < LOWER_HALF_END || virt_addr >= HIGHER_HALF_START
+}
+
+/// The physical address an entry points to (`12..52`).
+fn entry_address(entry: u64) -> u64 {
+    entry.get_bit_range(12..52) << 12
+}
+
+/// # Walk
+/// Walk the 4-level page tables rooted at `root` (the physical address in CR3) to
+/// translate `virt_addr`.
+///
+/// Returns `None` if `virt_addr` isn't canonical, the CPU faults on those before
+/// looking at the page tables.
+pub fn walk(reader: &impl TableReader, root: u64, virt_addr: u64) -> Option<PageWalk> {
+    if !is_canonical(virt_addr) {
+        return None;
+    }
+
+    let mut walk = PageWalk {
+        steps: [WalkStep {
+            level: 0,
+            table: 0,
+            index: 0,
+            entry: 0,
+        }; 4],
+        len: 0,
+        mapping: None,
+    };
+
+    let mut flags = PageFlags {
+        writable: true,
+        user: true,
+        write_through: false,
+        cache_disable: false,
+        global: false,
+        no_execute: false,
+    };
+
+    let mut table = root & !0xFFF;
+    for level in (1..=4u8).rev() {
+        let shift = 12 + (9 * (level as u64 - 1));
+        let index = virt_addr.get_bit_range(shift..(shift + 9)) as usize;
+        let entry = reader.read_entry(table, index);
+
+        walk.steps[walk.len] = WalkStep {
+            level,
+            table,
+            index,
+            entry,
+        };
+        walk.len += 1;
+
+        if !entry.get_bit(PRESENT_BIT) {
+            return Some(walk);
+        }
+
+        flags.writable &= entry.get_bit(WRITABLE_BIT);
+        flags.user &= entry.get_bit(USER_BIT);
+        flags.no_execute |= entry.get_bit(NO_EXECUTE_BIT);
+
+        // Huge pages are only possible at the PDPT and PD levels
+        if level == 1 || ((level == 2 || level == 3) && entry.get_bit(HUGE_PAGE_BIT)) {
+            let page_size = level_page_size(level);
+
+            flags.write_through = entry.get_bit(WRITE_THROUGH_BIT);
+            flags.cache_disable = entry.get_bit(CACHE_DISABLE_BIT);
+            flags.global = entry.get_bit(GLOBAL_BIT);
+
+            walk.mapping = Some(Mapping {
+                virt_page: virt_addr & !(page_size - 1),
+                phys_page: entry_address(entry) & !(page_size - 1),
+                page_size,
+                flags,
+            });
+            return Some(walk);
+        }
+
+        table = entry_address(entry);
+    }
+
+    Some(walk)
+}
+
+/// # Mapped Range
+/// A run of virtual memory mapped to contiguous physical memory with the same flags.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MappedRange {
+    pub virt: Range<u64>,
+    pub phys_start: u64,
+    pub flags: PageFlags,
+}
+
+/// # Dump Range
+/// Iterate over every mapping in `start..end`, merging neighboring pages that map to
+/// contiguous physical memory with identical flags.
+///
+/// Unmapped regions are skipped a whole table at a time (and the non-canonical hole
+/// all at once), so sparse address spaces are cheap to dump.
+pub fn dump_range<'a, R: TableReader>(
+    reader: &'a R,
+    root: u64,
+    start: u64,
+    end: u64,
+) -> impl Iterator<Item = MappedRange> + 'a {
+    let mut addr = start & !0xFFF;
+    let mut pending: Option<MappedRange> = None;
+
+    core::iter::from_fn(move || {
+        while addr < end {
+            let (mapping, next_addr) = match walk(reader, root, addr) {
+                Some(page_walk) => {
+                    let covered = page_walk.covered_size();
+                    let next_addr = (addr & !(covered - 1)).saturating_add(covered);
+
+                    (page_walk.mapping, next_addr.min(end))
+                }
+                None => (None, HIGHER_HALF_START.min(end)),
+            };
+
+            let Some(mapping) = mapping else {
+                addr = next_addr;
+
+                if let Some(range) = pending.take() {
+                    return Some(range);
+                }
+                continue;
+            };
+
+            let virt_start = addr;
+            let phys = mapping.phys_page + (addr - mapping.virt_page);
+            addr = next_addr;
+
+            match &mut pending {
+                Some(range)
+                    if range.flags == mapping.flags
+                        && range.virt.end == virt_start
+                        && range.phys_start + (range.virt.end - range.virt.start) == phys =>
+                {
+                    range.virt.end = next_addr;
+                }
+                _ => {
+                    let finished = pending.replace(MappedRange {
+                        virt: virt_start..next_addr,
+                        phys_start: phys,
+                        flags: mapping.flags,
+                    });
+
+                    if finished.is_some() {
+                        return finished;
+                    }
+                }
+            }
+        }
+
+        pending.take()
+    })
+}
+
+/// # Take Dirty
+/// Call `visit` with every page in `start..end` the CPU has written to since its
+/// dirty bit was last cleared, and clear it so the next call only sees new writes.
+///
+/// # Note
+/// The CPU caches dirty bits in the TLB, so the caller must flush the TLB for these
+/// pages (or reload CR3) before writes to them are tracked again.
+pub fn take_dirty(
+    tables: &mut impl TableWriter,
+    root: u64,
+    start: u64,
+    end: u64,
+    mut visit: impl FnMut(Mapping),
+) {
+    let mut addr = start & !0xFFF;
+
+    while addr < end {
+        let Some(page_walk) = walk(tables, root, addr) else {
+            addr = HIGHER_HALF_START;
+            continue;
+        };
+
+        let covered = page_walk.covered_size();
+        let next_addr = (addr & !(covered - 1)).saturating_add(covered);
+
+        if let (Some(mapping), Some(leaf)) = (page_walk.mapping, page_walk.steps().last())
+            && leaf.entry.get_bit(DIRTY_BIT)
+        {
+            let mut entry = leaf.entry;
+            entry.set_bit(DIRTY_BIT, false);
+
+            tables.write_entry(leaf.table, leaf.index, entry);
+            visit(mapping);
+        }
+
+        addr = next_addr;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate std;
+
+    use super::*;
+    use std::{vec, vec::Vec};
+
+    /// Page tables where each table's 'physical address' is its index * 4096.
+    struct FakeTables(Vec<[u64; 512]>);
+
+    impl FakeTables {
+        fn new(tables: usize) -> Self {
+            Self(vec![[0; 512]; tables])
+        }
+
+        fn link(&mut self, table: usize, index: usize, next: usize) {
+            self.0[table][index] = ((next as u64) << 12) | 0b11;
+        }
+    }
+
+    impl TableReader for FakeTables {
+        fn read_entry(&self, table: u64, index: usize) -> u64 {
+            self.0[(table >> 12) as usize][index]
+        }
+    }
+
+    impl TableWriter for FakeTables {
+        fn write_entry(&mut self, table: u64, index: usize, entry: u64) {
+            self.0[(table >> 12) as usize][index] = entry;
+        }
+    }
+
+    const GIB: u64 = 1024 * 1024 * 1024;
+    const MIB: u64 = 1024 * 1024;
+
+    /// PML4 -> PDPT -> PD -> PT, mapping:
+    /// - `0..8K` to `0x100000..` as 4K pages (second page read-only)
+    /// - `2M..6M` as 2M pages to `0x40000000..`
+    /// - `1G..2G` as a 1G page to `0x80000000`
+    fn example_tables() -> FakeTables {
+        let mut tables = FakeTables::new(4);
+        tables.link(0, 0, 1);
+        tables.link(1, 0, 2);
+        tables.link(2, 0, 3);
+
+        tables.0[3][0] = 0x100000 | 0b11;
+        tables.0[3][1] = 0x101000 | 0b01;
+        tables.0[2][1] = 0x40000000 | 0b1000_0011;
+        tables.0[2][2] = (0x40000000 + 2 * MIB) | 0b1000_0011;
+        tables.0[1][1] = 0x80000000 | (1 << 63) | 0b1000_0011;
+
+        tables
+    }
+
+    #[test]
+    fn test_walk_4k() {
+        let tables = example_tables();
+        let walk = walk(&tables, 0, 0x1234).unwrap();
+
+        assert_eq!(walk.steps().len(), 4);
+        assert_eq!(walk.steps()[3].index, 1);
+        assert_eq!(
+            walk.mapping,
+            Some(Mapping {
+                virt_page: 0x1000,
+                phys_page: 0x101000,
+                page_size: 4096,
+                flags: PageFlags {
+                    writable: false,
+                    user: false,
+                    write_through: false,
+                    cache_disable: false,
+                    global: false,
+                    no_execute: false,
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn test_walk_huge_pages() {
+        let tables = example_tables();
+
+        let walk_2m = walk(&tables, 0, 3 * MIB).unwrap();
+        assert_eq!(walk_2m.steps().len(), 3);
+        assert_eq!(walk_2m.mapping.unwrap().phys_page, 0x40000000);
+        assert_eq!(walk_2m.mapping.unwrap().page_size, 2 * MIB);
+
+        let walk_1g = walk(&tables, 0, GIB + 5).unwrap();
+        assert_eq!(walk_1g.steps().len(), 2);
+        assert!(walk_1g.mapping.unwrap().flags.no_execute);
+        assert_eq!(walk_1g.mapping.unwrap().page_size, GIB);
+    }
+
+    #[test]
+    fn test_walk_not_present() {
+        let tables = example_tables();
+
+        let walk = walk(&tables, 0, 512 * GIB).unwrap();
+        assert_eq!(walk.steps().len(), 1);
+        assert_eq!(walk.mapping, None);
+        assert_eq!(walk.covered_size(), 512 * GIB);
+    }
+
+    #[test]
+    fn test_walk_non_canonical() {
+        let mut tables = example_tables();
+        tables.link(0, 256, 1);
+
+        // Bit 47 set without the bits above it would use PML4 entry 256
+        assert!(walk(&tables, 0, LOWER_HALF_END + GIB).is_none());
+        assert!(walk(&tables, 0, HIGHER_HALF_START - 1).is_none());
+        assert!(walk(&tables, 0, HIGHER_HALF_START + GIB).is_some());
+        assert!(walk(&tables, 0, LOWER_HALF_END - 1).is_some());
+    }
+
+    #[test]
+    fn test_dump_range() {
+        let tables = example_tables();
+        let ranges: Vec<_> = dump_range(&tables, 0, 0, 4 * GIB).collect();
+
+        assert_eq!(ranges.len(), 4);
+        assert_eq!(ranges[0].virt, 0..0x1000);
+        assert_eq!(ranges[1].virt, 0x1000..0x2000);
+        assert_eq!(ranges[1].phys_start, 0x101000);
+        assert_eq!(ranges[2].virt, 2 * MIB..6 * MIB);
+        assert_eq!(ranges[2].phys_start, 0x40000000);
+        assert_eq!(ranges[3].virt, GIB..2 * GIB);
+    }
+
+    #[test]
+    fn test_dump_across_the_hole() {
+        let mut tables = example_tables();
+        tables.link(0, 256, 1);
+        tables.link(0, 511, 1);
+
+        let ranges: Vec<_> = dump_range(&tables, 0, 0, u64::MAX).collect();
+        let upper = HIGHER_HALF_START + 255 * 512 * GIB;
+
+        assert_eq!(ranges.len(), 12);
+        assert_eq!(ranges[4].virt.start, HIGHER_HALF_START);
+        assert_eq!(ranges[8].virt.start, upper);
+        assert_eq!(ranges[11].virt, upper + GIB..upper + 2 * GIB);
+    }
+
+    #[test]
+    fn test_dump_partial_range() {
+        let tables = example_tables();
+        let ranges: Vec<_> = dump_range(&tables, 0, 3 * MIB, 5 * MIB).collect();
+
+        assert_eq!(ranges.len(), 1);
+        assert_eq!(ranges[0].virt, 3 * MIB..5 * MIB);
+        assert_eq!(ranges[0].phys_start, 0x40000000 + MIB);
+    }
+
+    #[test]
+    fn test_take_dirty() {
+        let mut tables = example_tables();
+        tables.0[3][0] |= 1 << 6;
+        tables.0[2][2] |= 1 << 6;
+        tables.0[1][1] |= 1 << 6;
+
+        let mut dirty = Vec::new();
+        take_dirty(&mut tables, 0, 0, 2 * GIB, |mapping| {
+            dirty.push((mapping.virt_page, mapping.page_size))
+        });
+        assert_eq!(dirty, [(0, 4096), (4 * MIB, 2 * MIB), (GIB, GIB)]);
+
+        // Only the dirty bit was cleared
+        assert_eq!(tables.0[3][0], 0x100000 | 0b11);
+        assert_eq!(tables.0[1][1], 0x80000000 | (1 << 63) | 0b1000_0011);
+
+        let mut count = 0;
+        take_dirty(&mut tables, 0, 0, 2 * GIB, |_| count += 1);
+        assert_eq!(count, 0);
+    }
+}
@@ -0,0 +1,266 @@
+/*
+  ____                 __               __   _ __
+ / __ \__ _____ ____  / /___ ____ _    / /  (_) /
+/ /_/ / // / _ `/ _ \/ __/ // /  ' \  / /__/ / _ \
+\___\_\_,_/\_,_/_//_/\__/\_,_/_/_/_/ /____/_/_.__/
+    Part of the Quantum OS Project
+
+Copyright 2025 Gavin Kellam
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and
+associated documentation files (the "Software"), to deal in the Software without restriction,
+including without limitation the rights to use, copy, modify, merge, publish, distribute,
+sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial
+portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT
+NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
+OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+use bits::BitSliceManipulation;
+
+/// # Pcid
+/// A process context identifier, used to tag TLB entries with the address
+/// space they belong to so switching address spaces doesn't need a full flush.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Pcid(u16);
+
+impl Pcid {
+    /// PCID 0 is used by the kernel and when PCIDs are disabled.
+    pub const KERNEL: Self = Self(0);
+    /// The number of PCIDs the CPU supports (12 bits).
+    pub const COUNT: usize = 4096;
+
+    /// # Value
+    /// Get the raw PCID number.
+    pub const fn value(&self) -> u16 {
+        self.0
+    }
+
+    /// # Cr3 Value
+    /// Build the value to load into CR3 to switch to `table_phys` with this
+    /// PCID.
+    ///
+    /// When `preserve_tlb` is set, the TLB entries already tagged with this
+    /// PCID are kept.
+    pub const fn cr3_value(&self, table_phys: u64, preserve_tlb: bool) -> u64 {
+        let no_flush = if preserve_tlb { 1 << 63 } else { 0 };
+        (table_phys & 0x000f_ffff_ffff_f000) | self.0 as u64 | no_flush
+    }
+}
+
+/// # Address Space Id
+/// The PCID assigned to an address space, and which generation of the
+/// allocator it was assigned in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AddressSpaceId {
+    pcid: Pcid,
+    generation: u64,
+}
+
+impl AddressSpaceId {
+    pub const fn pcid(&self) -> Pcid {
+        self.pcid
+    }
+}
+
+/// # Pcid Flush
+/// Which TLB entries must be flushed before switching into an address space.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PcidFlush {
+    /// The PCID is still owned by this address space, nothing to flush.
+    None,
+    /// The PCID was just assigned and may hold entries from its last owner.
+    Pcid(Pcid),
+    /// All PCIDs were recycled, so every PCID must be flushed.
+    All,
+}
+
+/// # Pcid Switch
+/// The result of activating an address space.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PcidSwitch {
+    pub pcid: Pcid,
+    pub flush: PcidFlush,
+}
+
+/// # Pcid Allocator
+/// Hands out PCIDs to address spaces.
+///
+/// When every PCID is taken the allocator starts a new generation: all PCIDs
+/// are released at once and address spaces from older generations get a new
+/// PCID the next time they are activated.
+pub struct PcidAllocator {
+    used: [u64; Pcid::COUNT / 64],
+    next: usize,
+    generation: u64,
+}
+
+impl PcidAllocator {
+    pub const fn new() -> Self {
+        let mut used = [0; Pcid::COUNT / 64];
+
+        // The kernel's PCID is never handed out
+        used[0] = 1;
+
+        Self {
+            used,
+            next: 1,
+            generation: 0,
+        }
+    }
+
+    /// # Generation
+    /// The current generation of the allocator.
+    pub const fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// # Available
+    /// The number of PCIDs that are free in this generation.
+    pub fn available(&self) -> usize {
+        self.used
+            .iter()
+            .map(|word| word.count_zeros() as usize)
+            .sum()
+    }
+
+    /// # Activate
+    /// Get the PCID to use when switching into an address space, assigning
+    /// a new one if it doesn't have one (or its PCID was recycled).
+    pub fn activate(&mut self, id: &mut Option<AddressSpaceId>) -> PcidSwitch {
+        if let Some(id) = id.filter(|id| id.generation == self.generation) {
+            return PcidSwitch {
+                pcid: id.pcid,
+                flush: PcidFlush::None,
+            };
+        }
+
+        let (pcid, flush) = match self.alloc() {
+            Some(pcid) => (pcid, PcidFlush::Pcid(pcid)),
+            None => {
+                self.recycle();
+
+                // A new generation always has free PCIDs
+                (self.alloc().unwrap(), PcidFlush::All)
+            }
+        };
+
+        *id = Some(AddressSpaceId {
+            pcid,
+            generation: self.generation,
+        });
+
+        PcidSwitch { pcid, flush }
+    }
+
+    /// # Release
+    /// Give back the PCID of an address space that is being destroyed.
+    pub fn release(&mut self, id: AddressSpaceId) {
+        if id.generation == self.generation && id.pcid != Pcid::KERNEL {
+            self.used.set_bit(id.pcid.0 as usize, false);
+        }
+    }
+
+    fn alloc(&mut self) -> Option<Pcid> {
+        let index = (self.next..Pcid::COUNT)
+            .chain(1..self.next)
+            .find(|&index| !self.used.get_bit(index))?;
+
+        self.used.set_bit(index, true);
+        self.next = (index + 1) % Pcid::COUNT;
+
+        Some(Pcid(index as u16))
+    }
+
+    fn recycle(&mut self) {
+        *self = Self {
+            generation: self.generation + 1,
+            ..Self::new()
+        };
+    }
+}
+
+impl Default for PcidAllocator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_cr3_value() {
+        let pcid = Pcid(5);
+
+        assert_eq!(pcid.cr3_value(0x1000, false), 0x1005);
+        assert_eq!(pcid.cr3_value(0x1000, true), 0x8000_0000_0000_1005);
+    }
+
+    #[test]
+    fn test_activate_keeps_pcid() {
+        let mut allocator = PcidAllocator::new();
+        let mut space = None;
+
+        let first = allocator.activate(&mut space);
+        assert_eq!(first.pcid, Pcid(1));
+        assert_eq!(first.flush, PcidFlush::Pcid(Pcid(1)));
+
+        let second = allocator.activate(&mut space);
+        assert_eq!(second.pcid, Pcid(1));
+        assert_eq!(second.flush, PcidFlush::None);
+    }
+
+    #[test]
+    fn test_release_and_reuse() {
+        let mut allocator = PcidAllocator::new();
+        let mut a = None;
+        let mut b = None;
+
+        allocator.activate(&mut a);
+        allocator.activate(&mut b);
+        assert_eq!(allocator.available(), Pcid::COUNT - 3);
+
+        allocator.release(a.unwrap());
+        assert_eq!(allocator.available(), Pcid::COUNT - 2);
+    }
+
+    #[test]
+    fn test_recycle_on_exhaustion() {
+        let mut allocator = PcidAllocator::new();
+        let mut first = None;
+        allocator.activate(&mut first);
+
+        for _ in 2..Pcid::COUNT {
+            allocator.activate(&mut None);
+        }
+
+        assert_eq!(allocator.available(), 0);
+
+        let mut overflow = None;
+        let switch = allocator.activate(&mut overflow);
+        assert_eq!(switch.flush, PcidFlush::All);
+        assert_eq!(allocator.generation(), 1);
+
+        // The first space's PCID is stale now, so it gets a new one
+        let switch = allocator.activate(&mut first);
+        assert_eq!(switch.flush, PcidFlush::Pcid(switch.pcid));
+        assert_ne!(switch.pcid, overflow.unwrap().pcid());
+
+        // Releasing a stale id doesn't free a PCID from the new generation
+        let available = allocator.available();
+        allocator.release(AddressSpaceId {
+            pcid: Pcid(1),
+            generation: 0,
+        });
+        assert_eq!(allocator.available(), available);
+    }
+}
@@ -271,6 +271,109 @@ impl<const N: usize> PhysMemoryMap<N> {
         Ok(())
     }
 
+    /// # Subtract Region
+    /// Remove `start..end` from the map, no matter what kind of memory it
+    /// was. Regions that only partly overlap are clipped to the edges.
+    pub fn subtract_region(&mut self, start: u64, end: u64) -> Result<(), crate::MemoryError> {
+        if start >= end {
+            return Err(crate::MemoryError::InvalidSize);
+        }
+
+        let first = self.borders[..self.len].partition_point(|bor| bor.address < start);
+        let last = self.borders[..self.len].partition_point(|bor| bor.address <= end);
+
+        // Make sure the new borders will fit before we change anything
+        if self.len - (last - first) + 2 > N {
+            return Err(crate::MemoryError::ArrayTooSmall);
+        }
+
+        let end_kind = self.kind_at(end);
+
+        for _ in first..last {
+            self.remove_raw(first)?;
+        }
+
+        self.insert_raw(first, PhysMemoryBorder {
+            kind: PhysMemoryKind::None,
+            address: start,
+        })?;
+        self.insert_raw(first + 1, PhysMemoryBorder {
+            kind: end_kind,
+            address: end,
+        })?;
+
+        self.coalesce();
+        Ok(())
+    }
+
+    /// # Coalesce
+    /// Merge adjacent regions of the same kind, and drop any borders that
+    /// don't change the kind of memory.
+    pub fn coalesce(&mut self) {
+        let mut index = 0;
+        let mut prev_kind = PhysMemoryKind::None;
+
+        while index < self.len {
+            let border = self.borders[index];
+
+            // A region with zero length, the next border replaces this one
+            let empty = index + 1 < self.len && self.borders[index + 1].address == border.address;
+
+            if empty || border.kind == prev_kind {
+                // `index` is always in bounds here, so this cannot fail
+                let _ = self.remove_raw(index);
+                continue;
+            }
+
+            prev_kind = border.kind;
+            index += 1;
+        }
+    }
+
+    /// # Truncate Above
+    /// Remove all memory at or above `max_address`.
+    pub fn truncate_above(&mut self, max_address: u64) {
+        self.len = self.borders[..self.len].partition_point(|bor| bor.address < max_address);
+
+        for border in &mut self.borders[self.len..] {
+            border.kind = PhysMemoryKind::None;
+            border.address = 0;
+        }
+
+        // If the last region was cut, it needs a new end. This always fits
+        // since its old end border was just removed.
+        if self.len > 0 && self.borders[self.len - 1].kind != PhysMemoryKind::None {
+            let _ = self.insert_raw(self.len, PhysMemoryBorder {
+                kind: PhysMemoryKind::None,
+                address: max_address,
+            });
+        }
+    }
+
+    /// # Kind At
+    /// Get the kind of memory at `address`.
+    pub fn kind_at(&self, address: u64) -> PhysMemoryKind {
+        match self.borders[..self.len].partition_point(|bor| bor.address <= address) {
+            0 => PhysMemoryKind::None,
+            index => self.borders[index - 1].kind,
+        }
+    }
+
+    /// # Iter
+    /// Iterate over all the regions in the map that are not `None`.
+    pub fn iter(&self) -> impl Iterator<Item = PhysMemoryEntry> + '_ {
+        self.borders[..self.len]
+            .windows(2)
+            .filter(|pair| {
+                pair[0].kind != PhysMemoryKind::None && pair[0].address < pair[1].address
+            })
+            .map(|pair| PhysMemoryEntry {
+                kind: pair[0].kind,
+                start: pair[0].address,
+                end: pair[1].address,
+            })
+    }
+
     fn insert_raw(
         &mut self,
         index: usize,
@@ -329,6 +432,7 @@ impl<const N: usize> PhysMemoryMap<N> {
 
 #[cfg(test)]
 mod test {
+    extern crate std;
     use super::*;
 
     #[test]
@@ -778,4 +882,151 @@ mod test {
             }
         ]);
     }
+
+    fn entry(kind: PhysMemoryKind, start: u64, end: u64) -> PhysMemoryEntry {
+        PhysMemoryEntry { kind, start, end }
+    }
+
+    fn example_map() -> PhysMemoryMap<8> {
+        let mut mm = PhysMemoryMap::new();
+        mm.add_region(entry(PhysMemoryKind::Free, 0, 100)).unwrap();
+        mm.add_region(entry(PhysMemoryKind::Reserved, 100, 200))
+            .unwrap();
+        mm
+    }
+
+    fn entries<const N: usize>(mm: &PhysMemoryMap<N>) -> std::vec::Vec<PhysMemoryEntry> {
+        mm.iter().collect()
+    }
+
+    #[test]
+    fn test_subtract_middle() {
+        let mut mm = example_map();
+
+        assert_eq!(mm.subtract_region(20, 30), Ok(()));
+        assert_eq!(entries(&mm), [
+            entry(PhysMemoryKind::Free, 0, 20),
+            entry(PhysMemoryKind::Free, 30, 100),
+            entry(PhysMemoryKind::Reserved, 100, 200),
+        ]);
+    }
+
+    #[test]
+    fn test_subtract_across_regions() {
+        let mut mm = example_map();
+
+        assert_eq!(mm.subtract_region(50, 150), Ok(()));
+        assert_eq!(entries(&mm), [
+            entry(PhysMemoryKind::Free, 0, 50),
+            entry(PhysMemoryKind::Reserved, 150, 200),
+        ]);
+    }
+
+    #[test]
+    fn test_subtract_exact_edges() {
+        let mut mm = example_map();
+
+        assert_eq!(mm.subtract_region(0, 100), Ok(()));
+        assert_eq!(entries(&mm), [entry(PhysMemoryKind::Reserved, 100, 200)]);
+        assert_eq!(mm.len, 2);
+
+        assert_eq!(mm.subtract_region(100, 200), Ok(()));
+        assert_eq!(entries(&mm), []);
+        assert_eq!(mm.len, 0);
+    }
+
+    #[test]
+    fn test_subtract_outside_map() {
+        let mut mm = example_map();
+
+        assert_eq!(mm.subtract_region(190, 400), Ok(()));
+        assert_eq!(mm.subtract_region(500, 600), Ok(()));
+        assert_eq!(entries(&mm), [
+            entry(PhysMemoryKind::Free, 0, 100),
+            entry(PhysMemoryKind::Reserved, 100, 190),
+        ]);
+        assert_eq!(
+            mm.subtract_region(10, 10),
+            Err(crate::MemoryError::InvalidSize)
+        );
+    }
+
+    #[test]
+    fn test_subtract_array_too_small() {
+        let mut mm = PhysMemoryMap::<2>::new();
+        mm.add_region(entry(PhysMemoryKind::Free, 0, 100)).unwrap();
+
+        assert_eq!(
+            mm.subtract_region(20, 30),
+            Err(crate::MemoryError::ArrayTooSmall)
+        );
+        assert_eq!(entries(&mm), [entry(PhysMemoryKind::Free, 0, 100)]);
+    }
+
+    #[test]
+    fn test_coalesce() {
+        let mut mm = PhysMemoryMap::<8>::new();
+        mm.borders[..6].copy_from_slice(&[
+            PhysMemoryBorder {
+                kind: PhysMemoryKind::None,
+                address: 0,
+            },
+            PhysMemoryBorder {
+                kind: PhysMemoryKind::Free,
+                address: 10,
+            },
+            PhysMemoryBorder {
+                kind: PhysMemoryKind::Free,
+                address: 20,
+            },
+            PhysMemoryBorder {
+                kind: PhysMemoryKind::Reserved,
+                address: 30,
+            },
+            PhysMemoryBorder {
+                kind: PhysMemoryKind::Free,
+                address: 30,
+            },
+            PhysMemoryBorder {
+                kind: PhysMemoryKind::None,
+                address: 40,
+            },
+        ]);
+        mm.len = 6;
+
+        mm.coalesce();
+        assert_eq!(mm.len, 2);
+        assert_eq!(entries(&mm), [entry(PhysMemoryKind::Free, 10, 40)]);
+    }
+
+    #[test]
+    fn test_truncate_above() {
+        let mut mm = example_map();
+
+        mm.truncate_above(150);
+        assert_eq!(entries(&mm), [
+            entry(PhysMemoryKind::Free, 0, 100),
+            entry(PhysMemoryKind::Reserved, 100, 150),
+        ]);
+
+        mm.truncate_above(100);
+        assert_eq!(entries(&mm), [entry(PhysMemoryKind::Free, 0, 100)]);
+        assert_eq!(mm.len, 2);
+
+        mm.truncate_above(1000);
+        assert_eq!(entries(&mm), [entry(PhysMemoryKind::Free, 0, 100)]);
+
+        mm.truncate_above(0);
+        assert_eq!(mm.len, 0);
+    }
+
+    #[test]
+    fn test_kind_at() {
+        let mm = example_map();
+
+        assert_eq!(mm.kind_at(0), PhysMemoryKind::Free);
+        assert_eq!(mm.kind_at(99), PhysMemoryKind::Free);
+        assert_eq!(mm.kind_at(100), PhysMemoryKind::Reserved);
+        assert_eq!(mm.kind_at(200), PhysMemoryKind::None);
+    }
 }
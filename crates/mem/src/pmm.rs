@@ -0,0 +1,418 @@
+/*
+  ____                 __               __   _ __
+ / __ \__ _____ ____  / /___ ____ _    / /  (_) /
+/ /_/ / // / _ `/ _ \/ __/ // /  ' \  / /__/ / _ \
+\___\_\_,_/\_,_/_//_/\__/\_,_/_/_/_/ /____/_/_.__/
+    Part of the Quantum OS Project
+
+Copyright 2025 Gavin Kellam
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and
+associated documentation files (the "Software"), to deal in the Software without restriction,
+including without limitation the rights to use, copy, modify, merge, publish, distribute,
+sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial
+portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT
+NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
+OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+use crate::MemoryError;
+use bits::BitSliceManipulation;
+
+/// The size of the smallest block (order 0) the buddy allocator hands out.
+pub const PAGE_SIZE: u64 = 4096;
+
+/// The largest order the buddy allocator hands out (`4K << 18 = 1G`).
+pub const MAX_ORDER: usize = 18;
+
+const ORDERS: usize = MAX_ORDER + 1;
+
+/// # Buddy Allocator
+/// A physical memory allocator handing out power-of-two blocks of pages, from 4K
+/// (order 0) to 1G (order [`MAX_ORDER`]).
+///
+/// The allocator does not touch the memory it manages. Instead, it keeps one bitmap
+/// per order (in a caller provided `metadata` buffer) marking which blocks are free.
+/// Blocks are aligned relative to `base`, so `base` should be aligned to the largest
+/// block size you want correctly aligned.
+pub struct BuddyAllocator<'a> {
+    base: u64,
+    frames: usize,
+    metadata: &'a mut [u64],
+    order_offsets: [usize; ORDERS],
+    free_blocks: [usize; ORDERS],
+}
+
+/// # Buddy Stats
+/// A snapshot of how the buddy allocator's memory is being used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BuddyStats {
+    /// The total number of pages this allocator manages.
+    pub total_pages: usize,
+    /// The number of free pages.
+    pub free_pages: usize,
+    /// The number of free blocks of each order.
+    pub free_blocks: [usize; ORDERS],
+    /// The order of the largest free block (if there is any free memory).
+    pub largest_free_order: Option<usize>,
+}
+
+impl BuddyStats {
+    /// # Fragmentation
+    /// How fragmented the free memory is, as a percent. `0` means all free memory is
+    /// in the largest free block, and values close to `100` means free memory is
+    /// spread over many small blocks.
+    pub fn fragmentation(&self) -> usize {
+        let Some(largest) = self.largest_free_order else {
+            return 0;
+        };
+
+        100 - ((1 << largest) * 100 / self.free_pages)
+    }
+}
+
+impl<'a> BuddyAllocator<'a> {
+    /// # Metadata Words
+    /// The number of `u64`s of metadata needed to manage `frames` pages.
+    pub const fn metadata_words(frames: usize) -> usize {
+        let mut words = 0;
+        let mut order = 0;
+
+        while order < ORDERS {
+            words += (frames >> order).div_ceil(64);
+            order += 1;
+        }
+
+        words
+    }
+
+    /// # New
+    /// Create a new buddy allocator managing `frames` pages starting at `base`.
+    ///
+    /// All memory starts out as used, call [`BuddyAllocator::add_free_region`] to
+    /// give the allocator memory.
+    pub fn new(base: u64, frames: usize, metadata: &'a mut [u64]) -> Result<Self, MemoryError> {
+        if !base.is_multiple_of(PAGE_SIZE) {
+            return Err(MemoryError::NotAligned);
+        }
+
+        if metadata.len() < Self::metadata_words(frames) {
+            return Err(MemoryError::ArrayTooSmall);
+        }
+
+        let mut order_offsets = [0; ORDERS];
+        let mut offset = 0;
+        for (order, order_offset) in order_offsets.iter_mut().enumerate() {
+            *order_offset = offset;
+            offset += (frames >> order).div_ceil(64);
+        }
+
+        metadata[..offset].fill(0);
+
+        Ok(Self {
+            base,
+            frames,
+            metadata,
+            order_offsets,
+            free_blocks: [0; ORDERS],
+        })
+    }
+
+    fn bitmap(&self, order: usize) -> &[u64] {
+        let end = self
+            .order_offsets
+            .get(order + 1)
+            .copied()
+            .unwrap_or(Self::metadata_words(self.frames));
+
+        &self.metadata[self.order_offsets[order]..end]
+    }
+
+    fn is_free(&self, order: usize, frame: usize) -> bool {
+        (frame >> order) < (self.frames >> order) && self.bitmap(order).get_bit(frame >> order)
+    }
+
+    fn set_free(&mut self, order: usize, frame: usize, free: bool) {
+        let bit = self.order_offsets[order] * 64 + (frame >> order);
+
+        if self.metadata.get_bit(bit) != free {
+            self.metadata.set_bit(bit, free);
+
+            if free {
+                self.free_blocks[order] += 1;
+            } else {
+                self.free_blocks[order] -= 1;
+            }
+        }
+    }
+
+    fn find_free(&self, order: usize) -> Option<usize> {
+        self.bitmap(order)
+            .iter()
+            .enumerate()
+            .find(|(_, word)| **word != 0)
+            .map(|(index, word)| ((index * 64) + word.trailing_zeros() as usize) << order)
+    }
+
+    /// Free a block, merging it with its buddy for as long as the buddy is also free.
+    fn free_block(&mut self, mut frame: usize, mut order: usize) {
+        while order < MAX_ORDER {
+            let buddy = frame ^ (1 << order);
+
+            if !self.is_free(order, buddy) {
+                break;
+            }
+
+            self.set_free(order, buddy, false);
+            frame &= !(1 << order);
+            order += 1;
+        }
+
+        self.set_free(order, frame, true);
+    }
+
+    fn frame_of(&self, address: u64) -> Result<usize, MemoryError> {
+        if address < self.base || !(address - self.base).is_multiple_of(PAGE_SIZE) {
+            return Err(MemoryError::NotAligned);
+        }
+
+        let frame = ((address - self.base) / PAGE_SIZE) as usize;
+        if frame >= self.frames {
+            return Err(MemoryError::InvalidSize);
+        }
+
+        Ok(frame)
+    }
+
+    /// # Add Free Region
+    /// Give the memory in `start..end` to the allocator. Partial pages at either end of
+    /// the region are ignored.
+    pub fn add_free_region(&mut self, start: u64, end: u64) -> Result<(), MemoryError> {
+        if start >= end {
+            return Err(MemoryError::InvalidSize);
+        }
+
+        let limit = self.base + (self.frames as u64 * PAGE_SIZE);
+        let start = start.max(self.base).next_multiple_of(PAGE_SIZE);
+        let end = end.min(limit);
+
+        let mut frame = ((start - self.base) / PAGE_SIZE) as usize;
+        let end_frame = (end.saturating_sub(self.base) / PAGE_SIZE) as usize;
+
+        while frame < end_frame {
+            let order = (0..=MAX_ORDER)
+                .rev()
+                .find(|&order| {
+                    frame.is_multiple_of(1 << order) && frame + (1 << order) <= end_frame
+                })
+                .unwrap_or(0);
+
+            self.free_block(frame, order);
+            frame += 1 << order;
+        }
+
+        Ok(())
+    }
+
+    /// # Alloc Contiguous
+    /// Allocate a block of `PAGE_SIZE << order` bytes, returning its physical address.
+    pub fn alloc_contiguous(&mut self, order: usize) -> Result<u64, MemoryError> {
+        if order > MAX_ORDER {
+            return Err(MemoryError::InvalidSize);
+        }
+
+        let (mut found_order, frame) = (order..=MAX_ORDER)
+            .find_map(|order| self.find_free(order).map(|frame| (order, frame)))
+            .ok_or(MemoryError::OutOfMemory)?;
+
+        self.set_free(found_order, frame, false);
+
+        // Split the block, giving the upper halves back to the lower orders
+        while found_order > order {
+            found_order -= 1;
+            self.set_free(found_order, frame + (1 << found_order), true);
+        }
+
+        Ok(self.base + (frame as u64 * PAGE_SIZE))
+    }
+
+    /// # Alloc Page
+    /// Allocate a single page.
+    pub fn alloc_page(&mut self) -> Result<u64, MemoryError> {
+        self.alloc_contiguous(0)
+    }
+
+    /// # Free
+    /// Give a block (allocated with the same `order`) back to the allocator.
+    pub fn free(&mut self, address: u64, order: usize) -> Result<(), MemoryError> {
+        if order > MAX_ORDER {
+            return Err(MemoryError::InvalidSize);
+        }
+
+        let frame = self.frame_of(address)?;
+        if !frame.is_multiple_of(1 << order) {
+            return Err(MemoryError::NotAligned);
+        }
+
+        // Once freed, the block may have merged into a larger free block
+        if (order..=MAX_ORDER).any(|order| self.is_free(order, frame & !((1 << order) - 1))) {
+            return Err(MemoryError::DoubleFree);
+        }
+
+        self.free_block(frame, order);
+        Ok(())
+    }
+
+    /// # Stats
+    /// Get a snapshot of this allocator's memory usage.
+    pub fn stats(&self) -> BuddyStats {
+        BuddyStats {
+            total_pages: self.frames,
+            free_pages: self
+                .free_blocks
+                .iter()
+                .enumerate()
+                .map(|(order, blocks)| blocks << order)
+                .sum(),
+            free_blocks: self.free_blocks,
+            largest_free_order: self.free_blocks.iter().rposition(|blocks| *blocks != 0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate std;
+
+    use super::*;
+    use std::{vec, vec::Vec};
+
+    fn metadata(frames: usize) -> Vec<u64> {
+        vec![0; BuddyAllocator::metadata_words(frames)]
+    }
+
+    #[test]
+    fn test_metadata_too_small() {
+        let mut metadata = [0; 1];
+        assert!(matches!(
+            BuddyAllocator::new(0, 1024, &mut metadata),
+            Err(MemoryError::ArrayTooSmall)
+        ));
+    }
+
+    #[test]
+    fn test_free_region_merges_into_large_blocks() {
+        let mut metadata = metadata(1024);
+        let mut buddy = BuddyAllocator::new(0, 1024, &mut metadata).unwrap();
+
+        buddy.add_free_region(0, 1024 * PAGE_SIZE).unwrap();
+
+        let stats = buddy.stats();
+        assert_eq!(stats.free_pages, 1024);
+        assert_eq!(stats.free_blocks[10], 1);
+        assert_eq!(stats.largest_free_order, Some(10));
+        assert_eq!(stats.fragmentation(), 0);
+    }
+
+    #[test]
+    fn test_alloc_splits_and_free_merges() {
+        let mut metadata = metadata(16);
+        let mut buddy = BuddyAllocator::new(0x10000, 16, &mut metadata).unwrap();
+        buddy
+            .add_free_region(0x10000, 0x10000 + 16 * PAGE_SIZE)
+            .unwrap();
+
+        let page = buddy.alloc_page().unwrap();
+        assert_eq!(page, 0x10000);
+        assert_eq!(buddy.stats().free_blocks, {
+            let mut blocks = [0; ORDERS];
+            blocks[0..4].fill(1);
+            blocks
+        });
+
+        let block = buddy.alloc_contiguous(2).unwrap();
+        assert_eq!(block, 0x10000 + 4 * PAGE_SIZE);
+
+        buddy.free(page, 0).unwrap();
+        buddy.free(block, 2).unwrap();
+        assert_eq!(buddy.stats().free_blocks[4], 1);
+        assert_eq!(buddy.stats().free_pages, 16);
+    }
+
+    #[test]
+    fn test_out_of_memory() {
+        let mut metadata = metadata(8);
+        let mut buddy = BuddyAllocator::new(0, 8, &mut metadata).unwrap();
+        buddy.add_free_region(0, 8 * PAGE_SIZE).unwrap();
+
+        assert_eq!(buddy.alloc_contiguous(4), Err(MemoryError::OutOfMemory));
+        assert_eq!(buddy.alloc_contiguous(3), Ok(0));
+        assert_eq!(buddy.alloc_page(), Err(MemoryError::OutOfMemory));
+    }
+
+    #[test]
+    fn test_unaligned_region_and_fragmentation() {
+        let mut metadata = metadata(64);
+        let mut buddy = BuddyAllocator::new(0, 64, &mut metadata).unwrap();
+
+        // Pages 1..7 and 40..48
+        buddy
+            .add_free_region(PAGE_SIZE - 10, 7 * PAGE_SIZE)
+            .unwrap();
+        buddy
+            .add_free_region(40 * PAGE_SIZE, 48 * PAGE_SIZE)
+            .unwrap();
+
+        let stats = buddy.stats();
+        assert_eq!(stats.free_pages, 14);
+        assert_eq!(stats.largest_free_order, Some(3));
+        assert_eq!(stats.fragmentation(), 100 - (8 * 100 / 14));
+
+        // Pages 1..7 cannot hold an aligned block of 4 pages
+        assert_eq!(buddy.alloc_contiguous(2), Ok(40 * PAGE_SIZE));
+        assert_eq!(buddy.alloc_contiguous(2), Ok(44 * PAGE_SIZE));
+        assert_eq!(buddy.alloc_contiguous(1), Ok(2 * PAGE_SIZE));
+    }
+
+    #[test]
+    fn test_bad_frees() {
+        let mut metadata = metadata(8);
+        let mut buddy = BuddyAllocator::new(0, 8, &mut metadata).unwrap();
+        buddy.add_free_region(0, 8 * PAGE_SIZE).unwrap();
+
+        let block = buddy.alloc_contiguous(1).unwrap();
+        assert_eq!(
+            buddy.free(block + PAGE_SIZE, 1),
+            Err(MemoryError::NotAligned)
+        );
+        assert_eq!(buddy.free(block + 123, 0), Err(MemoryError::NotAligned));
+        assert_eq!(buddy.free(64 * PAGE_SIZE, 0), Err(MemoryError::InvalidSize));
+        assert_eq!(buddy.free(block, 1), Ok(()));
+        assert_eq!(buddy.free(block, 3), Err(MemoryError::DoubleFree));
+    }
+
+    #[test]
+    fn test_double_free_after_merge() {
+        let mut metadata = metadata(8);
+        let mut buddy = BuddyAllocator::new(0, 8, &mut metadata).unwrap();
+        buddy.add_free_region(0, 8 * PAGE_SIZE).unwrap();
+
+        let first = buddy.alloc_page().unwrap();
+        let second = buddy.alloc_page().unwrap();
+        buddy.free(first, 0).unwrap();
+        buddy.free(second, 0).unwrap();
+
+        // Both pages are now part of one free order 3 block
+        assert_eq!(buddy.stats().free_blocks[3], 1);
+        assert_eq!(buddy.free(first, 0), Err(MemoryError::DoubleFree));
+        assert_eq!(buddy.free(second, 0), Err(MemoryError::DoubleFree));
+        assert_eq!(buddy.stats().free_pages, 8);
+    }
+}
@@ -0,0 +1,945 @@
+/*
+  ____                 __               __   _ __
+ / __ \__ _____ ____  / /___ ____ _    / /  (_) /
+/ /_/ / // / _ `/ _ \/ __/ // /  ' \  / /__/ / _ \
+\___\_\_,_/\_,_/_//_/\__/\_,_/_/_/_/ /____/_/_.__/
+    Part of the Quantum OS Project
+
+Copyright 2025 Gavin Kellam
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and
+associated documentation files (the "Software"), to deal in the Software without restriction,
+including without limitation the rights to use, copy, modify, merge, publish, distribute,
+sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial
+portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT
+NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
+OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+use crate::MemoryError;
+use bits::BitSliceManipulation;
+use core::{
+    cell::UnsafeCell,
+    mem::size_of,
+    ptr::NonNull,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+
+/// The size (and alignment) of each slab.
+pub const SLAB_SIZE: usize = 4096;
+
+/// The smallest object a [`SlabCache`] hands out, smaller objects are rounded up.
+pub const MIN_OBJECT_SIZE: usize = 8;
+
+/// The most objects a single slab can hold.
+pub const MAX_OBJECTS_PER_SLAB: usize = SLAB_SIZE / MIN_OBJECT_SIZE;
+
+/// # Slab Backing
+/// Where a [`SlabCache`] gets the memory for its slabs from.
+pub trait SlabBacking {
+    /// # Alloc Slab
+    /// Allocate `SLAB_SIZE` bytes of memory aligned to `SLAB_SIZE`.
+    fn alloc_slab(&mut self) -> Option<NonNull<u8>>;
+
+    /// # Free Slab
+    /// Give a slab (from `alloc_slab`) back.
+    fn free_slab(&mut self, slab: NonNull<u8>);
+}
+
+#[repr(C)]
+struct SlabHeader {
+    next: Option<NonNull<SlabHeader>>,
+    /// The next slab with free objects, only used while this slab has free objects.
+    next_partial: Option<NonNull<SlabHeader>>,
+    /// The id of the cache this slab belongs to.
+    owner: usize,
+    /// One bit per object, set when the object is free. This is kept out of the
+    /// objects themselves so they never lose their constructed state.
+    free: [u64; MAX_OBJECTS_PER_SLAB / 64],
+    in_use: usize,
+}
+
+impl SlabHeader {
+    fn first_free(&self) -> Option<usize> {
+        self.free
+            .iter()
+            .enumerate()
+            .find(|(_, word)| **word != 0)
+            .map(|(index, word)| index * 64 + word.trailing_zeros() as usize)
+    }
+}
+
+/// # Slab Stats
+/// How a [`SlabCache`] is being used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SlabStats {
+    pub name: &'static str,
+    pub object_size: usize,
+    pub slabs: usize,
+    pub objects_total: usize,
+    pub objects_in_use: usize,
+    pub allocations: usize,
+    pub frees: usize,
+}
+
+/// The id given to the next [`SlabCache`], so slabs can tell which cache they are from.
+static NEXT_CACHE_ID: AtomicUsize = AtomicUsize::new(1);
+
+/// # Slab Cache
+/// A cache of same sized objects, carved out of `SLAB_SIZE` slabs.
+///
+/// Objects are constructed (with the optional constructor) when their slab is created,
+/// and destructed (with the optional destructor) only when the slab is given back with
+/// [`SlabCache::shrink`]. The cache never writes to an object itself, so objects handed
+/// out by [`SlabCache::alloc`] are always in their constructed state, as long as users
+/// return them that way.
+pub struct SlabCache<B: SlabBacking> {
+    id: usize,
+    name: &'static str,
+    object_size: usize,
+    align: usize,
+    ctor: Option<fn(*mut u8)>,
+    dtor: Option<fn(*mut u8)>,
+    slabs: Option<NonNull<SlabHeader>>,
+    /// The slabs with free objects.
+    partial: Option<NonNull<SlabHeader>>,
+    backing: B,
+    stats: SlabStats,
+}
+
+// The cache is the only owner of its slabs (objects handed out belong to whoever
+// allocated them), so moving it to another CPU moves the slabs along with it.
+unsafe impl<B: SlabBacking + Send> Send for SlabCache<B> {}
+
+impl<B: SlabBacking> SlabCache<B> {
+    /// # New
+    /// Create a new cache of objects with the given size and alignment.
+    pub fn new(
+        name: &'static str,
+        object_size: usize,
+        align: usize,
+        backing: B,
+    ) -> Result<Self, MemoryError> {
+        if !align.is_power_of_two() {
+            return Err(MemoryError::NotAligned);
+        }
+
+        let object_size = object_size.max(MIN_OBJECT_SIZE).next_multiple_of(align);
+
+        let cache = Self {
+            id: NEXT_CACHE_ID.fetch_add(1, Ordering::Relaxed),
+            name,
+            object_size,
+            align,
+            ctor: None,
+            dtor: None,
+            slabs: None,
+            partial: None,
+            backing,
+            stats: SlabStats {
+                name,
+                object_size,
+                slabs: 0,
+                objects_total: 0,
+                objects_in_use: 0,
+                allocations: 0,
+                frees: 0,
+            },
+        };
+
+        if cache.objects_per_slab() == 0 {
+            return Err(MemoryError::InvalidSize);
+        }
+
+        Ok(cache)
+    }
+
+    /// # With Constructor
+    /// Run `ctor` on every object when its slab is created.
+    pub fn with_constructor(mut self, ctor: fn(*mut u8)) -> Self {
+        self.ctor = Some(ctor);
+        self
+    }
+
+    /// # With Destructor
+    /// Run `dtor` on every object when its slab is given back to the backing.
+    pub fn with_destructor(mut self, dtor: fn(*mut u8)) -> Self {
+        self.dtor = Some(dtor);
+        self
+    }
+
+    fn first_object_offset(&self) -> usize {
+        size_of::<SlabHeader>().next_multiple_of(self.align)
+    }
+
+    fn objects_per_slab(&self) -> usize {
+        SLAB_SIZE.saturating_sub(self.first_object_offset()) / self.object_size
+    }
+
+    fn objects_of(
+        &self,
+        slab: NonNull<SlabHeader>,
+    ) -> impl DoubleEndedIterator<Item = *mut u8> + use<B> {
+        let first = unsafe { slab.cast::<u8>().as_ptr().add(self.first_object_offset()) };
+        let object_size = self.object_size;
+
+        (0..self.objects_per_slab()).map(move |index| unsafe { first.add(index * object_size) })
+    }
+
+    fn grow(&mut self) -> Result<NonNull<SlabHeader>, MemoryError> {
+        let memory = self.backing.alloc_slab().ok_or(MemoryError::OutOfMemory)?;
+
+        if !(memory.as_ptr() as usize).is_multiple_of(SLAB_SIZE) {
+            self.backing.free_slab(memory);
+            return Err(MemoryError::NotAligned);
+        }
+
+        let slab = memory.cast::<SlabHeader>();
+        unsafe {
+            slab.write(SlabHeader {
+                next: self.slabs,
+                next_partial: self.partial,
+                owner: self.id,
+                free: [0; MAX_OBJECTS_PER_SLAB / 64],
+                in_use: 0,
+            })
+        };
+
+        let header = unsafe { &mut *slab.as_ptr() };
+        for (index, object) in self.objects_of(slab).enumerate() {
+            if let Some(ctor) = self.ctor {
+                ctor(object);
+            }
+
+            header.free.set_bit(index, true);
+        }
+
+        self.slabs = Some(slab);
+        self.partial = Some(slab);
+        self.stats.slabs += 1;
+        self.stats.objects_total += self.objects_per_slab();
+
+        Ok(slab)
+    }
+
+    fn slabs(&self) -> impl Iterator<Item = NonNull<SlabHeader>> + use<B> {
+        let mut current = self.slabs;
+
+        core::iter::from_fn(move || {
+            let slab = current?;
+            current = unsafe { (*slab.as_ptr()).next };
+            Some(slab)
+        })
+    }
+
+    /// Find the slab `object` is in, and its index in that slab.
+    ///
+    /// # Safety
+    /// `object` must be in a slab held by a [`SlabCache`], see [`SlabCache::free`].
+    unsafe fn locate(
+        &self,
+        object: NonNull<u8>,
+    ) -> Result<(NonNull<SlabHeader>, usize), MemoryError> {
+        let offset = (object.as_ptr() as usize) & (SLAB_SIZE - 1);
+        let slab = NonNull::new(object.as_ptr().wrapping_sub(offset))
+            .ok_or(MemoryError::InvalidSize)?
+            .cast::<SlabHeader>();
+
+        if unsafe { (*slab.as_ptr()).owner } != self.id {
+            return Err(MemoryError::InvalidSize);
+        }
+
+        if offset < self.first_object_offset()
+            || !(offset - self.first_object_offset()).is_multiple_of(self.object_size)
+        {
+            return Err(MemoryError::NotAligned);
+        }
+
+        let index = (offset - self.first_object_offset()) / self.object_size;
+        if index >= self.objects_per_slab() {
+            return Err(MemoryError::NotAligned);
+        }
+
+        Ok((slab, index))
+    }
+
+    /// # Alloc
+    /// Get an object from this cache.
+    pub fn alloc(&mut self) -> Result<NonNull<u8>, MemoryError> {
+        let slab = match self.partial {
+            Some(slab) => slab,
+            None => self.grow()?,
+        };
+
+        let header = unsafe { &mut *slab.as_ptr() };
+        let index = header.first_free().ok_or(MemoryError::OutOfMemory)?;
+
+        header.free.set_bit(index, false);
+        header.in_use += 1;
+
+        if header.in_use == self.objects_per_slab() {
+            self.partial = header.next_partial.take();
+        }
+
+        self.stats.objects_in_use += 1;
+        self.stats.allocations += 1;
+
+        let object =
+            (slab.as_ptr() as usize) + self.first_object_offset() + index * self.object_size;
+        Ok(unsafe { NonNull::new_unchecked(object as *mut u8) })
+    }
+
+    /// # Free
+    /// Give an object (from [`SlabCache::alloc`]) back to this cache.
+    ///
+    /// Objects from another cache and objects freed twice are caught.
+    ///
+    /// # Safety
+    /// The slab is found from the address of `object` alone, so `object` must be in
+    /// a slab that is held by a [`SlabCache`]. Any object a cache handed out is, until
+    /// it is freed and its slab is given back with [`SlabCache::shrink`].
+    pub unsafe fn free(&mut self, object: NonNull<u8>) -> Result<(), MemoryError> {
+        let (slab, index) = unsafe { self.locate(object)? };
+        let header = unsafe { &mut *slab.as_ptr() };
+
+        if header.free.get_bit(index) {
+            return Err(MemoryError::DoubleFree);
+        }
+
+        // A full slab isn't on the partial list
+        if header.in_use == self.objects_per_slab() {
+            header.next_partial = self.partial;
+            self.partial = Some(slab);
+        }
+
+        header.free.set_bit(index, true);
+        header.in_use -= 1;
+
+        self.stats.objects_in_use -= 1;
+        self.stats.frees += 1;
+
+        Ok(())
+    }
+
+    /// # Shrink
+    /// Give every slab with no objects in use back to the backing, returning the
+    /// number of slabs released.
+    pub fn shrink(&mut self) -> usize {
+        let mut released = 0;
+        let mut link = &raw mut self.slabs;
+
+        while let Some(slab) = unsafe { *link } {
+            let header = unsafe { &mut *slab.as_ptr() };
+
+            if header.in_use != 0 {
+                link = &raw mut header.next;
+                continue;
+            }
+
+            unsafe { *link = header.next };
+
+            if let Some(dtor) = self.dtor {
+                self.objects_of(slab).for_each(dtor);
+            }
+
+            self.backing.free_slab(slab.cast());
+            self.stats.slabs -= 1;
+            self.stats.objects_total -= self.objects_per_slab();
+            released += 1;
+        }
+
+        // Released slabs were on the partial list, so build it again
+        let per_slab = self.objects_per_slab();
+        self.partial = None;
+        for slab in self.slabs() {
+            let header = unsafe { &mut *slab.as_ptr() };
+
+            if header.in_use < per_slab {
+                header.next_partial = self.partial;
+                self.partial = Some(slab);
+            }
+        }
+
+        released
+    }
+
+    /// # Name
+    /// The name of this cache.
+    pub const fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// # Object Size
+    /// The size of each object in this cache, after rounding for alignment.
+    pub const fn object_size(&self) -> usize {
+        self.object_size
+    }
+
+    /// # Stats
+    /// Get a snapshot of this cache's usage.
+    pub fn stats(&self) -> SlabStats {
+        self.stats
+    }
+}
+
+/// The object sizes of each cache in [`SizeClasses`].
+pub const SIZE_CLASSES: [usize; 9] = [8, 16, 32, 64, 128, 256, 512, 1024, 2048];
+
+const SIZE_CLASS_NAMES: [&str; SIZE_CLASSES.len()] = [
+    "size-8",
+    "size-16",
+    "size-32",
+    "size-64",
+    "size-128",
+    "size-256",
+    "size-512",
+    "size-1024",
+    "size-2048",
+];
+
+/// The alignment of objects from [`SizeClasses`].
+pub const SIZE_CLASS_ALIGN: usize = 8;
+
+/// # Size Classes
+/// A [`SlabCache`] for each of the [`SIZE_CLASSES`], for small allocations that
+/// don't have a cache of their own.
+pub struct SizeClasses<B: SlabBacking + Clone> {
+    caches: [SlabCache<B>; SIZE_CLASSES.len()],
+}
+
+impl<B: SlabBacking + Clone> SizeClasses<B> {
+    /// # New
+    /// Create a cache for each size class, all sharing `backing`.
+    pub fn new(backing: B) -> Self {
+        Self {
+            caches: core::array::from_fn(|class| {
+                SlabCache::new(
+                    SIZE_CLASS_NAMES[class],
+                    SIZE_CLASSES[class],
+                    SIZE_CLASS_ALIGN,
+                    backing.clone(),
+                )
+                .expect("Every size class should fit in a slab")
+            }),
+        }
+    }
+
+    fn class_of(size: usize) -> Result<usize, MemoryError> {
+        SIZE_CLASSES
+            .iter()
+            .position(|&class| class >= size)
+            .ok_or(MemoryError::InvalidSize)
+    }
+
+    /// # Alloc
+    /// Get an object of at least `size` bytes, aligned to `SIZE_CLASS_ALIGN`.
+    pub fn alloc(&mut self, size: usize) -> Result<NonNull<u8>, MemoryError> {
+        self.caches[Self::class_of(size)?].alloc()
+    }
+
+    /// # Free
+    /// Give an object back, `size` must be the same size it was allocated with.
+    ///
+    /// # Safety
+    /// `object` must have come from [`SizeClasses::alloc`], see [`SlabCache::free`].
+    pub unsafe fn free(&mut self, object: NonNull<u8>, size: usize) -> Result<(), MemoryError> {
+        unsafe { self.caches[Self::class_of(size)?].free(object) }
+    }
+
+    /// # Shrink
+    /// Shrink every size class, returning the number of slabs released.
+    pub fn shrink(&mut self) -> usize {
+        self.caches.iter_mut().map(|cache| cache.shrink()).sum()
+    }
+
+    /// # Stats
+    /// Get a snapshot of each size class's usage.
+    pub fn stats(&self) -> impl Iterator<Item = SlabStats> + '_ {
+        self.caches.iter().map(|cache| cache.stats())
+    }
+}
+
+struct Magazine<const ROUNDS: usize> {
+    rounds: [Option<NonNull<u8>>; ROUNDS],
+    len: usize,
+}
+
+// The rounds are free objects, which only the magazine has pointers to
+unsafe impl<const ROUNDS: usize> Send for Magazine<ROUNDS> {}
+
+impl<const ROUNDS: usize> Magazine<ROUNDS> {
+    const fn new() -> Self {
+        Self {
+            rounds: [None; ROUNDS],
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, object: NonNull<u8>) {
+        self.rounds[self.len] = Some(object);
+        self.len += 1;
+    }
+
+    fn pop(&mut self) -> Option<NonNull<u8>> {
+        self.len = self.len.checked_sub(1)?;
+        self.rounds[self.len].take()
+    }
+
+    fn contains(&self, object: NonNull<u8>) -> bool {
+        self.rounds[..self.len].contains(&Some(object))
+    }
+}
+
+/// # Depot
+/// The slab cache behind the magazines, behind a spin lock that a CPU only takes
+/// to refill or flush its magazine.
+struct Depot<B: SlabBacking> {
+    locked: AtomicBool,
+    cache: UnsafeCell<SlabCache<B>>,
+}
+
+impl<B: SlabBacking> Depot<B> {
+    fn lock<R>(&self, f: impl FnOnce(&mut SlabCache<B>) -> R) -> R {
+        while self
+            .locked
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            core::hint::spin_loop();
+        }
+
+        let result = f(unsafe { &mut *self.cache.get() });
+        self.locked.store(false, Ordering::Release);
+
+        result
+    }
+}
+
+/// # Magazine Cache
+/// A [`SlabCache`] with a magazine of up to `ROUNDS` free objects for each of `CPUS`
+/// CPUs in front of it.
+///
+/// Allocations and frees are served from the calling CPU's magazine without any
+/// locking, and only lock the slab cache to refill or flush half a magazine at a
+/// time. Objects sitting in a magazine still count as in use in the slab cache's
+/// stats, [`MagazineCache::flush`] gives them back.
+pub struct MagazineCache<B: SlabBacking, const CPUS: usize, const ROUNDS: usize> {
+    depot: Depot<B>,
+    magazines: [UnsafeCell<Magazine<ROUNDS>>; CPUS],
+}
+
+// Each magazine is only used by its own CPU (as `alloc` and `free` require), and the
+// slab cache is only used with the depot locked
+unsafe impl<B: SlabBacking + Send, const CPUS: usize, const ROUNDS: usize> Sync
+    for MagazineCache<B, CPUS, ROUNDS>
+{
+}
+
+impl<B: SlabBacking, const CPUS: usize, const ROUNDS: usize> MagazineCache<B, CPUS, ROUNDS> {
+    /// # New
+    /// Put per-CPU magazines in front of `cache`.
+    pub fn new(cache: SlabCache<B>) -> Self {
+        const { assert!(ROUNDS > 0, "Magazines need to hold at least one object") };
+
+        Self {
+            depot: Depot {
+                locked: AtomicBool::new(false),
+                cache: UnsafeCell::new(cache),
+            },
+            magazines: [const { UnsafeCell::new(Magazine::new()) }; CPUS],
+        }
+    }
+
+    /// # Alloc
+    /// Get an object for `cpu` (the id of the CPU we are running on).
+    ///
+    /// # Safety
+    /// Only `cpu` may use its magazine, so this must be called on that CPU with
+    /// nothing (like an interrupt handler) able to use the same magazine before it
+    /// returns.
+    pub unsafe fn alloc(&self, cpu: usize) -> Result<NonNull<u8>, MemoryError> {
+        let magazine = self.magazines.get(cpu).ok_or(MemoryError::InvalidSize)?;
+        let magazine = unsafe { &mut *magazine.get() };
+
+        if magazine.len == 0 {
+            self.depot.lock(|cache| {
+                for _ in 0..ROUNDS.div_ceil(2) {
+                    match cache.alloc() {
+                        Ok(object) => magazine.push(object),
+                        Err(err) if magazine.len == 0 => return Err(err),
+                        Err(_) => break,
+                    }
+                }
+
+                Ok(())
+            })?;
+        }
+
+        magazine.pop().ok_or(MemoryError::OutOfMemory)
+    }
+
+    /// # Free
+    /// Give an object back to `cpu`'s magazine.
+    ///
+    /// An object freed twice to the same magazine is caught here, other bad frees
+    /// are caught once the object is flushed back to the slab cache.
+    ///
+    /// # Safety
+    /// The same as [`MagazineCache::alloc`], and `object` must have come from this
+    /// cache.
+    pub unsafe fn free(&self, cpu: usize, object: NonNull<u8>) -> Result<(), MemoryError> {
+        let magazine = self.magazines.get(cpu).ok_or(MemoryError::InvalidSize)?;
+        let magazine = unsafe { &mut *magazine.get() };
+
+        if magazine.contains(object) {
+            return Err(MemoryError::DoubleFree);
+        }
+
+        if magazine.len == ROUNDS {
+            self.depot.lock(|cache| {
+                for _ in 0..ROUNDS.div_ceil(2) {
+                    if let Some(round) = magazine.pop() {
+                        unsafe { cache.free(round)? };
+                    }
+                }
+
+                Ok(())
+            })?;
+        }
+
+        magazine.push(object);
+        Ok(())
+    }
+
+    /// # Flush
+    /// Give the objects in every magazine back to the slab cache.
+    pub fn flush(&mut self) -> Result<(), MemoryError> {
+        let cache = self.depot.cache.get_mut();
+
+        for magazine in self.magazines.iter_mut() {
+            while let Some(object) = magazine.get_mut().pop() {
+                unsafe { cache.free(object)? };
+            }
+        }
+
+        Ok(())
+    }
+
+    /// # Shrink
+    /// Flush every magazine and shrink the slab cache, returning the number of
+    /// slabs released.
+    pub fn shrink(&mut self) -> Result<usize, MemoryError> {
+        self.flush()?;
+        Ok(self.depot.cache.get_mut().shrink())
+    }
+
+    /// # Cached
+    /// The number of free objects sitting in magazines.
+    pub fn cached(&mut self) -> usize {
+        self.magazines
+            .iter_mut()
+            .map(|magazine| magazine.get_mut().len)
+            .sum()
+    }
+
+    /// # Stats
+    /// Get a snapshot of the slab cache's usage.
+    pub fn stats(&self) -> SlabStats {
+        self.depot.lock(|cache| cache.stats())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate std;
+
+    use super::*;
+    use std::alloc::{Layout, alloc, dealloc};
+
+    #[derive(Default, Clone)]
+    struct TestBacking {
+        slabs_alive: usize,
+        limit: Option<usize>,
+    }
+
+    impl SlabBacking for TestBacking {
+        fn alloc_slab(&mut self) -> Option<NonNull<u8>> {
+            if self.limit.is_some_and(|limit| self.slabs_alive >= limit) {
+                return None;
+            }
+
+            self.slabs_alive += 1;
+            NonNull::new(unsafe { alloc(Layout::from_size_align(SLAB_SIZE, SLAB_SIZE).unwrap()) })
+        }
+
+        fn free_slab(&mut self, slab: NonNull<u8>) {
+            self.slabs_alive -= 1;
+            unsafe {
+                dealloc(
+                    slab.as_ptr(),
+                    Layout::from_size_align(SLAB_SIZE, SLAB_SIZE).unwrap(),
+                )
+            };
+        }
+    }
+
+    #[test]
+    fn test_alloc_and_free() {
+        let mut cache = SlabCache::new("test", 64, 8, TestBacking::default()).unwrap();
+
+        let a = cache.alloc().unwrap();
+        let b = cache.alloc().unwrap();
+        assert_eq!(b.as_ptr() as usize - a.as_ptr() as usize, 64);
+
+        let stats = cache.stats();
+        assert_eq!(stats.slabs, 1);
+        assert_eq!(stats.objects_in_use, 2);
+
+        unsafe { cache.free(a).unwrap() };
+        unsafe { cache.free(b).unwrap() };
+        assert_eq!(cache.alloc().unwrap(), a);
+
+        assert_eq!(cache.stats().allocations, 3);
+        assert_eq!(cache.stats().frees, 2);
+    }
+
+    #[test]
+    fn test_grows_and_shrinks() {
+        let mut cache = SlabCache::new("test", 1024, 8, TestBacking::default()).unwrap();
+        let per_slab = cache.objects_per_slab();
+
+        let objects: std::vec::Vec<_> = (0..per_slab + 1).map(|_| cache.alloc().unwrap()).collect();
+        assert_eq!(cache.stats().slabs, 2);
+        assert_eq!(cache.shrink(), 0);
+
+        objects
+            .into_iter()
+            .for_each(|object| unsafe { cache.free(object).unwrap() });
+        assert_eq!(cache.shrink(), 2);
+        assert_eq!(cache.stats().slabs, 0);
+        assert_eq!(cache.backing.slabs_alive, 0);
+    }
+
+    #[test]
+    fn test_ctor_and_dtor() {
+        use core::sync::atomic::{AtomicUsize, Ordering};
+        static DESTRUCTED: AtomicUsize = AtomicUsize::new(0);
+
+        let mut cache = SlabCache::new("test", 16, 8, TestBacking::default())
+            .unwrap()
+            .with_constructor(|object| unsafe {
+                object.cast::<u64>().write(0xBEEF);
+                object.cast::<u64>().add(1).write(0xCAFE);
+            })
+            .with_destructor(|_| {
+                DESTRUCTED.fetch_add(1, Ordering::Relaxed);
+            });
+
+        let per_slab = cache.objects_per_slab();
+        let objects: std::vec::Vec<_> = (0..per_slab).map(|_| cache.alloc().unwrap()).collect();
+        objects
+            .iter()
+            .for_each(|&object| unsafe { cache.free(object).unwrap() });
+
+        // Objects keep their constructed state after going around the free list
+        for _ in 0..per_slab {
+            let object = cache.alloc().unwrap();
+            assert_eq!(unsafe { object.cast::<u64>().read() }, 0xBEEF);
+            assert_eq!(unsafe { object.cast::<u64>().add(1).read() }, 0xCAFE);
+        }
+        assert_eq!(cache.stats().slabs, 1);
+
+        objects
+            .into_iter()
+            .for_each(|object| unsafe { cache.free(object).unwrap() });
+        cache.shrink();
+        assert_eq!(DESTRUCTED.load(Ordering::Relaxed), per_slab);
+    }
+
+    #[test]
+    fn test_double_free() {
+        let mut cache = SlabCache::new("test", 32, 8, TestBacking::default()).unwrap();
+
+        let a = cache.alloc().unwrap();
+        let b = cache.alloc().unwrap();
+
+        unsafe { cache.free(a).unwrap() };
+        assert_eq!(unsafe { cache.free(a) }, Err(MemoryError::DoubleFree));
+        assert_eq!(cache.stats().objects_in_use, 1);
+
+        unsafe { cache.free(b).unwrap() };
+        assert_eq!(unsafe { cache.free(b) }, Err(MemoryError::DoubleFree));
+        assert_eq!(cache.stats().objects_in_use, 0);
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut cache = SlabCache::new(
+            "test",
+            32,
+            8,
+            TestBacking {
+                limit: Some(1),
+                ..TestBacking::default()
+            },
+        )
+        .unwrap();
+
+        let object = cache.alloc().unwrap();
+        let misaligned = unsafe { NonNull::new_unchecked(object.as_ptr().add(1)) };
+        assert_eq!(
+            unsafe { cache.free(misaligned) },
+            Err(MemoryError::NotAligned)
+        );
+
+        let mut other = SlabCache::new("other", 32, 8, TestBacking::default()).unwrap();
+        let not_ours = other.alloc().unwrap();
+        assert_eq!(
+            unsafe { cache.free(not_ours) },
+            Err(MemoryError::InvalidSize)
+        );
+        unsafe { other.free(not_ours).unwrap() };
+
+        while cache.stats().objects_in_use < cache.stats().objects_total {
+            cache.alloc().unwrap();
+        }
+        assert_eq!(cache.alloc(), Err(MemoryError::OutOfMemory));
+
+        assert!(SlabCache::new("huge", SLAB_SIZE, 8, TestBacking::default()).is_err());
+        assert!(SlabCache::new("bad", 8, 3, TestBacking::default()).is_err());
+    }
+
+    #[test]
+    fn test_size_classes() {
+        let mut classes = SizeClasses::new(TestBacking::default());
+
+        let small = classes.alloc(1).unwrap();
+        let medium = classes.alloc(100).unwrap();
+        let large = classes.alloc(2048).unwrap();
+        assert_eq!(classes.alloc(2049), Err(MemoryError::InvalidSize));
+        assert!((medium.as_ptr() as usize).is_multiple_of(SIZE_CLASS_ALIGN));
+
+        let in_use = |classes: &SizeClasses<TestBacking>, name| {
+            classes
+                .stats()
+                .find(|stats| stats.name == name)
+                .unwrap()
+                .objects_in_use
+        };
+        assert_eq!(in_use(&classes, "size-8"), 1);
+        assert_eq!(in_use(&classes, "size-128"), 1);
+        assert_eq!(in_use(&classes, "size-2048"), 1);
+
+        unsafe { classes.free(small, 1).unwrap() };
+        unsafe { classes.free(medium, 100).unwrap() };
+        unsafe { classes.free(large, 2048).unwrap() };
+        assert_eq!(
+            unsafe { classes.free(medium, 100) },
+            Err(MemoryError::DoubleFree)
+        );
+        assert_eq!(classes.shrink(), 3);
+    }
+
+    #[test]
+    fn test_partial_slabs() {
+        let mut cache = SlabCache::new("test", 1024, 8, TestBacking::default()).unwrap();
+        let per_slab = cache.objects_per_slab();
+
+        let objects: std::vec::Vec<_> = (0..per_slab * 2).map(|_| cache.alloc().unwrap()).collect();
+        assert_eq!(cache.stats().slabs, 2);
+
+        // Freeing from a full slab makes it hand out objects again
+        unsafe { cache.free(objects[per_slab]).unwrap() };
+        assert_eq!(cache.alloc().unwrap(), objects[per_slab]);
+        assert_eq!(cache.stats().slabs, 2);
+
+        objects
+            .into_iter()
+            .for_each(|object| unsafe { cache.free(object).unwrap() });
+        assert_eq!(cache.shrink(), 2);
+    }
+
+    #[test]
+    fn test_magazines() {
+        let cache = SlabCache::new("test", 64, 8, TestBacking::default()).unwrap();
+        let mut magazines = MagazineCache::<_, 2, 4>::new(cache);
+
+        // The first alloc refills half a magazine from the slab cache
+        let a = unsafe { magazines.alloc(0).unwrap() };
+        assert_eq!(magazines.cached(), 1);
+        assert_eq!(magazines.stats().objects_in_use, 2);
+
+        // Freed objects go to the freeing CPU's magazine
+        let b = unsafe { magazines.alloc(1).unwrap() };
+        unsafe { magazines.free(1, a).unwrap() };
+        assert_eq!(
+            unsafe { magazines.free(1, a) },
+            Err(MemoryError::DoubleFree)
+        );
+        assert_eq!(unsafe { magazines.alloc(1).unwrap() }, a);
+
+        // A full magazine flushes half of itself back
+        let objects: std::vec::Vec<_> = (0..5)
+            .map(|_| unsafe { magazines.alloc(0).unwrap() })
+            .collect();
+        objects
+            .iter()
+            .for_each(|&object| unsafe { magazines.free(0, object).unwrap() });
+        assert!(magazines.cached() <= 4);
+
+        assert_eq!(unsafe { magazines.alloc(2) }, Err(MemoryError::InvalidSize));
+        assert_eq!(
+            unsafe { magazines.free(2, b) },
+            Err(MemoryError::InvalidSize)
+        );
+
+        unsafe { magazines.free(1, a).unwrap() };
+        unsafe { magazines.free(1, b).unwrap() };
+        assert_eq!(magazines.shrink(), Ok(1));
+        assert_eq!(magazines.cached(), 0);
+        assert_eq!(magazines.stats().objects_in_use, 0);
+    }
+
+    #[test]
+    fn test_magazines_across_cpus() {
+        // A double free to different magazines is only seen once both are flushed
+        let cache = SlabCache::new("test", 64, 8, TestBacking::default()).unwrap();
+        let mut magazines = MagazineCache::<_, 2, 4>::new(cache);
+
+        let object = unsafe { magazines.alloc(0).unwrap() };
+        unsafe { magazines.free(0, object).unwrap() };
+        unsafe { magazines.free(1, object).unwrap() };
+        assert_eq!(magazines.flush(), Err(MemoryError::DoubleFree));
+
+        // Each thread plays a CPU, only ever using its own magazine
+        let cache = SlabCache::new("test", 64, 8, TestBacking::default()).unwrap();
+        let mut magazines = MagazineCache::<_, 4, 8>::new(cache);
+
+        std::thread::scope(|scope| {
+            for cpu in 0..4 {
+                let magazines = &magazines;
+                scope.spawn(move || {
+                    for _ in 0..100 {
+                        let objects: std::vec::Vec<_> = (0..20)
+                            .map(|_| unsafe { magazines.alloc(cpu).unwrap() })
+                            .collect();
+                        objects
+                            .into_iter()
+                            .for_each(|object| unsafe { magazines.free(cpu, object).unwrap() });
+                    }
+                });
+            }
+        });
+
+        magazines.flush().unwrap();
+        assert_eq!(magazines.stats().objects_in_use, 0);
+        assert_eq!(magazines.stats().allocations, magazines.stats().frees);
+    }
+}
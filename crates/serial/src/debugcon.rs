@@ -0,0 +1,104 @@
+/*
+  ____                 __               __   _ __
+ / __ \__ _____ ____  / /___ ____ _    / /  (_) /
+/ /_/ / // / _ `/ _ \/ __/ // /  ' \  / /__/ / _ \
+\___\_\_,_/\_,_/_//_/\__/\_,_/_/_/_/ /____/_/_.__/
+    Part of the Quantum OS Project
+
+Copyright 2024 Gavin Kellam
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and
+associated documentation files (the "Software"), to deal in the Software without restriction,
+including without limitation the rights to use, copy, modify, merge, publish, distribute,
+sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial
+portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT
+NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
+OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+use arch::io::IOPort;
+use core::fmt::Write;
+use lldebug::telemetry::set_global_telemetry_fn;
+
+/// # Debug Console
+/// QEMU and Bochs 'debugcon' port. Every byte written to this port is
+/// sent directly to the emulator's debugcon chardev (`-debugcon file:...`).
+///
+/// This port has no line settings or FIFO, so it's great for structured
+/// output that must not be interleaved with the serial console.
+pub struct DebugCon {
+    port: IOPort,
+}
+
+impl DebugCon {
+    /// # Debugcon Port
+    /// The IO port the emulator's debugcon listens on by default.
+    pub const PORT: IOPort = IOPort::new(0xE9);
+
+    /// # New
+    /// Use the debugcon port without checking if a device is attached.
+    ///
+    /// Writing to this port when nothing is attached is harmless, the bytes
+    /// are simply dropped.
+    pub const fn new() -> Self {
+        Self { port: Self::PORT }
+    }
+
+    /// # Probe
+    /// Check if a debugcon device is attached.
+    ///
+    /// Reading from the debugcon port returns the port number (`0xE9`) when
+    /// the device is present.
+    pub fn probe() -> Option<Self> {
+        if unsafe { Self::PORT.read_byte() } == 0xE9 {
+            Some(Self::new())
+        } else {
+            None
+        }
+    }
+
+    /// # Transmit Byte
+    /// This will send a byte over debugcon.
+    #[inline]
+    pub fn transmit_byte(&self, byte: u8) {
+        unsafe { self.port.write_byte(byte) };
+    }
+}
+
+impl Default for DebugCon {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Write for DebugCon {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        for byte in s.bytes() {
+            self.transmit_byte(byte);
+        }
+
+        Ok(())
+    }
+}
+
+/// # Install Telemetry
+/// Send lldebug's telemetry events to debugcon, if a debugcon device is
+/// attached. Returns if one was.
+pub fn install_telemetry() -> bool {
+    if DebugCon::probe().is_none() {
+        return false;
+    }
+
+    set_global_telemetry_fn(|args| {
+        let _ = DebugCon::new().write_fmt(args);
+    });
+
+    true
+}
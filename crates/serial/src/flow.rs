@@ -0,0 +1,158 @@
+/*
+  ____                 __               __   _ __
+ / __ \__ _____ ____  / /___ ____ _    / /  (_) /
+/ /_/ / // / _ `/ _ \/ __/ // /  ' \  / /__/ / _ \
+\___\_\_,_/\_,_/_//_/\__/\_,_/_/_/_/ /____/_/_.__/
+    Part of the Quantum OS Project
+
+Copyright 2024 Gavin Kellam
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and
+associated documentation files (the "Software"), to deal in the Software without restriction,
+including without limitation the rights to use, copy, modify, merge, publish, distribute,
+sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial
+portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT
+NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
+OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+/// The byte a receiver sends to resume transmission.
+pub const XON: u8 = 0x11;
+
+/// The byte a receiver sends to pause transmission.
+pub const XOFF: u8 = 0x13;
+
+/// How many received bytes are held while looking for `XON`/`XOFF`.
+const RX_BUFFER_SIZE: usize = 32;
+
+/// How many times a paused transmit polls for `XON` before giving up.
+pub(crate) const XOFF_WAIT_POLLS: usize = 1_000_000;
+
+/// # Flow Control
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FlowControl {
+    #[default]
+    None,
+    /// Software flow control, the receiver pauses us with `XOFF` and resumes
+    /// us with `XON`.
+    XonXoff,
+}
+
+/// # Flow State
+/// Tracks if the other side has paused us, and holds the data bytes that
+/// were received while looking for `XON`/`XOFF`.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct FlowState {
+    mode: FlowControl,
+    paused: bool,
+    rx_buffer: [u8; RX_BUFFER_SIZE],
+    rx_head: usize,
+    rx_len: usize,
+}
+
+impl FlowState {
+    pub const fn new(mode: FlowControl) -> Self {
+        Self {
+            mode,
+            paused: false,
+            rx_buffer: [0; RX_BUFFER_SIZE],
+            rx_head: 0,
+            rx_len: 0,
+        }
+    }
+
+    pub const fn mode(&self) -> FlowControl {
+        self.mode
+    }
+
+    pub const fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// # Receive
+    /// Handle a byte that came off the wire, control bytes are consumed and
+    /// everything else is buffered until read.
+    ///
+    /// If the buffer is full the byte is dropped.
+    pub fn receive(&mut self, byte: u8) {
+        match (self.mode, byte) {
+            (FlowControl::XonXoff, XOFF) => self.paused = true,
+            (FlowControl::XonXoff, XON) => self.paused = false,
+            _ if self.rx_len < RX_BUFFER_SIZE => {
+                self.rx_buffer[(self.rx_head + self.rx_len) % RX_BUFFER_SIZE] = byte;
+                self.rx_len += 1;
+            }
+            _ => (),
+        }
+    }
+
+    /// # Take
+    /// Take the oldest buffered data byte.
+    pub fn take(&mut self) -> Option<u8> {
+        if self.rx_len == 0 {
+            return None;
+        }
+
+        let byte = self.rx_buffer[self.rx_head];
+        self.rx_head = (self.rx_head + 1) % RX_BUFFER_SIZE;
+        self.rx_len -= 1;
+
+        Some(byte)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_xon_xoff_pauses() {
+        let mut state = FlowState::new(FlowControl::XonXoff);
+
+        state.receive(b'a');
+        state.receive(XOFF);
+        assert!(state.is_paused());
+
+        state.receive(b'b');
+        state.receive(XON);
+        assert!(!state.is_paused());
+
+        assert_eq!(state.take(), Some(b'a'));
+        assert_eq!(state.take(), Some(b'b'));
+        assert_eq!(state.take(), None);
+    }
+
+    #[test]
+    fn test_no_flow_control_keeps_control_bytes() {
+        let mut state = FlowState::new(FlowControl::None);
+
+        state.receive(XOFF);
+        assert!(!state.is_paused());
+        assert_eq!(state.take(), Some(XOFF));
+    }
+
+    #[test]
+    fn test_full_buffer_drops_newest() {
+        let mut state = FlowState::new(FlowControl::XonXoff);
+
+        for byte in 0..(RX_BUFFER_SIZE as u8 + 4) {
+            state.receive(byte + 0x20);
+        }
+
+        for byte in 0..RX_BUFFER_SIZE as u8 {
+            assert_eq!(state.take(), Some(byte + 0x20));
+        }
+        assert_eq!(state.take(), None);
+
+        // Control bytes still work when the buffer was full
+        state.receive(XOFF);
+        assert!(state.is_paused());
+    }
+}
@@ -28,16 +28,142 @@ OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWA
 use arch::io::IOPort;
 
 pub mod baud;
+pub mod debugcon;
+pub mod flow;
+pub mod line;
 mod registers;
 
 pub struct Serial {
     baud: baud::SerialBaud,
     port: IOPort,
+    settings: line::LineSettings,
+    flow: flow::FlowState,
+}
+
+/// # Serial Error
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SerialError {
+    /// The port didn't loop-back data, so nothing is there (or it's broken).
+    NotPresent(ComPort),
+    /// The receiver paused us with `XOFF` and never sent `XON`.
+    Paused,
+}
+
+/// # Com Port
+/// One of the standard x86 COM ports.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ComPort {
+    Com1,
+    Com2,
+    Com3,
+    Com4,
+    Com5,
+    Com6,
+    Com7,
+    Com8,
+}
+
+impl ComPort {
+    /// All the COM ports in probing order.
+    pub const ALL: [ComPort; 8] = [
+        Self::Com1,
+        Self::Com2,
+        Self::Com3,
+        Self::Com4,
+        Self::Com5,
+        Self::Com6,
+        Self::Com7,
+        Self::Com8,
+    ];
+
+    /// # IO Port
+    /// Get the base IO port of this COM port.
+    pub const fn io_port(self) -> IOPort {
+        registers::ports::COMMS_ARRAY[self as usize]
+    }
+}
+
+/// # Serial Builder
+/// Configure and init a specific serial port.
+#[derive(Clone, Copy, Debug)]
+pub struct SerialBuilder {
+    port: ComPort,
+    baud: baud::SerialBaud,
+    settings: line::LineSettings,
+    flow_control: flow::FlowControl,
+}
+
+impl SerialBuilder {
+    /// # New
+    /// Use `port` at 115200 baud `8N1`.
+    pub const fn new(port: ComPort) -> Self {
+        Self {
+            port,
+            baud: baud::SerialBaud::Baud115200,
+            settings: line::LineSettings {
+                word_length: line::WordLength::Eight,
+                parity: line::Parity::None,
+                stop_bits: line::StopBits::One,
+            },
+            flow_control: flow::FlowControl::None,
+        }
+    }
+
+    pub const fn baud(mut self, baud: baud::SerialBaud) -> Self {
+        self.baud = baud;
+        self
+    }
+
+    pub const fn parity(mut self, parity: line::Parity) -> Self {
+        self.settings.parity = parity;
+        self
+    }
+
+    pub const fn stop_bits(mut self, stop_bits: line::StopBits) -> Self {
+        self.settings.stop_bits = stop_bits;
+        self
+    }
+
+    pub const fn word_length(mut self, word_length: line::WordLength) -> Self {
+        self.settings.word_length = word_length;
+        self
+    }
+
+    pub const fn line_settings(mut self, settings: line::LineSettings) -> Self {
+        self.settings = settings;
+        self
+    }
+
+    pub const fn flow_control(mut self, flow_control: flow::FlowControl) -> Self {
+        self.flow_control = flow_control;
+        self
+    }
+
+    /// # Build
+    /// Init the port with these settings, checking that it's there first.
+    pub fn build(self) -> Result<Serial, SerialError> {
+        let port = self.port.io_port();
+
+        if unsafe { init_serial_device(self.baud, port, self.settings) } {
+            Ok(Serial {
+                baud: self.baud,
+                port,
+                settings: self.settings,
+                flow: flow::FlowState::new(self.flow_control),
+            })
+        } else {
+            Err(SerialError::NotPresent(self.port))
+        }
+    }
 }
 
 /// # Init Serial Device
 /// Probe and init a serial device.
-unsafe fn init_serial_device(baud: baud::SerialBaud, port: IOPort) -> bool {
+unsafe fn init_serial_device(
+    baud: baud::SerialBaud,
+    port: IOPort,
+    settings: line::LineSettings,
+) -> bool {
     // Disable interrupts and enable DLAB bit
     registers::write_interrupt_enable(port, 0x00);
     registers::write_line_control(port, 0x80);
@@ -46,25 +172,19 @@ unsafe fn init_serial_device(baud: baud::SerialBaud, port: IOPort) -> bool {
     let divisor = baud.get_divisor();
     registers::write_dlab_lsb(port, divisor as u8);
     registers::write_dlab_msb(port, (divisor >> 8) as u8);
-    registers::write_line_control(port, 0x03); /* 8-bit char, no parity mode, and one stop bit */
+    registers::write_line_control(port, settings.line_control());
     registers::write_fifo_control(port, 0xC7);
     registers::write_modem_control(port, 0x0B);
     registers::write_modem_control(port, 0x1E);
 
-    // Preform 3 tests to check if loopback is working
-    registers::write_transmit_buffer(port, 0xFF);
-    if registers::read_receive_buffer(port) != 0xFF {
-        return false;
-    }
-
-    registers::write_transmit_buffer(port, 0xAB);
-    if registers::read_receive_buffer(port) != 0xAB {
-        return false;
-    }
-
-    registers::write_transmit_buffer(port, 0x00);
-    if registers::read_receive_buffer(port) != 0x00 {
-        return false;
+    // Preform 3 tests to check if loopback is working (shorter words only
+    // loop-back their low bits)
+    let mask = settings.word_length.data_mask();
+    for test in [0xFF, 0xAB, 0x00] {
+        registers::write_transmit_buffer(port, test);
+        if registers::read_receive_buffer(port) & mask != test & mask {
+            return false;
+        }
     }
 
     // Finally turn off loopback and go into normal mode
@@ -80,20 +200,74 @@ impl Serial {
     /// (When using an Emulator this is the best option to find which
     ///  serial port the emulator is connected to.)
     pub fn probe_first(baud: baud::SerialBaud) -> Option<Self> {
-        for port in registers::ports::COMMS_ARRAY {
-            if unsafe { init_serial_device(baud, port) } {
-                return Some(Self { baud, port });
-            }
-        }
-
-        None
+        ComPort::ALL
+            .into_iter()
+            .find_map(|port| SerialBuilder::new(port).baud(baud).build().ok())
     }
 
     /// # Transmit Byte
     /// This will send a byte over serial.
+    ///
+    /// This waits for the transmit holding register to be empty (so bytes
+    /// aren't dropped at high baud rates), and while the receiver has paused
+    /// us with `XOFF`. If the receiver doesn't send `XON` in time the byte
+    /// isn't sent and [`SerialError::Paused`] is returned.
     #[inline]
-    pub fn transmit_byte(&self, byte: u8) {
+    pub fn transmit_byte(&mut self, byte: u8) -> Result<(), SerialError> {
+        // Without flow control there is nothing to look for in the receive buffer
+        if self.flow.mode() != flow::FlowControl::None {
+            self.poll_receive();
+
+            let mut polls = 0;
+            while self.flow.is_paused() {
+                if polls == flow::XOFF_WAIT_POLLS {
+                    return Err(SerialError::Paused);
+                }
+
+                polls += 1;
+                core::hint::spin_loop();
+                self.poll_receive();
+            }
+        }
+
+        // Bit 5 of the line status is 'Transmitter Holding Register Empty'
+        while unsafe { registers::read_line_status(self.port) } & 0x20 == 0 {
+            core::hint::spin_loop();
+        }
+
         unsafe { registers::write_transmit_buffer(self.port, byte) };
+        Ok(())
+    }
+
+    /// # Try Receive Byte
+    /// Get the next byte received over serial, if one is waiting.
+    ///
+    /// With [`flow::FlowControl::XonXoff`] the `XON` and `XOFF` bytes are never
+    /// returned.
+    #[inline]
+    pub fn try_receive_byte(&mut self) -> Option<u8> {
+        self.poll_receive();
+        self.flow.take()
+    }
+
+    /// # Is Paused
+    /// Check if the receiver has paused us with `XOFF`.
+    pub fn is_paused(&self) -> bool {
+        self.flow.is_paused()
+    }
+
+    /// # Get Flow Control
+    pub fn get_flow_control(&self) -> flow::FlowControl {
+        self.flow.mode()
+    }
+
+    /// Move everything waiting in the receive buffer into the flow state.
+    fn poll_receive(&mut self) {
+        // Bit 0 of the line status is 'Data Ready'
+        while unsafe { registers::read_line_status(self.port) } & 0x01 != 0 {
+            self.flow
+                .receive(unsafe { registers::read_receive_buffer(self.port) });
+        }
     }
 
     /// # Get Baud
@@ -101,12 +275,18 @@ impl Serial {
     pub fn get_baud(&self) -> baud::SerialBaud {
         self.baud
     }
+
+    /// # Get Line Settings
+    /// Get the character framing this port was setup with.
+    pub fn get_line_settings(&self) -> line::LineSettings {
+        self.settings
+    }
 }
 
 impl core::fmt::Write for Serial {
     fn write_str(&mut self, s: &str) -> core::fmt::Result {
         for byte in s.bytes() {
-            self.transmit_byte(byte);
+            self.transmit_byte(byte).map_err(|_| core::fmt::Error)?;
         }
 
         Ok(())
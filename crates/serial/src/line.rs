@@ -0,0 +1,137 @@
+/*
+  ____                 __               __   _ __
+ / __ \__ _____ ____  / /___ ____ _    / /  (_) /
+/ /_/ / // / _ `/ _ \/ __/ // /  ' \  / /__/ / _ \
+\___\_\_,_/\_,_/_//_/\__/\_,_/_/_/_/ /____/_/_.__/
+    Part of the Quantum OS Project
+
+Copyright 2024 Gavin Kellam
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and
+associated documentation files (the "Software"), to deal in the Software without restriction,
+including without limitation the rights to use, copy, modify, merge, publish, distribute,
+sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial
+portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT
+NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
+OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+/// # Parity
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Parity {
+    #[default]
+    None,
+    Odd,
+    Even,
+    /// The parity bit is always set.
+    Mark,
+    /// The parity bit is always clear.
+    Space,
+}
+
+/// # Stop Bits
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StopBits {
+    #[default]
+    One,
+    /// Two stop bits, or one and a half with 5-bit words.
+    Two,
+}
+
+/// # Word Length
+/// The number of data bits in each character.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WordLength {
+    Five,
+    Six,
+    Seven,
+    #[default]
+    Eight,
+}
+
+impl WordLength {
+    /// # Data Mask
+    /// The bits of each byte that are sent with this word length.
+    pub const fn data_mask(self) -> u8 {
+        match self {
+            WordLength::Five => 0x1F,
+            WordLength::Six => 0x3F,
+            WordLength::Seven => 0x7F,
+            WordLength::Eight => 0xFF,
+        }
+    }
+}
+
+/// # Line Settings
+/// The character framing of a serial line, the default is `8N1`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LineSettings {
+    pub word_length: WordLength,
+    pub parity: Parity,
+    pub stop_bits: StopBits,
+}
+
+impl LineSettings {
+    /// # Line Control
+    /// Get the value of the line control register for these settings (with
+    /// DLAB clear).
+    pub const fn line_control(&self) -> u8 {
+        let word_length = match self.word_length {
+            WordLength::Five => 0b00,
+            WordLength::Six => 0b01,
+            WordLength::Seven => 0b10,
+            WordLength::Eight => 0b11,
+        };
+
+        let stop_bits = match self.stop_bits {
+            StopBits::One => 0,
+            StopBits::Two => 1 << 2,
+        };
+
+        let parity = match self.parity {
+            Parity::None => 0b000,
+            Parity::Odd => 0b001,
+            Parity::Even => 0b011,
+            Parity::Mark => 0b101,
+            Parity::Space => 0b111,
+        };
+
+        word_length | stop_bits | (parity << 3)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_default_is_8n1() {
+        assert_eq!(LineSettings::default().line_control(), 0x03);
+    }
+
+    #[test]
+    fn test_line_control_encoding() {
+        let settings = LineSettings {
+            word_length: WordLength::Seven,
+            parity: Parity::Even,
+            stop_bits: StopBits::Two,
+        };
+
+        assert_eq!(settings.line_control(), 0b0001_1110);
+
+        let settings = LineSettings {
+            word_length: WordLength::Five,
+            parity: Parity::Space,
+            stop_bits: StopBits::One,
+        };
+
+        assert_eq!(settings.line_control(), 0b0011_1000);
+    }
+}
@@ -73,7 +73,10 @@ mod offsets {
     pub const RW_MODEM_CONTROL: u16 = 4;
 
     /// # (Read) Line Status Register Offset
-    pub const R_LINE_STATUS: u16 = 6;
+    pub const R_LINE_STATUS: u16 = 5;
+
+    /// # (Read) Modem Status Register Offset
+    pub const R_MODEM_STATUS: u16 = 6;
 
     /// # (Read/Write) Scratch Register Offset
     pub const RW_SCRATCH: u16 = 7;
@@ -112,6 +115,7 @@ impl_reg!(W: write_fifo_control, offsets::W_FIFO_CONTROL);
 impl_reg!(RW: read_line_control, write_line_control, offsets::RW_LINE_CONTROL);
 impl_reg!(RW: read_modem_control, write_modem_control, offsets::RW_MODEM_CONTROL);
 impl_reg!(R: read_line_status, offsets::R_LINE_STATUS);
+impl_reg!(R: read_modem_status, offsets::R_MODEM_STATUS);
 impl_reg!(RW: read_scratch, write_scratch, offsets::RW_SCRATCH);
 
 // FIXME: I am not sure how I want to impl this, I just want to get some
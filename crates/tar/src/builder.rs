@@ -0,0 +1,380 @@
+/*
+  ____                 __               __   _ __
+ / __ \__ _____ ____  / /___ ____ _    / /  (_) /
+/ /_/ / // / _ `/ _ \/ __/ // /  ' \  / /__/ / _ \
+\___\_\_,_/\_,_/_//_/\__/\_,_/_/_/_/ /____/_/_.__/
+    Part of the Quantum OS Project
+
+Copyright 2025 Gavin Kellam
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and
+associated documentation files (the "Software"), to deal in the Software without restriction,
+including without limitation the rights to use, copy, modify, merge, publish, distribute,
+sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial
+portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT
+NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
+OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+use crate::header::{self, BLOCK_SIZE};
+use crate::{Result, TarError};
+
+/// # Tar Write
+/// Somewhere a [`TarBuilder`] can write an archive to.
+pub trait TarWrite {
+    fn write_all(&mut self, buf: &[u8]) -> Result<()>;
+}
+
+impl<T: TarWrite + ?Sized> TarWrite for &mut T {
+    fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+        (**self).write_all(buf)
+    }
+}
+
+#[cfg(feature = "std")]
+impl TarWrite for std::vec::Vec<u8> {
+    fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+        self.extend_from_slice(buf);
+        Ok(())
+    }
+}
+
+/// # Slice Writer
+/// Write an archive into a fixed buffer.
+pub struct SliceWriter<'a> {
+    buffer: &'a mut [u8],
+    written: usize,
+}
+
+impl<'a> SliceWriter<'a> {
+    pub const fn new(buffer: &'a mut [u8]) -> Self {
+        Self { buffer, written: 0 }
+    }
+
+    /// # Written
+    /// The part of the buffer that has been written.
+    pub fn written(&self) -> &[u8] {
+        &self.buffer[..self.written]
+    }
+}
+
+impl TarWrite for SliceWriter<'_> {
+    fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+        let dest = self
+            .buffer
+            .get_mut(self.written..self.written + buf.len())
+            .ok_or(TarError::WriteError)?;
+
+        dest.copy_from_slice(buf);
+        self.written += buf.len();
+
+        Ok(())
+    }
+}
+
+/// # Tar Builder
+/// Write a ustar archive one entry at a time.
+pub struct TarBuilder<W: TarWrite> {
+    writer: W,
+}
+
+impl<W: TarWrite> TarBuilder<W> {
+    pub const fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    /// # Append File
+    /// Add a regular file at `path` holding `data`.
+    pub fn append_file(&mut self, path: &str, mode: u32, mtime: u64, data: &[u8]) -> Result<()> {
+        self.append(path, header::TYPE_FILE, "", mode, mtime, data)
+    }
+
+    /// # Append Directory
+    /// Add a directory at `path`, a trailing `/` is added if it's missing.
+    pub fn append_directory(&mut self, path: &str, mode: u32, mtime: u64) -> Result<()> {
+        self.append(path, header::TYPE_DIRECTORY, "", mode, mtime, &[])
+    }
+
+    /// # Append Symlink
+    /// Add a symbolic link at `path` pointing to `target`.
+    pub fn append_symlink(
+        &mut self,
+        path: &str,
+        target: &str,
+        mode: u32,
+        mtime: u64,
+    ) -> Result<()> {
+        self.append(path, header::TYPE_SYMLINK, target, mode, mtime, &[])
+    }
+
+    /// # Append Hard Link
+    /// Add a hard link at `path` to the entry at `target`.
+    pub fn append_hard_link(
+        &mut self,
+        path: &str,
+        target: &str,
+        mode: u32,
+        mtime: u64,
+    ) -> Result<()> {
+        self.append(path, header::TYPE_HARD_LINK, target, mode, mtime, &[])
+    }
+
+    /// # Finish
+    /// End the archive with the two zero blocks and give back the writer.
+    pub fn finish(mut self) -> Result<W> {
+        self.writer.write_all(&[0; BLOCK_SIZE * 2])?;
+        Ok(self.writer)
+    }
+
+    /// Paths that don't fit in `name` are split over the ustar `prefix`, and
+    /// if that isn't enough they get a GNU long name entry.
+    fn append(
+        &mut self,
+        path: &str,
+        typeflag: u8,
+        link: &str,
+        mode: u32,
+        mtime: u64,
+        data: &[u8],
+    ) -> Result<()> {
+        if path.is_empty() {
+            return Err(TarError::InvalidHeader);
+        }
+        if link.len() > header::LINKNAME.len() {
+            return Err(TarError::NameTooLong);
+        }
+
+        let slash = typeflag == header::TYPE_DIRECTORY && !path.ends_with('/');
+        let mut block = Self::header(typeflag, mode, mtime, data.len() as u64)?;
+
+        if path.len() + slash as usize <= header::NAME.len() {
+            Self::write_name(&mut block[header::NAME], path, slash);
+        } else if let Some(split) = Self::ustar_split(path, slash) {
+            block[header::PREFIX][..split].copy_from_slice(&path.as_bytes()[..split]);
+            Self::write_name(&mut block[header::NAME], &path[split + 1..], slash);
+        } else {
+            self.append_long_name(path, slash)?;
+            Self::write_name(&mut block[header::NAME], path, false);
+        }
+
+        Self::write_name(&mut block[header::LINKNAME], link, false);
+        Self::seal(&mut block)?;
+        self.writer.write_all(&block)?;
+        self.writer.write_all(data)?;
+        self.pad(data.len())
+    }
+
+    fn append_long_name(&mut self, path: &str, slash: bool) -> Result<()> {
+        // The name is stored with a NUL on the end
+        let size = path.len() + slash as usize + 1;
+        let mut block = Self::header(header::TYPE_GNU_LONG_NAME, 0, 0, size as u64)?;
+
+        Self::write_name(&mut block[header::NAME], header::GNU_LONG_NAME_PATH, false);
+        Self::seal(&mut block)?;
+
+        self.writer.write_all(&block)?;
+        self.writer.write_all(path.as_bytes())?;
+        self.writer.write_all(if slash { b"/\0" } else { b"\0" })?;
+        self.pad(size)
+    }
+
+    /// Find the `/` to split `path` at so the part before it fits in `prefix`
+    /// and the (non-empty) part after it fits in `name`.
+    fn ustar_split(path: &str, slash: bool) -> Option<usize> {
+        path.match_indices('/')
+            .map(|(i, _)| i)
+            .filter(|&i| i + 1 < path.len() && i <= header::PREFIX.len())
+            .find(|&i| path.len() - (i + 1) + slash as usize <= header::NAME.len())
+    }
+
+    /// Copy as much of `name` as fits into the field, with an optional `/` after it.
+    fn write_name(field: &mut [u8], name: &str, slash: bool) {
+        let len = name.len().min(field.len());
+        field[..len].copy_from_slice(&name.as_bytes()[..len]);
+
+        if slash && len < field.len() {
+            field[len] = b'/';
+        }
+    }
+
+    fn header(typeflag: u8, mode: u32, mtime: u64, size: u64) -> Result<[u8; BLOCK_SIZE]> {
+        let mut block = [0; BLOCK_SIZE];
+
+        header::write_octal(&mut block[header::MODE], mode as u64)?;
+        header::write_octal(&mut block[header::UID], 0)?;
+        header::write_octal(&mut block[header::GID], 0)?;
+        header::write_octal(&mut block[header::SIZE], size)?;
+        header::write_octal(&mut block[header::MTIME], mtime)?;
+        block[header::TYPEFLAG] = typeflag;
+        block[header::MAGIC].copy_from_slice(header::USTAR_MAGIC);
+        block[header::VERSION].copy_from_slice(header::USTAR_VERSION);
+
+        Ok(block)
+    }
+
+    /// Fill in the checksum, as six octal digits followed by a NUL and a space.
+    fn seal(block: &mut [u8; BLOCK_SIZE]) -> Result<()> {
+        let checksum = header::checksum(block);
+        let field = &mut block[header::CHECKSUM];
+
+        header::write_octal(&mut field[..7], checksum as u64)?;
+        field[7] = b' ';
+
+        Ok(())
+    }
+
+    fn pad(&mut self, size: usize) -> Result<()> {
+        let padding = header::padded_size(size) - size;
+        self.writer.write_all(&[0; BLOCK_SIZE][..padding])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Tar;
+
+    #[test]
+    fn test_build_and_read_back() {
+        let mut buffer = [0xAA; BLOCK_SIZE * 8];
+        let mut builder = TarBuilder::new(SliceWriter::new(&mut buffer));
+
+        builder.append_directory("boot", 0o755, 100).unwrap();
+        builder
+            .append_file("boot/kernel.elf", 0o644, 100, &[1; 600])
+            .unwrap();
+        builder.append_file("empty", 0o644, 100, &[]).unwrap();
+
+        let writer = builder.finish().unwrap();
+        let archive = writer.written();
+
+        // dir + (header + 2 data blocks) + header + 2 end blocks
+        assert_eq!(archive.len(), BLOCK_SIZE * 7);
+        assert!(archive[BLOCK_SIZE * 5..].iter().all(|&b| b == 0));
+
+        let tar = Tar::new(archive);
+        let mut files = tar.iter().map(|f| f.unwrap());
+
+        let dir = files.next().unwrap();
+        assert_eq!(dir.name(), "boot/");
+        assert_eq!(dir.header().typeflag(), header::TYPE_DIRECTORY);
+        assert!(dir.header().is_ustar());
+
+        let kernel = files.next().unwrap();
+        assert_eq!(kernel.name(), "boot/kernel.elf");
+        assert_eq!(kernel.data(), &[1; 600]);
+
+        assert_eq!(files.next().unwrap().data(), &[]);
+        assert!(files.next().is_none());
+
+        assert!(tar.find("empty").unwrap().is_some());
+        assert!(tar.find("missing").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_checksum_field_format() {
+        let mut block = TarBuilder::<SliceWriter>::header(header::TYPE_FILE, 0o644, 0, 1).unwrap();
+        TarBuilder::<SliceWriter>::seal(&mut block).unwrap();
+        let field = &block[header::CHECKSUM];
+
+        assert_eq!(&field[6..], b"\0 ");
+        assert_eq!(
+            header::parse_octal(field).unwrap(),
+            header::checksum(&block) as u64
+        );
+    }
+
+    #[test]
+    fn test_ustar_prefix_names() {
+        let path = "system/servers/graphics/aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa/compositor.elf";
+        assert!(path.len() > header::NAME.len());
+
+        let mut buffer = [0; BLOCK_SIZE * 8];
+        let mut builder = TarBuilder::new(SliceWriter::new(&mut buffer));
+
+        builder.append_file(path, 0o755, 0, b"elf").unwrap();
+        builder
+            .append_directory(&path[..path.len() - 15], 0o755, 0)
+            .unwrap();
+
+        let writer = builder.finish().unwrap();
+        let tar = Tar::new(writer.written());
+        let mut files = tar.iter().map(|f| f.unwrap());
+
+        let file = files.next().unwrap();
+        assert_eq!(file.header().prefix().unwrap(), &path[..path.len() - 15]);
+        assert_eq!(file.header().name().unwrap(), "compositor.elf");
+        assert_eq!(file.name(), path);
+        assert_eq!(file.data(), b"elf");
+
+        let dir = files.next().unwrap();
+        assert_eq!(dir.name().len(), path.len() - 14);
+        assert!(dir.name().ends_with("aaaa/"));
+
+        assert!(files.next().is_none());
+        assert_eq!(tar.find(path).unwrap().unwrap().data(), b"elf");
+    }
+
+    #[test]
+    fn test_gnu_long_names() {
+        // Too long to fit even when split across the prefix
+        let long = core::str::from_utf8(&[b'a'; 300]).unwrap();
+
+        let mut buffer = [0; BLOCK_SIZE * 12];
+        let mut builder = TarBuilder::new(SliceWriter::new(&mut buffer));
+
+        builder.append_file(long, 0o644, 0, b"data").unwrap();
+        builder.append_directory(long, 0o755, 0).unwrap();
+        builder.append_file("short", 0o644, 0, b"after").unwrap();
+
+        let writer = builder.finish().unwrap();
+        let tar = Tar::new(writer.written());
+        let mut files = tar.iter().map(|f| f.unwrap());
+
+        let file = files.next().unwrap();
+        assert_eq!(file.name(), long);
+        assert_eq!(file.data(), b"data");
+
+        let dir = files.next().unwrap();
+        assert_eq!(&dir.name()[..300], long);
+        assert_eq!(&dir.name()[300..], "/");
+        assert_eq!(dir.header().typeflag(), header::TYPE_DIRECTORY);
+
+        // The long name doesn't leak into the next entry
+        assert_eq!(files.next().unwrap().name(), "short");
+        assert!(files.next().is_none());
+    }
+
+    #[test]
+    fn test_dangling_long_name() {
+        let long = core::str::from_utf8(&[b'a'; 300]).unwrap();
+
+        let mut buffer = [0; BLOCK_SIZE * 4];
+        let mut builder = TarBuilder::new(SliceWriter::new(&mut buffer));
+        builder.append_long_name(long, false).unwrap();
+
+        let writer = builder.finish().unwrap();
+        let mut files = Tar::new(writer.written()).iter();
+
+        assert_eq!(files.next().unwrap().err(), Some(TarError::InvalidHeader));
+        assert!(files.next().is_none());
+    }
+
+    #[test]
+    fn test_writer_out_of_space() {
+        let mut buffer = [0; BLOCK_SIZE];
+        let mut builder = TarBuilder::new(SliceWriter::new(&mut buffer));
+
+        assert_eq!(
+            builder.append_file("a", 0o644, 0, &[0; 10]),
+            Err(TarError::WriteError)
+        );
+    }
+}
@@ -0,0 +1,261 @@
+/*
+  ____                 __               __   _ __
+ / __ \__ _____ ____  / /___ ____ _    / /  (_) /
+/ /_/ / // / _ `/ _ \/ __/ // /  ' \  / /__/ / _ \
+\___\_\_,_/\_,_/_//_/\__/\_,_/_/_/_/ /____/_/_.__/
+    Part of the Quantum OS Project
+
+Copyright 2025 Gavin Kellam
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and
+associated documentation files (the "Software"), to deal in the Software without restriction,
+including without limitation the rights to use, copy, modify, merge, publish, distribute,
+sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial
+portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT
+NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
+OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+use crate::{Result, TarError};
+
+/// The size of a header, and the unit all data is padded to.
+pub const BLOCK_SIZE: usize = 512;
+
+pub const NAME: core::ops::Range<usize> = 0..100;
+pub const MODE: core::ops::Range<usize> = 100..108;
+pub const UID: core::ops::Range<usize> = 108..116;
+pub const GID: core::ops::Range<usize> = 116..124;
+pub const SIZE: core::ops::Range<usize> = 124..136;
+pub const MTIME: core::ops::Range<usize> = 136..148;
+pub const CHECKSUM: core::ops::Range<usize> = 148..156;
+pub const TYPEFLAG: usize = 156;
+pub const LINKNAME: core::ops::Range<usize> = 157..257;
+pub const MAGIC: core::ops::Range<usize> = 257..263;
+pub const VERSION: core::ops::Range<usize> = 263..265;
+pub const PREFIX: core::ops::Range<usize> = 345..500;
+
+pub const USTAR_MAGIC: &[u8; 6] = b"ustar\0";
+pub const USTAR_VERSION: &[u8; 2] = b"00";
+
+pub const TYPE_FILE: u8 = b'0';
+/// Pre-POSIX archives used NUL for regular files.
+pub const TYPE_OLD_FILE: u8 = 0;
+pub const TYPE_HARD_LINK: u8 = b'1';
+pub const TYPE_SYMLINK: u8 = b'2';
+pub const TYPE_CHAR_DEVICE: u8 = b'3';
+pub const TYPE_BLOCK_DEVICE: u8 = b'4';
+pub const TYPE_DIRECTORY: u8 = b'5';
+pub const TYPE_FIFO: u8 = b'6';
+pub const TYPE_CONTIGUOUS: u8 = b'7';
+/// GNU extension, the data of this entry is the path of the next one.
+pub const TYPE_GNU_LONG_NAME: u8 = b'L';
+
+/// The name GNU tar gives to long name entries.
+pub const GNU_LONG_NAME_PATH: &str = "././@LongLink";
+
+/// # Padded Size
+/// The size `size` bytes of data take up in the archive.
+pub const fn padded_size(size: usize) -> usize {
+    size.div_ceil(BLOCK_SIZE) * BLOCK_SIZE
+}
+
+/// # Checksum
+/// The sum of every byte in the header, with the checksum field counted as
+/// spaces.
+pub fn checksum(block: &[u8]) -> u32 {
+    block
+        .iter()
+        .enumerate()
+        .map(|(i, &b)| if CHECKSUM.contains(&i) { b' ' } else { b } as u32)
+        .sum()
+}
+
+/// # Signed Checksum
+/// Like [`checksum`], but summing the bytes as `i8` like some old versions
+/// of tar did.
+pub fn signed_checksum(block: &[u8]) -> i32 {
+    block
+        .iter()
+        .enumerate()
+        .map(|(i, &b)| if CHECKSUM.contains(&i) { b' ' } else { b } as i8 as i32)
+        .sum()
+}
+
+/// # Parse Octal
+/// Parse an octal number field, which can be padded with spaces and NULs.
+pub fn parse_octal(field: &[u8]) -> Result<u64> {
+    let mut digits = field
+        .iter()
+        .skip_while(|&&b| b == b' ')
+        .take_while(|&&b| b != b' ' && b != 0);
+
+    digits.try_fold(0u64, |acc, &b| {
+        if !(b'0'..=b'7').contains(&b) {
+            return Err(TarError::InvalidHeader);
+        }
+
+        acc.checked_mul(8)
+            .map(|acc| acc + (b - b'0') as u64)
+            .ok_or(TarError::FieldTooLarge)
+    })
+}
+
+/// # Write Octal
+/// Write `value` into `field` as zero padded octal, ending with a NUL.
+pub fn write_octal(field: &mut [u8], mut value: u64) -> Result<()> {
+    let Some((last, digits)) = field.split_last_mut() else {
+        return Err(TarError::FieldTooLarge);
+    };
+
+    *last = 0;
+    for digit in digits.iter_mut().rev() {
+        *digit = b'0' + (value & 7) as u8;
+        value >>= 3;
+    }
+
+    if value != 0 {
+        return Err(TarError::FieldTooLarge);
+    }
+
+    Ok(())
+}
+
+/// The string in a NUL padded field.
+fn field_str(field: &[u8]) -> Result<&str> {
+    let len = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    core::str::from_utf8(&field[..len]).map_err(|_| TarError::InvalidHeader)
+}
+
+/// # Tar Header
+/// A checked header block.
+#[derive(Clone, Copy)]
+pub struct TarHeader<'a> {
+    block: &'a [u8],
+}
+
+impl<'a> TarHeader<'a> {
+    /// # New
+    /// Check the header in `block`, a header that doesn't match its checksum
+    /// is an [`TarError::IntegrityError`].
+    pub fn new(block: &'a [u8]) -> Result<Self> {
+        let block = block.get(..BLOCK_SIZE).ok_or(TarError::NotEnoughBytes)?;
+        let stored = parse_octal(&block[CHECKSUM]).map_err(|_| TarError::IntegrityError)?;
+
+        if stored != checksum(block) as u64 && stored as i64 != signed_checksum(block) as i64 {
+            return Err(TarError::IntegrityError);
+        }
+
+        Ok(Self { block })
+    }
+
+    pub fn name(&self) -> Result<&'a str> {
+        field_str(&self.block[NAME])
+    }
+
+    /// # Prefix
+    /// The ustar prefix of the path, this is empty for older headers.
+    pub fn prefix(&self) -> Result<&'a str> {
+        if !self.is_ustar() {
+            return Ok("");
+        }
+
+        field_str(&self.block[PREFIX])
+    }
+
+    pub fn link_name(&self) -> Result<&'a str> {
+        field_str(&self.block[LINKNAME])
+    }
+
+    pub fn mode(&self) -> Result<u32> {
+        u32::try_from(parse_octal(&self.block[MODE])?).map_err(|_| TarError::FieldTooLarge)
+    }
+
+    pub fn mtime(&self) -> Result<u64> {
+        parse_octal(&self.block[MTIME])
+    }
+
+    pub fn size(&self) -> Result<u64> {
+        parse_octal(&self.block[SIZE])
+    }
+
+    pub fn typeflag(&self) -> u8 {
+        self.block[TYPEFLAG]
+    }
+
+    /// # Is Ustar
+    /// Check if this header has the ustar magic (so it has the ustar fields).
+    pub fn is_ustar(&self) -> bool {
+        &self.block[MAGIC] == USTAR_MAGIC
+    }
+
+    pub const fn raw(&self) -> &'a [u8] {
+        self.block
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_octal_round_trip() {
+        let mut field = [0u8; 12];
+
+        write_octal(&mut field, 0o644).unwrap();
+        assert_eq!(&field, b"00000000644\0");
+        assert_eq!(parse_octal(&field), Ok(0o644));
+
+        assert_eq!(parse_octal(b"  755 \0\0"), Ok(0o755));
+        assert_eq!(parse_octal(b"\0\0\0\0"), Ok(0));
+        assert_eq!(parse_octal(b"0089"), Err(TarError::InvalidHeader));
+
+        let mut field = [0u8; 4];
+        assert_eq!(
+            write_octal(&mut field, 0o7777),
+            Err(TarError::FieldTooLarge)
+        );
+    }
+
+    #[test]
+    fn test_padded_size() {
+        assert_eq!(padded_size(0), 0);
+        assert_eq!(padded_size(1), BLOCK_SIZE);
+        assert_eq!(padded_size(BLOCK_SIZE), BLOCK_SIZE);
+        assert_eq!(padded_size(BLOCK_SIZE + 1), BLOCK_SIZE * 2);
+    }
+
+    #[test]
+    fn test_bad_checksum() {
+        let mut block = [0u8; BLOCK_SIZE];
+        block[..5].copy_from_slice(b"hello");
+        let sum = checksum(&block) as u64;
+        write_octal(&mut block[CHECKSUM][..7], sum).unwrap();
+
+        assert!(TarHeader::new(&block).is_ok());
+
+        block[0] = b'j';
+        assert_eq!(TarHeader::new(&block).err(), Some(TarError::IntegrityError));
+
+        // A mangled checksum field is also corruption
+        block[CHECKSUM.start] = b'9';
+        assert_eq!(TarHeader::new(&block).err(), Some(TarError::IntegrityError));
+    }
+
+    #[test]
+    fn test_signed_checksum() {
+        let mut block = [0u8; BLOCK_SIZE];
+        block[..6].copy_from_slice("h\u{e9}llo".as_bytes());
+        let sum = signed_checksum(&block) as u64;
+        write_octal(&mut block[CHECKSUM][..7], sum).unwrap();
+
+        assert_ne!(sum, checksum(&block) as u64);
+        assert!(TarHeader::new(&block).is_ok());
+    }
+}
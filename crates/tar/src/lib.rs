@@ -0,0 +1,539 @@
+/*
+  ____                 __               __   _ __
+ / __ \__ _____ ____  / /___ ____ _    / /  (_) /
+/ /_/ / // / _ `/ _ \/ __/ // /  ' \  / /__/ / _ \
+\___\_\_,_/\_,_/_//_/\__/\_,_/_/_/_/ /____/_/_.__/
+    Part of the Quantum OS Project
+
+Copyright 2025 Gavin Kellam
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and
+associated documentation files (the "Software"), to deal in the Software without restriction,
+including without limitation the rights to use, copy, modify, merge, publish, distribute,
+sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial
+portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT
+NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
+OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+#![no_std]
+
+#[cfg(feature = "std")]
+extern crate std;
+
+pub mod builder;
+pub mod header;
+
+use header::TarHeader;
+
+/// # Tar Kind
+/// What kind of entry a [`TarFile`] is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TarKind {
+    File,
+    Directory,
+    Symlink,
+    HardLink,
+    CharDevice,
+    BlockDevice,
+    Fifo,
+    /// A typeflag we don't know about.
+    Other(u8),
+}
+
+impl TarKind {
+    /// # From Header
+    pub fn from_header(header: &TarHeader) -> Self {
+        match header.typeflag() {
+            // Before typeflags directories were files with a trailing `/`
+            header::TYPE_FILE | header::TYPE_OLD_FILE
+                if header.name().is_ok_and(|name| name.ends_with('/')) =>
+            {
+                TarKind::Directory
+            }
+            header::TYPE_FILE | header::TYPE_OLD_FILE | header::TYPE_CONTIGUOUS => TarKind::File,
+            header::TYPE_HARD_LINK => TarKind::HardLink,
+            header::TYPE_SYMLINK => TarKind::Symlink,
+            header::TYPE_CHAR_DEVICE => TarKind::CharDevice,
+            header::TYPE_BLOCK_DEVICE => TarKind::BlockDevice,
+            header::TYPE_DIRECTORY => TarKind::Directory,
+            header::TYPE_FIFO => TarKind::Fifo,
+            other => TarKind::Other(other),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TarError {
+    NotEnoughBytes,
+    /// A header doesn't match its checksum, so the archive is corrupted.
+    IntegrityError,
+    InvalidHeader,
+    /// The link target doesn't fit in the header.
+    NameTooLong,
+    /// A number doesn't fit in its octal field.
+    FieldTooLarge,
+    WriteError,
+}
+
+pub type Result<T> = core::result::Result<T, TarError>;
+
+/// # Tar
+/// A tar archive in memory.
+#[derive(Clone, Copy)]
+pub struct Tar<'a> {
+    archive: &'a [u8],
+}
+
+impl<'a> Tar<'a> {
+    pub const fn new(archive: &'a [u8]) -> Self {
+        Self { archive }
+    }
+
+    /// # Iter
+    /// Iterate all the entries in the archive, stopping at the first error.
+    pub const fn iter(&self) -> TarIter<'a> {
+        TarIter {
+            archive: self.archive,
+            offset: 0,
+            done: false,
+            found_end: false,
+        }
+    }
+
+    /// # Validate
+    /// Walk the whole archive, checking every header and that the archive
+    /// isn't truncated, and return how many entries it has.
+    ///
+    /// Tar has no checksum over file data, so this can't catch corrupted
+    /// contents, only corrupted headers.
+    pub fn validate(&self) -> Result<usize> {
+        let mut iter = self.iter();
+        let mut entries = 0;
+
+        for file in iter.by_ref() {
+            let file = file?;
+
+            file.mode()?;
+            file.mtime()?;
+            entries += 1;
+        }
+
+        // Without the end marker we could have stopped anywhere
+        if !iter.found_end {
+            return Err(TarError::NotEnoughBytes);
+        }
+
+        Ok(entries)
+    }
+
+    /// # Find
+    /// Find the entry at `path`.
+    pub fn find(&self, path: &str) -> Result<Option<TarFile<'a>>> {
+        for file in self.iter() {
+            let file = file?;
+
+            if file.name() == path {
+                return Ok(Some(file));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// # Entries Under
+    /// Iterate every entry inside the directory `prefix` (at any depth), not
+    /// including the directory itself.
+    ///
+    /// Leading `./` and `/` are ignored on both sides, so `"boot"`, `"/boot/"`
+    /// and `"./boot"` all match the entry `./boot/kernel.elf`. An empty
+    /// prefix matches everything.
+    pub fn entries_under<'p>(
+        &self,
+        prefix: &'p str,
+    ) -> impl Iterator<Item = Result<TarFile<'a>>> + 'p
+    where
+        'a: 'p,
+    {
+        let prefix = normalize_path(prefix);
+
+        self.iter().filter(move |file| {
+            let Ok(file) = file else {
+                return true;
+            };
+
+            let name = normalize_path(file.name());
+            if prefix.is_empty() {
+                return !name.is_empty();
+            }
+
+            name.strip_prefix(prefix)
+                .is_some_and(|rest| rest.len() > 1 && rest.starts_with('/'))
+        })
+    }
+}
+
+/// Strip the leading `./` and `/`, and any trailing `/`.
+fn normalize_path(mut path: &str) -> &str {
+    loop {
+        if let Some(rest) = path.strip_prefix("./") {
+            path = rest;
+        } else if let Some(rest) = path.strip_prefix('/') {
+            path = rest;
+        } else {
+            break;
+        }
+    }
+
+    path.trim_end_matches('/')
+}
+
+/// The longest path a ustar header can hold, `prefix` + `/` + `name`.
+const MAX_USTAR_PATH: usize =
+    (header::PREFIX.end - header::PREFIX.start) + 1 + (header::NAME.end - header::NAME.start);
+
+/// The path of an entry, ustar paths split over `prefix` and `name` have to
+/// be joined, everything else can be borrowed from the archive.
+#[derive(Clone, Copy)]
+#[allow(clippy::large_enum_variant)]
+enum TarName<'a> {
+    Archive(&'a str),
+    Joined([u8; MAX_USTAR_PATH], usize),
+}
+
+impl<'a> TarName<'a> {
+    fn from_header(header: &TarHeader<'a>) -> Result<Self> {
+        let prefix = header.prefix()?;
+        let name = header.name()?;
+
+        if prefix.is_empty() {
+            return Ok(Self::Archive(name));
+        }
+
+        let mut joined = [0; MAX_USTAR_PATH];
+        let len = prefix.len() + 1 + name.len();
+
+        joined[..prefix.len()].copy_from_slice(prefix.as_bytes());
+        joined[prefix.len()] = b'/';
+        joined[prefix.len() + 1..len].copy_from_slice(name.as_bytes());
+
+        Ok(Self::Joined(joined, len))
+    }
+
+    fn as_str(&self) -> &str {
+        match self {
+            TarName::Archive(name) => name,
+            // Both halves were already checked to be UTF-8
+            TarName::Joined(joined, len) => {
+                core::str::from_utf8(&joined[..*len]).unwrap_or_default()
+            }
+        }
+    }
+}
+
+/// # Tar File
+/// One entry in a tar archive.
+#[derive(Clone, Copy)]
+pub struct TarFile<'a> {
+    header: TarHeader<'a>,
+    name: TarName<'a>,
+    data: &'a [u8],
+}
+
+impl<'a> TarFile<'a> {
+    /// # Name
+    /// The full path of this entry, including any ustar `prefix` or GNU long
+    /// name.
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    pub const fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    pub fn kind(&self) -> TarKind {
+        TarKind::from_header(&self.header)
+    }
+
+    /// # Mode
+    /// The permission bits of this entry.
+    pub fn mode(&self) -> Result<u32> {
+        self.header.mode()
+    }
+
+    /// # Mtime
+    /// The last modification time, in seconds since the Unix epoch.
+    pub fn mtime(&self) -> Result<u64> {
+        self.header.mtime()
+    }
+
+    /// # Link Target
+    /// The path a symlink points to, or the entry a hard link is to.
+    pub fn link_target(&self) -> Result<&'a str> {
+        self.header.link_name()
+    }
+
+    pub const fn header(&self) -> &TarHeader<'a> {
+        &self.header
+    }
+}
+
+/// # Tar Iter
+pub struct TarIter<'a> {
+    archive: &'a [u8],
+    offset: usize,
+    done: bool,
+    found_end: bool,
+}
+
+impl<'a> Iterator for TarIter<'a> {
+    type Item = Result<TarFile<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        match self.next_entry() {
+            Ok(Some(file)) => Some(Ok(file)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(err) => {
+                self.done = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+impl<'a> TarIter<'a> {
+    /// # Offset
+    /// The offset in the archive of the next header, after an error this is
+    /// where the bad entry starts.
+    pub const fn offset(&self) -> usize {
+        self.offset
+    }
+
+    fn next_entry(&mut self) -> Result<Option<TarFile<'a>>> {
+        let mut long_name = None;
+
+        while let Some((header, data)) = self.next_block()? {
+            // GNU tar puts names that don't fit into the data of an extra
+            // entry just before the real one
+            if header.typeflag() == header::TYPE_GNU_LONG_NAME {
+                let len = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+                let name =
+                    core::str::from_utf8(&data[..len]).map_err(|_| TarError::InvalidHeader)?;
+
+                long_name = Some(name);
+                continue;
+            }
+
+            let name = match long_name {
+                Some(name) => TarName::Archive(name),
+                None => TarName::from_header(&header)?,
+            };
+
+            return Ok(Some(TarFile { header, name, data }));
+        }
+
+        match long_name {
+            Some(_) => Err(TarError::InvalidHeader),
+            None => Ok(None),
+        }
+    }
+
+    fn next_block(&mut self) -> Result<Option<(TarHeader<'a>, &'a [u8])>> {
+        // Archives are meant to end with two zero blocks, but plenty of
+        // tools just stop, so running out of bytes is also the end.
+        let Some(block) = self
+            .archive
+            .get(self.offset..self.offset + header::BLOCK_SIZE)
+        else {
+            return Ok(None);
+        };
+
+        if block.iter().all(|&b| b == 0) {
+            self.found_end = true;
+            return Ok(None);
+        }
+
+        let header = TarHeader::new(block)?;
+        let size = usize::try_from(header.size()?).map_err(|_| TarError::FieldTooLarge)?;
+        let data_start = self.offset + header::BLOCK_SIZE;
+
+        // The size comes from the archive, so it can be anything
+        let data_end = data_start
+            .checked_add(size)
+            .ok_or(TarError::NotEnoughBytes)?;
+        let data = self
+            .archive
+            .get(data_start..data_end)
+            .ok_or(TarError::NotEnoughBytes)?;
+
+        self.offset = size
+            .checked_next_multiple_of(header::BLOCK_SIZE)
+            .and_then(|padded_size| data_start.checked_add(padded_size))
+            .ok_or(TarError::NotEnoughBytes)?;
+
+        Ok(Some((header, data)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate std;
+    use super::*;
+    use builder::{SliceWriter, TarBuilder};
+    use std::string::String;
+    use std::vec::Vec;
+
+    fn build(buffer: &mut [u8]) -> &[u8] {
+        let mut builder = TarBuilder::new(SliceWriter::new(&mut *buffer));
+
+        builder.append_directory("./boot", 0o755, 1000).unwrap();
+        builder
+            .append_file("./boot/kernel.elf", 0o644, 1001, b"elf")
+            .unwrap();
+        builder
+            .append_directory("./boot/fonts", 0o755, 1002)
+            .unwrap();
+        builder
+            .append_file("./boot/fonts/small.bin", 0o600, 1003, b"font")
+            .unwrap();
+        builder
+            .append_symlink("./boot/latest", "kernel.elf", 0o777, 1004)
+            .unwrap();
+        builder
+            .append_hard_link("./bootstrap", "./boot/kernel.elf", 0o644, 1005)
+            .unwrap();
+
+        let len = builder.finish().unwrap().written().len();
+        &buffer[..len]
+    }
+
+    #[test]
+    fn test_entry_kinds() {
+        let mut buffer = [0; 8192];
+        let tar = Tar::new(build(&mut buffer));
+
+        let kinds = tar.iter().map(|f| f.unwrap().kind());
+        assert!(kinds.eq([
+            TarKind::Directory,
+            TarKind::File,
+            TarKind::Directory,
+            TarKind::File,
+            TarKind::Symlink,
+            TarKind::HardLink,
+        ]));
+
+        let font = tar.find("./boot/fonts/small.bin").unwrap().unwrap();
+        assert_eq!(font.mode(), Ok(0o600));
+        assert_eq!(font.mtime(), Ok(1003));
+
+        let link = tar.find("./boot/latest").unwrap().unwrap();
+        assert_eq!(link.link_target(), Ok("kernel.elf"));
+    }
+
+    #[test]
+    fn test_entries_under() {
+        let mut buffer = [0; 8192];
+        let tar = Tar::new(build(&mut buffer));
+
+        for prefix in ["boot", "/boot/", "./boot"] {
+            let names: Vec<String> = tar
+                .entries_under(prefix)
+                .map(|f| f.unwrap().name().into())
+                .collect();
+
+            assert_eq!(names, [
+                "./boot/kernel.elf",
+                "./boot/fonts/",
+                "./boot/fonts/small.bin",
+                "./boot/latest",
+            ]);
+        }
+
+        assert_eq!(tar.entries_under("boot/fonts").count(), 1);
+        assert_eq!(tar.entries_under("").count(), 6);
+        assert_eq!(tar.entries_under("boot/kernel.elf").count(), 0);
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut buffer = [0; 8192];
+        let archive = build(&mut buffer);
+
+        assert_eq!(Tar::new(archive).validate(), Ok(6));
+
+        // Cut off in the middle of the font data
+        let truncated = &archive[..header::BLOCK_SIZE * 5];
+        assert_eq!(
+            Tar::new(truncated).validate(),
+            Err(TarError::NotEnoughBytes)
+        );
+
+        // Cut off just before the end marker
+        let len = archive.len() - header::BLOCK_SIZE * 2;
+        assert_eq!(
+            Tar::new(&archive[..len]).validate(),
+            Err(TarError::NotEnoughBytes)
+        );
+    }
+
+    #[test]
+    fn test_huge_size_is_reported() {
+        let mut buffer = [0; 8192];
+        let len = build(&mut buffer).len();
+
+        // Give the kernel the largest size its header can hold
+        let block = &mut buffer[header::BLOCK_SIZE..header::BLOCK_SIZE * 2];
+        block[header::SIZE].copy_from_slice(b"777777777777");
+        let checksum = header::checksum(block) as u64;
+        header::write_octal(&mut block[header::CHECKSUM][..7], checksum).unwrap();
+
+        let tar = Tar::new(&buffer[..len]);
+        assert_eq!(tar.validate(), Err(TarError::NotEnoughBytes));
+    }
+
+    #[test]
+    fn test_corrupt_header_is_reported() {
+        let mut buffer = [0; 8192];
+        let len = build(&mut buffer).len();
+
+        // Flip a bit in the name of the font's header
+        buffer[header::BLOCK_SIZE * 4 + 10] ^= 0x01;
+        let tar = Tar::new(&buffer[..len]);
+
+        assert_eq!(tar.validate(), Err(TarError::IntegrityError));
+
+        let mut iter = tar.iter();
+        assert_eq!(iter.by_ref().filter_map(|f| f.ok()).count(), 3);
+        assert_eq!(iter.offset(), header::BLOCK_SIZE * 4);
+    }
+
+    #[test]
+    fn test_old_style_directory() {
+        let mut buffer = [0; 2048];
+        let mut builder = TarBuilder::new(SliceWriter::new(&mut buffer));
+        builder.append_file("old/", 0o755, 0, &[]).unwrap();
+
+        let writer = builder.finish().unwrap();
+        let tar = Tar::new(writer.written());
+
+        assert_eq!(
+            tar.iter().next().unwrap().unwrap().kind(),
+            TarKind::Directory
+        );
+    }
+}
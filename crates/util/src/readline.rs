@@ -0,0 +1,389 @@
+/*
+  ____                 __               __   _ __
+ / __ \__ _____ ____  / /___ ____ _    / /  (_) /
+/ /_/ / // / _ `/ _ \/ __/ // /  ' \  / /__/ / _ \
+\___\_\_,_/\_,_/_//_/\__/\_,_/_/_/_/ /____/_/_.__/
+    Part of the Quantum OS Project
+
+Copyright 2024 Gavin Kellam
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and
+associated documentation files (the "Software"), to deal in the Software without restriction,
+including without limitation the rights to use, copy, modify, merge, publish, distribute,
+sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial
+portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT
+NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
+OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+use core::fmt::Write;
+
+const CTRL_A: u8 = 0x01;
+const CTRL_E: u8 = 0x05;
+const CTRL_U: u8 = 0x15;
+const CTRL_W: u8 = 0x17;
+const BACKSPACE: u8 = 0x08;
+const DELETE: u8 = 0x7F;
+const ESCAPE: u8 = 0x1B;
+
+/// # Feed
+/// What happened after feeding a byte into a [`LineEditor`].
+#[derive(Debug, PartialEq, Eq)]
+pub enum Feed<'a> {
+    /// The line is still being edited.
+    Pending,
+    /// Enter was pressed, this is the finished line.
+    Line(&'a str),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum EscapeState {
+    None,
+    Escape,
+    Csi,
+}
+
+/// # Line Editor
+/// A small `readline` for raw terminals (like serial), it echos and edits the
+/// line in place with ANSI escapes and keeps a ring of the last `HISTORY`
+/// lines.
+///
+/// Lines are limited to `LINE` bytes of printable ASCII, anything typed after
+/// the line is full is dropped.
+pub struct LineEditor<const LINE: usize, const HISTORY: usize> {
+    line: [u8; LINE],
+    len: usize,
+    cursor: usize,
+    escape: EscapeState,
+    last_was_cr: bool,
+    finished: bool,
+    history: [[u8; LINE]; HISTORY],
+    history_len: [usize; HISTORY],
+    /// Where the next line will be put into the ring.
+    history_head: usize,
+    history_count: usize,
+    /// How many entries back we are while browsing with the arrows, 0 is the
+    /// line being typed.
+    browsing: usize,
+}
+
+impl<const LINE: usize, const HISTORY: usize> LineEditor<LINE, HISTORY> {
+    pub const fn new() -> Self {
+        Self {
+            line: [0; LINE],
+            len: 0,
+            cursor: 0,
+            escape: EscapeState::None,
+            last_was_cr: false,
+            finished: false,
+            history: [[0; LINE]; HISTORY],
+            history_len: [0; HISTORY],
+            history_head: 0,
+            history_count: 0,
+            browsing: 0,
+        }
+    }
+
+    /// # Line
+    /// The line as it currently is.
+    pub fn line(&self) -> &str {
+        // Only printable ASCII is ever put into the line
+        core::str::from_utf8(&self.line[..self.len]).unwrap_or_default()
+    }
+
+    pub const fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    /// # History
+    /// Iterate the history from newest to oldest.
+    pub fn history(&self) -> impl Iterator<Item = &str> + '_ {
+        (1..=self.history_count).map(|back| {
+            let index = self.history_index(back);
+            core::str::from_utf8(&self.history[index][..self.history_len[index]])
+                .unwrap_or_default()
+        })
+    }
+
+    /// # Feed
+    /// Feed a byte typed by the user, echoing any changes to `out`.
+    pub fn feed<W: Write>(&mut self, byte: u8, out: &mut W) -> Result<Feed<'_>, core::fmt::Error> {
+        if self.finished {
+            self.finished = false;
+            self.len = 0;
+            self.cursor = 0;
+        }
+
+        let last_was_cr = core::mem::replace(&mut self.last_was_cr, byte == b'\r');
+
+        match self.escape {
+            EscapeState::Escape => {
+                self.escape = if byte == b'[' {
+                    EscapeState::Csi
+                } else {
+                    EscapeState::None
+                };
+                return Ok(Feed::Pending);
+            }
+            EscapeState::Csi => {
+                // Parameters (like the `3` in `ESC [ 3 ~`) come before the final byte
+                if !(0x40..=0x7E).contains(&byte) {
+                    return Ok(Feed::Pending);
+                }
+
+                self.escape = EscapeState::None;
+                self.csi(byte, out)?;
+                return Ok(Feed::Pending);
+            }
+            EscapeState::None => (),
+        }
+
+        match byte {
+            b'\n' if last_was_cr => (),
+            b'\r' | b'\n' => {
+                out.write_str("\r\n")?;
+                self.push_history();
+                self.finished = true;
+
+                return Ok(Feed::Line(self.line()));
+            }
+            ESCAPE => self.escape = EscapeState::Escape,
+            BACKSPACE | DELETE if self.cursor > 0 => {
+                self.remove(self.cursor - 1..self.cursor, out)?;
+            }
+            CTRL_U => self.remove(0..self.cursor, out)?,
+            CTRL_W => {
+                let before = &self.line[..self.cursor];
+                let word_end = before.iter().rposition(|&c| c != b' ').map_or(0, |i| i + 1);
+                let word_start = before[..word_end]
+                    .iter()
+                    .rposition(|&c| c == b' ')
+                    .map_or(0, |i| i + 1);
+
+                self.remove(word_start..self.cursor, out)?;
+            }
+            CTRL_A => self.move_to(0, out)?,
+            CTRL_E => self.move_to(self.len, out)?,
+            0x20..=0x7E => self.insert(byte, out)?,
+            _ => (),
+        }
+
+        Ok(Feed::Pending)
+    }
+
+    fn csi<W: Write>(&mut self, byte: u8, out: &mut W) -> core::fmt::Result {
+        match byte {
+            b'A' if self.browsing < self.history_count => {
+                self.browsing += 1;
+                self.recall(out)
+            }
+            b'B' if self.browsing > 0 => {
+                self.browsing -= 1;
+                self.recall(out)
+            }
+            b'C' if self.cursor < self.len => self.move_to(self.cursor + 1, out),
+            b'D' if self.cursor > 0 => self.move_to(self.cursor - 1, out),
+            b'H' => self.move_to(0, out),
+            b'F' => self.move_to(self.len, out),
+            _ => Ok(()),
+        }
+    }
+
+    fn insert<W: Write>(&mut self, byte: u8, out: &mut W) -> core::fmt::Result {
+        if self.len >= LINE {
+            return Ok(());
+        }
+
+        self.line
+            .copy_within(self.cursor..self.len, self.cursor + 1);
+        self.line[self.cursor] = byte;
+        self.len += 1;
+        self.cursor += 1;
+
+        // Typing at the end of the line is by far the most common, so only
+        // echo the byte in that case.
+        if self.cursor == self.len {
+            return out.write_char(byte as char);
+        }
+
+        self.redraw(self.cursor - 1, out)
+    }
+
+    fn remove<W: Write>(
+        &mut self,
+        range: core::ops::Range<usize>,
+        out: &mut W,
+    ) -> core::fmt::Result {
+        if range.is_empty() {
+            return Ok(());
+        }
+
+        let old_cursor = self.cursor;
+        self.line.copy_within(range.end..self.len, range.start);
+        self.len -= range.len();
+        self.cursor = range.start;
+
+        self.redraw(old_cursor, out)
+    }
+
+    fn move_to<W: Write>(&mut self, cursor: usize, out: &mut W) -> core::fmt::Result {
+        let old_cursor = core::mem::replace(&mut self.cursor, cursor);
+
+        if cursor < old_cursor {
+            write!(out, "\x1b[{}D", old_cursor - cursor)
+        } else if cursor > old_cursor {
+            write!(out, "\x1b[{}C", cursor - old_cursor)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Rewrite the line from the start, with the terminal's cursor currently
+    /// at `old_cursor`.
+    fn redraw<W: Write>(&mut self, old_cursor: usize, out: &mut W) -> core::fmt::Result {
+        if old_cursor > 0 {
+            write!(out, "\x1b[{}D", old_cursor)?;
+        }
+
+        out.write_str(self.line())?;
+        out.write_str("\x1b[K")?;
+
+        if self.len > self.cursor {
+            write!(out, "\x1b[{}D", self.len - self.cursor)?;
+        }
+
+        Ok(())
+    }
+
+    /// Replace the line with the history entry we are browsing, or an empty
+    /// line once we are back at the bottom.
+    fn recall<W: Write>(&mut self, out: &mut W) -> core::fmt::Result {
+        let old_cursor = self.cursor;
+
+        if self.browsing == 0 {
+            self.len = 0;
+        } else {
+            let index = self.history_index(self.browsing);
+            self.len = self.history_len[index];
+            self.line[..self.len].copy_from_slice(&self.history[index][..self.len]);
+        }
+
+        self.cursor = self.len;
+        self.redraw(old_cursor, out)
+    }
+
+    fn push_history(&mut self) {
+        self.browsing = 0;
+
+        if HISTORY == 0 || self.len == 0 || self.history().next() == Some(self.line()) {
+            return;
+        }
+
+        self.history[self.history_head][..self.len].copy_from_slice(&self.line[..self.len]);
+        self.history_len[self.history_head] = self.len;
+        self.history_head = (self.history_head + 1) % HISTORY;
+        self.history_count = (self.history_count + 1).min(HISTORY);
+    }
+
+    /// The index into the ring of the entry `back` lines ago (1 is the newest).
+    const fn history_index(&self, back: usize) -> usize {
+        (self.history_head + HISTORY - back) % HISTORY
+    }
+}
+
+impl<const LINE: usize, const HISTORY: usize> Default for LineEditor<LINE, HISTORY> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate std;
+    use super::*;
+    use std::string::String;
+    use std::vec::Vec;
+
+    fn type_str<const L: usize, const H: usize>(
+        editor: &mut LineEditor<L, H>,
+        input: &[u8],
+    ) -> (Option<String>, String) {
+        let mut out = String::new();
+        let mut line = None;
+
+        for &byte in input {
+            if let Feed::Line(finished) = editor.feed(byte, &mut out).unwrap() {
+                line = Some(finished.into());
+            }
+        }
+
+        (line, out)
+    }
+
+    #[test]
+    fn test_typing_echos() {
+        let mut editor = LineEditor::<32, 4>::new();
+        let (line, out) = type_str(&mut editor, b"ls -l\r\n");
+
+        assert_eq!(line.as_deref(), Some("ls -l"));
+        assert_eq!(out, "ls -l\r\n");
+    }
+
+    #[test]
+    fn test_cursor_editing() {
+        let mut editor = LineEditor::<32, 4>::new();
+
+        // Left twice, then insert in the middle
+        let (line, _) = type_str(&mut editor, b"hllo\x1b[D\x1b[D\x1b[De\r");
+        assert_eq!(line.as_deref(), Some("hello"));
+
+        // Backspace, home, end and delete
+        let (line, _) = type_str(&mut editor, b"abcd\x7f\x01X\x05Y\r");
+        assert_eq!(line.as_deref(), Some("XabcY"));
+    }
+
+    #[test]
+    fn test_kill_line_and_word() {
+        let mut editor = LineEditor::<32, 4>::new();
+
+        let (line, _) = type_str(&mut editor, b"cat some file  \x17\x17other\r");
+        assert_eq!(line.as_deref(), Some("cat other"));
+
+        let (line, _) = type_str(&mut editor, b"rm -rf /\x15echo\r");
+        assert_eq!(line.as_deref(), Some("echo"));
+    }
+
+    #[test]
+    fn test_line_is_limited() {
+        let mut editor = LineEditor::<4, 0>::new();
+
+        let (line, _) = type_str(&mut editor, b"abcdef\r");
+        assert_eq!(line.as_deref(), Some("abcd"));
+    }
+
+    #[test]
+    fn test_history_ring() {
+        let mut editor = LineEditor::<16, 2>::new();
+
+        type_str(&mut editor, b"one\r");
+        type_str(&mut editor, b"two\r");
+        type_str(&mut editor, b"two\r");
+        type_str(&mut editor, b"three\r");
+
+        assert_eq!(editor.history().collect::<Vec<_>>(), ["three", "two"]);
+
+        let (line, _) = type_str(&mut editor, b"\x1b[A\x1b[A\x1b[A\r");
+        assert_eq!(line.as_deref(), Some("two"));
+
+        // Going back down past the newest entry gives an empty line
+        let (line, _) = type_str(&mut editor, b"\x1b[A\x1b[Bnew\r");
+        assert_eq!(line.as_deref(), Some("new"));
+    }
+}
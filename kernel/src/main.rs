@@ -29,8 +29,8 @@ OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWA
 mod panic;
 
 use bootloader::Stage32toStage64;
-use lldebug::{debug_ready, logln, make_debug};
-use serial::{Serial, baud::SerialBaud};
+use lldebug::{debug_ready, logln, make_debug, telemetry};
+use serial::{Serial, baud::SerialBaud, debugcon};
 
 make_debug! {
     "Serial": Option<Serial> = Serial::probe_first(SerialBaud::Baud115200);
@@ -46,4 +46,6 @@ extern "C" fn _start(stage_to_stage: u64) {
 #[debug_ready]
 fn main(stage_to_stage: &Stage32toStage64) {
     logln!("Kernel!");
+    debugcon::install_telemetry();
+    telemetry!(boot_phase: "kernel");
 }
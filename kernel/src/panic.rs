@@ -24,10 +24,11 @@ OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWA
 */
 
 use core::panic::PanicInfo;
-use lldebug::errorln;
+use lldebug::{errorln, telemetry};
 
 #[panic_handler]
 fn panic(info: &PanicInfo) -> ! {
     errorln!("PANIC! -- {}", info);
+    telemetry!(panic: "{}", info);
     loop {}
 }
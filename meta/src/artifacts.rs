@@ -1,3 +1,4 @@
+use crate::relocs::build_relocation_table;
 use anyhow::{Context, Error, Result};
 use async_process::{Command, Stdio};
 use futures::future;
@@ -12,7 +13,9 @@ pub struct Artifacts {
     pub bootsector: PathBuf,
     pub stage_16: PathBuf,
     pub stage_32: PathBuf,
+    pub stage_32_relocs: PathBuf,
     pub stage_64: PathBuf,
+    pub stage_64_relocs: PathBuf,
 
     pub kernel: PathBuf,
     pub boot_cfg: PathBuf,
@@ -129,7 +132,9 @@ async fn build_bootloader_config() -> Result<PathBuf> {
 
     file.write_all(
         br#"bootloader32=/bootloader/stage_32.bin
+bootloader32-relocs=/bootloader/stage_32.rel
 bootloader64=/bootloader/stage_64.bin
+bootloader64-relocs=/bootloader/stage_64.rel
 kernel=/kernel.elf
 vbe-mode=1280x720
 "#,
@@ -161,11 +166,19 @@ pub async fn build_project() -> Result<Artifacts> {
     )
     .await?;
 
+    let (stage_32_relocs, stage_64_relocs) = future::try_join(
+        build_relocation_table(&stage_32bit),
+        build_relocation_table(&stage_64bit),
+    )
+    .await?;
+
     Ok(Artifacts {
         bootsector,
         stage_16,
         stage_32,
+        stage_32_relocs,
         stage_64,
+        stage_64_relocs,
         kernel,
         boot_cfg,
     })
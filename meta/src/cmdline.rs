@@ -1,4 +1,5 @@
 use clap::{Parser, Subcommand};
+use std::path::PathBuf;
 
 #[derive(Parser, Debug, Clone)]
 #[command(author, version, about, long_about = None)]
@@ -19,6 +20,10 @@ pub struct CommandLine {
     /// Print std out to command-line
     #[arg(long = "nographic", default_value_t = false)]
     pub no_graphic: bool,
+
+    /// Serve the serial port on a socket for `console`, instead of std out
+    #[arg(long, default_value_t = false)]
+    pub serial_socket: bool,
 }
 
 #[derive(Subcommand, Debug, Clone)]
@@ -29,4 +34,20 @@ pub enum TaskOption {
     Run,
     /// Clean up all build artifacts
     Clean,
+    /// Pack a directory into a Qfs initfs image
+    Mkinitfs {
+        /// Directory to pack
+        dir: PathBuf,
+        /// Where to write the image
+        output: PathBuf,
+    },
+    /// Attach to the serial socket of a running Quantum OS (see `--serial-socket`)
+    Console {
+        /// The socket QEMU is serving the serial port on
+        #[arg(long)]
+        socket: Option<PathBuf>,
+        /// Save a plain-text copy of the output
+        #[arg(long)]
+        transcript: Option<PathBuf>,
+    },
 }
@@ -0,0 +1,389 @@
+use anyhow::{anyhow, Context, Result};
+use lldebug::LogContext;
+use std::{
+    fmt::Write as _,
+    io::Write as _,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+use tokio::{io::AsyncReadExt, net::UnixStream};
+
+/// How long to keep trying to connect, QEMU might not have made the socket yet.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// lldebug only ends a line once the next one starts, so a partial line is
+/// printed after the serial port has been quiet for this long (like the last
+/// line before a panic).
+const IDLE_FLUSH: Duration = Duration::from_millis(200);
+
+const RESET: &str = "\x1b[0m";
+const DIM_STYLE: &str = "\x1b[2m";
+/// Move back to the start of the line and erase it.
+const CLEAR_LINE: &str = "\r\x1b[2K";
+
+/// # Serial Socket Path
+/// Where QEMU serves the serial port when `--serial-socket` is used.
+pub fn serial_socket_path() -> PathBuf {
+    PathBuf::from("./target/serial.sock")
+}
+
+/// # Line Level
+/// The lldebug log level of one line of serial output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineLevel {
+    Log,
+    Warn,
+    Error,
+    /// Output that didn't come from lldebug (like the bootsector)
+    Raw,
+}
+
+impl LineLevel {
+    fn marker(self) -> &'static str {
+        match self {
+            LineLevel::Log => "+",
+            LineLevel::Warn => "-",
+            LineLevel::Error => "X",
+            LineLevel::Raw => " ",
+        }
+    }
+
+    fn style(self) -> &'static str {
+        match self {
+            LineLevel::Log => "\x1b[1;92m",
+            LineLevel::Warn => "\x1b[1;93m",
+            LineLevel::Error => "\x1b[1;91m",
+            LineLevel::Raw => DIM_STYLE,
+        }
+    }
+}
+
+/// # Console Line
+/// One line of serial output, split into the parts lldebug printed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConsoleLine {
+    pub level: LineLevel,
+    /// The guest's own timestamp, if lldebug was given a timestamp function.
+    pub timestamp: Option<Duration>,
+    /// Where the line was logged from, if lldebug was given a context function.
+    pub context: Option<LogContext>,
+    pub source: Option<String>,
+    pub message: String,
+}
+
+/// Split lldebug's `[secs.micros]` timestamp off the front of `header`.
+fn parse_timestamp(header: &str) -> Option<(Duration, &str)> {
+    let (timestamp, rest) = header.strip_prefix('[')?.split_once(']')?;
+    let (secs, micros) = timestamp.trim().split_once('.')?;
+
+    if micros.len() != 6 {
+        return None;
+    }
+
+    let timestamp = Duration::new(secs.parse().ok()?, micros.parse::<u32>().ok()? * 1000);
+    Some((timestamp, rest.trim_start()))
+}
+
+/// Split lldebug's `cpuN tM` (or `cpuN -` without a task) context off the
+/// front of `header`.
+fn parse_context(header: &str) -> Option<(LogContext, &str)> {
+    let (cpu, rest) = header.strip_prefix("cpu")?.split_once(' ')?;
+    let (task, rest) = rest.trim_start().split_once(' ')?;
+
+    let task = match task {
+        "-" => None,
+        task => Some(task.strip_prefix('t')?.parse().ok()?),
+    };
+
+    let context = LogContext {
+        cpu: cpu.parse().ok()?,
+        task,
+    };
+    Some((context, rest.trim_start()))
+}
+
+impl ConsoleLine {
+    /// # Parse
+    /// Parse a line in lldebug's `<marker>[timestamp] [context] <crate name> : <message>`
+    /// format (the timestamp and context are optional), anything else is kept
+    /// as a raw line.
+    pub fn parse(line: &str) -> Self {
+        let line = strip_ansi(line);
+        let line = line.trim_end_matches('\r');
+
+        let level = match line.chars().next() {
+            Some('+') => LineLevel::Log,
+            Some('-') => LineLevel::Warn,
+            Some('X') => LineLevel::Error,
+            _ => LineLevel::Raw,
+        };
+
+        match line.get(1..).unwrap_or("").split_once(" : ") {
+            Some((header, message)) if level != LineLevel::Raw => {
+                let (timestamp, header) = match parse_timestamp(header) {
+                    Some((timestamp, header)) => (Some(timestamp), header),
+                    None => (None, header),
+                };
+                let (context, header) = match parse_context(header) {
+                    Some((context, header)) => (Some(context), header),
+                    None => (None, header),
+                };
+
+                Self {
+                    level,
+                    timestamp,
+                    context,
+                    source: Some(header.trim().to_string()),
+                    message: message.to_string(),
+                }
+            }
+            _ => Self {
+                level: LineLevel::Raw,
+                timestamp: None,
+                context: None,
+                source: None,
+                message: line.to_string(),
+            },
+        }
+    }
+
+    /// # Render
+    /// Format this line with a timestamp, optionally colored for a terminal.
+    pub fn render(&self, elapsed: Duration, color: bool) -> String {
+        let (style, dim, reset) = match color {
+            true => (self.level.style(), DIM_STYLE, RESET),
+            false => ("", "", ""),
+        };
+
+        let mut rendered = format!(
+            "{dim}[{:>9.3}]{reset} {style}{}{reset} ",
+            elapsed.as_secs_f64(),
+            self.level.marker()
+        );
+
+        if let Some(timestamp) = self.timestamp {
+            let _ = write!(
+                rendered,
+                "{dim}[{:>5}.{:06}]{reset} ",
+                timestamp.as_secs(),
+                timestamp.subsec_micros()
+            );
+        }
+
+        match self.context {
+            Some(LogContext {
+                cpu,
+                task: Some(task),
+            }) => {
+                let _ = write!(rendered, "{dim}cpu{cpu} t{task:<4}{reset} ");
+            }
+            Some(LogContext { cpu, task: None }) => {
+                let _ = write!(rendered, "{dim}cpu{cpu} {:<5}{reset} ", "-");
+            }
+            None => (),
+        }
+
+        if let Some(source) = &self.source {
+            let _ = write!(rendered, "{dim}{source}{reset} : ");
+        }
+
+        rendered.push_str(&self.message);
+        rendered
+    }
+}
+
+/// # Strip Ansi
+/// Remove all the CSI escape sequences (colors, cursor movement) from `line`.
+pub fn strip_ansi(line: &str) -> String {
+    let mut stripped = String::with_capacity(line.len());
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            stripped.push(c);
+            continue;
+        }
+
+        if chars.next_if_eq(&'[').is_some() {
+            // Parameters until the final byte (`@` to `~`)
+            for c in chars.by_ref() {
+                if ('@'..='~').contains(&c) {
+                    break;
+                }
+            }
+        }
+    }
+
+    stripped
+}
+
+/// # Console
+/// Prints the serial output of a running QEMU as timestamped lines, and saves
+/// a plain-text transcript of it.
+struct Console {
+    start: Instant,
+    transcript: Option<std::fs::File>,
+    pending: Vec<u8>,
+    /// When an idle flush printed the partial line in `pending` (it's left on
+    /// the terminal without a newline), and how many bytes of it were printed.
+    shown: Option<(Duration, usize)>,
+    warnings: usize,
+    errors: usize,
+}
+
+impl Console {
+    fn new(transcript: Option<&Path>) -> Result<Self> {
+        let transcript = transcript
+            .map(|path| {
+                std::fs::File::create(path)
+                    .with_context(|| format!("Could not create transcript {:?}", path))
+            })
+            .transpose()?;
+
+        Ok(Self {
+            start: Instant::now(),
+            transcript,
+            pending: Vec::new(),
+            shown: None,
+            warnings: 0,
+            errors: 0,
+        })
+    }
+
+    /// Print a finished line, replacing its partial line if an idle flush
+    /// printed one.
+    fn emit(&mut self, raw: &[u8]) -> Result<()> {
+        let shown = self.shown.take();
+        let line = ConsoleLine::parse(&String::from_utf8_lossy(raw));
+
+        // lldebug starts every line with a newline, so skip the empty ones
+        if line.level == LineLevel::Raw && line.message.trim().is_empty() {
+            return Ok(());
+        }
+
+        match line.level {
+            LineLevel::Warn => self.warnings += 1,
+            LineLevel::Error => self.errors += 1,
+            _ => (),
+        }
+
+        let elapsed = match shown {
+            Some((elapsed, _)) => {
+                print!("{CLEAR_LINE}");
+                elapsed
+            }
+            None => self.start.elapsed(),
+        };
+        println!("{}", line.render(elapsed, true));
+
+        if let Some(transcript) = &mut self.transcript {
+            writeln!(transcript, "{}", line.render(elapsed, false))
+                .context("Could not write to transcript")?;
+        }
+
+        Ok(())
+    }
+
+    fn push(&mut self, bytes: &[u8]) -> Result<()> {
+        for &byte in bytes {
+            if byte == b'\n' {
+                let line = std::mem::take(&mut self.pending);
+                self.emit(&line)?;
+            } else {
+                self.pending.push(byte);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Print the partial line without ending it, so the rest of it can
+    /// replace it once it arrives. It only goes in the transcript once it's
+    /// finished.
+    fn flush_pending(&mut self) -> Result<()> {
+        if self
+            .shown
+            .is_some_and(|(_, shown_len)| shown_len == self.pending.len())
+        {
+            return Ok(());
+        }
+
+        let line = ConsoleLine::parse(&String::from_utf8_lossy(&self.pending));
+        if line.level == LineLevel::Raw && line.message.trim().is_empty() {
+            return Ok(());
+        }
+
+        let elapsed = match self.shown {
+            Some((elapsed, _)) => {
+                print!("{CLEAR_LINE}");
+                elapsed
+            }
+            None => self.start.elapsed(),
+        };
+        print!("{}", line.render(elapsed, true));
+        std::io::stdout()
+            .flush()
+            .context("Could not write to stdout")?;
+
+        self.shown = Some((elapsed, self.pending.len()));
+        Ok(())
+    }
+
+    /// Finish the partial line (QEMU exited, so nothing else is coming).
+    fn finish(&mut self) -> Result<()> {
+        let line = std::mem::take(&mut self.pending);
+        self.emit(&line)
+    }
+}
+
+async fn connect(socket: &Path) -> Result<UnixStream> {
+    let deadline = Instant::now() + CONNECT_TIMEOUT;
+
+    loop {
+        match UnixStream::connect(socket).await {
+            Ok(stream) => return Ok(stream),
+            Err(_) if Instant::now() < deadline => {
+                tokio::time::sleep(Duration::from_millis(100)).await
+            }
+            Err(err) => {
+                return Err(err).with_context(|| {
+                    format!(
+                        "Could not connect to {:?}, is QEMU running with --serial-socket?",
+                        socket
+                    )
+                })
+            }
+        }
+    }
+}
+
+/// # Attach
+/// Connect to QEMU's serial socket and print everything it sends until QEMU
+/// exits (or Ctrl-C).
+pub async fn attach(socket: &Path, transcript: Option<&Path>) -> Result<()> {
+    let mut stream = connect(socket).await?;
+    let mut console = Console::new(transcript)?;
+    let mut buf = [0u8; 4096];
+
+    loop {
+        let read = tokio::select! {
+            read = tokio::time::timeout(IDLE_FLUSH, stream.read(&mut buf)) => read,
+            _ = tokio::signal::ctrl_c() => break,
+        };
+
+        match read {
+            Ok(Ok(0)) => break,
+            Ok(Ok(len)) => console.push(&buf[..len])?,
+            Ok(Err(err)) => return Err(anyhow!("Could not read serial socket: {}", err)),
+            Err(_) => console.flush_pending()?,
+        }
+    }
+
+    console.finish()?;
+    println!(
+        "Console: {} warnings, {} errors",
+        console.warnings, console.errors
+    );
+
+    Ok(())
+}
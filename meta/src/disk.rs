@@ -124,6 +124,7 @@ impl DiskImgBaker {
 
         let fat = fatfs::FileSystem::new(&mut fat_slice, FsOptions::new())?;
         let root_dir = fat.root_dir();
+        let mut written_files = Vec::new();
 
         for dir in WalkDir::new(dir_path).into_iter() {
             let dir = dir.context("Failed to walk dir for filesystem building")?;
@@ -160,9 +161,14 @@ impl DiskImgBaker {
             fat_file
                 .write_all(&mut file_data)
                 .context("Failed to write real file data into fat file")?;
+
+            written_files.push((fat_path.to_string(), file_data.len()));
         }
 
-        Ok(())
+        drop(root_dir);
+        fat.unmount().context("Failed to unmount fat filesystem")?;
+
+        verify_fat(&mut fat_slice, &written_files)
     }
 
     pub async fn finish_and_write(mut self) -> Result<PathBuf> {
@@ -174,6 +180,38 @@ impl DiskImgBaker {
     }
 }
 
+/// # Verify Fat
+/// Re-open the filesystem with the same FAT driver the bootloader uses, and make sure
+/// it can find every file we wrote (with the correct size).
+fn verify_fat(
+    partition: impl std::io::Read + std::io::Seek,
+    written_files: &[(String, usize)],
+) -> Result<()> {
+    let mut fat = fs::fatfs::Fat::new(fs::host::StdDevice::new(partition)).map_err(|err| {
+        anyhow!(
+            "Bootloader FAT driver could not mount filesystem: {:?}",
+            err
+        )
+    })?;
+
+    for (path, size) in written_files {
+        let file = fat
+            .open(path)
+            .map_err(|err| anyhow!("Bootloader FAT driver could not open {:?}: {:?}", path, err))?;
+
+        if file.filesize() != *size {
+            return Err(anyhow!(
+                "Bootloader FAT driver reports {:?} as {} bytes, but {} bytes were written",
+                path,
+                file.filesize(),
+                size
+            ));
+        }
+    }
+
+    Ok(())
+}
+
 async fn create_diskimg(name: &str, size: usize) -> Result<File> {
     let target_dir = tmp_find_target().join("img");
     tokio::fs::create_dir_all(&target_dir)
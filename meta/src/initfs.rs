@@ -0,0 +1,48 @@
+use anyhow::{anyhow, Context, Result};
+use fs::qfs::{builder::QfsBuilder, Qfs};
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use walkdir::WalkDir;
+
+/// Block size of the initfs image (4 KiB)
+const INITFS_BLOCK_SIZE_LOG2: u16 = 12;
+
+/// # Build Initfs
+/// Pack everything under `dir` into a Qfs image, then re-open it with the same
+/// reader the kernel uses to make sure it's sound.
+pub fn build_initfs(dir: &Path) -> Result<Vec<u8>> {
+    let mut builder = QfsBuilder::new(INITFS_BLOCK_SIZE_LOG2)
+        .map_err(|err| anyhow!("Could not create initfs builder: {:?}", err))?;
+
+    for entry in WalkDir::new(dir).min_depth(1).sort_by_file_name() {
+        let entry = entry.context("Failed to walk initfs dir")?;
+        let path = entry
+            .path()
+            .strip_prefix(dir)?
+            .to_str()
+            .ok_or(anyhow!("Initfs path {:?} is not UTF-8", entry.path()))?;
+        let mode = entry.metadata()?.permissions().mode() & 0o7777;
+
+        if entry.file_type().is_dir() {
+            builder.add_directory(path, mode)
+        } else if entry.file_type().is_file() {
+            builder.add_file(path, mode, std::fs::read(entry.path())?)
+        } else {
+            return Err(anyhow!(
+                "Initfs only supports files and directories, not {:?}",
+                entry.path()
+            ));
+        }
+        .map_err(|err| anyhow!("Could not add {:?} to the initfs: {:?}", path, err))?;
+    }
+
+    let image = builder
+        .build()
+        .map_err(|err| anyhow!("Could not build initfs: {:?}", err))?;
+
+    Qfs::new(&image)
+        .and_then(|qfs| qfs.verify())
+        .map_err(|err| anyhow!("Initfs failed to verify: {:?}", err))?;
+
+    Ok(image)
+}
@@ -14,7 +14,11 @@ use crate::{
 
 mod artifacts;
 mod cmdline;
+mod console;
 mod disk;
+mod initfs;
+mod relocs;
+mod telemetry;
 
 async fn build() -> Result<PathBuf> {
     let (artifacts, disk) = tokio::join!(build_project(), DiskImgBaker::new());
@@ -44,10 +48,18 @@ async fn build() -> Result<PathBuf> {
                 &artifacts.stage_32.as_path(),
                 Path::new("bootloader/stage_32.bin"),
             ),
+            (
+                &artifacts.stage_32_relocs.as_path(),
+                Path::new("bootloader/stage_32.rel"),
+            ),
             (
                 &artifacts.stage_64.as_path(),
                 Path::new("bootloader/stage_64.bin"),
             ),
+            (
+                &artifacts.stage_64_relocs.as_path(),
+                Path::new("bootloader/stage_64.rel"),
+            ),
             (&artifacts.kernel.as_path(), Path::new("kernel.elf")),
         ]
         .into_iter(),
@@ -63,22 +75,37 @@ fn run_qemu(
     enable_kvm: bool,
     enable_no_graphic: bool,
     log_interrupts: bool,
+    serial_socket: bool,
 ) -> Result<()> {
     let kvm: &[&str] = if enable_kvm { &["--enable-kvm"] } else { &[] };
-    let no_graphic: &[&str] = if enable_no_graphic {
-        &["-nographic", "-serial", "mon:stdio"]
-    } else {
-        &["-serial", "stdio"]
+    let no_graphic: &[&str] = match (enable_no_graphic, serial_socket) {
+        (true, true) => &["-nographic"],
+        (true, false) => &["-nographic", "-serial", "mon:stdio"],
+        (false, _) => &["-serial", "stdio"],
     };
+    let serial_socket = serial_socket.then(|| {
+        let socket = console::serial_socket_path();
+        let _ = std::fs::remove_file(&socket);
+
+        println!("Serial on {:?}, attach with `console`", socket);
+        [
+            "-serial".to_string(),
+            format!("unix:{},server=on,wait=off", socket.to_str().unwrap()),
+        ]
+    });
     let log_interrupts: &[&str] = if log_interrupts {
         &["-d", "int"]
     } else {
         &["-d", "cpu_reset"]
     };
 
-    Command::new("qemu-system-x86_64")
+    let telemetry_log = telemetry::telemetry_log_path();
+    let _ = std::fs::remove_file(&telemetry_log);
+
+    let qemu_status = Command::new("qemu-system-x86_64")
         .args(kvm)
         .args(no_graphic)
+        .args(serial_socket.iter().flatten())
         .arg("--name")
         .arg("Quantum OS")
         .arg("-device")
@@ -91,6 +118,8 @@ fn run_qemu(
         .arg("en-us")
         .arg("-nic")
         .arg("none")
+        .arg("-debugcon")
+        .arg(format!("file:{}", telemetry_log.to_str().unwrap()))
         .arg("-drive")
         .arg(format!(
             "format=raw,file={}",
@@ -98,7 +127,19 @@ fn run_qemu(
         ))
         .stdout(std::process::Stdio::inherit())
         .status()
-        .context(anyhow!("Could not start qemu-system-x86_64!"))?
+        .context(anyhow!("Could not start qemu-system-x86_64!"))?;
+
+    if telemetry_log.exists() {
+        let report =
+            telemetry::collect_report(&telemetry_log, &telemetry::telemetry_report_path())?;
+        println!("Telemetry: {}", report);
+
+        if !report.success() {
+            return Err(anyhow!("Guest run failed ({})", report));
+        }
+    }
+
+    qemu_status
         .success()
         .then_some(())
         .ok_or(anyhow!("QEMU Failed"))?;
@@ -120,11 +161,21 @@ async fn main() -> Result<()> {
                 args.enable_kvm,
                 args.no_graphic,
                 args.log_interrupts,
+                args.serial_socket,
             )?;
         }
         cmdline::TaskOption::Clean => {
             todo!("clean")
         }
+        cmdline::TaskOption::Mkinitfs { dir, output } => {
+            let image = initfs::build_initfs(&dir)?;
+            std::fs::write(&output, image)
+                .with_context(|| format!("Failed to write initfs to {:?}", output))?;
+        }
+        cmdline::TaskOption::Console { socket, transcript } => {
+            let socket = socket.unwrap_or_else(console::serial_socket_path);
+            console::attach(&socket, transcript.as_deref()).await?;
+        }
     }
 
     Ok(())
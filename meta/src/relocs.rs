@@ -0,0 +1,548 @@
+use anyhow::{anyhow, Context, Result};
+use std::path::{Path, PathBuf};
+
+/// # Relocation Table Format
+/// Stage32 and stage64 are linked at a fixed address with `--emit-relocs`, so their
+/// ELFs still carry every relocation the linker applied. Stage16 loads each stage
+/// wherever the memory map has room, and uses this table to fix up the stage's
+/// absolute addresses. The GOT isn't covered by any relocation in a static link, so
+/// every filled GOT entry gets a fixup too (the stages are linked with `--no-relax`
+/// so the linker never turns a GOT load into an absolute immediate). Everything is
+/// little endian:
+///
+/// ```text
+/// [u8; 4]  magic ("QREL")
+/// u64      link base (the address the first byte of the image was linked at)
+/// u32      image size in memory (including .bss)
+/// u32      number of 32-bit fixups
+/// u32      number of 64-bit fixups
+/// [u32]    image offsets of the 32-bit fixups, then of the 64-bit fixups
+/// ```
+const RELOCS_MAGIC: &[u8; 4] = b"QREL";
+
+const SHT_RELA: u32 = 4;
+const SHT_NOBITS: u32 = 8;
+const SHT_REL: u32 = 9;
+const SHF_ALLOC: u64 = 2;
+const SHN_UNDEF: u16 = 0;
+const SHN_ABS: u16 = 0xfff1;
+
+const EM_386: u16 = 3;
+const EM_X86_64: u16 = 62;
+
+#[derive(Debug, Clone, Copy)]
+struct Section {
+    name: u32,
+    kind: u32,
+    flags: u64,
+    addr: u64,
+    offset: u64,
+    size: u64,
+    link: u32,
+    info: u32,
+    entsize: u64,
+}
+
+/// What needs to happen to the word a relocation was applied to when the image moves.
+enum Fixup {
+    None,
+    Word32,
+    Word64,
+}
+
+struct ElfFile<'a> {
+    bytes: &'a [u8],
+    is_64bit: bool,
+    machine: u16,
+}
+
+impl<'a> ElfFile<'a> {
+    fn new(bytes: &'a [u8]) -> Result<Self> {
+        if bytes.get(..4) != Some(b"\x7fELF".as_slice()) {
+            return Err(anyhow!("Not an ELF file"));
+        }
+
+        let is_64bit = match bytes.get(4) {
+            Some(1) => false,
+            Some(2) => true,
+            class => return Err(anyhow!("Unknown ELF class {:?}", class)),
+        };
+
+        if bytes.get(5) != Some(&1) {
+            return Err(anyhow!("Only little endian ELFs can be relocated"));
+        }
+
+        let mut elf = Self {
+            bytes,
+            is_64bit,
+            machine: 0,
+        };
+        elf.machine = elf.u16(18)?;
+
+        Ok(elf)
+    }
+
+    fn read<const N: usize>(&self, offset: u64) -> Result<[u8; N]> {
+        usize::try_from(offset)
+            .ok()
+            .and_then(|offset| self.bytes.get(offset..offset.checked_add(N)?))
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or(anyhow!("ELF is truncated at offset 0x{:x}", offset))
+    }
+
+    fn u16(&self, offset: u64) -> Result<u16> {
+        self.read(offset).map(u16::from_le_bytes)
+    }
+
+    fn u32(&self, offset: u64) -> Result<u32> {
+        self.read(offset).map(u32::from_le_bytes)
+    }
+
+    fn u64(&self, offset: u64) -> Result<u64> {
+        self.read(offset).map(u64::from_le_bytes)
+    }
+
+    /// Read a field that is 32 bits in ELF32 and 64 bits in ELF64.
+    fn word(&self, offset: u64) -> Result<u64> {
+        match self.is_64bit {
+            true => self.u64(offset),
+            false => self.u32(offset).map(u64::from),
+        }
+    }
+
+    fn sections(&self) -> Result<Vec<Section>> {
+        let (shoff, shentsize, shnum) = match self.is_64bit {
+            true => (self.u64(40)?, self.u16(58)?, self.u16(60)?),
+            false => (self.u32(32)? as u64, self.u16(46)?, self.u16(48)?),
+        };
+
+        (0..shnum as u64)
+            .map(|index| {
+                let header = shoff + index * shentsize as u64;
+
+                Ok(match self.is_64bit {
+                    true => Section {
+                        name: self.u32(header)?,
+                        kind: self.u32(header + 4)?,
+                        flags: self.u64(header + 8)?,
+                        addr: self.u64(header + 16)?,
+                        offset: self.u64(header + 24)?,
+                        size: self.u64(header + 32)?,
+                        link: self.u32(header + 40)?,
+                        info: self.u32(header + 44)?,
+                        entsize: self.u64(header + 56)?,
+                    },
+                    false => Section {
+                        name: self.u32(header)?,
+                        kind: self.u32(header + 4)?,
+                        flags: self.u32(header + 8)? as u64,
+                        addr: self.u32(header + 12)? as u64,
+                        offset: self.u32(header + 16)? as u64,
+                        size: self.u32(header + 20)? as u64,
+                        link: self.u32(header + 24)?,
+                        info: self.u32(header + 28)?,
+                        entsize: self.u32(header + 36)? as u64,
+                    },
+                })
+            })
+            .collect()
+    }
+
+    fn section_name(&self, sections: &[Section], section: &Section) -> Result<&'a [u8]> {
+        let shstrndx = match self.is_64bit {
+            true => self.u16(62)?,
+            false => self.u16(50)?,
+        };
+        let shstrtab = sections
+            .get(shstrndx as usize)
+            .ok_or(anyhow!("ELF has no section name table"))?;
+
+        let start = (shstrtab.offset + section.name as u64) as usize;
+        let name = self
+            .bytes
+            .get(start..)
+            .ok_or(anyhow!("Section name is outside of the ELF"))?;
+
+        Ok(&name[..name
+            .iter()
+            .position(|&byte| byte == 0)
+            .unwrap_or(name.len())])
+    }
+
+    /// The section index of symbol `index` in `symtab`.
+    fn symbol_section(&self, symtab: &Section, index: u64) -> Result<u16> {
+        let symbol = symtab.offset + index * symtab.entsize;
+
+        match self.is_64bit {
+            true => self.u16(symbol + 6),
+            false => self.u16(symbol + 14),
+        }
+    }
+
+    /// The `(offset, symbol, type)` of relocation `index` in `section`.
+    fn relocation(&self, section: &Section, index: u64) -> Result<(u64, u64, u32)> {
+        let entry = section.offset + index * section.entsize;
+        let offset = self.word(entry)?;
+        let info = self.word(entry + if self.is_64bit { 8 } else { 4 })?;
+
+        Ok(match self.is_64bit {
+            true => (offset, info >> 32, info as u32),
+            false => (offset, info >> 8, (info & 0xff) as u32),
+        })
+    }
+
+    fn fixup_for(&self, kind: u32) -> Result<Fixup> {
+        Ok(match (self.machine, kind) {
+            // R_386_NONE, R_X86_64_NONE
+            (_, 0) => Fixup::None,
+            // R_386_32
+            (EM_386, 1) => Fixup::Word32,
+            // R_386_PC32, R_386_GOT32, R_386_PLT32, R_386_GOTOFF, R_386_GOTPC, R_386_GOT32X
+            (EM_386, 2 | 3 | 4 | 9 | 10 | 43) => Fixup::None,
+            // R_X86_64_64
+            (EM_X86_64, 1) => Fixup::Word64,
+            // R_X86_64_32, R_X86_64_32S
+            (EM_X86_64, 10 | 11) => Fixup::Word32,
+            // R_X86_64_PC32, R_X86_64_PLT32, R_X86_64_GOTPCREL, R_X86_64_PC64,
+            // R_X86_64_GOTOFF64, R_X86_64_GOTPC32, R_X86_64_GOTPCRELX, R_X86_64_REX_GOTPCRELX
+            (EM_X86_64, 2 | 4 | 9 | 24 | 25 | 26 | 41 | 42) => Fixup::None,
+            (machine, kind) => {
+                return Err(anyhow!(
+                    "Relocation type {} (machine {}) cannot be applied when relocating",
+                    kind,
+                    machine
+                ))
+            }
+        })
+    }
+}
+
+/// # Relocation Table
+/// Build the relocation table (see [`RELOCS_MAGIC`]) for a stage's ELF.
+pub fn relocation_table(elf_bytes: &[u8]) -> Result<Vec<u8>> {
+    let elf = ElfFile::new(elf_bytes)?;
+    let sections = elf.sections()?;
+
+    let loaded = || {
+        sections
+            .iter()
+            .filter(|section| section.flags & SHF_ALLOC != 0 && section.size != 0)
+    };
+
+    // objcopy's binary output starts at the first section with contents
+    let link_base = loaded()
+        .filter(|section| section.kind != SHT_NOBITS)
+        .map(|section| section.addr)
+        .min()
+        .ok_or(anyhow!("ELF has nothing to load"))?;
+    let image_end = loaded()
+        .map(|section| section.addr + section.size)
+        .max()
+        .unwrap_or(link_base);
+    let image_size = u32::try_from(image_end - link_base).context("Image is too large")?;
+
+    let mut fixups32 = Vec::new();
+    let mut fixups64 = Vec::new();
+
+    for section in sections
+        .iter()
+        .filter(|section| matches!(section.kind, SHT_REL | SHT_RELA) && section.entsize != 0)
+    {
+        // Relocations for debug info and other sections that are never loaded
+        let target = sections
+            .get(section.info as usize)
+            .ok_or(anyhow!("Relocation section targets a missing section"))?;
+        if target.flags & SHF_ALLOC == 0 {
+            continue;
+        }
+
+        let symtab = sections
+            .get(section.link as usize)
+            .ok_or(anyhow!("Relocation section has no symbol table"))?;
+
+        for index in 0..(section.size / section.entsize) {
+            let (offset, symbol, kind) = elf.relocation(section, index)?;
+
+            let fixups = match elf.fixup_for(kind)? {
+                Fixup::None => continue,
+                Fixup::Word32 => &mut fixups32,
+                Fixup::Word64 => &mut fixups64,
+            };
+
+            // Absolute symbols (and undefined weak ones) don't move with the image
+            if symbol != 0 && matches!(elf.symbol_section(symtab, symbol)?, SHN_UNDEF | SHN_ABS) {
+                continue;
+            }
+
+            let image_offset = offset
+                .checked_sub(link_base)
+                .filter(|image_offset| *image_offset < image_size as u64)
+                .ok_or(anyhow!(
+                    "Relocation at 0x{:x} is outside of the image",
+                    offset
+                ))?;
+
+            fixups.push(image_offset as u32);
+        }
+    }
+
+    // The linker filled the GOT with absolute addresses
+    for got in sections
+        .iter()
+        .filter(|section| section.flags & SHF_ALLOC != 0)
+    {
+        if !matches!(elf.section_name(&sections, got)?, b".got" | b".got.plt") {
+            continue;
+        }
+
+        let word_size = if elf.is_64bit { 8 } else { 4 };
+        for entry in (0..got.size).step_by(word_size) {
+            if elf.word(got.offset + entry)? == 0 {
+                continue;
+            }
+
+            let image_offset = (got.addr - link_base + entry) as u32;
+            match elf.is_64bit {
+                true => fixups64.push(image_offset),
+                false => fixups32.push(image_offset),
+            }
+        }
+    }
+
+    fixups32.sort_unstable();
+    fixups32.dedup();
+    fixups64.sort_unstable();
+    fixups64.dedup();
+
+    let mut table = Vec::with_capacity(24 + (fixups32.len() + fixups64.len()) * 4);
+    table.extend_from_slice(RELOCS_MAGIC);
+    table.extend_from_slice(&link_base.to_le_bytes());
+    table.extend_from_slice(&image_size.to_le_bytes());
+    table.extend_from_slice(&(fixups32.len() as u32).to_le_bytes());
+    table.extend_from_slice(&(fixups64.len() as u32).to_le_bytes());
+    fixups32
+        .iter()
+        .chain(fixups64.iter())
+        .for_each(|offset| table.extend_from_slice(&offset.to_le_bytes()));
+
+    Ok(table)
+}
+
+/// # Build Relocation Table
+/// Write the relocation table for the stage ELF at `elf_path` next to it.
+pub async fn build_relocation_table(elf_path: &Path) -> Result<PathBuf> {
+    let elf_bytes = tokio::fs::read(elf_path)
+        .await
+        .with_context(|| format!("Failed to read {:?}", elf_path))?;
+
+    let table = relocation_table(&elf_bytes)
+        .with_context(|| format!("Failed to build relocation table for {:?}", elf_path))?;
+
+    let table_path = elf_path.with_extension("rel");
+    tokio::fs::write(&table_path, table)
+        .await
+        .context("Failed to write relocation table")?;
+
+    Ok(table_path)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SHT_PROGBITS: u32 = 1;
+    const SHT_SYMTAB: u32 = 2;
+    const SHT_STRTAB: u32 = 3;
+
+    const R_X86_64_64: u32 = 1;
+    const R_X86_64_PC32: u32 = 2;
+    const R_X86_64_32: u32 = 10;
+
+    const NULL_SECTION: Section = Section {
+        name: 0,
+        kind: 0,
+        flags: 0,
+        addr: 0,
+        offset: 0,
+        size: 0,
+        link: 0,
+        info: 0,
+        entsize: 0,
+    };
+
+    fn push_section(elf: &mut Vec<u8>, section: Section) {
+        elf.extend_from_slice(&section.name.to_le_bytes());
+        elf.extend_from_slice(&section.kind.to_le_bytes());
+        elf.extend_from_slice(&section.flags.to_le_bytes());
+        elf.extend_from_slice(&section.addr.to_le_bytes());
+        elf.extend_from_slice(&section.offset.to_le_bytes());
+        elf.extend_from_slice(&section.size.to_le_bytes());
+        elf.extend_from_slice(&section.link.to_le_bytes());
+        elf.extend_from_slice(&section.info.to_le_bytes());
+        elf.extend_from_slice(&8u64.to_le_bytes());
+        elf.extend_from_slice(&section.entsize.to_le_bytes());
+    }
+
+    /// An x86_64 ELF linked at 0x1000 with a 16 byte `.text`, a two entry `.got`
+    /// (only the first filled) and 32 bytes of `.bss`. Symbol 1 is in `.text` and
+    /// symbol 2 is absolute. `relocations` are `(address, symbol, type)` against `.text`.
+    fn test_elf(relocations: &[(u64, u64, u32)]) -> Vec<u8> {
+        let text_offset = 64;
+        let got_offset = text_offset + 16;
+        let rela_offset = got_offset + 16;
+        let symtab_offset = rela_offset + relocations.len() as u64 * 24;
+        let shstrtab_offset = symtab_offset + 3 * 24;
+        let shstrtab = b"\0.text\0.got\0.rela.text\0.symtab\0.shstrtab\0.bss\0";
+        let shoff = shstrtab_offset + shstrtab.len() as u64;
+
+        let mut elf = vec![0x7f, b'E', b'L', b'F', 2, 1, 1, 0];
+        elf.extend_from_slice(&[0; 8]);
+        elf.extend_from_slice(&2u16.to_le_bytes());
+        elf.extend_from_slice(&EM_X86_64.to_le_bytes());
+        elf.extend_from_slice(&1u32.to_le_bytes());
+        elf.extend_from_slice(&0x1000u64.to_le_bytes());
+        elf.extend_from_slice(&0u64.to_le_bytes());
+        elf.extend_from_slice(&shoff.to_le_bytes());
+        elf.extend_from_slice(&0u32.to_le_bytes());
+        elf.extend_from_slice(&64u16.to_le_bytes());
+        elf.extend_from_slice(&56u16.to_le_bytes());
+        elf.extend_from_slice(&0u16.to_le_bytes());
+        elf.extend_from_slice(&64u16.to_le_bytes());
+        elf.extend_from_slice(&7u16.to_le_bytes());
+        elf.extend_from_slice(&5u16.to_le_bytes());
+
+        elf.extend_from_slice(&[0x90; 16]);
+        elf.extend_from_slice(&0x1004u64.to_le_bytes());
+        elf.extend_from_slice(&0u64.to_le_bytes());
+
+        for &(address, symbol, kind) in relocations {
+            elf.extend_from_slice(&address.to_le_bytes());
+            elf.extend_from_slice(&((symbol << 32) | kind as u64).to_le_bytes());
+            elf.extend_from_slice(&0i64.to_le_bytes());
+        }
+
+        for section in [0, 1, SHN_ABS] {
+            elf.extend_from_slice(&[0; 6]);
+            elf.extend_from_slice(&section.to_le_bytes());
+            elf.extend_from_slice(&[0; 16]);
+        }
+
+        elf.extend_from_slice(shstrtab);
+
+        push_section(&mut elf, NULL_SECTION);
+        push_section(
+            &mut elf,
+            Section {
+                name: 1,
+                kind: SHT_PROGBITS,
+                flags: SHF_ALLOC,
+                addr: 0x1000,
+                offset: text_offset,
+                size: 16,
+                ..NULL_SECTION
+            },
+        );
+        push_section(
+            &mut elf,
+            Section {
+                name: 7,
+                kind: SHT_PROGBITS,
+                flags: SHF_ALLOC,
+                addr: 0x1010,
+                offset: got_offset,
+                size: 16,
+                entsize: 8,
+                ..NULL_SECTION
+            },
+        );
+        push_section(
+            &mut elf,
+            Section {
+                name: 12,
+                kind: SHT_RELA,
+                offset: rela_offset,
+                size: relocations.len() as u64 * 24,
+                link: 4,
+                info: 1,
+                entsize: 24,
+                ..NULL_SECTION
+            },
+        );
+        push_section(
+            &mut elf,
+            Section {
+                name: 23,
+                kind: SHT_SYMTAB,
+                offset: symtab_offset,
+                size: 3 * 24,
+                link: 5,
+                entsize: 24,
+                ..NULL_SECTION
+            },
+        );
+        push_section(
+            &mut elf,
+            Section {
+                name: 31,
+                kind: SHT_STRTAB,
+                offset: shstrtab_offset,
+                size: shstrtab.len() as u64,
+                ..NULL_SECTION
+            },
+        );
+        push_section(
+            &mut elf,
+            Section {
+                name: 41,
+                kind: SHT_NOBITS,
+                flags: SHF_ALLOC,
+                addr: 0x1020,
+                size: 32,
+                ..NULL_SECTION
+            },
+        );
+
+        elf
+    }
+
+    #[test]
+    fn test_relocation_table() {
+        let elf = test_elf(&[
+            (0x1008, 1, R_X86_64_64),
+            (0x1000, 1, R_X86_64_32),
+            (0x1004, 1, R_X86_64_32),
+            // Doesn't move with the image
+            (0x100c, 1, R_X86_64_PC32),
+            // Against an absolute symbol
+            (0x1004, 2, R_X86_64_64),
+        ]);
+
+        let mut expected = Vec::new();
+        expected.extend_from_slice(RELOCS_MAGIC);
+        expected.extend_from_slice(&0x1000u64.to_le_bytes());
+        expected.extend_from_slice(&0x40u32.to_le_bytes());
+        expected.extend_from_slice(&2u32.to_le_bytes());
+        expected.extend_from_slice(&2u32.to_le_bytes());
+        // The 32-bit fixups, then the 64-bit fixup and the filled GOT entry
+        for offset in [0u32, 4, 8, 0x10] {
+            expected.extend_from_slice(&offset.to_le_bytes());
+        }
+
+        assert_eq!(relocation_table(&elf).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_unknown_relocation_type() {
+        let elf = test_elf(&[(0x1000, 1, 0x7f)]);
+
+        let error = relocation_table(&elf).unwrap_err().to_string();
+        assert!(error.contains("Relocation type 127"), "{}", error);
+    }
+
+    #[test]
+    fn test_relocation_outside_image() {
+        let elf = test_elf(&[(0x2000, 1, R_X86_64_64)]);
+
+        assert!(relocation_table(&elf).is_err());
+    }
+}
@@ -0,0 +1,167 @@
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+
+/// # Telemetry Log Path
+/// Where QEMU's debugcon output (the OS's telemetry events) is written to.
+pub fn telemetry_log_path() -> PathBuf {
+    PathBuf::from("./target/telemetry.log")
+}
+
+/// # Telemetry Report Path
+/// Where the machine-readable report for the last run is written to.
+pub fn telemetry_report_path() -> PathBuf {
+    PathBuf::from("./target/telemetry-report.json")
+}
+
+/// # Telemetry Report
+/// A summary of all the telemetry events the OS emitted during one run.
+#[derive(Debug, Default)]
+pub struct TelemetryReport {
+    boot_phases: Vec<String>,
+    passed_tests: Vec<String>,
+    failed_tests: Vec<String>,
+    panics: Vec<Value>,
+    malformed_lines: usize,
+}
+
+impl TelemetryReport {
+    /// # From Events
+    /// Build a report from the raw debugcon output, where each line is one
+    /// JSON event.
+    pub fn from_events(log: &str) -> Self {
+        let mut report = Self::default();
+
+        for line in log.lines().filter(|line| !line.trim().is_empty()) {
+            let Ok(event) = serde_json::from_str::<Value>(line) else {
+                report.malformed_lines += 1;
+                continue;
+            };
+
+            let field = |name: &str| event[name].as_str().unwrap_or("").to_string();
+
+            match event["event"].as_str() {
+                Some("boot_phase") => report.boot_phases.push(field("phase")),
+                Some("test_result") if event["passed"].as_bool() == Some(true) => {
+                    report.passed_tests.push(field("name"))
+                }
+                Some("test_result") => report.failed_tests.push(field("name")),
+                Some("panic") => report.panics.push(json!({
+                    "source": field("source"),
+                    "message": field("message"),
+                })),
+                _ => report.malformed_lines += 1,
+            }
+        }
+
+        report
+    }
+
+    /// # Success
+    /// If the run had no panics and no failing tests.
+    pub fn success(&self) -> bool {
+        self.panics.is_empty() && self.failed_tests.is_empty()
+    }
+
+    /// # To Json
+    /// Convert this report into the JSON format consumed by CI.
+    pub fn to_json(&self) -> Value {
+        json!({
+            "success": self.success(),
+            "boot_phases": self.boot_phases,
+            "last_boot_phase": self.boot_phases.last(),
+            "tests": {
+                "passed": self.passed_tests.len(),
+                "failed": self.failed_tests.len(),
+                "failed_names": self.failed_tests,
+            },
+            "panics": self.panics,
+            "malformed_lines": self.malformed_lines,
+        })
+    }
+}
+
+impl std::fmt::Display for TelemetryReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "boot phases: [{}], tests: {} passed / {} failed, panics: {}",
+            self.boot_phases.join(" -> "),
+            self.passed_tests.len(),
+            self.failed_tests.len(),
+            self.panics.len()
+        )
+    }
+}
+
+/// # Collect Report
+/// Read the debugcon telemetry log and write the report next to it.
+pub fn collect_report(log_path: &Path, report_path: &Path) -> Result<TelemetryReport> {
+    let log = std::fs::read_to_string(log_path)
+        .with_context(|| format!("Could not read telemetry log {:?}", log_path))?;
+    let report = TelemetryReport::from_events(&log);
+
+    std::fs::write(
+        report_path,
+        serde_json::to_string_pretty(&report.to_json())?,
+    )
+    .with_context(|| format!("Could not write telemetry report {:?}", report_path))?;
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_report_from_events() {
+        let log = concat!(
+            "{\"source\":\"stage_16bit\",\"event\":\"boot_phase\",\"phase\":\"stage16\"}\n",
+            "\n",
+            "{\"source\":\"kernel\",\"event\":\"boot_phase\",\"phase\":\"kernel\"}\n",
+            "{\"source\":\"kernel\",\"event\":\"test_result\",\"name\":\"fat_read\",\"passed\":true}\n",
+            "{\"source\":\"kernel\",\"event\":\"test_result\",\"name\":\"fat_write\",\"passed\":false}\n",
+        );
+        let report = TelemetryReport::from_events(log);
+
+        assert_eq!(report.boot_phases, ["stage16", "kernel"]);
+        assert_eq!(report.passed_tests, ["fat_read"]);
+        assert_eq!(report.failed_tests, ["fat_write"]);
+        assert_eq!(report.malformed_lines, 0);
+        assert!(!report.success());
+
+        let json = report.to_json();
+        assert_eq!(json["last_boot_phase"], "kernel");
+        assert_eq!(json["tests"]["failed_names"], json!(["fat_write"]));
+    }
+
+    #[test]
+    fn test_report_panic() {
+        let log = concat!(
+            "{\"source\":\"kernel\",\"event\":\"boot_phase\",\"phase\":\"kernel\"}\n",
+            "{\"source\":\"kernel::mem\",\"event\":\"panic\",\"message\":\"out of memory\"}\n",
+        );
+        let report = TelemetryReport::from_events(log);
+
+        assert!(!report.success());
+        assert_eq!(
+            report.to_json()["panics"],
+            json!([{ "source": "kernel::mem", "message": "out of memory" }])
+        );
+    }
+
+    #[test]
+    fn test_report_malformed_lines() {
+        let log = concat!(
+            "{\"source\":\"kernel\",\"event\":\"boot_phase\",\"phase\":\"kernel\"}\n",
+            "{\"source\":\"kernel\",\"event\":\"boot_ph\n",
+            "{\"source\":\"kernel\",\"event\":\"unknown\"}\n",
+        );
+        let report = TelemetryReport::from_events(log);
+
+        assert_eq!(report.boot_phases, ["kernel"]);
+        assert_eq!(report.malformed_lines, 2);
+        assert!(report.success());
+    }
+}